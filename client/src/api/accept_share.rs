@@ -18,6 +18,7 @@ pub async fn accept_share(url: &str) -> Result<NodeId> {
     let share_info = match share_info_response {
         GetAcceptShareInfoResponse::Ok(share_info) => share_info,
         GetAcceptShareInfoResponse::NotFound => return Err(anyhow!("share not found")),
+        GetAcceptShareInfoResponse::Expired => return Err(anyhow!("the share link has expired")),
     };
 
     // unwrap the key that was encrypted with the key in the url
@@ -39,5 +40,6 @@ pub async fn accept_share(url: &str) -> Result<NodeId> {
             Err(anyhow!("Server returned NotFound when accepting node"))
         }
         PostAcceptShareResponse::BadRequest(error) => Err(anyhow!("BadRequest: {error}")),
+        PostAcceptShareResponse::Expired => Err(anyhow!("the share link has expired")),
     }
 }
@@ -0,0 +1,66 @@
+use crate::utils::browser::SessionStorage;
+use crate::{api, utils};
+
+use crabdrive_common::payloads::auth::request::password::PostChangePasswordRequest;
+use crabdrive_common::payloads::auth::response::password::PostChangePasswordResponse;
+
+use anyhow::{Context, Result, anyhow};
+use tracing::debug_span;
+
+/// Changes the password of the signed in user. The keys are re-wrapped with the new password, so
+/// no data needs to be re-encrypted. All other sessions of the user are signed out.
+pub async fn change_password(old_password: &str, new_password: &str) -> Result<()> {
+    let _guard = debug_span!("api::changePassword").entered();
+
+    if !utils::auth::is_authenticated()? {
+        tracing::error!("Cannot change password, because you are not signed in.");
+        return Err(anyhow!("Please sign in first and try again"));
+    }
+
+    if !utils::auth::is_valid_password(new_password) {
+        return Err(anyhow!("Password does not meet minimum requirements!"));
+    }
+
+    let username: String =
+        SessionStorage::get("username")?.ok_or(anyhow!("Please sign in first and try again"))?;
+
+    let old_kdf_params = super::fetch_kdf_params(&username).await?;
+    let old_salt = utils::auth::salt_from_kdf_params(&username, &old_kdf_params).await;
+    let (old_server_password, _) = utils::encryption::auth::derive_from_password(
+        old_password,
+        &old_salt,
+        old_kdf_params.version,
+    )?;
+
+    // Use the chance to upgrade the KDF parameters
+    let kdf_params = utils::auth::generate_kdf_params()?;
+    let (new_server_password, new_derived_key) = utils::encryption::auth::derive_from_password(
+        new_password,
+        kdf_params.salt.as_ref().unwrap(),
+        kdf_params.version,
+    )?;
+
+    let keys = utils::encryption::auth::rewrap_user_keys(
+        &utils::encryption::auth::get_master_key()?,
+        &utils::encryption::auth::get_root_key()?,
+        &utils::encryption::auth::get_trash_key()?,
+        utils::encryption::key_pair::get_private_key().ok().as_ref(),
+        &new_derived_key,
+    )
+    .await?;
+
+    let response = api::requests::auth::post_change_password(PostChangePasswordRequest {
+        old_password: old_server_password,
+        new_password: new_server_password,
+        keys,
+        kdf_params,
+    })
+    .await
+    .context("Server currently not reachable - Please try again later")?;
+
+    match response {
+        PostChangePasswordResponse::Ok => Ok(()),
+        PostChangePasswordResponse::Unauthorized => Err(anyhow!("Invalid credentials")),
+        PostChangePasswordResponse::BadRequest(error) => Err(anyhow!("BadRequest: {error}")),
+    }
+}
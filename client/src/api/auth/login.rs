@@ -1,16 +1,31 @@
+use crate::model::encryption::{MasterKey, MetadataKey, PrivateKey};
 use crate::utils::browser::{LocalStorage, SessionStorage, redirect};
 use crate::{api, utils};
 
-use crabdrive_common::payloads::auth::request::login::PostLoginRequest;
-use crabdrive_common::payloads::auth::response::login::PostLoginResponse;
+use crabdrive_common::payloads::auth::request::keys::PutKeyPairRequest;
+use crabdrive_common::payloads::auth::request::login::{
+    PostLoginRequest, PostLoginSecondFactorRequest,
+};
+use crabdrive_common::payloads::auth::request::password::PostChangePasswordRequest;
+use crabdrive_common::payloads::auth::response::keys::PutKeyPairResponse;
+use crabdrive_common::payloads::auth::response::login::{LoginDeniedReason, PostLoginResponse};
+use crabdrive_common::payloads::auth::response::password::PostChangePasswordResponse;
+
+use crabdrive_common::user::UserKeys;
 
 use anyhow::{Context, Result, anyhow};
 use tracing::{debug, debug_span};
 
-/// Attempts to authenticate a user, with username and (unencrypted) password.
+/// Attempts to authenticate a user, with username and (unencrypted) password. Users with
+/// two-factor authentication also need a code from their authenticator app or a backup code.
 ///
 /// This function does not return, if successful. Instead, it redirects to the URL.
-pub async fn login(username: &str, password: &str, remember_username: bool) -> Result<()> {
+pub async fn login(
+    username: &str,
+    password: &str,
+    second_factor: Option<&str>,
+    remember_username: bool,
+) -> Result<()> {
     let _guard = debug_span!("api::login").entered();
 
     if utils::auth::is_authenticated()? {
@@ -22,25 +37,50 @@ pub async fn login(username: &str, password: &str, remember_username: bool) -> R
 
     SessionStorage::clear().context("Failed to clear SessionStore")?;
 
-    let salt = utils::auth::salt_from_username(username).await;
+    let kdf_params = super::fetch_kdf_params(username).await?;
+    let salt = utils::auth::salt_from_kdf_params(username, &kdf_params).await;
     let (server_password, derived_key) =
-        utils::encryption::auth::derive_from_password(password, &salt)?;
+        utils::encryption::auth::derive_from_password(password, &salt, kdf_params.version)?;
 
     debug!("Attempting to login");
 
     let response = api::requests::auth::post_login(PostLoginRequest {
         username: username.parse()?,
-        password: server_password,
+        password: server_password.clone(),
     })
     .await
     .context("Server currently not reachable - Please try again later")?;
 
+    let response = match response {
+        PostLoginResponse::SecondFactorRequired(challenge) => {
+            let Some(code) = second_factor.filter(|code| !code.is_empty()) else {
+                debug!("Login denied (Second factor missing)");
+                return Err(anyhow!("Please enter your two-factor code"));
+            };
+
+            api::requests::auth::post_login_second_factor(PostLoginSecondFactorRequest {
+                challenge: challenge.challenge,
+                code: code.to_string(),
+            })
+            .await
+            .context("Server currently not reachable - Please try again later")?
+        }
+        response => response,
+    };
+
     let login_response = match response {
         PostLoginResponse::Ok(login_success) => Ok(login_success),
+        PostLoginResponse::Unauthorized(LoginDeniedReason::SecondFactor) => {
+            debug!("Login denied (Invalid second factor)");
+            Err(anyhow!("Invalid two-factor code"))
+        }
         PostLoginResponse::Unauthorized(_) => {
             debug!("Login denied (Invalid Username or Password)");
             Err(anyhow!("Invalid credentials"))
         }
+        PostLoginResponse::SecondFactorRequired(_) => {
+            Err(anyhow!("Unexpected response from the server"))
+        }
     }?;
 
     if login_response.user_keys.is_none() || login_response.should_initialize_encryption {
@@ -65,7 +105,7 @@ pub async fn login(username: &str, password: &str, remember_username: bool) -> R
         .await
         .inspect_err(|_| tracing::error!("Failed to unwrap trash key"))?;
 
-    // TODO: Unwrap assymmetrical keys
+    let private_key = unwrap_or_create_key_pair(&keys, &master_key).await?;
 
     super::fetch_user_nodes(
         login_response.root_node_id,
@@ -75,11 +115,26 @@ pub async fn login(username: &str, password: &str, remember_username: bool) -> R
     )
     .await?;
 
+    if kdf_params.needs_upgrade() {
+        // Not critical, the upgrade is attempted again on the next login
+        let _ = upgrade_kdf(
+            password,
+            server_password,
+            &master_key,
+            &root_key,
+            &trash_key,
+            &private_key,
+        )
+        .await
+        .inspect_err(|e| tracing::warn!("Failed to upgrade KDF parameters: {:?}", e));
+    }
+
     // Store keys in session storage
     // See also: https://github.com/mtfwvi/crabdrive/issues/114
     SessionStorage::set("master_key", &utils::encryption::encode_key(&master_key))?;
     SessionStorage::set("root_key", &utils::encryption::encode_key(&root_key))?;
     SessionStorage::set("trash_key", &utils::encryption::encode_key(&trash_key))?;
+    utils::encryption::key_pair::set_private_key(&private_key)?;
 
     // Store username in storage
     SessionStorage::set("username", &username)?;
@@ -106,3 +161,73 @@ pub async fn login(username: &str, password: &str, remember_username: bool) -> R
 
     Ok(())
 }
+
+/// Unwraps the private key of the user. Accounts created before key pairs were introduced do not
+/// have one yet, so a new key pair is generated and uploaded.
+async fn unwrap_or_create_key_pair(keys: &UserKeys, master_key: &MasterKey) -> Result<PrivateKey> {
+    if !keys.public_key.is_empty() {
+        return utils::encryption::key_pair::unwrap_private_key(&keys.private_key, master_key)
+            .await;
+    }
+
+    debug!("Generating missing key pair");
+
+    let (public_key, private_key) = utils::encryption::key_pair::generate_key_pair().await?;
+    let wrapped_private_key =
+        utils::encryption::key_pair::wrap_private_key(&private_key, master_key).await?;
+
+    let response = api::requests::auth::put_key_pair(PutKeyPairRequest {
+        public_key,
+        private_key: wrapped_private_key,
+    })
+    .await?;
+
+    match response {
+        PutKeyPairResponse::Ok => Ok(private_key),
+        PutKeyPairResponse::Conflict => Err(anyhow!("A key pair already exists")),
+        PutKeyPairResponse::BadRequest => Err(anyhow!("Invalid key pair")),
+    }
+}
+
+/// Derives the password again with the current KDF parameters and a new random salt. The keys are
+/// re-wrapped with the new derived key.
+async fn upgrade_kdf(
+    password: &str,
+    server_password: String,
+    master_key: &MasterKey,
+    root_key: &MetadataKey,
+    trash_key: &MetadataKey,
+    private_key: &PrivateKey,
+) -> Result<()> {
+    let _guard = debug_span!("api::upgradeKdf").entered();
+
+    let kdf_params = utils::auth::generate_kdf_params()?;
+    let (new_server_password, new_derived_key) = utils::encryption::auth::derive_from_password(
+        password,
+        kdf_params.salt.as_ref().unwrap(),
+        kdf_params.version,
+    )?;
+
+    let keys = utils::encryption::auth::rewrap_user_keys(
+        master_key,
+        root_key,
+        trash_key,
+        Some(private_key),
+        &new_derived_key,
+    )
+    .await?;
+
+    let response = api::requests::auth::post_upgrade_kdf(PostChangePasswordRequest {
+        old_password: server_password,
+        new_password: new_server_password,
+        keys,
+        kdf_params,
+    })
+    .await?;
+
+    match response {
+        PostChangePasswordResponse::Ok => Ok(()),
+        PostChangePasswordResponse::Unauthorized => Err(anyhow!("Invalid credentials")),
+        PostChangePasswordResponse::BadRequest(error) => Err(anyhow!("BadRequest: {error}")),
+    }
+}
@@ -1,10 +1,16 @@
+mod change_password;
 mod login;
 mod logout;
+mod recovery;
 mod register;
+mod totp;
 
+pub use change_password::change_password;
 pub use login::login;
 pub use logout::logout;
+pub use recovery::{create_recovery_key, recover_account};
 pub use register::register;
+pub use totp::{confirm_totp, disable_totp, regenerate_backup_codes, start_totp_enrolment};
 
 use crate::model::encryption::MetadataKey;
 use crate::model::node::NodeMetadata;
@@ -12,25 +18,31 @@ use crate::utils::browser::SessionStorage;
 use crate::{api, utils};
 
 use crabdrive_common::encrypted_metadata::EncryptedMetadata;
+use crabdrive_common::payloads::auth::request::login::PostPreLoginRequest;
+use crabdrive_common::payloads::auth::response::login::PostPreLoginResponse;
 use crabdrive_common::payloads::node::request::node::PatchNodeRequest;
 use crabdrive_common::payloads::node::response::node::GetNodeResponse;
 use crabdrive_common::storage::NodeId;
+use crabdrive_common::user::KdfParams;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use tracing::debug_span;
 #[cfg(debug_assertions)]
 use wasm_bindgen::prelude::wasm_bindgen;
 
-// The next three functions are used for testing via browser console. They can be accessed via:
+// The next functions are used for testing via browser console. They can be accessed via:
 // - await window.wasmBindings._register_user()
 // - await window.wasmBindings._login_user()
 // - await window.wasmBindings._logout_user()
+// - await window.wasmBindings._change_password()
+// - await window.wasmBindings._create_recovery_key()
+// - await window.wasmBindings._recover_account()
 // Only present when building in debug mode.
 
 #[cfg(debug_assertions)]
 #[wasm_bindgen]
-pub async fn _login_user(username: &str, password: &str) {
-    let result = login(username, password, false).await;
+pub async fn _login_user(username: &str, password: &str, second_factor: Option<String>) {
+    let result = login(username, password, second_factor.as_deref(), false).await;
     if result.is_err() {
         tracing::error!("Failed to login: {:?}", result);
     }
@@ -54,6 +66,45 @@ pub async fn _logout_user() {
     }
 }
 
+#[cfg(debug_assertions)]
+#[wasm_bindgen]
+pub async fn _change_password(old_password: &str, new_password: &str) {
+    let result = change_password(old_password, new_password).await;
+    if result.is_err() {
+        tracing::error!("Failed to change password: {:?}", result);
+    }
+}
+
+#[cfg(debug_assertions)]
+#[wasm_bindgen]
+pub async fn _create_recovery_key(password: &str) {
+    match create_recovery_key(password).await {
+        Ok(recovery_code) => tracing::info!("Recovery code: {}", recovery_code),
+        Err(e) => tracing::error!("Failed to create recovery key: {:?}", e),
+    }
+}
+
+#[cfg(debug_assertions)]
+#[wasm_bindgen]
+pub async fn _recover_account(username: &str, recovery_code: &str, new_password: &str) {
+    let result = recover_account(username, recovery_code, new_password).await;
+    if result.is_err() {
+        tracing::error!("Failed to recover account: {:?}", result);
+    }
+}
+
+/// Get the parameters to derive the password of a user with
+async fn fetch_kdf_params(username: &str) -> Result<KdfParams> {
+    let response = api::requests::auth::post_pre_login(PostPreLoginRequest {
+        username: username.to_string(),
+    })
+    .await
+    .context("Server currently not reachable - Please try again later")?;
+
+    let PostPreLoginResponse::Ok(kdf_params) = response;
+    Ok(kdf_params)
+}
+
 /// Updates the cached node IDs, and initializes (if uninitialized)
 async fn fetch_user_nodes(
     root_node_id: NodeId,
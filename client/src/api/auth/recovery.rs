@@ -0,0 +1,155 @@
+use crate::model::encryption::DerivedKey;
+use crate::utils::browser::SessionStorage;
+use crate::{api, utils};
+
+use crabdrive_common::payloads::auth::request::recovery::{
+    PostRecoveryKeysRequest, PostRecoveryResetRequest, PutRecoveryKeyRequest, RecoveryKey,
+};
+use crabdrive_common::payloads::auth::response::recovery::{
+    PostRecoveryKeysResponse, PostRecoveryResetResponse, PutRecoveryKeyResponse,
+};
+use crabdrive_common::user::KdfParams;
+
+use anyhow::{Context, Result, anyhow};
+use tracing::debug_span;
+
+/// Derives the recovery password and the key wrapping the recovery copy of the master key. The
+/// recovery code has enough entropy on its own, so the salt and the parameters never change.
+async fn derive_from_recovery_code(
+    username: &str,
+    recovery_code: &str,
+) -> Result<(String, DerivedKey)> {
+    let salt = utils::auth::salt_from_username(username).await;
+    utils::encryption::auth::derive_from_password(
+        &utils::encryption::auth::normalize_recovery_code(recovery_code),
+        &salt,
+        KdfParams::LEGACY_VERSION,
+    )
+}
+
+/// Creates a new recovery key for the signed in user, replacing the previous one. Returns the
+/// recovery code, which has to be stored by the user.
+pub async fn create_recovery_key(password: &str) -> Result<String> {
+    let _guard = debug_span!("api::createRecoveryKey").entered();
+
+    if !utils::auth::is_authenticated()? {
+        tracing::error!("Cannot create recovery key, because you are not signed in.");
+        return Err(anyhow!("Please sign in first and try again"));
+    }
+
+    let username: String =
+        SessionStorage::get("username")?.ok_or(anyhow!("Please sign in first and try again"))?;
+
+    let kdf_params = super::fetch_kdf_params(&username).await?;
+    let salt = utils::auth::salt_from_kdf_params(&username, &kdf_params).await;
+    let (server_password, _) =
+        utils::encryption::auth::derive_from_password(password, &salt, kdf_params.version)?;
+
+    let recovery_code = utils::encryption::auth::generate_recovery_code()?;
+    let (recovery_password, recovery_derived_key) =
+        derive_from_recovery_code(&username, &recovery_code).await?;
+
+    let master_key = utils::encryption::wrap_key(
+        &utils::encryption::auth::get_master_key()?,
+        &recovery_derived_key,
+    )
+    .await
+    .inspect_err(|_| tracing::error!("Failed to wrap master key"))?;
+
+    let response = api::requests::auth::put_recovery_key(PutRecoveryKeyRequest {
+        password: server_password,
+        recovery_key: RecoveryKey {
+            recovery_password,
+            master_key,
+        },
+    })
+    .await
+    .context("Server currently not reachable - Please try again later")?;
+
+    match response {
+        PutRecoveryKeyResponse::Ok => Ok(recovery_code),
+        PutRecoveryKeyResponse::Unauthorized => Err(anyhow!("Invalid credentials")),
+    }
+}
+
+/// Sets a new password with the recovery code. The master key is unwrapped with the recovery code
+/// and wrapped with the new password, so no data is lost. All sessions of the user are signed out.
+pub async fn recover_account(
+    username: &str,
+    recovery_code: &str,
+    new_password: &str,
+) -> Result<()> {
+    let _guard = debug_span!("api::recoverAccount").entered();
+
+    if !utils::auth::is_valid_password(new_password) {
+        return Err(anyhow!("Password does not meet minimum requirements!"));
+    }
+
+    let (recovery_password, recovery_derived_key) =
+        derive_from_recovery_code(username, recovery_code).await?;
+
+    let response = api::requests::auth::post_recovery_keys(PostRecoveryKeysRequest {
+        username: username.to_string(),
+        recovery_password: recovery_password.clone(),
+    })
+    .await
+    .context("Server currently not reachable - Please try again later")?;
+
+    let recovery_keys = match response {
+        PostRecoveryKeysResponse::Ok(recovery_keys) => Ok(recovery_keys),
+        PostRecoveryKeysResponse::Unauthorized => Err(anyhow!("Invalid recovery code")),
+    }?;
+
+    let master_key =
+        utils::encryption::unwrap_key(&recovery_keys.recovery_master_key, &recovery_derived_key)
+            .await
+            .inspect_err(|_| tracing::error!("Failed to unwrap master key"))?;
+    let root_key = utils::encryption::unwrap_key(&recovery_keys.keys.root_key, &master_key)
+        .await
+        .inspect_err(|_| tracing::error!("Failed to unwrap root key"))?;
+    let trash_key = utils::encryption::unwrap_key(&recovery_keys.keys.trash_key, &master_key)
+        .await
+        .inspect_err(|_| tracing::error!("Failed to unwrap trash key"))?;
+    let private_key = if recovery_keys.keys.public_key.is_empty() {
+        None
+    } else {
+        Some(
+            utils::encryption::key_pair::unwrap_private_key(
+                &recovery_keys.keys.private_key,
+                &master_key,
+            )
+            .await?,
+        )
+    };
+
+    let kdf_params = utils::auth::generate_kdf_params()?;
+    let (new_server_password, new_derived_key) = utils::encryption::auth::derive_from_password(
+        new_password,
+        kdf_params.salt.as_ref().unwrap(),
+        kdf_params.version,
+    )?;
+    let keys = utils::encryption::auth::rewrap_user_keys(
+        &master_key,
+        &root_key,
+        &trash_key,
+        private_key.as_ref(),
+        &new_derived_key,
+    )
+    .await?;
+
+    let response = api::requests::auth::post_recovery_reset(PostRecoveryResetRequest {
+        username: username.to_string(),
+        recovery_password,
+        new_password: new_server_password,
+        keys,
+        kdf_params,
+    })
+    .await
+    .context("Server currently not reachable - Please try again later")?;
+
+    match response {
+        PostRecoveryResetResponse::Ok => Ok(()),
+        PostRecoveryResetResponse::Unauthorized => Err(anyhow!("Invalid recovery code")),
+        PostRecoveryResetResponse::BadRequest(error) => Err(anyhow!("BadRequest: {error}")),
+    }
+}
@@ -1,7 +1,6 @@
 use crate::utils::browser::LocalStorage;
 use crate::{api, utils};
 
-use crabdrive_common::encryption_key::EncryptionKey;
 use crabdrive_common::payloads::auth::request::register::PostRegisterRequest;
 use crabdrive_common::payloads::auth::response::register::PostRegisterResponse;
 
@@ -22,9 +21,12 @@ pub async fn register(username: &str, password: &str, invite_code: &str) -> Resu
         return Err(anyhow!("Password does not meet minimum requirements!"));
     }
 
-    let salt = utils::auth::salt_from_username(username).await;
-    let (server_password, derived_key) =
-        utils::encryption::auth::derive_from_password(password, &salt)?;
+    let kdf_params = utils::auth::generate_kdf_params()?;
+    let (server_password, derived_key) = utils::encryption::auth::derive_from_password(
+        password,
+        kdf_params.salt.as_ref().unwrap(),
+        kdf_params.version,
+    )?;
 
     // Generate a new master key, root node & trash node keys
     let master_key = utils::encryption::generate_aes256_key().await?;
@@ -41,17 +43,24 @@ pub async fn register(username: &str, password: &str, invite_code: &str) -> Resu
         .await
         .inspect_err(|_| tracing::error!("Failed to wrap trash key"))?;
 
+    // The key pair is used for receiving shares from other users
+    let (public_key, private_key) = utils::encryption::key_pair::generate_key_pair().await?;
+    let wrapped_private_key =
+        utils::encryption::key_pair::wrap_private_key(&private_key, &master_key).await?;
+
     let response = api::requests::auth::post_register(PostRegisterRequest {
         username: username.parse()?,
         password: server_password,
         invite_code: invite_code.parse()?,
         keys: UserKeys::new(
-            vec![],
-            EncryptionKey::nil(),
+            public_key,
+            wrapped_private_key,
             wrapped_master_key,
             wrapped_root_key,
             wrapped_trash_key,
         ),
+        recovery_key: None,
+        kdf_params,
     })
     .await
     .context("Server currently not reachable - Please try again later")?;
@@ -64,6 +73,9 @@ pub async fn register(username: &str, password: &str, invite_code: &str) -> Resu
         PostRegisterResponse::Conflict(reason) => {
             Err(anyhow!("Failed to create a new account: {}", reason))
         }
+        PostRegisterResponse::BadRequest(error) => {
+            Err(anyhow!("Failed to create a new account: {error}"))
+        }
     }?;
 
     LocalStorage::set("last_user", &username)?;
@@ -0,0 +1,86 @@
+use crate::{api, utils};
+
+use crabdrive_common::payloads::auth::request::totp::TotpCodeRequest;
+use crabdrive_common::payloads::auth::response::totp::{
+    BackupCodes, DeleteTotpResponse, PostTotpBackupCodesResponse, PostTotpConfirmResponse,
+    PostTotpResponse, TotpEnrolment,
+};
+
+use anyhow::{Result, anyhow};
+use tracing::debug_span;
+
+fn ensure_authenticated() -> Result<()> {
+    if !utils::auth::is_authenticated()? {
+        tracing::error!("Cannot change two-factor authentication, because you are not signed in.");
+        return Err(anyhow!("Please sign in first and try again"));
+    }
+    Ok(())
+}
+
+/// Starts the two-factor enrolment. The returned secret has to be added to an authenticator app,
+/// before the enrolment is completed with `confirm_totp`.
+pub async fn start_totp_enrolment() -> Result<TotpEnrolment> {
+    let _guard = debug_span!("api::startTotpEnrolment").entered();
+    ensure_authenticated()?;
+
+    match api::requests::auth::post_totp().await? {
+        PostTotpResponse::Ok(enrolment) => Ok(enrolment),
+        PostTotpResponse::Conflict => Err(anyhow!(
+            "Two-factor authentication is already enabled. Disable it first."
+        )),
+    }
+}
+
+/// Completes the two-factor enrolment with a code from the authenticator app. Returns the backup
+/// codes, which have to be stored by the user.
+pub async fn confirm_totp(code: &str) -> Result<BackupCodes> {
+    let _guard = debug_span!("api::confirmTotp").entered();
+    ensure_authenticated()?;
+
+    let response = api::requests::auth::post_totp_confirm(TotpCodeRequest {
+        code: code.to_string(),
+    })
+    .await?;
+
+    match response {
+        PostTotpConfirmResponse::Ok(backup_codes) => Ok(backup_codes),
+        PostTotpConfirmResponse::Unauthorized => Err(anyhow!("Invalid code")),
+        PostTotpConfirmResponse::Conflict => Err(anyhow!("No enrolment in progress")),
+    }
+}
+
+/// Disables two-factor authentication. Accepts a code from the authenticator app or a backup code.
+pub async fn disable_totp(code: &str) -> Result<()> {
+    let _guard = debug_span!("api::disableTotp").entered();
+    ensure_authenticated()?;
+
+    let response = api::requests::auth::delete_totp(TotpCodeRequest {
+        code: code.to_string(),
+    })
+    .await?;
+
+    match response {
+        DeleteTotpResponse::Ok => Ok(()),
+        DeleteTotpResponse::Unauthorized => Err(anyhow!("Invalid code")),
+        DeleteTotpResponse::NotFound => Err(anyhow!("Two-factor authentication is not enabled")),
+    }
+}
+
+/// Replaces all backup codes. Accepts a code from the authenticator app or a backup code.
+pub async fn regenerate_backup_codes(code: &str) -> Result<BackupCodes> {
+    let _guard = debug_span!("api::regenerateBackupCodes").entered();
+    ensure_authenticated()?;
+
+    let response = api::requests::auth::post_totp_backup_codes(TotpCodeRequest {
+        code: code.to_string(),
+    })
+    .await?;
+
+    match response {
+        PostTotpBackupCodesResponse::Ok(backup_codes) => Ok(backup_codes),
+        PostTotpBackupCodesResponse::Unauthorized => Err(anyhow!("Invalid code")),
+        PostTotpBackupCodesResponse::NotFound => {
+            Err(anyhow!("Two-factor authentication is not enabled"))
+        }
+    }
+}
@@ -131,6 +131,9 @@ pub async fn create_file(
             "No such node: {}. Check if you have permission to access it",
             parent.id
         )),
+        PostCreateFileResponse::Forbidden => Err(anyhow!(
+            "You do not have permission to upload files into this folder"
+        )),
         PostCreateFileResponse::BadRequest => Err(anyhow!("Bad request")),
         PostCreateFileResponse::Conflict => Err(anyhow!("Bad request")),
     }
@@ -154,6 +157,11 @@ pub async fn create_file_version(file: File, node: DecryptedNode) -> Result<Decr
                 "server returned not found when trying to upload new version"
             ));
         }
+        PostUpdateFileResponse::Forbidden => {
+            return Err(anyhow!(
+                "You do not have permission to upload a new version of this file"
+            ));
+        }
         PostUpdateFileResponse::BadRequest => return Err(anyhow!("server returned bad request")),
     };
 
@@ -201,6 +209,9 @@ async fn upload_file(
             Err(anyhow!("Server returned bad request: {:?}", err))
         }
         PostCommitFileResponse::NotFound => Err(anyhow!("no such node: {}", node_id)),
+        PostCommitFileResponse::Forbidden => {
+            Err(anyhow!("You do not have permission to upload to this file"))
+        }
     }
 }
 
@@ -226,6 +237,9 @@ async fn encrypt_and_upload_chunk(
     match response {
         PostChunkResponse::Created => Ok(()),
         PostChunkResponse::OutOfStorage => Err(anyhow!("You have exceeded your quota")),
+        PostChunkResponse::Forbidden => {
+            Err(anyhow!("You do not have permission to upload to this file"))
+        }
         _ => Err(anyhow!(
             "Unexpected error while uploading chunk: {:?}",
             response
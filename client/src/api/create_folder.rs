@@ -73,6 +73,9 @@ pub async fn create_folder(parent: DecryptedNode, folder_name: String) -> Result
             "No such node: {}. Check if you have permission to access it",
             parent.id
         )),
+        PostCreateFolderResponse::Forbidden => Err(anyhow!(
+            "You do not have permission to create folders in this folder"
+        )),
         PostCreateFolderResponse::BadRequest => Err(anyhow!("Bad Request")),
         PostCreateFolderResponse::Conflict => Err(anyhow!("Please try again")),
     }
@@ -76,6 +76,15 @@ async fn download_chunk_and_decrypt(
         .await
         .inspect_err(|e| tracing::error!("Failed to get chunk from server: {}", e))?;
 
+    decrypt_chunk_response(chunk_response, revision, file_key, index).await
+}
+
+pub(super) async fn decrypt_chunk_response(
+    chunk_response: GetChunkResponse,
+    revision: &FileRevision,
+    file_key: &FileKey,
+    index: ChunkIndex,
+) -> Result<Uint8Array> {
     match chunk_response {
         GetChunkResponse::Ok(encrypted_chunk_buffer) => {
             let encrypted_chunk = EncryptedChunk {
@@ -100,7 +109,7 @@ async fn download_chunk_and_decrypt(
     }
 }
 
-async fn save_file(data: Blob, file_name: &str) -> Result<()> {
+pub(super) async fn save_file(data: Blob, file_name: &str) -> Result<()> {
     let url = wrap_js_err(Url::create_object_url_with_blob(&data))?;
     let document = utils::browser::get_document()?;
     let a = wrap_js_err(document.create_element("a"))?;
@@ -0,0 +1,221 @@
+use crate::api::requests::chunk::PostChunkResponse;
+use crate::api::requests::file_drop::{
+    get_dropped_files, get_file_drop, post_adopt_dropped_files, post_drop_chunk, post_drop_commit,
+    post_drop_file, post_file_drop,
+};
+use crate::constants::CHUNK_SIZE;
+use crate::model::encryption::{FileKey, MetadataKey};
+use crate::model::node::{DecryptedNode, MetadataV1, NodeMetadata};
+use crate::utils;
+use crate::utils::encryption::key_pair::{decrypt_with, encrypt_for, get_private_key};
+use crate::utils::encryption::node::{decrypt_metadata, encrypt_metadata};
+use crate::utils::share::{create_file_drop_url, parse_file_drop_url};
+use anyhow::{Result, anyhow};
+use chrono::{Local, NaiveDateTime};
+use crabdrive_common::da;
+use crabdrive_common::payloads::node::request::file_drop::{
+    PostAdoptDroppedFilesRequest, PostDropFileRequest, PostFileDropRequest,
+};
+use crabdrive_common::payloads::node::response::file_drop::{
+    GetDroppedFilesResponse, GetFileDropResponse, PostAdoptDroppedFilesResponse,
+    PostDropCommitResponse, PostDropFileResponse, PostFileDropResponse,
+};
+use crabdrive_common::storage::NodeId;
+use tracing::debug_span;
+use web_sys::File;
+use web_sys::js_sys::Uint8Array;
+
+/// returns a url that allows anyone to upload files into the folder, without being able to see its
+/// contents. The url stops working after `expires_at`.
+pub async fn create_file_drop(
+    node: &DecryptedNode,
+    expires_at: Option<NaiveDateTime>,
+) -> Result<String> {
+    match post_file_drop(node.id, PostFileDropRequest { expires_at }).await? {
+        PostFileDropResponse::Ok(drop_id) => create_file_drop_url(&drop_id),
+        PostFileDropResponse::NotFound => {
+            Err(anyhow!("server returned NotFound on create file drop"))
+        }
+        PostFileDropResponse::BadRequest(error) => {
+            Err(anyhow!("Server returned BadRequest: {:?}", error))
+        }
+    }
+}
+
+/// Check that the file drop behind the url can still be used
+pub async fn check_file_drop(url: &str) -> Result<()> {
+    let drop_id = parse_file_drop_url(url)?;
+    match get_file_drop(drop_id).await? {
+        GetFileDropResponse::Ok(_) => Ok(()),
+        GetFileDropResponse::NotFound => Err(anyhow!("The link does not exist")),
+        GetFileDropResponse::Expired => Err(anyhow!("The link has expired")),
+    }
+}
+
+/// Upload a file through a file drop link. The metadata key of the file is encrypted with the
+/// public key of the folder owner, the metadata of the folder is not touched.
+pub async fn drop_file(url: &str, file: File) -> Result<()> {
+    let _guard = debug_span!("api::dropFile").entered();
+
+    let drop_id = parse_file_drop_url(url)?;
+    let public_key = match get_file_drop(drop_id).await? {
+        GetFileDropResponse::Ok(info) => info.public_key,
+        GetFileDropResponse::NotFound => return Err(anyhow!("The link does not exist")),
+        GetFileDropResponse::Expired => return Err(anyhow!("The link has expired")),
+    };
+
+    let metadata_key: MetadataKey = utils::encryption::generate_aes256_key().await?;
+    let file_key: FileKey = utils::encryption::generate_aes256_key().await?;
+
+    let file_type = file.type_();
+    let metadata = NodeMetadata::V1(MetadataV1 {
+        name: file.name(),
+        last_modified: Local::now().naive_local(),
+        created: Local::now().naive_local(),
+        size: Some(da!(file.size())),
+        mime_type: if file_type.is_empty() {
+            None
+        } else {
+            Some(file_type)
+        },
+        file_key: Some(file_key),
+        children_key: vec![],
+    });
+
+    let node_id = NodeId::random();
+    let file_iv = utils::encryption::random::get_random_iv()?;
+
+    let body = PostDropFileRequest {
+        node_id,
+        node_metadata: encrypt_metadata(&metadata, &metadata_key).await?,
+        encrypted_metadata_key: encrypt_for(&public_key, &metadata_key).await?,
+        file_iv,
+        chunk_count: (file.size() / CHUNK_SIZE).ceil() as i64,
+    };
+
+    let revision = match post_drop_file(drop_id, body).await? {
+        PostDropFileResponse::Created(revision) => revision,
+        PostDropFileResponse::NotFound => return Err(anyhow!("The link is not available")),
+        PostDropFileResponse::BadRequest => return Err(anyhow!("Bad request")),
+        PostDropFileResponse::Conflict => return Err(anyhow!("Please try again")),
+    };
+
+    // if this fails the server is lying to us
+    if revision.iv != file_iv {
+        return Err(anyhow!("The server is lying to us"));
+    }
+
+    let revision_id = revision.id;
+    utils::file::load_file_by_chunk(file, |chunk| {
+        let chunk = chunk.clone();
+        async move {
+            let encrypted_chunk =
+                utils::encryption::chunk::encrypt_chunk(&chunk, &file_key, file_iv).await?;
+            let body = Uint8Array::new(&encrypted_chunk.chunk);
+            match post_drop_chunk(drop_id, node_id, revision_id, chunk.index, body).await? {
+                PostChunkResponse::Created => Ok(()),
+                PostChunkResponse::OutOfStorage => {
+                    Err(anyhow!("The owner of the folder has no storage left"))
+                }
+                response => Err(anyhow!(
+                    "Unexpected error while uploading chunk: {:?}",
+                    response
+                )),
+            }
+        }
+    })
+    .await?;
+
+    match post_drop_commit(drop_id, node_id, revision_id).await? {
+        PostDropCommitResponse::Ok => Ok(()),
+        PostDropCommitResponse::NotFound => Err(anyhow!("The link is not available")),
+        PostDropCommitResponse::BadRequest(err) => {
+            Err(anyhow!("Server returned bad request: {:?}", err))
+        }
+    }
+}
+
+/// Returns the ids and metadata keys of the completely uploaded files dropped into the folder.
+/// Returns an empty list if the folder is not owned by the current user.
+async fn get_dropped_file_keys(folder: &DecryptedNode) -> Result<Vec<(NodeId, MetadataKey)>> {
+    let GetDroppedFilesResponse::Ok(dropped_files) = get_dropped_files(folder.id).await? else {
+        return Ok(vec![]);
+    };
+
+    let committed_files: Vec<_> = dropped_files
+        .into_iter()
+        .filter(|dropped_file| {
+            dropped_file
+                .node
+                .current_revision
+                .as_ref()
+                .is_some_and(|revision| revision.upload_ended_on.is_some())
+        })
+        .collect();
+
+    if committed_files.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let private_key = get_private_key()?;
+
+    let mut keys = Vec::with_capacity(committed_files.len());
+    for dropped_file in committed_files {
+        let metadata_key: MetadataKey =
+            decrypt_with(&private_key, &dropped_file.encrypted_metadata_key)
+                .await?
+                .try_into()
+                .map_err(|_| anyhow!("Invalid metadata key"))?;
+
+        // only adopt files, which we are actually able to decrypt
+        decrypt_metadata(&dropped_file.node.encrypted_metadata, &metadata_key).await?;
+        keys.push((dropped_file.node.id, metadata_key));
+    }
+    Ok(keys)
+}
+
+/// Returns the amount of files, which were dropped into the folder and can be adopted
+pub async fn get_dropped_file_count(folder: &DecryptedNode) -> Result<usize> {
+    Ok(get_dropped_file_keys(folder).await?.len())
+}
+
+/// Add all completely uploaded files, which were dropped into the folder, to the folder. Returns
+/// the amount of adopted files.
+pub async fn adopt_dropped_files(folder: &mut DecryptedNode) -> Result<usize> {
+    let _guard = debug_span!("api::adoptDroppedFiles").entered();
+
+    let keys = get_dropped_file_keys(folder).await?;
+    if keys.is_empty() {
+        return Ok(0);
+    }
+
+    let node_ids = keys.iter().map(|(node_id, _)| *node_id).collect();
+
+    let mut new_metadata = folder.metadata.clone();
+    match new_metadata {
+        NodeMetadata::V1(ref mut metadata) => metadata.children_key.extend(keys.iter().copied()),
+    }
+
+    let body = PostAdoptDroppedFilesRequest {
+        parent_metadata: encrypt_metadata(&new_metadata, &folder.encryption_key).await?,
+        parent_metadata_version: folder.change_count,
+        node_ids,
+    };
+
+    match post_adopt_dropped_files(folder.id, body).await? {
+        PostAdoptDroppedFilesResponse::Ok => {
+            folder.metadata = new_metadata;
+            folder.change_count += 1;
+            Ok(keys.len())
+        }
+        PostAdoptDroppedFilesResponse::NotFound => {
+            Err(anyhow!("server returned NotFound on adopt dropped files"))
+        }
+        PostAdoptDroppedFilesResponse::BadRequest(error) => {
+            Err(anyhow!("Server returned BadRequest: {:?}", error))
+        }
+        PostAdoptDroppedFilesResponse::Conflict => Err(anyhow!(
+            "The folder was modified in the meantime, please try again"
+        )),
+    }
+}
@@ -4,6 +4,7 @@ mod create_file;
 mod create_folder;
 mod delete;
 mod download_file;
+mod file_drop;
 mod get_accepted_nodes;
 mod get_accessible_path;
 mod get_children;
@@ -13,8 +14,11 @@ mod get_shared_node_encryption_key;
 mod get_trash_node;
 mod get_versions;
 mod move_node;
+mod outgoing_shares;
+mod public_link;
 mod rename_node;
 mod requests;
+mod share_inbox;
 mod share_node;
 
 pub use accept_share::accept_share;
@@ -22,14 +26,22 @@ pub use create_file::create_file;
 pub use create_folder::create_folder;
 
 pub use download_file::download_file;
+pub use file_drop::{
+    adopt_dropped_files, check_file_drop, create_file_drop, drop_file, get_dropped_file_count,
+};
 pub use get_accepted_nodes::get_accepted_nodes;
 pub use get_accessible_path::get_accessible_path;
 pub use get_children::get_children;
 pub use get_root_node::get_root_node;
 pub use get_shared_node_encryption_key::get_shared_node_encryption_key;
 pub use get_trash_node::get_trash_node;
+pub use outgoing_shares::{SharedByMe, get_shared_by_me, revoke_share};
+pub use public_link::{create_public_link, download_public_file, get_public_file_name};
 pub use rename_node::rename_node;
-pub use share_node::share_node;
+pub use share_inbox::{
+    IncomingShare, accept_incoming_share, decline_incoming_share, get_incoming_shares,
+};
+pub use share_node::{share_node, share_node_with_user};
 
 pub use create_file::create_file_version;
 pub use get_versions::file_versions;
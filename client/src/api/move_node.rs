@@ -60,6 +60,9 @@ pub async fn move_node(
         PostMoveNodeResponse::Ok => Ok(()),
         PostMoveNodeResponse::BadRequest => bail!("Cannot move a file into another file!"),
         PostMoveNodeResponse::NotFound => bail!("One of the nodes referenced could not be found"),
+        PostMoveNodeResponse::Forbidden => {
+            bail!("You do not have permission to move nodes in one of the folders")
+        }
         PostMoveNodeResponse::Conflict => bail!("Refresh the page and try again!"),
     }
 }
@@ -0,0 +1,60 @@
+use crate::api::get_accessible_path;
+use crate::api::requests::share::{delete_share, get_outgoing_shares};
+use crate::model::node::NodeMetadata;
+use anyhow::{Result, anyhow};
+use crabdrive_common::payloads::node::response::share::{
+    DeleteShareResponse, GetOutgoingSharesResponse, OutgoingShare,
+};
+use crabdrive_common::storage::{NodeId, ShareId};
+use std::collections::HashMap;
+
+/// A share created by the current user, together with the decrypted name of the shared node
+#[derive(Debug, Clone)]
+pub struct SharedByMe {
+    pub share: OutgoingShare,
+    pub name: String,
+}
+
+/// returns all shares the current user created, newest first
+pub async fn get_shared_by_me() -> Result<Vec<SharedByMe>> {
+    let GetOutgoingSharesResponse::Ok(shares) = get_outgoing_shares().await?;
+
+    // a node may be shared multiple times, so every name is only decrypted once
+    let mut names: HashMap<NodeId, String> = HashMap::new();
+    let mut shared_by_me = Vec::with_capacity(shares.len());
+    for share in shares {
+        let name = match names.get(&share.node_id) {
+            Some(name) => name.clone(),
+            None => {
+                let name = get_node_name(share.node_id).await.unwrap_or_else(|e| {
+                    tracing::warn!("Failed to get name of shared node: {}", e);
+                    "Unknown".to_string()
+                });
+                names.insert(share.node_id, name.clone());
+                name
+            }
+        };
+        shared_by_me.push(SharedByMe { share, name });
+    }
+
+    Ok(shared_by_me)
+}
+
+async fn get_node_name(node_id: NodeId) -> Result<String> {
+    let path = get_accessible_path(node_id).await?;
+    let node = path
+        .into_iter()
+        .last()
+        .ok_or(anyhow!("path returned by server is empty"))?;
+    let NodeMetadata::V1(metadata) = node.metadata;
+    Ok(metadata.name)
+}
+
+/// revokes a share. Users who accepted the share lose their access.
+pub async fn revoke_share(share_id: ShareId) -> Result<()> {
+    match delete_share(share_id).await? {
+        DeleteShareResponse::Ok => Ok(()),
+        DeleteShareResponse::NotFound => Err(anyhow!("The share does not exist anymore")),
+        DeleteShareResponse::BadRequest(error) => Err(anyhow!("BadRequest: {error}")),
+    }
+}
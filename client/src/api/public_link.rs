@@ -0,0 +1,93 @@
+use crate::api::download_file::{decrypt_chunk_response, save_file};
+use crate::api::requests::public_link::{get_public_chunk, get_public_link, post_public_link};
+use crate::model::node::{DecryptedNode, NodeMetadata};
+use crate::utils;
+use crate::utils::encryption::node::decrypt_metadata;
+use crate::utils::encryption::{generate_aes256_key, unwrap_key, wrap_key};
+use crate::utils::share::{create_public_link_url, parse_public_link_url};
+use anyhow::{Result, anyhow};
+use chrono::NaiveDateTime;
+use crabdrive_common::payloads::node::request::public_link::PostPublicLinkRequest;
+use crabdrive_common::payloads::node::response::public_link::{
+    GetPublicLinkResponse, PostPublicLinkResponse, PublicNode,
+};
+use crabdrive_common::storage::PublicLinkId;
+use tracing::debug_span;
+
+/// returns a url that allows anyone to download the file, without an account. The url stops
+/// working after `expires_at`.
+pub async fn create_public_link(
+    node: &DecryptedNode,
+    expires_at: Option<NaiveDateTime>,
+) -> Result<String> {
+    let encryption_key = generate_aes256_key().await?;
+    let wrapped_metadata_key = wrap_key(&node.encryption_key, &encryption_key).await?;
+
+    let body = PostPublicLinkRequest {
+        wrapped_metadata_key,
+        expires_at,
+    };
+
+    match post_public_link(node.id, body).await? {
+        PostPublicLinkResponse::Ok(link_id) => create_public_link_url(&link_id, &encryption_key),
+        PostPublicLinkResponse::NotFound => {
+            Err(anyhow!("server returned NotFound on create public link"))
+        }
+        PostPublicLinkResponse::BadRequest(error) => {
+            Err(anyhow!("Server returned BadRequest: {:?}", error))
+        }
+    }
+}
+
+/// Decrypt the metadata of the file behind a public link. Returns the name of the file.
+pub async fn get_public_file_name(url: &str) -> Result<String> {
+    let (_, _, NodeMetadata::V1(metadata)) = get_public_file(url).await?;
+    Ok(metadata.name)
+}
+
+/// Download the current revision of the file behind a public link
+pub async fn download_public_file(url: &str) -> Result<()> {
+    let _guard = debug_span!("api::downloadPublicFile").entered();
+
+    let (link_id, public_node, NodeMetadata::V1(metadata)) = get_public_file(url).await?;
+
+    let file_key = metadata
+        .file_key
+        .ok_or(anyhow!("Cannot download folders or symlinks"))?;
+
+    let revision = public_node
+        .current_revision
+        .ok_or(anyhow!("This file does not have any contents"))?;
+
+    let mut chunks = Vec::with_capacity(revision.chunk_count as usize);
+    for i in 1..=revision.chunk_count {
+        let chunk_response = get_public_chunk(link_id, revision.id, i)
+            .await
+            .inspect_err(|e| tracing::error!("Failed to get chunk from server: {}", e))?;
+        chunks.push(decrypt_chunk_response(chunk_response, &revision, &file_key, i).await?);
+    }
+
+    save_file(
+        utils::file::combine_chunks(chunks)
+            .inspect_err(|e| tracing::error!("Failed to combine decrypted file chunks: {}", e))?,
+        &metadata.name,
+    )
+    .await
+}
+
+async fn get_public_file(url: &str) -> Result<(PublicLinkId, PublicNode, NodeMetadata)> {
+    let (link_id, wrapping_encryption_key) = parse_public_link_url(url)?;
+
+    let public_node = match get_public_link(link_id).await? {
+        GetPublicLinkResponse::Ok(public_node) => public_node,
+        GetPublicLinkResponse::NotFound => return Err(anyhow!("The link does not exist")),
+        GetPublicLinkResponse::Expired => return Err(anyhow!("The link has expired")),
+    };
+
+    // the metadata key was wrapped with the key in the url
+    let metadata_key =
+        unwrap_key(&public_node.wrapped_metadata_key, &wrapping_encryption_key).await?;
+    let metadata = decrypt_metadata(&public_node.encrypted_metadata, &metadata_key).await?;
+
+    Ok((link_id, public_node, metadata))
+}
@@ -34,6 +34,9 @@ pub async fn rename_node(node: DecryptedNode, new_name: String) -> Result<()> {
             "no such node: {}. Check if you have permission to access it",
             node.id
         )),
+        PatchNodeResponse::Forbidden => {
+            Err(anyhow!("You do not have permission to rename this node"))
+        }
         PatchNodeResponse::Conflict => Err(anyhow!("Please try again")),
     }
 }
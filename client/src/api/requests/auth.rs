@@ -1,8 +1,25 @@
 use anyhow::Result;
-use crabdrive_common::payloads::auth::response::info::SelfUserInfo;
+use crabdrive_common::payloads::auth::response::info::{GetSelfInfoResponse, SelfUserInfo};
 use crabdrive_common::payloads::auth::{
-    request::{login::PostLoginRequest, register::PostRegisterRequest},
-    response::{login::PostLoginResponse, register::PostRegisterResponse},
+    request::{
+        keys::PutKeyPairRequest,
+        login::{PostLoginRequest, PostLoginSecondFactorRequest, PostPreLoginRequest},
+        password::PostChangePasswordRequest,
+        recovery::{PostRecoveryKeysRequest, PostRecoveryResetRequest, PutRecoveryKeyRequest},
+        register::PostRegisterRequest,
+        totp::TotpCodeRequest,
+    },
+    response::{
+        keys::PutKeyPairResponse,
+        login::{PostLoginResponse, PostPreLoginResponse},
+        password::PostChangePasswordResponse,
+        recovery::{PostRecoveryKeysResponse, PostRecoveryResetResponse, PutRecoveryKeyResponse},
+        register::PostRegisterResponse,
+        totp::{
+            DeleteTotpResponse, PostTotpBackupCodesResponse, PostTotpConfirmResponse,
+            PostTotpResponse,
+        },
+    },
 };
 use crabdrive_common::routes;
 
@@ -11,6 +28,18 @@ use crate::{
     utils,
 };
 
+pub async fn post_pre_login(body: PostPreLoginRequest) -> Result<PostPreLoginResponse> {
+    let url = routes::auth::pre_login();
+    let body = RequestBody::Json(serde_json::to_string(&body)?);
+
+    let response = request(&url, RequestMethod::POST, body, None, true).await?;
+
+    let response_string = string_from_response(response).await?;
+    let response_object = serde_json::from_str(&response_string)?;
+
+    Ok(response_object)
+}
+
 pub async fn post_login(body: PostLoginRequest) -> Result<PostLoginResponse> {
     let url = crabdrive_common::routes::auth::login();
     let body = RequestBody::Json(serde_json::to_string(&body)?);
@@ -23,6 +52,20 @@ pub async fn post_login(body: PostLoginRequest) -> Result<PostLoginResponse> {
     Ok(response_object)
 }
 
+pub async fn post_login_second_factor(
+    body: PostLoginSecondFactorRequest,
+) -> Result<PostLoginResponse> {
+    let url = routes::auth::login_second_factor();
+    let body = RequestBody::Json(serde_json::to_string(&body)?);
+
+    let response = request(&url, RequestMethod::POST, body, None, true).await?;
+
+    let response_string = string_from_response(response).await?;
+    let response_object = serde_json::from_str(&response_string)?;
+
+    Ok(response_object)
+}
+
 pub async fn post_register(body: PostRegisterRequest) -> Result<PostRegisterResponse> {
     let url = routes::auth::register();
     let body = RequestBody::Json(serde_json::to_string(&body)?);
@@ -51,5 +94,76 @@ pub async fn post_logout() -> Result<()> {
 
 pub async fn get_self_user_info() -> Result<SelfUserInfo> {
     let url = routes::auth::info();
-    json_api_request(&url, RequestMethod::GET, ()).await
+    let GetSelfInfoResponse::Ok(info) = json_api_request(&url, RequestMethod::GET, ()).await?;
+    Ok(info)
+}
+
+pub async fn post_change_password(
+    body: PostChangePasswordRequest,
+) -> Result<PostChangePasswordResponse> {
+    let url = routes::auth::password();
+    json_api_request(&url, RequestMethod::POST, body).await
+}
+
+pub async fn post_upgrade_kdf(
+    body: PostChangePasswordRequest,
+) -> Result<PostChangePasswordResponse> {
+    let url = routes::auth::password_upgrade();
+    json_api_request(&url, RequestMethod::POST, body).await
+}
+
+pub async fn put_key_pair(body: PutKeyPairRequest) -> Result<PutKeyPairResponse> {
+    let url = routes::auth::key_pair();
+    json_api_request(&url, RequestMethod::PUT, body).await
+}
+
+pub async fn put_recovery_key(body: PutRecoveryKeyRequest) -> Result<PutRecoveryKeyResponse> {
+    let url = routes::auth::recovery_key();
+    json_api_request(&url, RequestMethod::PUT, body).await
+}
+
+pub async fn post_recovery_keys(body: PostRecoveryKeysRequest) -> Result<PostRecoveryKeysResponse> {
+    let url = routes::auth::recovery_keys();
+    let body = RequestBody::Json(serde_json::to_string(&body)?);
+
+    let response = request(&url, RequestMethod::POST, body, None, true).await?;
+
+    let response_string = string_from_response(response).await?;
+    let response_object = serde_json::from_str(&response_string)?;
+
+    Ok(response_object)
+}
+
+pub async fn post_recovery_reset(
+    body: PostRecoveryResetRequest,
+) -> Result<PostRecoveryResetResponse> {
+    let url = routes::auth::recovery_reset();
+    let body = RequestBody::Json(serde_json::to_string(&body)?);
+
+    let response = request(&url, RequestMethod::POST, body, None, true).await?;
+
+    let response_string = string_from_response(response).await?;
+    let response_object = serde_json::from_str(&response_string)?;
+
+    Ok(response_object)
+}
+
+pub async fn post_totp() -> Result<PostTotpResponse> {
+    let url = routes::auth::totp();
+    json_api_request(&url, RequestMethod::POST, ()).await
+}
+
+pub async fn post_totp_confirm(body: TotpCodeRequest) -> Result<PostTotpConfirmResponse> {
+    let url = routes::auth::totp_confirm();
+    json_api_request(&url, RequestMethod::POST, body).await
+}
+
+pub async fn delete_totp(body: TotpCodeRequest) -> Result<DeleteTotpResponse> {
+    let url = routes::auth::totp();
+    json_api_request(&url, RequestMethod::DELETE, body).await
+}
+
+pub async fn post_totp_backup_codes(body: TotpCodeRequest) -> Result<PostTotpBackupCodesResponse> {
+    let url = routes::auth::totp_backup_codes();
+    json_api_request(&url, RequestMethod::POST, body).await
 }
@@ -15,6 +15,7 @@ pub enum GetChunkResponse {
 pub enum PostChunkResponse {
     Created,
     NotFound,
+    Forbidden,
     BadRequest,
     Conflict,
     OutOfStorage,
@@ -66,6 +67,7 @@ pub async fn post_chunk(
     let parsed_response = match response.status() {
         201 => PostChunkResponse::Created,
         404 => PostChunkResponse::NotFound,
+        403 => PostChunkResponse::Forbidden,
         400 => PostChunkResponse::BadRequest,
         409 => PostChunkResponse::Conflict,
         413 => PostChunkResponse::OutOfStorage,
@@ -0,0 +1,93 @@
+use crate::api::requests::chunk::PostChunkResponse;
+use crate::api::requests::{
+    RequestBody, RequestMethod, json_api_request, public_json_api_request, request,
+};
+use anyhow::{Result, anyhow};
+use crabdrive_common::payloads::node::request::file_drop::{
+    PostAdoptDroppedFilesRequest, PostDropFileRequest, PostFileDropRequest,
+};
+use crabdrive_common::payloads::node::response::file_drop::{
+    GetDroppedFilesResponse, GetFileDropResponse, PostAdoptDroppedFilesResponse,
+    PostDropCommitResponse, PostDropFileResponse, PostFileDropResponse,
+};
+use crabdrive_common::routes;
+use crabdrive_common::storage::{ChunkIndex, FileDropId, NodeId, RevisionId};
+use web_sys::Response;
+use web_sys::js_sys::Uint8Array;
+
+pub async fn post_file_drop(
+    node_id: NodeId,
+    body: PostFileDropRequest,
+) -> Result<PostFileDropResponse> {
+    let url = routes::node::share::file_drop(node_id);
+    json_api_request(&url, RequestMethod::POST, body).await
+}
+
+pub async fn get_dropped_files(node_id: NodeId) -> Result<GetDroppedFilesResponse> {
+    let url = routes::node::share::dropped_files(node_id);
+    json_api_request(&url, RequestMethod::GET, ()).await
+}
+
+pub async fn post_adopt_dropped_files(
+    node_id: NodeId,
+    body: PostAdoptDroppedFilesRequest,
+) -> Result<PostAdoptDroppedFilesResponse> {
+    let url = routes::node::share::adopt_dropped_files(node_id);
+    json_api_request(&url, RequestMethod::POST, body).await
+}
+
+pub async fn get_file_drop(drop_id: FileDropId) -> Result<GetFileDropResponse> {
+    let url = routes::drop::by_id(drop_id);
+    public_json_api_request(&url, RequestMethod::GET, ()).await
+}
+
+pub async fn post_drop_file(
+    drop_id: FileDropId,
+    body: PostDropFileRequest,
+) -> Result<PostDropFileResponse> {
+    let url = routes::drop::files(drop_id);
+    public_json_api_request(&url, RequestMethod::POST, body).await
+}
+
+pub async fn post_drop_chunk(
+    drop_id: FileDropId,
+    node_id: NodeId,
+    version_id: RevisionId,
+    chunk_index: ChunkIndex,
+    body: Uint8Array,
+) -> Result<PostChunkResponse> {
+    let url = routes::drop::chunks(drop_id, node_id, version_id, chunk_index);
+
+    let response: Response = request(
+        &url,
+        RequestMethod::POST,
+        RequestBody::Bytes(body),
+        None,
+        true,
+    )
+    .await?;
+
+    let parsed_response = match response.status() {
+        201 => PostChunkResponse::Created,
+        404 => PostChunkResponse::NotFound,
+        400 => PostChunkResponse::BadRequest,
+        413 => PostChunkResponse::OutOfStorage,
+        _ => {
+            return Err(anyhow!(
+                "unexpected status code on post drop chunk: {}",
+                response.status()
+            ));
+        }
+    };
+
+    Ok(parsed_response)
+}
+
+pub async fn post_drop_commit(
+    drop_id: FileDropId,
+    node_id: NodeId,
+    version_id: RevisionId,
+) -> Result<PostDropCommitResponse> {
+    let url = routes::drop::commit(drop_id, node_id, version_id);
+    public_json_api_request(&url, RequestMethod::POST, ()).await
+}
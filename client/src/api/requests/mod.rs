@@ -1,8 +1,10 @@
 pub mod auth;
 pub mod chunk;
 pub mod file;
+pub mod file_drop;
 pub mod folder;
 pub mod node;
+pub mod public_link;
 pub mod share;
 
 use crate::utils::auth::{get_token, go_to_login};
@@ -10,6 +12,7 @@ use crate::utils::browser::{LocalStorage, SessionStorage, get_origin, get_window
 use crate::utils::error::{dyn_into, future_from_js_promise, wrap_js_err};
 use anyhow::{Context, Result, anyhow};
 use crabdrive_common::payloads::auth::response::refresh::PostRefreshResponse;
+use crabdrive_common::payloads::error::ErrorResponse;
 use crabdrive_common::routes;
 use leptos::wasm_bindgen::JsValue;
 use serde::Serialize;
@@ -25,7 +28,6 @@ enum RequestMethod {
     POST,
     PATCH,
     DELETE,
-    #[cfg(test)]
     PUT,
 }
 
@@ -189,6 +191,15 @@ where
 
     let response: Response = request(url, request_method, body, token, true).await?;
 
+    // too many failed attempts, e.g. when signing in
+    if response.status() == 429 {
+        let retry_after = response.headers().get("Retry-After").ok().flatten();
+        return Err(match retry_after {
+            Some(seconds) => anyhow!("Too many attempts. Please try again in {seconds} seconds."),
+            None => anyhow!("Too many attempts. Please try again later."),
+        });
+    }
+
     // if the server returns Unauthorized, store the current url in the storage to be able to redirect to it on login
     if response.status() == 401 {
         go_to_login()?;
@@ -198,9 +209,53 @@ where
 
     let response_string = string_from_response(response).await?;
 
-    let response_object = serde_json::from_str(&response_string)
-        .map_err(|_| anyhow!("could not parse json response: {:?}", response_string))?;
-    Ok(response_object)
+    parse_json_response(&response_string)
+}
+
+/// Like [`json_api_request`], but without authentication. Used for the routes that can be used
+/// without an account, e.g. public links.
+async fn public_json_api_request<BodyT, ResponseT>(
+    url: &str,
+    request_method: RequestMethod,
+    body: BodyT,
+) -> Result<ResponseT>
+where
+    ResponseT: DeserializeOwned,
+    BodyT: Serialize,
+{
+    let json = serde_json::to_string(&body)?;
+
+    let body = if json == "null" {
+        RequestBody::Empty
+    } else {
+        RequestBody::Json(json)
+    };
+
+    let response: Response = request(url, request_method, body, None, true).await?;
+
+    let response_string = string_from_response(response).await?;
+
+    parse_json_response(&response_string)
+}
+
+/// Parse the body of a response. Errors reported by the server (see [`ErrorResponse`]) are turned
+/// into an `Err`, which includes the request ID.
+fn parse_json_response<ResponseT: DeserializeOwned>(response_string: &str) -> Result<ResponseT> {
+    if let Ok(response_object) = serde_json::from_str(response_string) {
+        return Ok(response_object);
+    }
+
+    match serde_json::from_str::<ErrorResponse>(response_string) {
+        Ok(ErrorResponse { error }) => Err(anyhow!(
+            "{} (request id: {})",
+            error.details,
+            error.request_id.as_deref().unwrap_or("unknown")
+        )),
+        Err(_) => Err(anyhow!(
+            "could not parse json response: {:?}",
+            response_string
+        )),
+    }
 }
 
 async fn string_from_response(response: Response) -> Result<String> {
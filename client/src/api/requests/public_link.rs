@@ -0,0 +1,50 @@
+use crate::api::requests::chunk::GetChunkResponse;
+use crate::api::requests::{
+    RequestBody, RequestMethod, json_api_request, public_json_api_request, request,
+    uint8array_from_response,
+};
+use anyhow::{Result, anyhow};
+use crabdrive_common::payloads::node::request::public_link::PostPublicLinkRequest;
+use crabdrive_common::payloads::node::response::public_link::{
+    GetPublicLinkResponse, PostPublicLinkResponse,
+};
+use crabdrive_common::routes;
+use crabdrive_common::storage::{ChunkIndex, NodeId, PublicLinkId, RevisionId};
+use web_sys::Response;
+
+pub async fn post_public_link(
+    node_id: NodeId,
+    body: PostPublicLinkRequest,
+) -> Result<PostPublicLinkResponse> {
+    let url = routes::node::share::public_link(node_id);
+    json_api_request(&url, RequestMethod::POST, body).await
+}
+
+pub async fn get_public_link(link_id: PublicLinkId) -> Result<GetPublicLinkResponse> {
+    let url = routes::public::by_id(link_id);
+    public_json_api_request(&url, RequestMethod::GET, ()).await
+}
+
+pub async fn get_public_chunk(
+    link_id: PublicLinkId,
+    version_id: RevisionId,
+    chunk_index: ChunkIndex,
+) -> Result<GetChunkResponse> {
+    let url = routes::public::chunks(link_id, version_id, chunk_index);
+
+    let response: Response =
+        request(&url, RequestMethod::GET, RequestBody::Empty, None, true).await?;
+
+    let parsed_response = match response.status() {
+        200 => GetChunkResponse::Ok(uint8array_from_response(response).await?),
+        404 => GetChunkResponse::NotFound,
+        _ => {
+            return Err(anyhow!(
+                "unexpected status code on get public chunk: {}",
+                response.status()
+            ));
+        }
+    };
+
+    Ok(parsed_response)
+}
@@ -1,10 +1,11 @@
 use crate::api::requests::{RequestMethod, json_api_request};
 use anyhow::Result;
 use crabdrive_common::payloads::node::request::share::{
-    PostAcceptShareRequest, PostShareNodeRequest,
+    PostAcceptShareRequest, PostShareNodeRequest, PostShareNodeWithUserRequest,
 };
 use crabdrive_common::payloads::node::response::share::{
-    GetAcceptShareInfoResponse, GetAcceptedSharedResponse, GetNodeShareInfo,
+    DeleteShareResponse, GetAcceptShareInfoResponse, GetAcceptedSharedResponse, GetNodeShareInfo,
+    GetOutgoingSharesResponse, GetShareInboxResponse, GetUserPublicKeyResponse,
     PostAcceptShareResponse, PostShareNodeResponse,
 };
 use crabdrive_common::routes;
@@ -40,3 +41,36 @@ pub async fn get_accepted_shared_nodes() -> Result<GetAcceptedSharedResponse> {
     let url = routes::node::share::get_accepted_shared();
     json_api_request(&url, RequestMethod::GET, ()).await
 }
+
+pub async fn post_share_node_with_user(
+    node_id: NodeId,
+    body: PostShareNodeWithUserRequest,
+) -> Result<PostShareNodeResponse> {
+    let url = routes::node::share::share_with_user(node_id);
+    json_api_request(&url, RequestMethod::POST, body).await
+}
+
+pub async fn get_user_public_key(username: &str) -> Result<GetUserPublicKeyResponse> {
+    let url = routes::node::share::user_public_key(username);
+    json_api_request(&url, RequestMethod::GET, ()).await
+}
+
+pub async fn get_share_inbox() -> Result<GetShareInboxResponse> {
+    let url = routes::node::share::inbox();
+    json_api_request(&url, RequestMethod::GET, ()).await
+}
+
+pub async fn delete_inbox_share(share_id: ShareId) -> Result<DeleteShareResponse> {
+    let url = routes::node::share::inbox_share(share_id);
+    json_api_request(&url, RequestMethod::DELETE, ()).await
+}
+
+pub async fn get_outgoing_shares() -> Result<GetOutgoingSharesResponse> {
+    let url = routes::node::share::outgoing_shares();
+    json_api_request(&url, RequestMethod::GET, ()).await
+}
+
+pub async fn delete_share(share_id: ShareId) -> Result<DeleteShareResponse> {
+    let url = routes::node::share::share_by_id(share_id);
+    json_api_request(&url, RequestMethod::DELETE, ()).await
+}
@@ -0,0 +1,90 @@
+use crate::api::requests::share::{delete_inbox_share, get_share_inbox, post_accept_share};
+use crate::model::encryption::{MetadataKey, PrivateKey};
+use crate::model::node::NodeMetadata;
+use crate::utils::encryption::auth::get_master_key;
+use crate::utils::encryption::key_pair::{decrypt_with, get_private_key};
+use crate::utils::encryption::node::decrypt_metadata;
+use crate::utils::encryption::{unwrap_key, wrap_key};
+use anyhow::{Result, anyhow};
+use chrono::NaiveDateTime;
+use crabdrive_common::payloads::node::request::share::PostAcceptShareRequest;
+use crabdrive_common::payloads::node::response::share::{
+    DeleteShareResponse, GetShareInboxResponse, PostAcceptShareResponse, ShareOffer,
+};
+use crabdrive_common::storage::{NodeId, ShareId, SharePermission};
+
+/// A share that was sent directly to the current user and has not been accepted yet
+#[derive(Debug, Clone)]
+pub struct IncomingShare {
+    pub share_id: ShareId,
+    pub node_id: NodeId,
+    pub shared_by: String,
+    pub time_shared: NaiveDateTime,
+    pub permission: SharePermission,
+    pub expires_at: Option<NaiveDateTime>,
+    pub name: String,
+    metadata_key: MetadataKey,
+}
+
+/// returns the shares in the inbox of the current user, with decrypted names
+pub async fn get_incoming_shares() -> Result<Vec<IncomingShare>> {
+    let GetShareInboxResponse::Ok(offers) = get_share_inbox().await?;
+
+    let private_key = get_private_key()?;
+
+    let mut shares = Vec::with_capacity(offers.len());
+    for offer in offers {
+        shares.push(decrypt_offer(offer, &private_key).await?);
+    }
+    Ok(shares)
+}
+
+async fn decrypt_offer(offer: ShareOffer, private_key: &PrivateKey) -> Result<IncomingShare> {
+    // the key of the share was encrypted with our public key, it unwraps the metadata key
+    let share_key: MetadataKey = decrypt_with(private_key, &offer.encrypted_share_key)
+        .await?
+        .try_into()
+        .map_err(|_| anyhow!("Invalid share key"))?;
+    let metadata_key = unwrap_key(&offer.wrapped_metadata_key, &share_key).await?;
+
+    let NodeMetadata::V1(metadata) =
+        decrypt_metadata(&offer.encrypted_metadata, &metadata_key).await?;
+
+    Ok(IncomingShare {
+        share_id: offer.share_id,
+        node_id: offer.node_id,
+        shared_by: offer.shared_by,
+        time_shared: offer.time_shared,
+        permission: offer.permission,
+        expires_at: offer.expires_at,
+        name: metadata.name,
+        metadata_key,
+    })
+}
+
+pub async fn accept_incoming_share(share: &IncomingShare) -> Result<NodeId> {
+    let master_key = get_master_key()?;
+
+    // wrap the key with our own key to be able to decrypt it later
+    let new_wrapped_key = wrap_key(&share.metadata_key, &master_key).await?;
+
+    let body = PostAcceptShareRequest {
+        new_wrapped_metadata_key: new_wrapped_key,
+    };
+    match post_accept_share(share.share_id, body).await? {
+        PostAcceptShareResponse::Ok => Ok(share.node_id),
+        PostAcceptShareResponse::NotFound => {
+            Err(anyhow!("Server returned NotFound when accepting node"))
+        }
+        PostAcceptShareResponse::BadRequest(error) => Err(anyhow!("BadRequest: {error}")),
+        PostAcceptShareResponse::Expired => Err(anyhow!("the share has expired")),
+    }
+}
+
+pub async fn decline_incoming_share(share_id: ShareId) -> Result<()> {
+    match delete_inbox_share(share_id).await? {
+        DeleteShareResponse::Ok => Ok(()),
+        DeleteShareResponse::NotFound => Err(anyhow!("The share does not exist anymore")),
+        DeleteShareResponse::BadRequest(error) => Err(anyhow!("BadRequest: {error}")),
+    }
+}
@@ -1,13 +1,29 @@
-use crate::api::requests::share::post_share_node;
+use crate::api::requests::share::{
+    get_user_public_key, post_share_node, post_share_node_with_user,
+};
 use crate::model::node::DecryptedNode;
+use crate::utils::encryption::key_pair::encrypt_for;
 use crate::utils::encryption::{generate_aes256_key, wrap_key};
 use crate::utils::share::create_share_url;
-use anyhow::Result;
-use crabdrive_common::payloads::node::request::share::PostShareNodeRequest;
-use crabdrive_common::payloads::node::response::share::PostShareNodeResponse;
+use anyhow::{Result, anyhow};
+use chrono::NaiveDateTime;
+use crabdrive_common::payloads::node::request::share::{
+    PostShareNodeRequest, PostShareNodeWithUserRequest,
+};
+use crabdrive_common::payloads::node::response::share::{
+    GetUserPublicKeyResponse, PostShareNodeResponse,
+};
+use crabdrive_common::storage::SharePermission;
 
-/// returns the url that a user can use to accept the share
-pub async fn share_node(node: &DecryptedNode) -> Result<String> {
+/// returns the url that a user can use to accept the share with the given permission. The url can
+/// not be accepted after `expires_at`. If `revoke_on_expiry` is set, users who accepted the share
+/// also lose their access at that time.
+pub async fn share_node(
+    node: &DecryptedNode,
+    permission: SharePermission,
+    expires_at: Option<NaiveDateTime>,
+    revoke_on_expiry: bool,
+) -> Result<String> {
     let encryption_key = generate_aes256_key().await?;
 
     // TODO maybe change the type of wrap key to be more consistent
@@ -15,6 +31,9 @@ pub async fn share_node(node: &DecryptedNode) -> Result<String> {
 
     let body = PostShareNodeRequest {
         wrapped_metadata_key,
+        permission,
+        expires_at,
+        revoke_on_expiry,
     };
 
     let response = post_share_node(node.id, body).await?;
@@ -32,3 +51,40 @@ pub async fn share_node(node: &DecryptedNode) -> Result<String> {
         }
     }
 }
+
+/// shares the node with the user directly. The share shows up in the inbox of the user, so no url
+/// needs to be exchanged. The key of the share is encrypted with the public key of the user. If
+/// `revoke_on_expiry` is set, the user loses their access at `expires_at`.
+pub async fn share_node_with_user(
+    node: &DecryptedNode,
+    username: &str,
+    permission: SharePermission,
+    expires_at: Option<NaiveDateTime>,
+    revoke_on_expiry: bool,
+) -> Result<()> {
+    let public_key = match get_user_public_key(username).await? {
+        GetUserPublicKeyResponse::Ok(public_key) => public_key,
+        GetUserPublicKeyResponse::NotFound => {
+            return Err(anyhow!("{username} cannot receive shares"));
+        }
+    };
+
+    let encryption_key = generate_aes256_key().await?;
+    let wrapped_metadata_key = wrap_key(&node.encryption_key, &encryption_key).await?;
+    let encrypted_share_key = encrypt_for(&public_key, &encryption_key).await?;
+
+    let body = PostShareNodeWithUserRequest {
+        username: username.to_string(),
+        wrapped_metadata_key,
+        encrypted_share_key,
+        permission,
+        expires_at,
+        revoke_on_expiry,
+    };
+
+    match post_share_node_with_user(node.id, body).await? {
+        PostShareNodeResponse::Ok(_) => Ok(()),
+        PostShareNodeResponse::NotFound => Err(anyhow!("server returned NotFound on share node")),
+        PostShareNodeResponse::BadRequest(error) => Err(anyhow!("{error}")),
+    }
+}
@@ -1,4 +1,6 @@
 use crate::api::auth::logout;
+use crate::components::change_password_button::ChangePasswordButton;
+use crate::components::two_factor_button::TwoFactorButton;
 use crate::constants::DEFAULT_TOAST_TIMEOUT;
 use crate::utils::auth::is_authenticated;
 use crate::utils::browser::SessionStorage;
@@ -102,6 +104,8 @@ pub fn AccountSider() -> impl IntoView {
                     Trash
                 </Button>
             </ButtonGroup>
+            <ChangePasswordButton />
+            <TwoFactorButton />
             <Button
                 on_click=move |_| {
                     logout_action.dispatch(());
@@ -0,0 +1,99 @@
+use crate::api::auth::change_password;
+use crate::components::basic::custom_dialog::CustomDialog;
+use crate::constants::DEFAULT_TOAST_TIMEOUT;
+use crate::utils::auth::is_valid_password;
+use leptos::prelude::*;
+use thaw::{
+    Button, Flex, Input, InputType, Toast, ToastIntent, ToastOptions, ToastTitle, ToasterInjection,
+};
+
+#[component]
+pub fn ChangePasswordButton() -> impl IntoView {
+    let toaster = ToasterInjection::expect_context();
+
+    let add_toast = move |text: String, intent: ToastIntent| {
+        toaster.dispatch_toast(
+            move || {
+                view! {
+                    <Toast>
+                        <ToastTitle>{text}</ToastTitle>
+                    </Toast>
+                }
+            },
+            ToastOptions::default()
+                .with_intent(intent)
+                .with_timeout(DEFAULT_TOAST_TIMEOUT),
+        )
+    };
+
+    let dialog_open = RwSignal::new(false);
+    let old_password = RwSignal::new(String::new());
+    let new_password = RwSignal::new(String::new());
+
+    let change_password_action = Action::new_local(move |input: &(String, String)| {
+        let (old_password, new_password) = input.to_owned();
+        async move {
+            change_password(&old_password, &new_password)
+                .await
+                .map_err(|err| err.to_string())
+        }
+    });
+
+    Effect::new(move || {
+        let status = change_password_action.value().get();
+        if status.is_some() {
+            match status.unwrap() {
+                Ok(_) => add_toast("Password changed".to_string(), ToastIntent::Success),
+                Err(e) => add_toast(
+                    format!("Failed to change password: {}", e),
+                    ToastIntent::Error,
+                ),
+            }
+        }
+    });
+
+    let on_confirm = Callback::new(move |_: ()| {
+        change_password_action.dispatch((old_password.get(), new_password.get()));
+        old_password.set(String::new());
+        new_password.set(String::new());
+        dialog_open.set(false);
+    });
+
+    view! {
+        <Button
+            on_click=move |_| dialog_open.set(true)
+            block=true
+            icon=icondata_mdi::MdiLockReset
+            class="mt-3"
+        >
+            "Change password"
+        </Button>
+
+        <CustomDialog
+            open=dialog_open
+            title="Change password"
+            show_cancel=true
+            show_confirm=true
+            confirm_label="Change"
+            confirm_disabled=Signal::derive(move || {
+                old_password.get().is_empty() || !is_valid_password(&new_password.get())
+            })
+            on_confirm
+        >
+            <Flex vertical=true>
+                <Input
+                    value=old_password
+                    placeholder="Current password"
+                    input_type=InputType::Password
+                    autocomplete="current-password"
+                />
+                <Input
+                    value=new_password
+                    placeholder="New password (at least 12 characters)"
+                    input_type=InputType::Password
+                    autocomplete="new-password"
+                />
+            </Flex>
+        </CustomDialog>
+    }
+}
@@ -0,0 +1,97 @@
+use crate::api::{adopt_dropped_files, get_dropped_file_count};
+use crate::constants::{DEFAULT_TOAST_TIMEOUT, INFINITE_TOAST_TIMEOUT};
+use crate::model::node::DecryptedNode;
+use leptos::prelude::*;
+use thaw::{
+    Button, ButtonAppearance, Toast, ToastIntent, ToastOptions, ToastTitle, ToasterInjection,
+};
+
+/// Shows a button to add the files, which were uploaded through a file drop link, to the folder.
+/// Hidden if there are no such files.
+#[component]
+pub fn DroppedFilesButton(
+    #[prop(into)] current_node: Signal<DecryptedNode>,
+    on_adopted: Callback<()>,
+) -> impl IntoView {
+    let toaster = ToasterInjection::expect_context();
+    let add_toast = move |text: String, intent: ToastIntent| {
+        toaster.dispatch_toast(
+            move || {
+                view! {
+                    <Toast>
+                        <ToastTitle>{text}</ToastTitle>
+                    </Toast>
+                }
+            },
+            ToastOptions::default().with_intent(intent).with_timeout(
+                if matches!(intent, ToastIntent::Error) {
+                    INFINITE_TOAST_TIMEOUT
+                } else {
+                    DEFAULT_TOAST_TIMEOUT
+                },
+            ),
+        )
+    };
+
+    let count_res = LocalResource::new(move || {
+        let node = current_node.get();
+        async move {
+            get_dropped_file_count(&node)
+                .await
+                .inspect_err(|e| tracing::error!("Failed to get dropped files: {}", e))
+                .unwrap_or(0)
+        }
+    });
+    let count = Signal::derive(move || count_res.get().unwrap_or(0));
+
+    let adopt_action = Action::new_local(move |_: &()| {
+        let mut node = current_node.get();
+        async move {
+            adopt_dropped_files(&mut node)
+                .await
+                .map_err(|err| err.to_string())
+        }
+    });
+
+    Effect::new(move || {
+        let Some(result) = adopt_action.value().get() else {
+            return;
+        };
+        match result {
+            Ok(adopted) => {
+                add_toast(
+                    format!(
+                        "Added {} dropped file{}",
+                        adopted,
+                        if adopted == 1 { "" } else { "s" }
+                    ),
+                    ToastIntent::Success,
+                );
+                on_adopted.run(());
+            }
+            Err(e) => add_toast(
+                format!("Failed to add dropped files: {}", e),
+                ToastIntent::Error,
+            ),
+        }
+        count_res.refetch();
+    });
+
+    view! {
+        <Show when=move || { count.get() > 0 }>
+            <Button
+                appearance=ButtonAppearance::Secondary
+                icon=icondata_mdi::MdiInboxArrowDown
+                disabled=adopt_action.pending()
+                on_click=move |_| {
+                    adopt_action.dispatch(());
+                }
+            >
+                {move || {
+                    let count = count.get();
+                    format!("Add {} dropped file{}", count, if count == 1 { "" } else { "s" })
+                }}
+            </Button>
+        </Show>
+    }
+}
@@ -1,3 +1,4 @@
+use crate::components::dropped_files_button::DroppedFilesButton;
 use crate::components::file_creation_button::FileCreationButton;
 use crate::components::folder_creation_button::FolderCreationButton;
 use crate::model::node::DecryptedNode;
@@ -21,6 +22,7 @@ pub fn FolderBottomBar(
                     parent_node=Signal::derive(move || current_node.get())
                     on_created=on_children_modified
                 />
+                <DroppedFilesButton current_node on_adopted=on_children_modified />
             </Space>
         </Space>
     }
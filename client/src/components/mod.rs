@@ -2,7 +2,9 @@ pub mod account_sider;
 pub mod basic;
 pub mod content_frame;
 
+mod change_password_button;
 mod data_provider;
+mod dropped_files_button;
 mod file_creation_button;
 mod file_download_button;
 mod file_history_button;
@@ -17,8 +19,11 @@ mod node_list;
 mod node_share_button;
 mod path_breadcrumb;
 mod revision_list;
+mod share_inbox;
+mod shared_by_me_list;
 mod shared_view;
 mod trash_empty_button;
 mod trash_item_delete_button;
 mod trash_item_restore_button;
 mod trash_view;
+mod two_factor_button;
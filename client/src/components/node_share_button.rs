@@ -1,13 +1,26 @@
-use crate::api::share_node;
+use crate::api::{create_file_drop, create_public_link, share_node, share_node_with_user};
+use crate::components::basic::custom_dialog::CustomDialog;
 use crate::constants::INFINITE_TOAST_TIMEOUT;
 use crate::model::node::DecryptedNode;
 use crate::model::node::NodeMetadata;
+use chrono::{Duration, NaiveDateTime, Utc};
+use crabdrive_common::storage::{NodeType, SharePermission};
 use leptos::prelude::*;
 use leptos_use::{UseClipboardReturn, use_clipboard};
 use thaw::{
-    Button, ButtonAppearance, Toast, ToastIntent, ToastOptions, ToastTitle, ToasterInjection,
+    Button, ButtonAppearance, Checkbox, Flex, Input, Menu, MenuItem, MenuTrigger, MenuTriggerType,
+    Text, Toast, ToastIntent, ToastOptions, ToastTitle, ToasterInjection,
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkType {
+    Share(SharePermission),
+    /// Download-only link, which does not require an account
+    Public,
+    /// Upload-only link for folders, which does not require an account
+    FileDrop,
+}
+
 #[component]
 pub fn NodeShareButton(#[prop(into)] node: Signal<DecryptedNode>) -> impl IntoView {
     let UseClipboardReturn { copy, .. } = use_clipboard();
@@ -33,19 +46,96 @@ pub fn NodeShareButton(#[prop(into)] node: Signal<DecryptedNode>) -> impl IntoVi
         metadata.name
     });
 
-    let create_link_action = Action::new_local(|input: &DecryptedNode| {
-        let node = input.to_owned();
-        async move { share_node(&node).await.map_err(|err| err.to_string()) }
+    let dialog_open = RwSignal::new(false);
+    let link_type = RwSignal::new(LinkType::Share(SharePermission::View));
+    // empty if the link should not expire
+    let expires_in_days = RwSignal::new(String::new());
+    let revoke_on_expiry = RwSignal::new(false);
+    // empty if a link should be created instead of sharing with a user directly
+    let username = RwSignal::new(String::new());
+
+    let expires_at = Signal::derive(move || {
+        let days = expires_in_days.get();
+        let days = days.trim();
+        if days.is_empty() {
+            return Ok(None);
+        }
+        match days.parse::<i64>() {
+            Ok(days) if days > 0 => Ok(Some(Utc::now().naive_utc() + Duration::days(days))),
+            _ => Err(()),
+        }
     });
-    let handle_share = move |_| {
-        create_link_action.dispatch(node.get().clone());
+
+    // resolves to the url of the link, or `None` if the node was shared with a user directly
+    let create_link_action = Action::new_local(
+        |input: &(DecryptedNode, LinkType, Option<NaiveDateTime>, bool, String)| {
+            let (node, link_type, expires_at, revoke_on_expiry, username) = input.to_owned();
+            async move {
+                let url = match link_type {
+                    LinkType::Share(permission) if !username.is_empty() => share_node_with_user(
+                        &node,
+                        &username,
+                        permission,
+                        expires_at,
+                        revoke_on_expiry,
+                    )
+                    .await
+                    .map(|_| None),
+                    LinkType::Share(permission) => {
+                        share_node(&node, permission, expires_at, revoke_on_expiry)
+                            .await
+                            .map(Some)
+                    }
+                    LinkType::Public => create_public_link(&node, expires_at).await.map(Some),
+                    LinkType::FileDrop => create_file_drop(&node, expires_at).await.map(Some),
+                };
+                url.map_err(|err| err.to_string())
+            }
+        },
+    );
+    let on_select = move |key: &str| {
+        let selected_link_type = match key {
+            "view" => LinkType::Share(SharePermission::View),
+            "edit" => LinkType::Share(SharePermission::Edit),
+            "manage" => LinkType::Share(SharePermission::Manage),
+            "public" => LinkType::Public,
+            "file_drop" => LinkType::FileDrop,
+            _ => return,
+        };
+        link_type.set(selected_link_type);
+        expires_in_days.set(String::new());
+        revoke_on_expiry.set(false);
+        username.set(String::new());
+        dialog_open.set(true);
     };
 
+    let on_confirm = Callback::new(move |_: ()| {
+        let Ok(expires_at) = expires_at.get() else {
+            return;
+        };
+        dialog_open.set(false);
+        create_link_action.dispatch((
+            node.get().clone(),
+            link_type.get(),
+            expires_at,
+            revoke_on_expiry.get(),
+            username.get().trim().to_string(),
+        ));
+    });
+
     Effect::new(move || {
         let status = create_link_action.value().get();
         if status.is_some() {
             match status.unwrap() {
-                Ok(url) => {
+                Ok(None) => add_toast(
+                    format!(
+                        "Shared {} with {}",
+                        file_name.get(),
+                        username.get_untracked().trim()
+                    ),
+                    ToastIntent::Success,
+                ),
+                Ok(Some(url)) => {
                     copy(&url);
                     add_toast(
                         format!(
@@ -68,13 +158,69 @@ pub fn NodeShareButton(#[prop(into)] node: Signal<DecryptedNode>) -> impl IntoVi
     });
 
     view! {
-        <Button
-            on_click=handle_share
-            appearance=ButtonAppearance::Secondary
-            icon=icondata_mdi::MdiShare
-            block=true
+        <Menu on_select trigger_type=MenuTriggerType::Hover>
+            <MenuTrigger slot>
+                <Button appearance=ButtonAppearance::Secondary icon=icondata_mdi::MdiShare block=true>
+                    "Share"
+                </Button>
+            </MenuTrigger>
+            <MenuItem value="view" icon=icondata_mdi::MdiEyeOutline>
+                "Can view"
+            </MenuItem>
+            <MenuItem value="edit" icon=icondata_mdi::MdiPencilOutline>
+                "Can edit"
+            </MenuItem>
+            <MenuItem value="manage" icon=icondata_mdi::MdiAccountMultipleOutline>
+                "Can edit and share"
+            </MenuItem>
+            <Show when=move || node.get().node_type == NodeType::File>
+                <MenuItem value="public" icon=icondata_mdi::MdiEarth>
+                    "Public download link"
+                </MenuItem>
+            </Show>
+            <Show when=move || node.get().node_type == NodeType::Folder>
+                <MenuItem value="file_drop" icon=icondata_mdi::MdiInboxArrowDown>
+                    "File drop link"
+                </MenuItem>
+            </Show>
+        </Menu>
+
+        <CustomDialog
+            open=dialog_open
+            title=Signal::derive(move || format!("Share '{}'", file_name.get()))
+            show_cancel=true
+            show_confirm=true
+            confirm_label=Signal::derive(move || {
+                let label = if username.get().trim().is_empty() { "Create link" } else { "Share" };
+                Some(label.to_string())
+            })
+            confirm_disabled=Signal::derive(move || expires_at.get().is_err())
+            on_confirm
         >
-            "Share"
-        </Button>
+            <Flex vertical=true class="max-w-100">
+                <Text>
+                    "The link expires after the given amount of days. Leave the field empty to create a link that does not expire."
+                </Text>
+                <Input value=expires_in_days placeholder="Days until the link expires" />
+                <Show when=move || link_type.get() == LinkType::Public>
+                    <Text>"Anyone with the link can download the file without an account."</Text>
+                </Show>
+                <Show when=move || link_type.get() == LinkType::FileDrop>
+                    <Text>
+                        "Anyone with the link can upload files into this folder without an account, but cannot see any of its contents. Uploaded files appear once you add them to the folder."
+                    </Text>
+                </Show>
+                <Show when=move || matches!(link_type.get(), LinkType::Share(_))>
+                    <Text>
+                        "Enter a username to share directly with that user instead of creating a link."
+                    </Text>
+                    <Input value=username placeholder="Username (optional)" />
+                    <Checkbox
+                        checked=revoke_on_expiry
+                        label="End the access of users who accepted the share, once it expires"
+                    />
+                </Show>
+            </Flex>
+        </CustomDialog>
     }
 }
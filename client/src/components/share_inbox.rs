@@ -0,0 +1,115 @@
+use crate::api::{
+    IncomingShare, accept_incoming_share, decline_incoming_share, get_incoming_shares,
+};
+use crate::components::basic::resource_wrapper::ResourceWrapper;
+use crate::constants::DEFAULT_TOAST_TIMEOUT;
+use crate::utils::ui::{format_date_time, format_share_permission};
+use leptos::prelude::*;
+use thaw::{
+    Button, ButtonAppearance, Space, SpaceAlign, Text, Toast, ToastIntent, ToastOptions,
+    ToastTitle, ToasterInjection,
+};
+
+/// Lists the shares other users sent directly to the current user
+#[component]
+pub fn ShareInbox(on_accepted: Callback<()>) -> impl IntoView {
+    let toaster = ToasterInjection::expect_context();
+    let add_toast = move |text: String| {
+        toaster.dispatch_toast(
+            move || {
+                view! {
+                    <Toast>
+                        <ToastTitle>{text}</ToastTitle>
+                    </Toast>
+                }
+            },
+            ToastOptions::default()
+                .with_intent(ToastIntent::Error)
+                .with_timeout(DEFAULT_TOAST_TIMEOUT),
+        )
+    };
+
+    let inbox_res = LocalResource::new(move || async move {
+        get_incoming_shares().await.map_err(|err| err.to_string())
+    });
+
+    // true if the share should be accepted, false if it should be declined
+    let respond_action = Action::new_local(move |input: &(IncomingShare, bool)| {
+        let (share, accept) = input.to_owned();
+        async move {
+            let result = if accept {
+                accept_incoming_share(&share).await.map(|_| ())
+            } else {
+                decline_incoming_share(share.share_id).await
+            };
+            result.map(|_| accept).map_err(|err| err.to_string())
+        }
+    });
+
+    Effect::new(move || {
+        let Some(result) = respond_action.value().get() else {
+            return;
+        };
+        match result {
+            Ok(true) => on_accepted.run(()),
+            Ok(false) => {}
+            Err(e) => add_toast(format!("Failed to respond to share: {}", e)),
+        }
+        inbox_res.refetch();
+    });
+
+    view! {
+        <ResourceWrapper
+            resource=inbox_res
+            error_text="Failed to load the shares sent to you"
+            children=move |shares| {
+                view! {
+                    <Show when=move || !shares.get().is_empty()>
+                        <Text class="!text-lg">"Sent to you"</Text>
+                        <For
+                            each=move || shares.get()
+                            key=|share| share.share_id
+                            children=move |share| {
+                                let accept_share = share.clone();
+                                let decline_share = share.clone();
+                                view! {
+                                    <Space align=SpaceAlign::Center class="mb-2">
+                                        <Text class="!font-bold">{share.name.clone()}</Text>
+                                        <Text>
+                                            {format!(
+                                                "from {} ({}), {}",
+                                                share.shared_by,
+                                                format_share_permission(share.permission),
+                                                format_date_time(share.time_shared),
+                                            )}
+                                        </Text>
+                                        <Button
+                                            appearance=ButtonAppearance::Primary
+                                            icon=icondata_mdi::MdiCheck
+                                            disabled=respond_action.pending()
+                                            on_click=move |_| {
+                                                respond_action.dispatch((accept_share.clone(), true));
+                                            }
+                                        >
+                                            "Accept"
+                                        </Button>
+                                        <Button
+                                            appearance=ButtonAppearance::Secondary
+                                            icon=icondata_mdi::MdiClose
+                                            disabled=respond_action.pending()
+                                            on_click=move |_| {
+                                                respond_action.dispatch((decline_share.clone(), false));
+                                            }
+                                        >
+                                            "Decline"
+                                        </Button>
+                                    </Space>
+                                }
+                            }
+                        />
+                    </Show>
+                }
+            }
+        />
+    }
+}
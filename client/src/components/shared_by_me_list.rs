@@ -0,0 +1,121 @@
+use crate::api::{SharedByMe, get_shared_by_me, revoke_share};
+use crate::components::basic::resource_wrapper::ResourceWrapper;
+use crate::constants::DEFAULT_TOAST_TIMEOUT;
+use crate::utils::ui::{format_date_time, format_share_permission};
+use chrono::Utc;
+use leptos::prelude::*;
+use thaw::{
+    Button, ButtonAppearance, Space, SpaceAlign, Text, Toast, ToastIntent, ToastOptions,
+    ToastTitle, ToasterInjection,
+};
+
+fn describe_state(shared: &SharedByMe) -> String {
+    let share = &shared.share;
+    let recipient = match &share.shared_with {
+        Some(user) => format!("sent to {}", user.username),
+        None => "share link".to_string(),
+    };
+
+    let state = match (&share.accepted_by, share.time_accepted) {
+        (Some(user), Some(time_accepted)) => format!(
+            "accepted by {} on {}",
+            user.username,
+            format_date_time(time_accepted)
+        ),
+        (Some(user), None) => format!("accepted by {}", user.username),
+        _ if share
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now().naive_utc()) =>
+        {
+            "expired".to_string()
+        }
+        _ => "pending".to_string(),
+    };
+
+    format!(
+        "{} ({}), {}, shared on {}",
+        recipient,
+        format_share_permission(share.permission),
+        state,
+        format_date_time(share.time_shared)
+    )
+}
+
+/// Lists all shares the current user created. Shares can be revoked from here.
+#[component]
+pub fn SharedByMeList() -> impl IntoView {
+    let toaster = ToasterInjection::expect_context();
+    let add_toast = move |text: String| {
+        toaster.dispatch_toast(
+            move || {
+                view! {
+                    <Toast>
+                        <ToastTitle>{text}</ToastTitle>
+                    </Toast>
+                }
+            },
+            ToastOptions::default()
+                .with_intent(ToastIntent::Error)
+                .with_timeout(DEFAULT_TOAST_TIMEOUT),
+        )
+    };
+
+    let shares_res = LocalResource::new(move || async move {
+        get_shared_by_me().await.map_err(|err| err.to_string())
+    });
+
+    let revoke_action = Action::new_local(move |input: &SharedByMe| {
+        let share_id = input.share.share_id;
+        async move { revoke_share(share_id).await.map_err(|err| err.to_string()) }
+    });
+
+    Effect::new(move || {
+        let Some(result) = revoke_action.value().get() else {
+            return;
+        };
+        if let Err(e) = result {
+            add_toast(format!("Failed to revoke share: {}", e));
+        }
+        shares_res.refetch();
+    });
+
+    view! {
+        <ResourceWrapper
+            resource=shares_res
+            error_text="Failed to load your shares"
+            children=move |shares| {
+                view! {
+                    <Show
+                        when=move || !shares.get().is_empty()
+                        fallback=|| view! { <Text>"You have not shared anything so far"</Text> }
+                    >
+                        <For
+                            each=move || shares.get()
+                            key=|shared| shared.share.share_id
+                            children=move |shared| {
+                                let description = describe_state(&shared);
+                                let name = shared.name.clone();
+                                view! {
+                                    <Space align=SpaceAlign::Center class="mb-2">
+                                        <Text class="!font-bold">{name}</Text>
+                                        <Text>{description}</Text>
+                                        <Button
+                                            appearance=ButtonAppearance::Secondary
+                                            icon=icondata_mdi::MdiLinkOff
+                                            disabled=revoke_action.pending()
+                                            on_click=move |_| {
+                                                revoke_action.dispatch(shared.clone());
+                                            }
+                                        >
+                                            "Revoke"
+                                        </Button>
+                                    </Space>
+                                }
+                            }
+                        />
+                    </Show>
+                }
+            }
+        />
+    }
+}
@@ -1,9 +1,11 @@
 use crate::components::node_details::{DetailsViewType, NodeDetails};
 use crate::components::node_list::NodeList;
+use crate::components::share_inbox::ShareInbox;
+use crate::components::shared_by_me_list::SharedByMeList;
 use crate::model::node::DecryptedNode;
 use leptos::prelude::*;
 use leptos_router::hooks::use_navigate;
-use thaw::{Divider, Icon, Space, SpaceAlign, Text};
+use thaw::{Divider, Icon, Space, SpaceAlign, Tab, TabList, Text};
 
 #[component]
 pub fn SharedView(
@@ -15,6 +17,9 @@ pub fn SharedView(
         navigate(&format!("/{}", node.id), Default::default());
     });
 
+    // "with_me" or "by_me"
+    let selected_tab = RwSignal::new("with_me".to_string());
+
     let selection: RwSignal<Option<DecryptedNode>> = RwSignal::new(None);
     let toggle_selection = Callback::new(move |file: DecryptedNode| {
         let selected = selection.get().clone();
@@ -31,17 +36,28 @@ pub fn SharedView(
         <Space vertical=true class="flex-1 flex-column p-8 gap-3 justify-start">
             <Space align=SpaceAlign::Center>
                 <Icon class="!text-2xl mr-1" icon=icondata_mdi::MdiFolderAccountOutline />
-                <Text class="!text-2xl !font-bold">"Shared with you"</Text>
+                <Text class="!text-2xl !font-bold">"Shared"</Text>
             </Space>
+            <TabList selected_value=selected_tab>
+                <Tab value="with_me">"Shared with you"</Tab>
+                <Tab value="by_me">"Shared by me"</Tab>
+            </TabList>
             <Divider class="mb-3" />
 
-            <NodeList
-                nodes=accepted_nodes
-                no_nodes_message="No shares accepted so far"
-                on_node_click=toggle_selection
-                on_folder_dblclick=navigate_to_node
-                folders_only=false
-            />
+            <Show
+                when=move || selected_tab.get() == "with_me"
+                fallback=|| view! { <SharedByMeList /> }
+            >
+                <ShareInbox on_accepted=request_accepted_nodes_refetch />
+
+                <NodeList
+                    nodes=accepted_nodes
+                    no_nodes_message="No shares accepted so far"
+                    on_node_click=toggle_selection
+                    on_folder_dblclick=navigate_to_node
+                    folders_only=false
+                />
+            </Show>
         </Space>
 
         <Show when=move || selection.get().is_some()>
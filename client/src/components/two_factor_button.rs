@@ -0,0 +1,269 @@
+use crate::api::auth::{confirm_totp, disable_totp, regenerate_backup_codes, start_totp_enrolment};
+use crate::api::get_self_info;
+use crate::components::basic::custom_dialog::CustomDialog;
+use crate::constants::DEFAULT_TOAST_TIMEOUT;
+use crabdrive_common::payloads::auth::response::totp::{BackupCodes, TotpEnrolment};
+use leptos::prelude::*;
+use thaw::{
+    Button, ButtonAppearance, Flex, Input, Spinner, Text, Toast, ToastIntent, ToastOptions,
+    ToastTitle, ToasterInjection,
+};
+
+#[derive(Clone, PartialEq)]
+enum TwoFactorState {
+    Loading,
+    Disabled,
+    /// The secret has to be added to the authenticator app and confirmed with a code
+    Enrolling(TotpEnrolment),
+    Enabled,
+    /// Shown once after enabling or regenerating
+    BackupCodes(BackupCodes),
+}
+
+#[component]
+pub fn TwoFactorButton() -> impl IntoView {
+    let toaster = ToasterInjection::expect_context();
+
+    let add_toast = move |text: String, intent: ToastIntent| {
+        toaster.dispatch_toast(
+            move || {
+                view! {
+                    <Toast>
+                        <ToastTitle>{text}</ToastTitle>
+                    </Toast>
+                }
+            },
+            ToastOptions::default()
+                .with_intent(intent)
+                .with_timeout(DEFAULT_TOAST_TIMEOUT),
+        )
+    };
+
+    let dialog_open = RwSignal::new(false);
+    let state = RwSignal::new(TwoFactorState::Loading);
+    let code = RwSignal::new(String::new());
+
+    let load_action = Action::new_local(move |_: &()| async move {
+        get_self_info()
+            .await
+            .map(|info| info.totp_enabled)
+            .map_err(|err| err.to_string())
+    });
+
+    let start_action = Action::new_local(move |_: &()| async move {
+        start_totp_enrolment().await.map_err(|err| err.to_string())
+    });
+
+    let confirm_action = Action::new_local(move |code: &String| {
+        let code = code.to_owned();
+        async move { confirm_totp(&code).await.map_err(|err| err.to_string()) }
+    });
+
+    let disable_action = Action::new_local(move |code: &String| {
+        let code = code.to_owned();
+        async move { disable_totp(&code).await.map_err(|err| err.to_string()) }
+    });
+
+    let regenerate_action = Action::new_local(move |code: &String| {
+        let code = code.to_owned();
+        async move {
+            regenerate_backup_codes(&code)
+                .await
+                .map_err(|err| err.to_string())
+        }
+    });
+
+    Effect::new(move || {
+        if let Some(status) = load_action.value().get() {
+            match status {
+                Ok(true) => state.set(TwoFactorState::Enabled),
+                Ok(false) => state.set(TwoFactorState::Disabled),
+                Err(e) => {
+                    dialog_open.set(false);
+                    add_toast(
+                        format!("Failed to load two-factor settings: {}", e),
+                        ToastIntent::Error,
+                    )
+                }
+            }
+        }
+    });
+
+    Effect::new(move || {
+        if let Some(status) = start_action.value().get() {
+            match status {
+                Ok(enrolment) => state.set(TwoFactorState::Enrolling(enrolment)),
+                Err(e) => add_toast(
+                    format!("Failed to set up two-factor authentication: {}", e),
+                    ToastIntent::Error,
+                ),
+            }
+        }
+    });
+
+    Effect::new(move || {
+        if let Some(status) = confirm_action.value().get() {
+            match status {
+                Ok(backup_codes) => {
+                    add_toast(
+                        "Two-factor authentication enabled".to_string(),
+                        ToastIntent::Success,
+                    );
+                    state.set(TwoFactorState::BackupCodes(backup_codes))
+                }
+                Err(e) => add_toast(
+                    format!("Failed to enable two-factor authentication: {}", e),
+                    ToastIntent::Error,
+                ),
+            }
+        }
+    });
+
+    Effect::new(move || {
+        if let Some(status) = disable_action.value().get() {
+            match status {
+                Ok(_) => {
+                    add_toast(
+                        "Two-factor authentication disabled".to_string(),
+                        ToastIntent::Success,
+                    );
+                    dialog_open.set(false)
+                }
+                Err(e) => add_toast(
+                    format!("Failed to disable two-factor authentication: {}", e),
+                    ToastIntent::Error,
+                ),
+            }
+        }
+    });
+
+    Effect::new(move || {
+        if let Some(status) = regenerate_action.value().get() {
+            match status {
+                Ok(backup_codes) => state.set(TwoFactorState::BackupCodes(backup_codes)),
+                Err(e) => add_toast(
+                    format!("Failed to create new backup codes: {}", e),
+                    ToastIntent::Error,
+                ),
+            }
+        }
+    });
+
+    let on_open = move |_| {
+        state.set(TwoFactorState::Loading);
+        code.set(String::new());
+        dialog_open.set(true);
+        load_action.dispatch(());
+    };
+
+    let on_confirm = Callback::new(move |_: ()| {
+        match state.get() {
+            TwoFactorState::Loading => {}
+            TwoFactorState::Disabled => {
+                start_action.dispatch(());
+            }
+            TwoFactorState::Enrolling(_) => {
+                confirm_action.dispatch(code.get());
+            }
+            TwoFactorState::Enabled => {
+                disable_action.dispatch(code.get());
+            }
+            TwoFactorState::BackupCodes(_) => dialog_open.set(false),
+        }
+        code.set(String::new());
+    });
+
+    let confirm_label = Signal::derive(move || {
+        match state.get() {
+            TwoFactorState::Loading | TwoFactorState::Disabled => "Set up",
+            TwoFactorState::Enrolling(_) => "Enable",
+            TwoFactorState::Enabled => "Disable",
+            TwoFactorState::BackupCodes(_) => "Done",
+        }
+        .to_string()
+    });
+
+    let confirm_disabled = Signal::derive(move || match state.get() {
+        TwoFactorState::Loading => true,
+        TwoFactorState::Enrolling(_) | TwoFactorState::Enabled => code.get().is_empty(),
+        TwoFactorState::Disabled | TwoFactorState::BackupCodes(_) => false,
+    });
+
+    view! {
+        <Button on_click=on_open block=true icon=icondata_mdi::MdiTwoFactorAuthentication class="mt-3">
+            "Two-factor authentication"
+        </Button>
+
+        <CustomDialog
+            open=dialog_open
+            title="Two-factor authentication"
+            show_cancel=Signal::derive(move || {
+                !matches!(state.get(), TwoFactorState::BackupCodes(_))
+            })
+            show_confirm=true
+            confirm_label
+            confirm_disabled
+            on_confirm
+        >
+            <Flex vertical=true class="max-w-100">
+                {move || match state.get() {
+                    TwoFactorState::Loading => view! { <Spinner /> }.into_any(),
+                    TwoFactorState::Disabled => {
+                        view! {
+                            <Text>
+                                "Protect your account with codes from an authenticator app, in addition to your password."
+                            </Text>
+                        }
+                            .into_any()
+                    }
+                    TwoFactorState::Enrolling(enrolment) => {
+                        view! {
+                            <Text>
+                                "Add this account to your authenticator app with the link or the secret, then enter the code shown by the app."
+                            </Text>
+                            <a href=enrolment.provisioning_uri.clone() class="break-all">
+                                {enrolment.provisioning_uri.clone()}
+                            </a>
+                            <Text class="!font-mono break-all">{enrolment.secret.clone()}</Text>
+                            <Input value=code placeholder="Code" autocomplete="one-time-code" />
+                        }
+                            .into_any()
+                    }
+                    TwoFactorState::Enabled => {
+                        view! {
+                            <Text>
+                                "Two-factor authentication is enabled. Enter a code from your authenticator app or a backup code to disable it or to create new backup codes."
+                            </Text>
+                            <Input value=code placeholder="Code" autocomplete="one-time-code" />
+                            <Button
+                                appearance=ButtonAppearance::Secondary
+                                disabled=Signal::derive(move || code.get().is_empty())
+                                on_click=move |_| {
+                                    regenerate_action.dispatch(code.get());
+                                    code.set(String::new());
+                                }
+                            >
+                                "Create new backup codes"
+                            </Button>
+                        }
+                            .into_any()
+                    }
+                    TwoFactorState::BackupCodes(backup_codes) => {
+                        view! {
+                            <Text>
+                                "Store these backup codes in a safe place. Each code can be used once instead of a code from your authenticator app. They will not be shown again."
+                            </Text>
+                            {backup_codes
+                                .into_iter()
+                                .map(|backup_code| {
+                                    view! { <Text class="!font-mono">{backup_code}</Text> }
+                                })
+                                .collect_view()}
+                        }
+                            .into_any()
+                    }
+                }}
+            </Flex>
+        </CustomDialog>
+    }
+}
@@ -20,8 +20,10 @@ use tracing_subscriber::util::SubscriberInitExt;
 use tracing_web::{MakeWebConsoleWriter, performance_layer};
 
 use crate::pages::accept_share_page::AcceptSharePage;
+use crate::pages::file_drop_page::FileDropPage;
 use crate::pages::home_page::HomePageType;
 use crate::pages::login_page::LoginType;
+use crate::pages::public_link_page::PublicLinkPage;
 #[cfg(test)]
 use wasm_bindgen_test::wasm_bindgen_test_configure;
 
@@ -83,6 +85,14 @@ fn CrabDrive() -> impl IntoView {
                             path=path!("/shared/:shareId")
                             view=move || view! { <AcceptSharePage /> }
                         />
+                        <Route
+                            path=path!("/public/:linkId")
+                            view=move || view! { <PublicLinkPage /> }
+                        />
+                        <Route
+                            path=path!("/drop/:dropId")
+                            view=move || view! { <FileDropPage /> }
+                        />
                         <Route
                             path=path!("/trash")
                             view=move || view! { <HomePage view_type=HomePageType::Trash /> }
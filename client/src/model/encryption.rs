@@ -15,5 +15,9 @@ pub type MasterKey = RawEncryptionKey;
 pub type FileKey = RawEncryptionKey; // IV is stored in Revision
 /// The encryption key is used to encrypt and decrypt node metadata.
 pub type MetadataKey = RawEncryptionKey; // IV is stored inside metadata
+/// The public key of a user (SPKI), used to encrypt share keys for that user
+pub type PublicKey = Vec<u8>;
+/// The private key of a user (PKCS#8), used to decrypt share keys sent to that user
+pub type PrivateKey = Vec<u8>;
 
 pub type ChildKey = (NodeId, MetadataKey);
@@ -1,7 +1,7 @@
 use crate::model::encryption::{ChildKey, FileKey, MetadataKey};
 use chrono::NaiveDateTime;
 use crabdrive_common::data::DataAmount;
-use crabdrive_common::storage::{FileRevision, NodeId, NodeType};
+use crabdrive_common::storage::{FileRevision, NodeId, NodeType, SharePermission};
 use crabdrive_common::user::UserId;
 use serde::{Deserialize, Serialize};
 
@@ -59,6 +59,6 @@ pub struct DecryptedNode {
     pub metadata: NodeMetadata,
     /// The metadata encryption key for this node
     pub encryption_key: MetadataKey,
-    /// vec containing user_id and username
-    pub has_access: Vec<(UserId, String)>,
+    /// vec containing user_id, username and the permission of the user
+    pub has_access: Vec<(UserId, String, SharePermission)>,
 }
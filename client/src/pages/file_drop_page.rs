@@ -0,0 +1,126 @@
+use crate::api::{check_file_drop, drop_file};
+use crate::components::basic::resource_wrapper::ResourceWrapper;
+use crate::constants::{DEFAULT_TOAST_TIMEOUT, INFINITE_TOAST_TIMEOUT};
+use crate::utils::browser::get_current_url;
+use leptos::prelude::*;
+use thaw::{
+    Button, ButtonAppearance, Image, Space, SpaceAlign, Text, Toast, ToastIntent, ToastOptions,
+    ToastTitle, ToasterInjection, Upload, UploadDragger,
+};
+use web_sys::{File, FileList};
+
+/// Upload page for file drops, which does not require an account. The uploader cannot see any
+/// files in the folder, including the ones they uploaded.
+#[component]
+pub fn FileDropPage() -> impl IntoView {
+    let toaster = ToasterInjection::expect_context();
+    let add_toast = move |text: String, intent: ToastIntent| {
+        toaster.dispatch_toast(
+            move || {
+                view! {
+                    <Toast>
+                        <ToastTitle>{text}</ToastTitle>
+                    </Toast>
+                }
+            },
+            ToastOptions::default().with_intent(intent).with_timeout(
+                if matches!(intent, ToastIntent::Error) {
+                    INFINITE_TOAST_TIMEOUT
+                } else {
+                    DEFAULT_TOAST_TIMEOUT
+                },
+            ),
+        )
+    };
+
+    let file_drop_res = LocalResource::new(move || async move {
+        let url = get_current_url().map_err(|err| err.to_string())?;
+        check_file_drop(&url).await.map_err(|err| err.to_string())
+    });
+
+    let selection = RwSignal::new_local(vec![]);
+    let set_file_list = move |file_list: FileList| {
+        let mut files: Vec<File> = vec![];
+        for i in 0..file_list.length() {
+            let file = file_list.item(i).unwrap();
+            files.push(file)
+        }
+        selection.set(files)
+    };
+
+    let file_names = move || {
+        let names: Vec<String> = selection.get().iter().map(File::name).collect();
+        names
+    };
+
+    let upload_action = Action::new_local(move |files: &Vec<File>| {
+        let files = files.to_owned();
+        async move {
+            let url = get_current_url().map_err(|err| err.to_string())?;
+            for file in files {
+                let name = file.name();
+                drop_file(&url, file)
+                    .await
+                    .map_err(|err| format!("{}: {}", name, err))?;
+            }
+            Ok::<(), String>(())
+        }
+    });
+
+    Effect::new(move || {
+        let Some(result) = upload_action.value().get() else {
+            return;
+        };
+        match result {
+            Ok(_) => add_toast("Upload complete".to_string(), ToastIntent::Success),
+            Err(e) => add_toast(format!("Failed to upload file {}", e), ToastIntent::Error),
+        }
+        selection.set(vec![]);
+    });
+
+    view! {
+        <Space vertical=true class="h-screen py-15" align=SpaceAlign::Center>
+            <Space align=SpaceAlign::Center>
+                <Image src="/logo.svg" attr:width=50 />
+                <Text class="!text-3xl !font-bold">"crabdrive"</Text>
+            </Space>
+
+            <div class="h-fit w-100 mt-15 px-15 py-10 flex flex-col gap-2 rounded-sm outline outline-gray-300">
+                <ResourceWrapper
+                    resource=file_drop_res
+                    error_text="This link is not available"
+                    children=move |_| {
+                        view! {
+                            <Text class="!text-2xl">"Upload files"</Text>
+                            <Text>
+                                "The files are encrypted before uploading. Only the owner of the folder can see them."
+                            </Text>
+                            <Upload custom_request=set_file_list multiple=true>
+                                // This inline style is necessary since this div that needs styling
+                                // is inserted between Upload and UploadDragger in the DOM
+                                <style>".thaw-upload__trigger { width: 100% } "</style>
+                                <UploadDragger>"Click or drag files to this area"</UploadDragger>
+                            </Upload>
+                            <For each=file_names key=|name| name.clone() let:name>
+                                <Text class="!block !text-center">{name}</Text>
+                            </For>
+                            <Button
+                                appearance=ButtonAppearance::Primary
+                                icon=icondata_mdi::MdiUpload
+                                block=true
+                                disabled=Signal::derive(move || {
+                                    upload_action.pending().get() || selection.get().is_empty()
+                                })
+                                on_click=move |_| {
+                                    upload_action.dispatch_local(selection.get());
+                                }
+                            >
+                                "Upload"
+                            </Button>
+                        }
+                    }
+                />
+            </div>
+        </Space>
+    }
+}
@@ -76,6 +76,7 @@ pub fn LoginPage(#[prop(into)] login_type: Signal<LoginType>) -> impl IntoView {
     let username = RwSignal::new(String::from(""));
     let password = RwSignal::new(String::from(""));
     let invite_code = RwSignal::new(String::from(""));
+    let second_factor = RwSignal::new(String::from(""));
     let is_password_valid = RwSignal::new(true);
 
     let register_action = Action::new_local(move |input: &(String, String, String)| {
@@ -113,11 +114,11 @@ pub fn LoginPage(#[prop(into)] login_type: Signal<LoginType>) -> impl IntoView {
         true,
     );
 
-    let login_action = Action::new_local(move |input: &(String, String)| {
-        let (username, password) = input.to_owned();
+    let login_action = Action::new_local(move |input: &(String, String, String)| {
+        let (username, password, second_factor) = input.to_owned();
         async move {
             add_auth_in_progress_toast("Login");
-            login(&username, &password, true)
+            login(&username, &password, Some(&second_factor), true)
                 .await
                 .map_err(|err| err.to_string())
         }
@@ -138,6 +139,7 @@ pub fn LoginPage(#[prop(into)] login_type: Signal<LoginType>) -> impl IntoView {
         let username = username.get();
         let password = password.get();
         let invite_code = invite_code.get();
+        let second_factor = second_factor.get();
         let password_valid = is_valid_password(&password);
         is_password_valid.set(password_valid);
         if username.is_empty() || !password_valid {
@@ -146,7 +148,7 @@ pub fn LoginPage(#[prop(into)] login_type: Signal<LoginType>) -> impl IntoView {
 
         match login_type.get() {
             LoginType::Register => register_action.dispatch((username, password, invite_code)),
-            LoginType::Login => login_action.dispatch((username, password)),
+            LoginType::Login => login_action.dispatch((username, password, second_factor)),
         };
     });
 
@@ -206,6 +208,14 @@ pub fn LoginPage(#[prop(into)] login_type: Signal<LoginType>) -> impl IntoView {
                         LoginType::Login => "current-password",
                     }
                 />
+                <Show when=move || login_type.get().eq(&LoginType::Login)>
+                    <Input
+                        placeholder="Two-factor code (if enabled)"
+                        class="w-full"
+                        value=second_factor
+                        autocomplete="one-time-code"
+                    />
+                </Show>
                 <Show when=move || login_type.get().eq(&LoginType::Register)>
                     <Input
                         placeholder="Invite code"
@@ -1,3 +1,5 @@
 pub mod accept_share_page;
+pub mod file_drop_page;
 pub mod home_page;
 pub mod login_page;
+pub mod public_link_page;
@@ -0,0 +1,81 @@
+use crate::api::{download_public_file, get_public_file_name};
+use crate::components::basic::resource_wrapper::ResourceWrapper;
+use crate::constants::INFINITE_TOAST_TIMEOUT;
+use crate::utils::browser::get_current_url;
+use leptos::prelude::*;
+use thaw::{
+    Button, ButtonAppearance, Image, Space, SpaceAlign, Text, Toast, ToastIntent, ToastOptions,
+    ToastTitle, ToasterInjection,
+};
+
+/// Download page for public links, which does not require an account
+#[component]
+pub fn PublicLinkPage() -> impl IntoView {
+    let toaster = ToasterInjection::expect_context();
+    let add_toast = move |text: String| {
+        toaster.dispatch_toast(
+            move || {
+                view! {
+                    <Toast>
+                        <ToastTitle>{text}</ToastTitle>
+                    </Toast>
+                }
+            },
+            ToastOptions::default()
+                .with_intent(ToastIntent::Error)
+                .with_timeout(INFINITE_TOAST_TIMEOUT),
+        )
+    };
+
+    let file_name_res = LocalResource::new(move || async move {
+        let url = get_current_url().map_err(|err| err.to_string())?;
+        get_public_file_name(&url)
+            .await
+            .map_err(|err| err.to_string())
+    });
+
+    let download_action = Action::new_local(move |_: &()| async move {
+        let url = get_current_url().map_err(|err| err.to_string())?;
+        download_public_file(&url)
+            .await
+            .map_err(|err| err.to_string())
+    });
+
+    Effect::new(move || {
+        if let Some(Err(e)) = download_action.value().get() {
+            add_toast(format!("Failed to download file: {}", e));
+        }
+    });
+
+    view! {
+        <Space vertical=true class="h-screen py-15" align=SpaceAlign::Center>
+            <Space align=SpaceAlign::Center>
+                <Image src="/logo.svg" attr:width=50 />
+                <Text class="!text-3xl !font-bold">"crabdrive"</Text>
+            </Space>
+
+            <div class="h-fit w-100 mt-15 px-15 py-10 flex flex-col gap-2 rounded-sm outline outline-gray-300">
+                <ResourceWrapper
+                    resource=file_name_res
+                    error_text="This link is not available"
+                    children=move |file_name| {
+                        view! {
+                            <Text class="!text-2xl">{file_name}</Text>
+                            <Button
+                                appearance=ButtonAppearance::Primary
+                                icon=icondata_mdi::MdiDownload
+                                block=true
+                                disabled=download_action.pending()
+                                on_click=move |_| {
+                                    download_action.dispatch(());
+                                }
+                            >
+                                "Download"
+                            </Button>
+                        }
+                    }
+                />
+            </div>
+        </Space>
+    }
+}
@@ -4,6 +4,7 @@ use crate::utils;
 use anyhow::{Result, anyhow};
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD_NO_PAD;
+use crabdrive_common::user::KdfParams;
 
 /// Checks if a username is remembered
 pub fn is_authenticated() -> Result<bool> {
@@ -25,6 +26,25 @@ pub async fn salt_from_username(username: &str) -> String {
     BASE64_STANDARD_NO_PAD.encode(username)
 }
 
+/// Get the salt to derive the password of a user with. Accounts without a stored salt use the
+/// hash of the username.
+pub async fn salt_from_kdf_params(username: &str, kdf_params: &KdfParams) -> String {
+    match &kdf_params.salt {
+        Some(salt) => salt.clone(),
+        None => salt_from_username(username).await,
+    }
+}
+
+/// Creates KDF parameters with the current version and a new random salt
+pub fn generate_kdf_params() -> Result<KdfParams> {
+    let salt = utils::encryption::random::get_random_bytes(16)?;
+    // No padding, because argon2 returns `Err` if Base-64 encoded string contains `=`
+    Ok(KdfParams::new(
+        KdfParams::CURRENT_VERSION,
+        Some(BASE64_STANDARD_NO_PAD.encode(salt)),
+    ))
+}
+
 /// Get the JWT Bearer token. Will return `Err` if no token is present but should redirect to the login page.
 pub fn get_token() -> Result<String> {
     if let Some(token) = SessionStorage::get("bearer")? {
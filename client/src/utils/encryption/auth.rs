@@ -1,29 +1,31 @@
 use crate::model::encryption::DerivedKey;
-use crate::model::encryption::{MasterKey, MetadataKey};
+use crate::model::encryption::{MasterKey, MetadataKey, PrivateKey};
 use crate::utils;
 use crate::utils::browser::SessionStorage;
 
+use crabdrive_common::encryption_key::EncryptionKey;
+use crabdrive_common::user::{KdfVersion, UserKeys};
+
 use anyhow::{Result, anyhow};
-use argon2::{Algorithm, Argon2, ParamsBuilder, PasswordHasher, Version, password_hash::Salt};
+use argon2::{
+    Algorithm, Argon2, Params, ParamsBuilder, PasswordHasher, Version, password_hash::Salt,
+};
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
 
-/// Generates the server password, and the derived key from the password.
-///
-/// Returns `Result<(Password, DerivedKey)>`. The password is Base64-Encoded.
+/// Get the Argon2 parameters of a [`KdfVersion`].
 ///
 /// Parameters are based on [OWASP Recommmendations](https://cheatsheetseries.owasp.org/cheatsheets/Password_Storage_Cheat_Sheet.html#argon2id):
-///  - Minimum Memory: 12 MiB
-///  - Iterations: 3
-///  - Parallelism Degree: 1
+///  - Version 1: 12 MiB memory, 3 iterations, parallelism degree 1
+///  - Version 2: 19 MiB memory, 3 iterations, parallelism degree 1
 ///
-/// **This function is very computation-heavy, and may cause unresponsive UI!**
-pub fn derive_from_password(password_hash: &str, salt: &str) -> Result<(String, DerivedKey)> {
-    tracing::debug_span!("encryption::utils::auth::deriveFromPassword");
-
-    let salt = Salt::from_b64(salt)
-        .map_err(|_| anyhow!("Invalid salt provided"))
-        .inspect_err(|_| tracing::error!("Invalid salt! Is the username too short?"))?;
+/// Existing versions must never be changed, otherwise users of that version cannot sign in anymore.
+fn kdf_params(version: KdfVersion) -> Result<Params> {
+    let m_cost = match version {
+        1 => 1024 * 12,
+        2 => 1024 * 19,
+        _ => return Err(anyhow!("Unsupported KDF version {}", version)),
+    };
 
     let mut params_builder = ParamsBuilder::new();
     // First 32 bytes are Base-64 encoded into password
@@ -31,9 +33,30 @@ pub fn derive_from_password(password_hash: &str, salt: &str) -> Result<(String,
     params_builder.output_len(64);
     params_builder.p_cost(1);
     params_builder.t_cost(3);
-    params_builder.m_cost(1024 * 12);
+    params_builder.m_cost(m_cost);
+
+    params_builder
+        .build()
+        .map_err(|_| anyhow!("Invalid KDF parameters"))
+}
 
-    let params = params_builder.build().unwrap();
+/// Generates the server password, and the derived key from the password.
+///
+/// Returns `Result<(Password, DerivedKey)>`. The password is Base64-Encoded.
+///
+/// **This function is very computation-heavy, and may cause unresponsive UI!**
+pub fn derive_from_password(
+    password_hash: &str,
+    salt: &str,
+    version: KdfVersion,
+) -> Result<(String, DerivedKey)> {
+    tracing::debug_span!("encryption::utils::auth::deriveFromPassword");
+
+    let salt = Salt::from_b64(salt)
+        .map_err(|_| anyhow!("Invalid salt provided"))
+        .inspect_err(|_| tracing::error!("Invalid salt! Is the username too short?"))?;
+
+    let params = kdf_params(version)?;
     let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
 
     let hash = argon2
@@ -49,6 +72,64 @@ pub fn derive_from_password(password_hash: &str, salt: &str) -> Result<(String,
     Ok((password, derived_key.try_into()?))
 }
 
+/// Wraps the keys of a user for a new password. The master key is wrapped with the new derived
+/// key, all other keys are wrapped with the (unchanged) master key again. Data encrypted with
+/// these keys does not need to be re-encrypted. Accounts without a key pair pass `None` as private
+/// key.
+pub async fn rewrap_user_keys(
+    master_key: &MasterKey,
+    root_key: &MetadataKey,
+    trash_key: &MetadataKey,
+    private_key: Option<&PrivateKey>,
+    derived_key: &DerivedKey,
+) -> Result<UserKeys> {
+    let wrapped_master_key = utils::encryption::wrap_key(master_key, derived_key)
+        .await
+        .inspect_err(|_| tracing::error!("Failed to wrap master key"))?;
+    let wrapped_root_key = utils::encryption::wrap_key(root_key, master_key)
+        .await
+        .inspect_err(|_| tracing::error!("Failed to wrap root key"))?;
+    let wrapped_trash_key = utils::encryption::wrap_key(trash_key, master_key)
+        .await
+        .inspect_err(|_| tracing::error!("Failed to wrap trash key"))?;
+
+    let wrapped_private_key = match private_key {
+        Some(private_key) => {
+            utils::encryption::key_pair::wrap_private_key(private_key, master_key).await?
+        }
+        None => EncryptionKey::nil(),
+    };
+
+    // The public key does not change and is ignored by the server
+    Ok(UserKeys::new(
+        vec![],
+        wrapped_private_key,
+        wrapped_master_key,
+        wrapped_root_key,
+        wrapped_trash_key,
+    ))
+}
+
+/// Generates a random recovery code, formatted as 8 groups of 4 hex characters (128 bit).
+pub fn generate_recovery_code() -> Result<String> {
+    let bytes = utils::encryption::random::get_random_bytes(16)?;
+    let code = bytes
+        .chunks(2)
+        .map(|group| format!("{:02x}{:02x}", group[0], group[1]))
+        .collect::<Vec<_>>()
+        .join("-");
+    Ok(code)
+}
+
+/// Removes the formatting of a recovery code, so typos in the formatting do not matter
+pub fn normalize_recovery_code(recovery_code: &str) -> String {
+    recovery_code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
 /// Get the root token. Will return `Err` if no token is present.
 pub fn get_root_key() -> Result<MetadataKey> {
     let root_key: String = SessionStorage::get("root_key")?
@@ -0,0 +1,215 @@
+//! RSA-OAEP key pairs are used to share nodes directly with another user. The sender encrypts the
+//! key of the share with the public key of the recipient, only the recipient can decrypt it with
+//! their private key. The private key is stored on the server, encrypted with the master key.
+
+use crate::constants::AES_GCM;
+use crate::model::encryption::{MasterKey, PrivateKey, PublicKey};
+use crate::utils::browser::{SessionStorage, get_subtle_crypto};
+use crate::utils::encryption::{import_key, random};
+use crate::utils::error::{dyn_into, future_from_js_promise, wrap_js_err};
+
+use crabdrive_common::encryption_key::EncryptionKey;
+
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use wasm_bindgen::JsValue;
+use web_sys::js_sys::{Array, ArrayBuffer, Object, Reflect, Uint8Array};
+use web_sys::{AesGcmParams, CryptoKey};
+
+const RSA_OAEP: &str = "RSA-OAEP";
+
+/// Builds the algorithm object for RSA-OAEP with SHA-256
+fn rsa_params(key_gen: bool) -> Result<Object> {
+    let params = Object::new();
+    wrap_js_err(Reflect::set(&params, &"name".into(), &RSA_OAEP.into()))?;
+    wrap_js_err(Reflect::set(&params, &"hash".into(), &"SHA-256".into()))?;
+    if key_gen {
+        let public_exponent = Uint8Array::new_from_slice(&[0x01, 0x00, 0x01]);
+        wrap_js_err(Reflect::set(
+            &params,
+            &"modulusLength".into(),
+            &JsValue::from(2048),
+        ))?;
+        wrap_js_err(Reflect::set(
+            &params,
+            &"publicExponent".into(),
+            &public_exponent,
+        ))?;
+    }
+    Ok(params)
+}
+
+fn usages(usages: &[&str]) -> Array {
+    usages.iter().map(|usage| JsValue::from(*usage)).collect()
+}
+
+async fn export_rsa_key(format: &str, key: &CryptoKey) -> Result<Vec<u8>> {
+    let key_buffer: ArrayBuffer =
+        future_from_js_promise(wrap_js_err(get_subtle_crypto()?.export_key(format, key))?).await?;
+    Ok(Uint8Array::new(&key_buffer).to_vec())
+}
+
+async fn import_rsa_key(format: &str, key: &[u8], usage: &str) -> Result<CryptoKey> {
+    let key_data = Uint8Array::new_from_slice(key);
+    let key_promise = wrap_js_err(get_subtle_crypto()?.import_key_with_object(
+        format,
+        &key_data,
+        &rsa_params(false)?,
+        false,
+        &usages(&[usage]),
+    ))?;
+    future_from_js_promise(key_promise).await
+}
+
+/// Generates a new RSA-OAEP key pair. The public key is exported as SPKI, the private key as
+/// PKCS#8.
+pub async fn generate_key_pair() -> Result<(PublicKey, PrivateKey)> {
+    let _guard = tracing::trace_span!("utils::encryption::generateKeyPair").entered();
+
+    let key_pair: Object =
+        future_from_js_promise(wrap_js_err(get_subtle_crypto()?.generate_key_with_object(
+            &rsa_params(true)?,
+            true,
+            &usages(&["encrypt", "decrypt"]),
+        ))?)
+        .await?;
+
+    let public_key: CryptoKey =
+        dyn_into(wrap_js_err(Reflect::get(&key_pair, &"publicKey".into()))?)?;
+    let private_key: CryptoKey =
+        dyn_into(wrap_js_err(Reflect::get(&key_pair, &"privateKey".into()))?)?;
+
+    Ok((
+        export_rsa_key("spki", &public_key).await?,
+        export_rsa_key("pkcs8", &private_key).await?,
+    ))
+}
+
+/// Encrypts the private key with the master key, so it can be stored on the server
+pub async fn wrap_private_key(
+    private_key: &PrivateKey,
+    master_key: &MasterKey,
+) -> Result<EncryptionKey> {
+    let _guard = tracing::trace_span!("utils::encryption::wrapPrivateKey").entered();
+
+    let iv = random::get_random_iv()?;
+    let params = AesGcmParams::new(AES_GCM, &Uint8Array::new_from_slice(&iv.get()));
+    let master_key = import_key(master_key).await?;
+
+    let encrypted: ArrayBuffer = future_from_js_promise(wrap_js_err(
+        get_subtle_crypto()?.encrypt_with_object_and_buffer_source(
+            &params,
+            &master_key,
+            &Uint8Array::new_from_slice(private_key),
+        ),
+    )?)
+    .await
+    .inspect_err(|_| tracing::error!("Failed to wrap private key"))?;
+
+    Ok(EncryptionKey::new(Uint8Array::new(&encrypted).to_vec(), iv))
+}
+
+/// Decrypts a private key, that was encrypted with [`wrap_private_key`]
+pub async fn unwrap_private_key(
+    wrapped_key: &EncryptionKey,
+    master_key: &MasterKey,
+) -> Result<PrivateKey> {
+    let _guard = tracing::trace_span!("utils::encryption::unwrapPrivateKey").entered();
+
+    let params = AesGcmParams::new(
+        AES_GCM,
+        &Uint8Array::new_from_slice(&wrapped_key.iv().get()),
+    );
+    let master_key = import_key(master_key).await?;
+
+    let decrypted: ArrayBuffer = future_from_js_promise(wrap_js_err(
+        get_subtle_crypto()?.decrypt_with_object_and_buffer_source(
+            &params,
+            &master_key,
+            &Uint8Array::new_from_slice(wrapped_key.key_slice()),
+        ),
+    )?)
+    .await
+    .inspect_err(|_| tracing::error!("Failed to unwrap private key"))?;
+
+    Ok(Uint8Array::new(&decrypted).to_vec())
+}
+
+/// Encrypts a small amount of data (like a key) for the owner of the public key
+pub async fn encrypt_for(public_key: &PublicKey, data: &[u8]) -> Result<Vec<u8>> {
+    let _guard = tracing::trace_span!("utils::encryption::encryptFor").entered();
+
+    let public_key = import_rsa_key("spki", public_key, "encrypt").await?;
+    let encrypted: ArrayBuffer = future_from_js_promise(wrap_js_err(
+        get_subtle_crypto()?.encrypt_with_object_and_buffer_source(
+            &rsa_params(false)?,
+            &public_key,
+            &Uint8Array::new_from_slice(data),
+        ),
+    )?)
+    .await?;
+
+    Ok(Uint8Array::new(&encrypted).to_vec())
+}
+
+/// Decrypts data, that was encrypted with [`encrypt_for`] using the matching public key
+pub async fn decrypt_with(private_key: &PrivateKey, data: &[u8]) -> Result<Vec<u8>> {
+    let _guard = tracing::trace_span!("utils::encryption::decryptWith").entered();
+
+    let private_key = import_rsa_key("pkcs8", private_key, "decrypt").await?;
+    let decrypted: ArrayBuffer = future_from_js_promise(wrap_js_err(
+        get_subtle_crypto()?.decrypt_with_object_and_buffer_source(
+            &rsa_params(false)?,
+            &private_key,
+            &Uint8Array::new_from_slice(data),
+        ),
+    )?)
+    .await?;
+
+    Ok(Uint8Array::new(&decrypted).to_vec())
+}
+
+/// Get the private key of the signed in user. Will return `Err` if no key is present.
+pub fn get_private_key() -> Result<PrivateKey> {
+    let private_key: String = SessionStorage::get("private_key")?
+        .ok_or(anyhow!("Invalid encryption key. Please re-authenticate"))?;
+    BASE64_STANDARD
+        .decode(private_key)
+        .map_err(|_| anyhow!("Failed to decode key!"))
+}
+
+pub fn set_private_key(private_key: &PrivateKey) -> Result<()> {
+    SessionStorage::set("private_key", &BASE64_STANDARD.encode(private_key))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::encryption::generate_aes256_key;
+    use crate::utils::encryption::key_pair::*;
+
+    use pretty_assertions::assert_eq;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    async fn test_encrypt_for_decrypt_with() {
+        let (public_key, private_key) = generate_key_pair().await.unwrap();
+        let data = generate_aes256_key().await.unwrap();
+
+        let encrypted = encrypt_for(&public_key, &data).await.unwrap();
+        assert_eq!(encrypted.len(), 256);
+
+        let decrypted = decrypt_with(&private_key, &encrypted).await.unwrap();
+        assert_eq!(decrypted, data.to_vec());
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_wrap_unwrap_private_key() {
+        let (_, private_key) = generate_key_pair().await.unwrap();
+        let master_key = generate_aes256_key().await.unwrap();
+
+        let wrapped = wrap_private_key(&private_key, &master_key).await.unwrap();
+        let unwrapped = unwrap_private_key(&wrapped, &master_key).await.unwrap();
+        assert_eq!(unwrapped, private_key);
+    }
+}
@@ -1,5 +1,6 @@
 pub mod auth;
 pub mod chunk;
+pub mod key_pair;
 pub mod node;
 pub mod random;
 
@@ -3,7 +3,7 @@ use crate::utils::browser::get_origin;
 use crate::utils::encryption::{decode_key, encode_key};
 use anyhow::Result;
 use anyhow::anyhow;
-use crabdrive_common::storage::ShareId;
+use crabdrive_common::storage::{FileDropId, PublicLinkId, ShareId};
 use crabdrive_common::uuid::UUID;
 
 pub fn parse_share_url(url: &str) -> Result<(ShareId, RawEncryptionKey)> {
@@ -31,6 +31,37 @@ pub fn create_share_url(share_id: &ShareId, wrapped_key: &RawEncryptionKey) -> R
     Ok(url)
 }
 
+/// Public links have the same format as share urls, except for the path
+pub fn parse_public_link_url(url: &str) -> Result<(PublicLinkId, RawEncryptionKey)> {
+    parse_share_url(url)
+}
+
+pub fn create_public_link_url(
+    link_id: &PublicLinkId,
+    wrapped_key: &RawEncryptionKey,
+) -> Result<String> {
+    let encoded_key = encode_key(wrapped_key);
+    let origin = get_origin()?;
+    let url = format!("{origin}/public/{link_id}#{encoded_key}");
+    Ok(url)
+}
+
+/// File drop links do not contain a key, as the uploader only needs the public key of the owner
+pub fn create_file_drop_url(drop_id: &FileDropId) -> Result<String> {
+    let origin = get_origin()?;
+    Ok(format!("{origin}/drop/{drop_id}"))
+}
+
+pub fn parse_file_drop_url(url: &str) -> Result<FileDropId> {
+    let url = url.split(['#', '?']).next().unwrap_or(url);
+    let drop_id = url
+        .trim_end_matches('/')
+        .split('/')
+        .next_back()
+        .ok_or(anyhow!("url {url} is not valid"))?;
+    UUID::parse_string(drop_id).ok_or(anyhow!("not a valid uuid"))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -51,6 +82,22 @@ mod test {
         assert_eq!(parsed.1, key);
     }
 
+    #[wasm_bindgen_test]
+    fn test_create_parse_public_link_url() {
+        let key = [
+            3, 2, 5, 87, 58, 5, 4, 7, 8, 56, 64, 85, 63, 84, 53, 74, 7, 4, 2, 6, 7, 8, 9, 7, 56, 4,
+            7, 8, 6, 3, 2, 9,
+        ];
+        let link_id = PublicLinkId::random();
+
+        let url = create_public_link_url(&link_id, &key).unwrap();
+        assert!(url.contains("/public/"));
+        let parsed = parse_public_link_url(&url).unwrap();
+
+        assert_eq!(parsed.0, link_id);
+        assert_eq!(parsed.1, key);
+    }
+
     #[test]
     fn test_parse_url() {
         let url = "http://localhost:2722/shared/99202218-bee9-4c9d-b3b7-5bfb6ccc7862#AW5TSC+Dp00T92iZsie4UjByeXg/vQPqTxztE4mo3es=";
@@ -62,4 +109,13 @@ mod test {
         let parsed = parse_share_url(url).unwrap();
         assert_eq!((expected_share_id, expected_encryption_key), parsed);
     }
+
+    #[wasm_bindgen_test]
+    fn test_create_parse_file_drop_url() {
+        let drop_id = FileDropId::random();
+
+        let url = create_file_drop_url(&drop_id).unwrap();
+        assert!(url.contains("/drop/"));
+        assert_eq!(parse_file_drop_url(&url).unwrap(), drop_id);
+    }
 }
@@ -1,6 +1,6 @@
 use crate::model::node::DecryptedNode;
 use chrono::NaiveDateTime;
-use crabdrive_common::storage::NodeType;
+use crabdrive_common::storage::{NodeType, SharePermission};
 
 pub fn format_date_time(naive_date_time: NaiveDateTime) -> String {
     naive_date_time.format("%d/%m/%Y, %H:%M:%S").to_string()
@@ -50,13 +50,21 @@ pub fn get_node_icon(node_type: NodeType, name: String) -> &'static icondata_cor
     }
 }
 
+pub fn format_share_permission(permission: SharePermission) -> &'static str {
+    match permission {
+        SharePermission::View => "can view",
+        SharePermission::Edit => "can edit",
+        SharePermission::Manage => "can manage",
+    }
+}
+
 pub fn get_owner_username(node: DecryptedNode) -> Option<String> {
     let owner_id = node.owner_id;
 
     node.has_access
         .into_iter()
-        .find(|(user_id, _)| user_id == &owner_id)
-        .map(|(_, username)| username)
+        .find(|(user_id, _, _)| user_id == &owner_id)
+        .map(|(_, username, _)| username)
 }
 
 pub fn get_share_acceptor_usernames(node: DecryptedNode) -> Option<Vec<String>> {
@@ -65,8 +73,10 @@ pub fn get_share_acceptor_usernames(node: DecryptedNode) -> Option<Vec<String>>
     let share_acceptor_usernames: Vec<String> = node
         .has_access
         .into_iter()
-        .filter(|(user_id, _)| user_id != &owner_id)
-        .map(|(_, username)| username)
+        .filter(|(user_id, _, _)| user_id != &owner_id)
+        .map(|(_, username, permission)| {
+            format!("{} ({})", username, format_share_permission(permission))
+        })
         .collect();
 
     if share_acceptor_usernames.is_empty() {
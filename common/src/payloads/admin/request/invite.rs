@@ -0,0 +1,17 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{data::DataAmount, user::UserType};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostInviteCodeRequest {
+    /// The code can not be redeemed after this point in time (UTC). Never expires, if not set.
+    pub expires_at: Option<NaiveDateTime>,
+    /// How often the code can be redeemed. Unlimited, if not set.
+    pub max_uses: Option<i64>,
+    /// The role of users registering with this code
+    pub user_type: UserType,
+    /// The storage limit of users registering with this code. Defaults to the regular storage
+    /// limit, if not set.
+    pub storage_limit: Option<DataAmount>,
+}
@@ -1 +1,2 @@
+pub mod invite;
 pub mod user;
@@ -2,17 +2,27 @@ use serde::{Deserialize, Serialize};
 
 use crate::{data::DataAmount, user::UserType};
 
+/// Create an account for a user. The user sets up the password and the keys by registering with
+/// the returned invite code, which only accepts `username`.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PostUserRequest {
-    username: String,
-    password: String,
-    user_type: UserType,
-    storage_limit: Option<DataAmount>,
+    pub username: String,
+    pub user_type: UserType,
+    /// Defaults to the regular storage limit, if not set
+    pub storage_limit: Option<DataAmount>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Partial update of a user. Fields which are `None` are left untouched.
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct PatchUserRequest {
-    username: String,
-    user_type: UserType,
-    storage_limit: Option<DataAmount>,
+    pub username: Option<String>,
+    pub user_type: Option<UserType>,
+    pub storage_limit: Option<DataAmount>,
+}
+
+/// Query parameters for listing users. Pages start at 0.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct GetUserListQuery {
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
 }
@@ -0,0 +1,46 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::DataAmount,
+    user::{InviteCodeId, UserId, UserType},
+};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum PostInviteCodeResponse {
+    Created(CreatedInviteCode),
+    BadRequest(String),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum GetInviteCodesResponse {
+    Ok(Vec<InviteCodeInfo>),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum DeleteInviteCodeResponse {
+    Ok(InviteCodeInfo),
+    NotFound,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CreatedInviteCode {
+    /// The plain invite code. Only the hash is stored, so this is the only time it is visible.
+    pub code: String,
+    pub info: InviteCodeInfo,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InviteCodeInfo {
+    pub id: InviteCodeId,
+    /// `None` if the creator was deleted
+    pub created_by: Option<UserId>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub max_uses: Option<i64>,
+    pub uses: i64,
+    pub user_type: UserType,
+    pub storage_limit: DataAmount,
+    /// The only username, which can be registered with the code. Any username, if not set.
+    pub username: Option<String>,
+}
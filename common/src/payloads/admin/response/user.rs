@@ -1,7 +1,12 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
-use crate::{data::DataAmount, user::UserType};
+use crate::payloads::admin::response::invite::CreatedInviteCode;
+use crate::payloads::auth::response::register::RegisterConflictReason;
+use crate::{
+    data::DataAmount,
+    user::{UserId, UserType},
+};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum GetUserResponse {
@@ -9,10 +14,17 @@ pub enum GetUserResponse {
     NotFound,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub enum GetUserListResponse {
+    Ok(UserList),
+    BadRequest(String),
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum PostUserResponse {
-    Created(UserInfo),
-    Conflict,
+    /// The account is created, once the user registers with this code
+    Created(CreatedInviteCode),
+    Conflict(RegisterConflictReason),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -25,13 +37,25 @@ pub enum DeleteUserResponse {
 pub enum PatchUserResponse {
     Ok(UserInfo),
     NotFound,
+    Conflict(RegisterConflictReason),
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct UserInfo {
+    pub id: UserId,
     pub username: String,
     pub user_type: UserType,
     pub storage_limit: Option<DataAmount>,
+    pub storage_used: DataAmount,
     pub created_on: NaiveDateTime,
     pub updated_on: NaiveDateTime,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserList {
+    pub users: Vec<UserInfo>,
+    /// The total amount of users, across all pages
+    pub total: u64,
+    pub page: u64,
+    pub per_page: u64,
+}
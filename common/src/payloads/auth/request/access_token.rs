@@ -0,0 +1,13 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::user::AccessTokenScope;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostAccessTokenRequest {
+    /// A name to recognize the token, e.g. the script using it
+    pub name: String,
+    pub scope: AccessTokenScope,
+    /// The token is rejected after this point in time (UTC). Never expires, if not set.
+    pub expires_at: Option<NaiveDateTime>,
+}
@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+use crate::encryption_key::EncryptionKey;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PutKeyPairRequest {
+    /// The public key, which other users use to share nodes with the user
+    pub public_key: Vec<u8>,
+    /// The private key, wrapped with the master key
+    pub private_key: EncryptionKey,
+}
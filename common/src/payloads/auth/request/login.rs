@@ -5,3 +5,16 @@ pub struct PostLoginRequest {
     pub username: String,
     pub password: String,
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostPreLoginRequest {
+    pub username: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostLoginSecondFactorRequest {
+    /// The challenge from `PostLoginResponse::SecondFactorRequired`
+    pub challenge: String,
+    /// A code from the authenticator app or a backup code
+    pub code: String,
+}
@@ -1,2 +1,7 @@
+pub mod access_token;
+pub mod keys;
 pub mod login;
+pub mod password;
+pub mod recovery;
 pub mod register;
+pub mod totp;
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+use crate::user::{KdfParams, UserKeys};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostChangePasswordRequest {
+    pub old_password: String,
+    pub new_password: String,
+    /// The keys of the user, re-wrapped with the key derived from the new password
+    pub keys: UserKeys,
+    /// The parameters used to derive `new_password` and the key wrapping the master key
+    pub kdf_params: KdfParams,
+}
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+use crate::encryption_key::EncryptionKey;
+use crate::user::{KdfParams, UserKeys};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecoveryKey {
+    /// Derived from the recovery code, the same way the server password is derived from the password
+    pub recovery_password: String,
+    /// The master key, wrapped with the key derived from the recovery code
+    pub master_key: EncryptionKey,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PutRecoveryKeyRequest {
+    /// The current password of the user
+    pub password: String,
+    pub recovery_key: RecoveryKey,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostRecoveryKeysRequest {
+    pub username: String,
+    pub recovery_password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostRecoveryResetRequest {
+    pub username: String,
+    pub recovery_password: String,
+    pub new_password: String,
+    /// The keys of the user, re-wrapped with the key derived from the new password
+    pub keys: UserKeys,
+    /// The parameters used to derive `new_password` and the key wrapping the master key
+    pub kdf_params: KdfParams,
+}
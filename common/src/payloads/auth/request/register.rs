@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 
-use crate::user::UserKeys;
+use crate::payloads::auth::request::recovery::RecoveryKey;
+use crate::user::{KdfParams, UserKeys};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PostRegisterRequest {
@@ -8,4 +9,9 @@ pub struct PostRegisterRequest {
     pub password: String,
     pub invite_code: String,
     pub keys: UserKeys,
+    /// The parameters used to derive `password` and the key wrapping the master key
+    pub kdf_params: KdfParams,
+    /// Optional, a recovery key can also be created later
+    #[serde(default)]
+    pub recovery_key: Option<RecoveryKey>,
 }
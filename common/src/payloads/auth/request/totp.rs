@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+/// Used to confirm the enrolment, to disable the second factor and to create new backup codes
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TotpCodeRequest {
+    /// A code from the authenticator app. Disabling and creating new backup codes also accept a
+    /// backup code.
+    pub code: String,
+}
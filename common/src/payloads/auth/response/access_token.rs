@@ -0,0 +1,39 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::user::{AccessTokenId, AccessTokenScope};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum PostAccessTokenResponse {
+    Created(CreatedAccessToken),
+    BadRequest(String),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum GetAccessTokensResponse {
+    Ok(Vec<AccessTokenInfo>),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum DeleteAccessTokenResponse {
+    Ok,
+    NotFound,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CreatedAccessToken {
+    /// The plain token. Only the hash is stored, so this is the only time it is visible.
+    pub token: String,
+    pub info: AccessTokenInfo,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AccessTokenInfo {
+    pub id: AccessTokenId,
+    pub name: String,
+    pub scope: AccessTokenScope,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    /// `None` if the token was never used
+    pub last_used_at: Option<NaiveDateTime>,
+}
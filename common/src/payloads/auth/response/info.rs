@@ -8,6 +8,7 @@ pub struct SelfUserInfo {
     pub user_id: UserId,
     pub storage_limit: DataAmount,
     pub storage_used: DataAmount,
+    pub totp_enabled: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
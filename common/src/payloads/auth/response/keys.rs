@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum PutKeyPairResponse {
+    Ok,
+    /// The user already has a key pair. It cannot be replaced, as pending shares were encrypted
+    /// with the public key.
+    Conflict,
+    BadRequest,
+}
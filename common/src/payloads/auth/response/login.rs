@@ -1,14 +1,30 @@
 use crate::storage::NodeId;
-use crate::user::UserKeys;
+use crate::user::{KdfParams, UserKeys};
 use serde::{Deserialize, Serialize};
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum PostPreLoginResponse {
+    /// The parameters to derive the password with. Unknown users get made up parameters, so the
+    /// response does not reveal which usernames exist.
+    Ok(KdfParams),
+}
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Serialize, Deserialize, Debug)]
 pub enum PostLoginResponse {
     Ok(LoginSuccess),
+    /// The password is correct, but the user has to enter a second factor. The login is completed
+    /// with the challenge in a second request (see `PostLoginSecondFactorRequest`).
+    SecondFactorRequired(SecondFactorChallenge),
     Unauthorized(LoginDeniedReason),
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SecondFactorChallenge {
+    /// Proves that the password was correct. Expires after a few minutes.
+    pub challenge: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LoginSuccess {
     /// The jwt token signed by the server
@@ -49,5 +65,7 @@ impl LoginSuccess {
 pub enum LoginDeniedReason {
     Password,
     Username,
+    /// The second factor or the challenge is wrong
+    SecondFactor,
     OTHER,
 }
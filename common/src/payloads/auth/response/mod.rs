@@ -1,4 +1,10 @@
+pub mod access_token;
 pub mod info;
+pub mod keys;
 pub mod login;
+pub mod password;
+pub mod recovery;
 pub mod refresh;
 pub mod register;
+pub mod session;
+pub mod totp;
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub enum PostChangePasswordResponse {
+    Ok,
+    /// The old password is wrong
+    Unauthorized,
+    /// The KDF parameters are invalid
+    BadRequest(String),
+}
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+use crate::encryption_key::EncryptionKey;
+use crate::user::UserKeys;
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum PutRecoveryKeyResponse {
+    Ok,
+    /// The password is wrong
+    Unauthorized,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum DeleteRecoveryKeyResponse {
+    Ok,
+    NotFound,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecoveryKeys {
+    /// The master key, wrapped with the key derived from the recovery code
+    pub recovery_master_key: EncryptionKey,
+    pub keys: UserKeys,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum PostRecoveryKeysResponse {
+    Ok(RecoveryKeys),
+    /// The username or the recovery password is wrong, or the user has no recovery key
+    Unauthorized,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub enum PostRecoveryResetResponse {
+    Ok,
+    /// The username or the recovery password is wrong, or the user has no recovery key
+    Unauthorized,
+    /// The KDF parameters are invalid
+    BadRequest(String),
+}
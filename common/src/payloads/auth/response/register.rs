@@ -1,11 +1,13 @@
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub enum PostRegisterResponse {
     Created,
     Unauthorized,
     Conflict(RegisterConflictReason),
+    /// The KDF parameters are invalid
+    BadRequest(String),
 }
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
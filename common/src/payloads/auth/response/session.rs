@@ -0,0 +1,32 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::user::SessionId;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionInfo {
+    pub id: SessionId,
+    /// `None` for sessions created before the creation time was tracked
+    pub created_at: Option<NaiveDateTime>,
+    /// The session expires if it is not refreshed until then
+    pub expires_at: NaiveDateTime,
+    /// Whether this is the session the request was made with
+    pub current: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum GetSessionsResponse {
+    Ok(Vec<SessionInfo>),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum DeleteSessionResponse {
+    Ok,
+    NotFound,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum DeleteOtherSessionsResponse {
+    /// Contains the amount of revoked sessions
+    Ok(u64),
+}
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TotpEnrolment {
+    /// The base32 encoded secret, for manual entry in the authenticator app
+    pub secret: String,
+    /// The `otpauth://` URI to show as a QR code
+    pub provisioning_uri: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum PostTotpResponse {
+    Ok(TotpEnrolment),
+    /// The second factor is already enabled. It has to be disabled before enrolling again.
+    Conflict,
+}
+
+/// Backup codes can be used instead of a TOTP code. Each code can only be used once.
+pub type BackupCodes = Vec<String>;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum PostTotpConfirmResponse {
+    Ok(BackupCodes),
+    /// The code is wrong
+    Unauthorized,
+    /// No enrolment was started, or the second factor is already enabled
+    Conflict,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum DeleteTotpResponse {
+    Ok,
+    /// The code is wrong
+    Unauthorized,
+    /// The second factor is not enabled
+    NotFound,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum PostTotpBackupCodesResponse {
+    Ok(BackupCodes),
+    /// The code is wrong
+    Unauthorized,
+    /// The second factor is not enabled
+    NotFound,
+}
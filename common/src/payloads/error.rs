@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+/// The body of every response, which reports an error instead of the regular response payload
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorResponse {
+    pub error: ErrorBody,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorBody {
+    pub kind: ErrorKind,
+    /// A human readable description. Internal details are only included in debug builds.
+    pub details: String,
+    /// The ID of the request (also sent as `X-Request-ID` header), to find it in the server logs
+    pub request_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The server failed to process the request (e.g. the database is not available)
+    Internal,
+    /// The requested entity does not exist
+    NotFound,
+    /// The request conflicts with the stored data
+    Conflict,
+    /// The request body exceeds a size limit (e.g. the maximum size of a chunk)
+    PayloadTooLarge,
+    /// The request handler crashed
+    Panic,
+    /// Too many requests were sent
+    RateLimit,
+}
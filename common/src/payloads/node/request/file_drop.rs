@@ -0,0 +1,29 @@
+use crate::encrypted_metadata::EncryptedMetadata;
+use crate::storage::{ChunkIndex, NodeId, RevisionIv};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostFileDropRequest {
+    /// no files can be dropped after this time
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostDropFileRequest {
+    pub node_id: NodeId,
+    pub node_metadata: EncryptedMetadata,
+    /// the metadata key encrypted with the public key of the folder owner. The metadata of the
+    /// folder is not changed until the owner adopts the file.
+    pub encrypted_metadata_key: Vec<u8>,
+    pub file_iv: RevisionIv,
+    pub chunk_count: ChunkIndex,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostAdoptDroppedFilesRequest {
+    /// the metadata of the folder, now containing the keys of the adopted files
+    pub parent_metadata: EncryptedMetadata,
+    pub parent_metadata_version: i64,
+    pub node_ids: Vec<NodeId>,
+}
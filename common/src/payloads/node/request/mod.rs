@@ -1,4 +1,6 @@
 pub mod file;
+pub mod file_drop;
 pub mod folder;
 pub mod node;
+pub mod public_link;
 pub mod share;
@@ -0,0 +1,11 @@
+use crate::encryption_key::EncryptionKey;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostPublicLinkRequest {
+    /// the metadata key encrypted with the key in the url
+    pub wrapped_metadata_key: EncryptionKey,
+    /// the link cannot be used after this time
+    pub expires_at: Option<NaiveDateTime>,
+}
@@ -1,10 +1,18 @@
 use crate::encryption_key::EncryptionKey;
+use crate::storage::{NodeId, SharePermission};
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PostShareNodeRequest {
     /// the metadata key encrypted with the key in the url
     pub wrapped_metadata_key: EncryptionKey,
+    /// the permission the user accepting the share will get
+    pub permission: SharePermission,
+    /// the share link cannot be accepted after this time
+    pub expires_at: Option<NaiveDateTime>,
+    /// whether users who accepted the share lose their access once it expires
+    pub revoke_on_expiry: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -12,3 +20,27 @@ pub struct PostAcceptShareRequest {
     /// the metadata key encrypted with the users master key
     pub new_wrapped_metadata_key: EncryptionKey,
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostShareNodeWithUserRequest {
+    /// the user the node is shared with
+    pub username: String,
+    /// the metadata key encrypted with the share key
+    pub wrapped_metadata_key: EncryptionKey,
+    /// the share key encrypted with the public key of the recipient. Replaces the key in the url
+    /// of a share link.
+    pub encrypted_share_key: Vec<u8>,
+    /// the permission the user will get when accepting the share
+    pub permission: SharePermission,
+    /// the share cannot be accepted after this time
+    pub expires_at: Option<NaiveDateTime>,
+    /// whether the user loses their access once the share expires
+    pub revoke_on_expiry: bool,
+}
+
+/// Query parameters for listing the shares created by the user
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct GetOutgoingSharesQuery {
+    /// only list shares of this node
+    pub node_id: Option<NodeId>,
+}
@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 pub enum PostCreateFileResponse {
     Created(EncryptedNode),
     NotFound,
+    Forbidden,
     BadRequest,
     Conflict,
 }
@@ -13,6 +14,7 @@ pub enum PostCreateFileResponse {
 pub enum PostUpdateFileResponse {
     Ok(FileRevision),
     NotFound,
+    Forbidden,
     BadRequest,
 }
 
@@ -21,6 +23,7 @@ pub enum PostCommitFileResponse {
     Ok(EncryptedNode),
     BadRequest(CommitFileError),
     NotFound,
+    Forbidden,
 }
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
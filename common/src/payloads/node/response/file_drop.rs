@@ -0,0 +1,71 @@
+use crate::payloads::node::response::file::CommitFileError;
+use crate::storage::{EncryptedNode, FileDropId, FileRevision};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+/// Everything an uploader needs to know about a file drop. Nothing about the folder or its
+/// contents is revealed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileDropInfo {
+    /// the public key of the folder owner, used to encrypt the metadata key of dropped files
+    pub public_key: Vec<u8>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+/// A file, which was dropped into a folder and has not been adopted yet
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DroppedFile {
+    pub node: EncryptedNode,
+    /// the metadata key encrypted with the public key of the owner
+    pub encrypted_metadata_key: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum PostFileDropResponse {
+    Ok(FileDropId),
+    NotFound,
+    BadRequest(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum GetFileDropResponse {
+    Ok(FileDropInfo),
+    NotFound,
+    Expired,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum DeleteFileDropResponse {
+    Ok,
+    NotFound,
+    BadRequest(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum PostDropFileResponse {
+    Created(FileRevision),
+    NotFound,
+    BadRequest,
+    Conflict,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum PostDropCommitResponse {
+    Ok,
+    NotFound,
+    BadRequest(CommitFileError),
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum GetDroppedFilesResponse {
+    Ok(Vec<DroppedFile>),
+    NotFound,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum PostAdoptDroppedFilesResponse {
+    Ok,
+    NotFound,
+    BadRequest(String),
+    Conflict,
+}
@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 pub enum PostCreateFolderResponse {
     Created(EncryptedNode),
     NotFound,
+    Forbidden,
     BadRequest,
     Conflict,
 }
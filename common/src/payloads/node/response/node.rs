@@ -11,6 +11,7 @@ pub enum GetNodeResponse {
 pub enum PatchNodeResponse {
     Ok(EncryptedNode),
     NotFound,
+    Forbidden,
     Conflict,
 }
 
@@ -19,6 +20,7 @@ pub enum PostMoveNodeResponse {
     Ok,
     BadRequest,
     NotFound,
+    Forbidden,
     Conflict,
 }
 
@@ -0,0 +1,41 @@
+use crate::encrypted_metadata::EncryptedMetadata;
+use crate::encryption_key::EncryptionKey;
+use crate::storage::{FileRevision, NodeId, PublicLinkId};
+use serde::{Deserialize, Serialize};
+
+/// The parts of a node, which are required to download it with a public link
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PublicNode {
+    pub id: NodeId,
+    pub encrypted_metadata: EncryptedMetadata,
+    pub current_revision: Option<FileRevision>,
+    /// the metadata key encrypted with the key in the url
+    pub wrapped_metadata_key: EncryptionKey,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum PostPublicLinkResponse {
+    Ok(PublicLinkId),
+    NotFound,
+    BadRequest(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum GetPublicLinkResponse {
+    Ok(PublicNode),
+    NotFound,
+    Expired,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum GetPublicVersionsResponse {
+    Ok(Vec<FileRevision>),
+    NotFound,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum DeletePublicLinkResponse {
+    Ok,
+    NotFound,
+    BadRequest(String),
+}
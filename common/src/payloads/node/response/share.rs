@@ -1,5 +1,8 @@
+use crate::encrypted_metadata::EncryptedMetadata;
 use crate::encryption_key::EncryptionKey;
-use crate::storage::{EncryptedNode, NodeId, ShareId};
+use crate::storage::{EncryptedNode, NodeId, ShareId, SharePermission};
+use crate::user::UserId;
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -19,6 +22,7 @@ pub enum PostShareNodeResponse {
 pub enum GetAcceptShareInfoResponse {
     Ok(ShareEncryptionInfo),
     NotFound,
+    Expired,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -32,9 +36,76 @@ pub enum PostAcceptShareResponse {
     Ok,
     NotFound,
     BadRequest(String),
+    Expired,
 }
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
 pub enum GetAcceptedSharedResponse {
     Ok(Vec<(EncryptionKey, EncryptedNode)>),
 }
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum DeleteShareResponse {
+    Ok,
+    NotFound,
+    BadRequest(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum GetUserPublicKeyResponse {
+    Ok(Vec<u8>),
+    /// the user does not exist or has no key pair yet
+    NotFound,
+}
+
+/// A share, which was shared directly with the user and can be accepted without a link
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ShareOffer {
+    pub share_id: ShareId,
+    pub node_id: NodeId,
+    /// the username of the user, who shared the node
+    pub shared_by: String,
+    pub time_shared: NaiveDateTime,
+    pub permission: SharePermission,
+    pub expires_at: Option<NaiveDateTime>,
+    /// the metadata key encrypted with the share key
+    pub wrapped_metadata_key: EncryptionKey,
+    /// the share key encrypted with the public key of the user
+    pub encrypted_share_key: Vec<u8>,
+    /// allows showing the name of the node before accepting the share
+    pub encrypted_metadata: EncryptedMetadata,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum GetShareInboxResponse {
+    Ok(Vec<ShareOffer>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ShareUser {
+    pub id: UserId,
+    pub username: String,
+}
+
+/// A share created by the user, either as a share link or sent directly to another user
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OutgoingShare {
+    pub share_id: ShareId,
+    pub node_id: NodeId,
+    pub time_shared: NaiveDateTime,
+    pub permission: SharePermission,
+    pub expires_at: Option<NaiveDateTime>,
+    pub revoke_on_expiry: bool,
+    /// the user the share was sent to directly, `None` for share links
+    pub shared_with: Option<ShareUser>,
+    /// whether the share has not been accepted yet
+    pub pending: bool,
+    /// `None` while the share is pending
+    pub accepted_by: Option<ShareUser>,
+    pub time_accepted: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum GetOutgoingSharesResponse {
+    Ok(Vec<OutgoingShare>),
+}
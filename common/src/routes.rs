@@ -33,6 +33,7 @@ pub mod node {
 
     pub mod share {
         use crate::storage::{NodeId, ShareId};
+        use crate::user::UserId;
 
         pub const ROUTE_SHARE_NODE: &str = "/api/node/{id}/share/";
         /// `/api/node/{id}/share/`
@@ -40,6 +41,15 @@ pub mod node {
             ROUTE_SHARE_NODE.replace("{id}", &id.to_string())
         }
 
+        // remove a user, which accepted a share of the node
+        pub const ROUTE_SHARE_NODE_USER: &str = "/api/node/{id}/share/{user_id}/";
+        /// `/api/node/{id}/share/{user_id}/`
+        pub fn share_user(id: NodeId, user_id: UserId) -> String {
+            ROUTE_SHARE_NODE_USER
+                .replace("{id}", &id.to_string())
+                .replace("{user_id}", &user_id.to_string())
+        }
+
         // get information about a node shared that was accepted
         pub const ROUTE_NODE_SHARE_INFO: &str = "/api/node/{id}/shared_info/";
         /// `/api/node/{id}/shared_info//`
@@ -60,6 +70,80 @@ pub mod node {
             ROUTE_ACCEPT_SHARE.replace("{share_id}", &id.to_string())
         }
 
+        // revoke a share (pending or accepted)
+        pub const ROUTE_SHARE_BY_ID: &str = "/api/shared/{share_id}/";
+        /// `/api/shared/{share_id}/`
+        pub fn share_by_id(id: ShareId) -> String {
+            ROUTE_SHARE_BY_ID.replace("{share_id}", &id.to_string())
+        }
+
+        // create a public download link, which can be used without an account
+        pub const ROUTE_PUBLIC_LINK_NODE: &str = "/api/node/{id}/public_link/";
+        /// `/api/node/{id}/public_link/`
+        pub fn public_link(id: NodeId) -> String {
+            ROUTE_PUBLIC_LINK_NODE.replace("{id}", &id.to_string())
+        }
+
+        // create an upload-only link for a folder, see [`crate::routes::drop`]
+        pub const ROUTE_FILE_DROP_NODE: &str = "/api/node/{id}/file_drop/";
+        /// `/api/node/{id}/file_drop/`
+        pub fn file_drop(id: NodeId) -> String {
+            ROUTE_FILE_DROP_NODE.replace("{id}", &id.to_string())
+        }
+
+        // files dropped into a folder, which were not adopted by the owner yet
+        pub const ROUTE_DROPPED_FILES: &str = "/api/node/{id}/dropped_files/";
+        /// `/api/node/{id}/dropped_files/`
+        pub fn dropped_files(id: NodeId) -> String {
+            ROUTE_DROPPED_FILES.replace("{id}", &id.to_string())
+        }
+
+        pub const ROUTE_ADOPT_DROPPED_FILES: &str = "/api/node/{id}/dropped_files/adopt/";
+        /// `/api/node/{id}/dropped_files/adopt/`
+        pub fn adopt_dropped_files(id: NodeId) -> String {
+            ROUTE_ADOPT_DROPPED_FILES.replace("{id}", &id.to_string())
+        }
+
+        // share a node directly with another user, without a link
+        pub const ROUTE_SHARE_NODE_WITH_USER: &str = "/api/node/{id}/share_with/";
+        /// `/api/node/{id}/share_with/`
+        pub fn share_with_user(id: NodeId) -> String {
+            ROUTE_SHARE_NODE_WITH_USER.replace("{id}", &id.to_string())
+        }
+
+        // the public key, which is used to share nodes directly with a user
+        pub const ROUTE_USER_PUBLIC_KEY: &str = "/api/users/{username}/public_key/";
+        /// `/api/users/{username}/public_key/`
+        pub fn user_public_key(username: &str) -> String {
+            ROUTE_USER_PUBLIC_KEY.replace("{username}", username)
+        }
+
+        // shares, which were shared directly with the user and are not accepted yet
+        pub const ROUTE_INBOX: &str = "/api/inbox/";
+        /// `/api/inbox/`
+        pub fn inbox() -> String {
+            ROUTE_INBOX.to_string()
+        }
+
+        // decline a share in the inbox
+        pub const ROUTE_INBOX_SHARE: &str = "/api/inbox/{share_id}/";
+        /// `/api/inbox/{share_id}/`
+        pub fn inbox_share(id: ShareId) -> String {
+            ROUTE_INBOX_SHARE.replace("{share_id}", &id.to_string())
+        }
+
+        // shares created by the user, optionally only the shares of a single node
+        pub const ROUTE_OUTGOING_SHARES: &str = "/api/shares/outgoing/";
+        /// `/api/shares/outgoing/`
+        pub fn outgoing_shares() -> String {
+            ROUTE_OUTGOING_SHARES.to_string()
+        }
+
+        /// `/api/shares/outgoing/?node_id={node_id}`
+        pub fn outgoing_shares_of_node(node_id: NodeId) -> String {
+            format!("{ROUTE_OUTGOING_SHARES}?node_id={node_id}")
+        }
+
         pub const ROUTE_GET_ACCEPTED_SHARED: &str = "/api/shared/";
         pub fn get_accepted_shared() -> String {
             ROUTE_GET_ACCEPTED_SHARED.to_string()
@@ -122,13 +206,98 @@ pub mod node {
     }
 }
 
+/// Routes for public download links. Except for revoking a link, these do not require an account.
+pub mod public {
+    use crate::storage::{ChunkIndex, PublicLinkId, RevisionId};
+
+    pub const ROUTE_PUBLIC_LINK: &str = "/api/public/{link_id}/";
+    /// `/api/public/{link_id}/`
+    pub fn by_id(id: PublicLinkId) -> String {
+        ROUTE_PUBLIC_LINK.replace("{link_id}", &id.to_string())
+    }
+
+    pub const ROUTE_PUBLIC_VERSIONS: &str = "/api/public/{link_id}/versions/";
+    /// `/api/public/{link_id}/versions/`
+    pub fn versions(id: PublicLinkId) -> String {
+        ROUTE_PUBLIC_VERSIONS.replace("{link_id}", &id.to_string())
+    }
+
+    pub const ROUTE_PUBLIC_CHUNKS: &str =
+        "/api/public/{link_id}/versions/{version_id}/chunks/{chunk_index}/";
+    /// `/api/public/{link_id}/versions/{version_id}/chunks/{chunk_index}/`
+    pub fn chunks(id: PublicLinkId, version_id: RevisionId, chunk_index: ChunkIndex) -> String {
+        ROUTE_PUBLIC_CHUNKS
+            .replace("{link_id}", &id.to_string())
+            .replace("{version_id}", &version_id.to_string())
+            .replace("{chunk_index}", &chunk_index.to_string())
+    }
+}
+
+/// Routes for file drops. Uploaders can only add files to the folder, except for revoking a drop,
+/// these do not require an account.
+pub mod drop {
+    use crate::storage::{ChunkIndex, FileDropId, NodeId, RevisionId};
+
+    pub const ROUTE_FILE_DROP: &str = "/api/drop/{drop_id}/";
+    /// `/api/drop/{drop_id}/`
+    pub fn by_id(id: FileDropId) -> String {
+        ROUTE_FILE_DROP.replace("{drop_id}", &id.to_string())
+    }
+
+    pub const ROUTE_DROP_FILES: &str = "/api/drop/{drop_id}/files/";
+    /// `/api/drop/{drop_id}/files/`
+    pub fn files(id: FileDropId) -> String {
+        ROUTE_DROP_FILES.replace("{drop_id}", &id.to_string())
+    }
+
+    pub const ROUTE_DROP_CHUNKS: &str =
+        "/api/drop/{drop_id}/files/{id}/versions/{version_id}/chunks/{chunk_index}/";
+    /// `/api/drop/{drop_id}/files/{id}/versions/{version_id}/chunks/{chunk_index}/`
+    pub fn chunks(
+        id: FileDropId,
+        node_id: NodeId,
+        version_id: RevisionId,
+        chunk_index: ChunkIndex,
+    ) -> String {
+        ROUTE_DROP_CHUNKS
+            .replace("{drop_id}", &id.to_string())
+            .replace("{id}", &node_id.to_string())
+            .replace("{version_id}", &version_id.to_string())
+            .replace("{chunk_index}", &chunk_index.to_string())
+    }
+
+    pub const ROUTE_DROP_COMMIT: &str =
+        "/api/drop/{drop_id}/files/{id}/versions/{version_id}/commit/";
+    /// `/api/drop/{drop_id}/files/{id}/versions/{version_id}/commit/`
+    pub fn commit(id: FileDropId, node_id: NodeId, version_id: RevisionId) -> String {
+        ROUTE_DROP_COMMIT
+            .replace("{drop_id}", &id.to_string())
+            .replace("{id}", &node_id.to_string())
+            .replace("{version_id}", &version_id.to_string())
+    }
+}
+
 pub mod auth {
+    use crate::user::{AccessTokenId, SessionId};
+
+    pub const ROUTE_PRE_LOGIN: &str = "/api/auth/prelogin/";
+    /// `/api/auth/prelogin/`
+    pub fn pre_login() -> String {
+        ROUTE_PRE_LOGIN.to_string()
+    }
+
     pub const ROUTE_LOGIN: &str = "/api/auth/login/";
     /// `/api/auth/login/`
     pub fn login() -> String {
         ROUTE_LOGIN.to_string()
     }
 
+    pub const ROUTE_LOGIN_SECOND_FACTOR: &str = "/api/auth/login/second_factor/";
+    /// `/api/auth/login/second_factor/`
+    pub fn login_second_factor() -> String {
+        ROUTE_LOGIN_SECOND_FACTOR.to_string()
+    }
+
     pub const ROUTE_REGISTER: &str = "/api/auth/register/";
     /// `/api/auth/register/`
     pub fn register() -> String {
@@ -152,10 +321,88 @@ pub mod auth {
     pub fn info() -> String {
         ROUTE_INFO.to_string()
     }
+
+    pub const ROUTE_PASSWORD: &str = "/api/auth/password/";
+    /// `/api/auth/password/`
+    pub fn password() -> String {
+        ROUTE_PASSWORD.to_string()
+    }
+
+    pub const ROUTE_PASSWORD_UPGRADE: &str = "/api/auth/password/upgrade/";
+    /// `/api/auth/password/upgrade/`
+    pub fn password_upgrade() -> String {
+        ROUTE_PASSWORD_UPGRADE.to_string()
+    }
+
+    pub const ROUTE_RECOVERY_KEY: &str = "/api/auth/recovery/";
+    /// `/api/auth/recovery/`
+    pub fn recovery_key() -> String {
+        ROUTE_RECOVERY_KEY.to_string()
+    }
+
+    pub const ROUTE_RECOVERY_KEYS: &str = "/api/auth/recovery/keys/";
+    /// `/api/auth/recovery/keys/`
+    pub fn recovery_keys() -> String {
+        ROUTE_RECOVERY_KEYS.to_string()
+    }
+
+    pub const ROUTE_RECOVERY_RESET: &str = "/api/auth/recovery/reset/";
+    /// `/api/auth/recovery/reset/`
+    pub fn recovery_reset() -> String {
+        ROUTE_RECOVERY_RESET.to_string()
+    }
+
+    pub const ROUTE_TOTP: &str = "/api/auth/totp/";
+    /// `/api/auth/totp/`
+    pub fn totp() -> String {
+        ROUTE_TOTP.to_string()
+    }
+
+    pub const ROUTE_TOTP_CONFIRM: &str = "/api/auth/totp/confirm/";
+    /// `/api/auth/totp/confirm/`
+    pub fn totp_confirm() -> String {
+        ROUTE_TOTP_CONFIRM.to_string()
+    }
+
+    pub const ROUTE_TOTP_BACKUP_CODES: &str = "/api/auth/totp/backup_codes/";
+    /// `/api/auth/totp/backup_codes/`
+    pub fn totp_backup_codes() -> String {
+        ROUTE_TOTP_BACKUP_CODES.to_string()
+    }
+
+    pub const ROUTE_SESSIONS: &str = "/api/auth/sessions/";
+    /// `/api/auth/sessions/`
+    pub fn sessions() -> String {
+        ROUTE_SESSIONS.to_string()
+    }
+
+    pub const ROUTE_SESSION_BY_ID: &str = "/api/auth/sessions/{id}/";
+    /// `/api/auth/sessions/{id}/`
+    pub fn session_by_id(id: SessionId) -> String {
+        ROUTE_SESSION_BY_ID.replace("{id}", &id.to_string())
+    }
+
+    pub const ROUTE_KEY_PAIR: &str = "/api/auth/keys/";
+    /// `/api/auth/keys/`
+    pub fn key_pair() -> String {
+        ROUTE_KEY_PAIR.to_string()
+    }
+
+    pub const ROUTE_ACCESS_TOKENS: &str = "/api/auth/tokens/";
+    /// `/api/auth/tokens/`
+    pub fn access_tokens() -> String {
+        ROUTE_ACCESS_TOKENS.to_string()
+    }
+
+    pub const ROUTE_ACCESS_TOKEN_BY_ID: &str = "/api/auth/tokens/{id}/";
+    /// `/api/auth/tokens/{id}/`
+    pub fn access_token_by_id(id: AccessTokenId) -> String {
+        ROUTE_ACCESS_TOKEN_BY_ID.replace("{id}", &id.to_string())
+    }
 }
 
 pub mod admin {
-    use crate::user::UserId;
+    use crate::user::{InviteCodeId, UserId};
 
     pub const ROUTE_USER_BY_ID: &str = "/api/admin/user/{id}/";
     /// `/api/admin/user/{id}/`
@@ -164,8 +411,25 @@ pub mod admin {
     }
 
     pub const ROUTE_USER: &str = "/api/admin/user/";
-    /// `/api/admin/user/{id}/`
+    /// `/api/admin/user/`
     pub fn user() -> String {
         ROUTE_USER.to_string()
     }
+
+    /// `/api/admin/user/?page={page}&per_page={per_page}`
+    pub fn user_list(page: u64, per_page: u64) -> String {
+        format!("{ROUTE_USER}?page={page}&per_page={per_page}")
+    }
+
+    pub const ROUTE_INVITE_BY_ID: &str = "/api/admin/invite/{id}/";
+    /// `/api/admin/invite/{id}/`
+    pub fn invite_by_id(id: InviteCodeId) -> String {
+        ROUTE_INVITE_BY_ID.replace("{id}", &id.to_string())
+    }
+
+    pub const ROUTE_INVITE: &str = "/api/admin/invite/";
+    /// `/api/admin/invite/`
+    pub fn invite() -> String {
+        ROUTE_INVITE.to_string()
+    }
 }
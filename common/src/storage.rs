@@ -63,6 +63,56 @@ pub type RevisionId = UUID;
 /// Unique ID (UUID) for an instance of a node shared
 pub type ShareId = UUID;
 
+/// What a user is allowed to do with a node shared with them. The levels are ordered, each level
+/// includes all permissions of the levels before it.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(FromSqlRow, AsExpression))]
+#[cfg_attr(feature = "server", diesel(sql_type = Text))]
+pub enum SharePermission {
+    /// Read the node and its children
+    View,
+    /// Create, upload, rename and move nodes
+    Edit,
+    /// Share the node with other users and revoke shares. Owners always have this permission.
+    Manage,
+}
+
+#[cfg(feature = "server")]
+impl ToSql<Text, Sqlite> for SharePermission {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        let value = match self {
+            SharePermission::View => "VIEW",
+            SharePermission::Edit => "EDIT",
+            SharePermission::Manage => "MANAGE",
+        };
+
+        out.set_value(value);
+        Ok(IsNull::No)
+    }
+}
+
+#[cfg(feature = "server")]
+impl FromSql<Text, Sqlite> for SharePermission {
+    fn from_sql(
+        bytes: <Sqlite as diesel::backend::Backend>::RawValue<'_>,
+    ) -> deserialize::Result<Self> {
+        let s = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+
+        match s.as_str() {
+            "VIEW" => Ok(SharePermission::View),
+            "EDIT" => Ok(SharePermission::Edit),
+            "MANAGE" => Ok(SharePermission::Manage),
+            _ => Err(format!("Invalid SharePermission: {}", s).into()),
+        }
+    }
+}
+
+/// Unique ID (UUID) for a public download link of a node
+pub type PublicLinkId = UUID;
+
+/// Unique ID (UUID) for a file drop link, which allows uploading files into a folder
+pub type FileDropId = UUID;
+
 /// The index of a chunk within a file
 pub type ChunkIndex = i64;
 
@@ -79,8 +129,8 @@ pub struct EncryptedNode {
     pub node_type: NodeType,
     pub current_revision: Option<FileRevision>,
     pub encrypted_metadata: EncryptedMetadata,
-    /// vec containing user_id and username
-    pub has_access: Vec<(UserId, String)>,
+    /// vec containing user_id, username and the permission of the user
+    pub has_access: Vec<(UserId, String, SharePermission)>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -13,6 +13,9 @@ use diesel::{
 };
 
 pub type UserId = UUID;
+pub type InviteCodeId = UUID;
+pub type SessionId = UUID;
+pub type AccessTokenId = UUID;
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "server", derive(FromSqlRow, AsExpression))]
@@ -53,6 +56,81 @@ impl FromSql<Text, Sqlite> for UserType {
     }
 }
 
+/// What a personal access token may be used for
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(FromSqlRow, AsExpression))]
+#[cfg_attr(feature = "server", diesel(sql_type = Text))]
+pub enum AccessTokenScope {
+    /// Only requests, which do not modify anything (`GET` and `HEAD`)
+    Read,
+    /// Everything the user may do, except managing the account
+    ReadWrite,
+}
+
+#[cfg(feature = "server")]
+impl ToSql<Text, Sqlite> for AccessTokenScope {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        let value = match self {
+            AccessTokenScope::Read => "READ",
+            AccessTokenScope::ReadWrite => "READ_WRITE",
+        };
+
+        out.set_value(value);
+        Ok(IsNull::No)
+    }
+}
+
+#[cfg(feature = "server")]
+impl FromSql<Text, Sqlite> for AccessTokenScope {
+    fn from_sql(
+        bytes: <Sqlite as diesel::backend::Backend>::RawValue<'_>,
+    ) -> deserialize::Result<Self> {
+        let s = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+
+        match s.as_str() {
+            "READ" => Ok(AccessTokenScope::Read),
+            "READ_WRITE" => Ok(AccessTokenScope::ReadWrite),
+            _ => Err(format!("Invalid AccessTokenScope: {}", s).into()),
+        }
+    }
+}
+
+/// Version of the Argon2 parameters, which are used to derive the server password and the
+/// key-wrapping key from the password of a user.
+pub type KdfVersion = i32;
+
+/// Parameters to derive the server password and the key-wrapping key from the password
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct KdfParams {
+    pub version: KdfVersion,
+    /// Base64-encoded (without padding) random salt. `None` if the hash of the username is used.
+    pub salt: Option<String>,
+}
+
+impl KdfParams {
+    /// 12 MiB memory, 3 iterations. Used before parameters were stored per user.
+    pub const LEGACY_VERSION: KdfVersion = 1;
+    /// 19 MiB memory, 3 iterations
+    pub const CURRENT_VERSION: KdfVersion = 2;
+
+    pub fn new(version: KdfVersion, salt: Option<String>) -> Self {
+        Self { version, salt }
+    }
+
+    /// Parameters of accounts created before parameters were stored per user
+    pub fn legacy() -> Self {
+        Self {
+            version: Self::LEGACY_VERSION,
+            salt: None,
+        }
+    }
+
+    /// Whether the client should derive new keys with the current parameters on the next login
+    pub fn needs_upgrade(&self) -> bool {
+        self.version < Self::CURRENT_VERSION || self.salt.is_none()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct UserKeys {
     pub public_key: Vec<u8>,
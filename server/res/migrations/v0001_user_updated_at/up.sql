@@ -0,0 +1 @@
+ALTER TABLE User ADD COLUMN updated_at TIMESTAMP NULL;
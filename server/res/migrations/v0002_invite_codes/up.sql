@@ -0,0 +1,12 @@
+CREATE TABLE InviteCode (
+    id                          TEXT        NOT NULL PRIMARY KEY,
+    -- SHA-512 of the code (hex), the plain code is never stored
+    code_hash                   TEXT        NOT NULL UNIQUE,
+    created_by                  TEXT            NULL REFERENCES User(id) ON DELETE SET NULL,
+    created_at                  TIMESTAMP   NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at                  TIMESTAMP       NULL,
+    max_uses                    INTEGER         NULL,
+    uses                        INTEGER     NOT NULL DEFAULT 0,
+    user_type                   TEXT        NOT NULL CHECK (user_type IN ('ADMIN', 'USER', 'RESTRICTED')),
+    storage_limit               INTEGER     NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE RefreshToken DROP COLUMN session_created_at;
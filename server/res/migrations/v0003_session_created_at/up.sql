@@ -0,0 +1 @@
+ALTER TABLE RefreshToken ADD COLUMN session_created_at TIMESTAMP NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE User DROP COLUMN recovery_master_key;
+ALTER TABLE User DROP COLUMN recovery_password_hash;
@@ -0,0 +1,4 @@
+-- Argon2 hash of the password derived from the recovery code
+ALTER TABLE User ADD COLUMN recovery_password_hash TEXT NULL;
+-- The master key, wrapped with the key derived from the recovery code
+ALTER TABLE User ADD COLUMN recovery_master_key BLOB NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE User DROP COLUMN kdf_version;
+ALTER TABLE User DROP COLUMN kdf_salt;
@@ -0,0 +1,3 @@
+-- NULL if the client derives the salt from the username (accounts created before this migration)
+ALTER TABLE User ADD COLUMN kdf_salt TEXT NULL;
+ALTER TABLE User ADD COLUMN kdf_version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,4 @@
+DROP TABLE TotpBackupCode;
+ALTER TABLE User DROP COLUMN totp_last_step;
+ALTER TABLE User DROP COLUMN totp_enabled;
+ALTER TABLE User DROP COLUMN totp_secret;
@@ -0,0 +1,12 @@
+-- Base32 encoded TOTP secret. Set when the enrolment is started, even before it is confirmed.
+ALTER TABLE User ADD COLUMN totp_secret TEXT NULL;
+ALTER TABLE User ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT 0;
+-- The last accepted time step, codes can only be used once
+ALTER TABLE User ADD COLUMN totp_last_step INTEGER NULL;
+
+CREATE TABLE TotpBackupCode (
+    user_id                     TEXT        NOT NULL REFERENCES User(id) ON DELETE CASCADE,
+    -- SHA-256 of the code (hex), the plain code is never stored
+    code_hash                   TEXT        NOT NULL,
+    PRIMARY KEY (user_id, code_hash)
+);
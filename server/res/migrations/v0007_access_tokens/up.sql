@@ -0,0 +1,11 @@
+CREATE TABLE AccessToken (
+    id                          TEXT        NOT NULL PRIMARY KEY,
+    user_id                     TEXT        NOT NULL REFERENCES User(id) ON DELETE CASCADE,
+    name                        TEXT        NOT NULL,
+    -- SHA-256 of the token (hex), the plain token is never stored
+    token_hash                  TEXT        NOT NULL UNIQUE,
+    scope                       TEXT        NOT NULL CHECK (scope IN ('READ', 'READ_WRITE')),
+    created_at                  TIMESTAMP   NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at                  TIMESTAMP       NULL,
+    last_used_at                TIMESTAMP       NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE Share DROP COLUMN permission;
@@ -0,0 +1 @@
+ALTER TABLE Share ADD COLUMN permission TEXT NOT NULL DEFAULT 'EDIT' CHECK (permission IN ('VIEW', 'EDIT', 'MANAGE'));
@@ -0,0 +1,2 @@
+ALTER TABLE Share DROP COLUMN revoke_on_expiry;
+ALTER TABLE Share DROP COLUMN expires_at;
@@ -0,0 +1,3 @@
+ALTER TABLE Share ADD COLUMN expires_at TIMESTAMP NULL;
+-- if set, the access of the user who accepted the share also ends when the share expires
+ALTER TABLE Share ADD COLUMN revoke_on_expiry BOOLEAN NOT NULL DEFAULT FALSE;
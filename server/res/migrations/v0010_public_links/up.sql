@@ -0,0 +1,9 @@
+CREATE TABLE PublicLink (
+    id                          TEXT        NOT NULL PRIMARY KEY,
+    node_id                     TEXT        NOT NULL REFERENCES Node(id) ON DELETE CASCADE,
+    created_by                  TEXT        NOT NULL REFERENCES User(id) ON DELETE CASCADE,
+    -- the metadata key of the node, wrapped with the key in the url fragment
+    wrapped_metadata_key        BLOB        NOT NULL,
+    created_at                  TIMESTAMP   NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at                  TIMESTAMP       NULL
+);
@@ -0,0 +1,2 @@
+ALTER TABLE Share DROP COLUMN encrypted_share_key;
+ALTER TABLE Share DROP COLUMN shared_with;
@@ -0,0 +1,4 @@
+-- the user a share was sent to directly. Only this user can see and accept the share.
+ALTER TABLE Share ADD COLUMN shared_with TEXT NULL REFERENCES User(id) ON DELETE CASCADE;
+-- the key in the url of a share link, encrypted with the public key of the recipient
+ALTER TABLE Share ADD COLUMN encrypted_share_key BLOB NULL;
@@ -0,0 +1,2 @@
+DROP TABLE DroppedFile;
+DROP TABLE FileDrop;
@@ -0,0 +1,16 @@
+CREATE TABLE FileDrop (
+    id                          TEXT        NOT NULL PRIMARY KEY,
+    node_id                     TEXT        NOT NULL REFERENCES Node(id) ON DELETE CASCADE,
+    created_by                  TEXT        NOT NULL REFERENCES User(id) ON DELETE CASCADE,
+    created_at                  TIMESTAMP   NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at                  TIMESTAMP       NULL
+);
+
+-- files uploaded through a file drop, which were not adopted by the owner of the folder yet
+CREATE TABLE DroppedFile (
+    node_id                     TEXT        NOT NULL PRIMARY KEY REFERENCES Node(id) ON DELETE CASCADE,
+    -- dropped files stay around after the drop was revoked or expired
+    file_drop_id                TEXT            NULL REFERENCES FileDrop(id) ON DELETE SET NULL,
+    -- the metadata key of the node, encrypted with the public key of the folder owner
+    encrypted_metadata_key      BLOB        NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE InviteCode DROP COLUMN username;
@@ -0,0 +1,2 @@
+-- Codes created for a single account (see `post_user`) can only be redeemed with this username
+ALTER TABLE InviteCode ADD COLUMN username TEXT NULL;
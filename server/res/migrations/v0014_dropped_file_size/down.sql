@@ -0,0 +1 @@
+ALTER TABLE DroppedFile DROP COLUMN uploaded_size;
@@ -0,0 +1,3 @@
+-- The size of the chunks uploaded so far. It was added to the storage used by the owner of the
+-- folder and is given back if the upload is abandoned.
+ALTER TABLE DroppedFile ADD COLUMN uploaded_size BIGINT NOT NULL DEFAULT 0;
@@ -1,3 +1,5 @@
+use crate::db::operations::user::backfill_kdf_salts;
+
 use diesel::connection::SimpleConnection;
 use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool};
 use diesel::{prelude::*, r2d2};
@@ -14,6 +16,7 @@ impl CustomizeConnection<SqliteConnection, r2d2::Error> for ConnectionCustomizer
             PRAGMA journal_mode = WAL;
             PRAGMA synchronous = NORMAL;
             PRAGMA foreign_keys = ON;
+            PRAGMA busy_timeout = 5000;
         ",
         )
         .map_err(r2d2::Error::QueryError)?;
@@ -37,5 +40,11 @@ pub fn create_pool(database_url: &str, pool_size: usize) -> DbPool {
     tracing::info!("Running pending migrations");
     conn.run_pending_migrations(MIGRATIONS).unwrap();
 
+    // SQLite cannot compute the salts in a migration
+    let backfilled = backfill_kdf_salts(&mut conn).expect("Failed to backfill KDF salts");
+    if backfilled > 0 {
+        tracing::info!("Stored the salts of {backfilled} users");
+    }
+
     db_pool
 }
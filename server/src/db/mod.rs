@@ -2,9 +2,15 @@ pub mod connection;
 pub mod operations;
 pub mod schema;
 
+pub use schema::AccessToken::dsl as AccessTokenDsl;
+pub use schema::DroppedFile::dsl as DroppedFileDsl;
+pub use schema::FileDrop::dsl as FileDropDsl;
+pub use schema::InviteCode::dsl as InviteCodeDsl;
 pub use schema::Node::dsl as NodeDsl;
+pub use schema::PublicLink::dsl as PublicLinkDsl;
 pub use schema::RefreshToken::dsl as RefreshTokenDsl;
 pub use schema::Revision::dsl as RevisionDsl;
 pub use schema::Share::dsl as ShareDsl;
 pub use schema::TokenBlacklist::dsl as TokenBlacklistDsl;
+pub use schema::TotpBackupCode::dsl as TotpBackupCodeDsl;
 pub use schema::User::dsl as UserDsl;
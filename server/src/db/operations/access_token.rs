@@ -0,0 +1,93 @@
+use crate::db::AccessTokenDsl;
+use crate::user::AccessTokenEntity;
+
+use crabdrive_common::user::{AccessTokenId, UserId};
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+    SelectableHelper, SqliteConnection,
+};
+use tracing::instrument;
+
+#[instrument(skip(conn, access_token), err)]
+pub fn insert_access_token(
+    conn: &mut SqliteConnection,
+    access_token: &AccessTokenEntity,
+) -> Result<AccessTokenEntity> {
+    conn.transaction(|conn| {
+        let access_token = diesel::insert_into(AccessTokenDsl::AccessToken)
+            .values(access_token)
+            .returning(AccessTokenEntity::as_select())
+            .get_result(conn)?;
+        Ok(access_token)
+    })
+}
+
+/// Select all access tokens of a user (including expired ones), ordered by their creation date
+#[instrument(skip(conn), err)]
+pub fn select_access_tokens(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+) -> Result<Vec<AccessTokenEntity>> {
+    conn.transaction(|conn| {
+        let access_tokens = AccessTokenDsl::AccessToken
+            .filter(AccessTokenDsl::user_id.eq(user_id))
+            .order(AccessTokenDsl::created_at.asc())
+            .load::<AccessTokenEntity>(conn)?;
+        Ok(access_tokens)
+    })
+}
+
+/// Delete an access token of a user. Returns `None` if the user has no such token.
+#[instrument(skip(conn), err)]
+pub fn delete_access_token(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    id: AccessTokenId,
+) -> Result<Option<AccessTokenEntity>> {
+    conn.transaction(|conn| {
+        let access_token = diesel::delete(AccessTokenDsl::AccessToken)
+            .filter(AccessTokenDsl::id.eq(id))
+            .filter(AccessTokenDsl::user_id.eq(user_id))
+            .returning(AccessTokenEntity::as_select())
+            .get_result(conn)
+            .optional()?;
+        Ok(access_token)
+    })
+}
+
+#[instrument(skip(conn), err)]
+pub fn delete_access_tokens(conn: &mut SqliteConnection, user_id: UserId) -> Result<usize> {
+    conn.transaction(|conn| {
+        let deleted = diesel::delete(AccessTokenDsl::AccessToken)
+            .filter(AccessTokenDsl::user_id.eq(user_id))
+            .execute(conn)?;
+        Ok(deleted)
+    })
+}
+
+/// Mark an access token as used, if it is not expired. Returns `None` if the token is unknown or
+/// expired.
+#[instrument(skip(conn, token_hash), err)]
+pub fn use_access_token(
+    conn: &mut SqliteConnection,
+    token_hash: &str,
+    now: NaiveDateTime,
+) -> Result<Option<AccessTokenEntity>> {
+    conn.transaction(|conn| {
+        let access_token = diesel::update(AccessTokenDsl::AccessToken)
+            .filter(AccessTokenDsl::token_hash.eq(token_hash))
+            .filter(
+                AccessTokenDsl::expires_at
+                    .is_null()
+                    .or(AccessTokenDsl::expires_at.gt(now)),
+            )
+            .set(AccessTokenDsl::last_used_at.eq(Some(now)))
+            .returning(AccessTokenEntity::as_select())
+            .get_result(conn)
+            .optional()?;
+        Ok(access_token)
+    })
+}
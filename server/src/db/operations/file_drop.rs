@@ -0,0 +1,206 @@
+use crate::db::operations::user::decrease_storage_used;
+use crate::db::{DroppedFileDsl, FileDropDsl, NodeDsl, RevisionDsl};
+use crate::storage::node::NodeEntity;
+use crate::storage::revision::RevisionEntity;
+use crate::storage::share::{DroppedFileEntity, FileDropEntity};
+
+use crabdrive_common::data::DataAmount;
+use crabdrive_common::storage::{FileDropId, NodeId, RevisionId};
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::{
+    Connection, ExpressionMethods, JoinOnDsl, OptionalExtension, QueryDsl, RunQueryDsl,
+    SelectableHelper, SqliteConnection,
+};
+use tracing::instrument;
+
+#[instrument(skip(conn), err)]
+pub fn select_file_drop(
+    conn: &mut SqliteConnection,
+    id: FileDropId,
+) -> Result<Option<FileDropEntity>> {
+    conn.transaction(|conn| {
+        let file_drop = FileDropDsl::FileDrop
+            .filter(FileDropDsl::id.eq(id))
+            .first::<FileDropEntity>(conn)
+            .optional()?;
+        Ok(file_drop)
+    })
+}
+
+#[instrument(skip(conn), err)]
+pub fn insert_file_drop(
+    conn: &mut SqliteConnection,
+    file_drop: &FileDropEntity,
+) -> Result<FileDropEntity> {
+    conn.transaction(|conn| {
+        let file_drop = diesel::insert_into(FileDropDsl::FileDrop)
+            .values(file_drop)
+            .returning(FileDropEntity::as_select())
+            .get_result(conn)?;
+        Ok(file_drop)
+    })
+}
+
+#[instrument(skip(conn), err)]
+pub fn delete_file_drop(
+    conn: &mut SqliteConnection,
+    id: FileDropId,
+) -> Result<Option<FileDropEntity>> {
+    conn.transaction(|conn| {
+        let file_drop = diesel::delete(FileDropDsl::FileDrop)
+            .filter(FileDropDsl::id.eq(id))
+            .returning(FileDropEntity::as_select())
+            .get_result(conn)
+            .optional()?;
+        Ok(file_drop)
+    })
+}
+
+/// Delete all expired file drops. Files dropped before are kept. Returns the amount of deleted
+/// drops.
+#[instrument(skip(conn), err)]
+pub fn delete_expired_file_drops(conn: &mut SqliteConnection, now: NaiveDateTime) -> Result<usize> {
+    conn.transaction(|conn| {
+        let count = diesel::delete(FileDropDsl::FileDrop)
+            .filter(FileDropDsl::expires_at.le(now))
+            .execute(conn)?;
+        Ok(count)
+    })
+}
+
+#[instrument(skip(conn, dropped_file), err)]
+pub fn insert_dropped_file(
+    conn: &mut SqliteConnection,
+    dropped_file: &DroppedFileEntity,
+) -> Result<DroppedFileEntity> {
+    conn.transaction(|conn| {
+        let dropped_file = diesel::insert_into(DroppedFileDsl::DroppedFile)
+            .values(dropped_file)
+            .returning(DroppedFileEntity::as_select())
+            .get_result(conn)?;
+        Ok(dropped_file)
+    })
+}
+
+#[instrument(skip(conn), err)]
+pub fn select_dropped_file(
+    conn: &mut SqliteConnection,
+    node_id: NodeId,
+) -> Result<Option<DroppedFileEntity>> {
+    conn.transaction(|conn| {
+        let dropped_file = DroppedFileDsl::DroppedFile
+            .filter(DroppedFileDsl::node_id.eq(node_id))
+            .first::<DroppedFileEntity>(conn)
+            .optional()?;
+        Ok(dropped_file)
+    })
+}
+
+/// Get all files dropped into a folder, which were not adopted yet
+#[instrument(skip(conn), err)]
+pub fn get_dropped_files_in_folder(
+    conn: &mut SqliteConnection,
+    folder_id: NodeId,
+) -> Result<Vec<DroppedFileEntity>> {
+    conn.transaction(|conn| {
+        let dropped_files = DroppedFileDsl::DroppedFile
+            .inner_join(NodeDsl::Node.on(NodeDsl::id.eq(DroppedFileDsl::node_id)))
+            .filter(NodeDsl::parent_id.eq(folder_id))
+            .select(DroppedFileEntity::as_select())
+            .load::<DroppedFileEntity>(conn)?;
+        Ok(dropped_files)
+    })
+}
+
+/// Mark the files as adopted. Returns the amount of adopted files.
+#[instrument(skip(conn), err)]
+pub fn delete_dropped_files(conn: &mut SqliteConnection, node_ids: &[NodeId]) -> Result<usize> {
+    conn.transaction(|conn| {
+        let count = diesel::delete(DroppedFileDsl::DroppedFile)
+            .filter(DroppedFileDsl::node_id.eq_any(node_ids))
+            .execute(conn)?;
+        Ok(count)
+    })
+}
+
+/// Add `amount` to the uploaded size of a dropped file. Does nothing, if the file was not dropped
+/// or was adopted already.
+#[instrument(skip(conn), err)]
+pub fn increase_dropped_file_size(
+    conn: &mut SqliteConnection,
+    node_id: NodeId,
+    amount: DataAmount,
+) -> Result<()> {
+    let amount: i64 = amount.as_bytes().try_into()?;
+    diesel::update(DroppedFileDsl::DroppedFile)
+        .filter(DroppedFileDsl::node_id.eq(node_id))
+        .set(DroppedFileDsl::uploaded_size.eq(DroppedFileDsl::uploaded_size + amount))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Select the revisions of dropped files, whose upload started before `started_before` and was
+/// never committed
+#[instrument(skip(conn), err)]
+pub fn select_stale_dropped_revisions(
+    conn: &mut SqliteConnection,
+    started_before: NaiveDateTime,
+) -> Result<Vec<RevisionEntity>> {
+    conn.transaction(|conn| {
+        let revisions = RevisionDsl::Revision
+            .inner_join(
+                DroppedFileDsl::DroppedFile.on(DroppedFileDsl::node_id.eq(RevisionDsl::file_id)),
+            )
+            .filter(RevisionDsl::upload_ended_on.is_null())
+            .filter(RevisionDsl::upload_started_on.lt(started_before))
+            .select(RevisionEntity::as_select())
+            .load::<RevisionEntity>(conn)?;
+        Ok(revisions)
+    })
+}
+
+/// Delete a dropped file together with its node and its uncommitted revision, and give the
+/// uploaded size back to the owner. Returns `false` (and changes nothing) if the revision was
+/// committed or the file is not a dropped file anymore.
+#[instrument(skip(conn), err)]
+pub fn delete_uncommitted_dropped_file(
+    conn: &mut SqliteConnection,
+    revision_id: RevisionId,
+) -> Result<bool> {
+    conn.transaction(|conn| {
+        let Some(revision) = RevisionDsl::Revision
+            .filter(RevisionDsl::id.eq(revision_id))
+            .filter(RevisionDsl::upload_ended_on.is_null())
+            .first::<RevisionEntity>(conn)
+            .optional()?
+        else {
+            return Ok(false);
+        };
+
+        let Some(dropped_file) = diesel::delete(DroppedFileDsl::DroppedFile)
+            .filter(DroppedFileDsl::node_id.eq(revision.file_id))
+            .returning(DroppedFileEntity::as_select())
+            .get_result(conn)
+            .optional()?
+        else {
+            return Ok(false);
+        };
+
+        diesel::update(NodeDsl::Node)
+            .filter(NodeDsl::id.eq(revision.file_id))
+            .set(NodeDsl::current_revision.eq(None::<RevisionId>))
+            .execute(conn)?;
+        diesel::delete(RevisionDsl::Revision)
+            .filter(RevisionDsl::file_id.eq(revision.file_id))
+            .execute(conn)?;
+        let node: NodeEntity = diesel::delete(NodeDsl::Node)
+            .filter(NodeDsl::id.eq(revision.file_id))
+            .returning(NodeEntity::as_select())
+            .get_result(conn)?;
+
+        decrease_storage_used(conn, node.owner_id, dropped_file.uploaded_size)?;
+        Ok(true)
+    })
+}
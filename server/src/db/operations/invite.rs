@@ -0,0 +1,133 @@
+use crate::db::InviteCodeDsl;
+use crate::user::InviteCodeEntity;
+
+use crabdrive_common::user::InviteCodeId;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, NullableExpressionMethods,
+    OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper, SqliteConnection,
+};
+use tracing::instrument;
+
+#[instrument(skip(conn), err)]
+pub fn insert_invite_code(
+    conn: &mut SqliteConnection,
+    invite_code: &InviteCodeEntity,
+) -> Result<InviteCodeEntity> {
+    conn.transaction(|conn| {
+        let invite_code = diesel::insert_into(InviteCodeDsl::InviteCode)
+            .values(invite_code)
+            .returning(InviteCodeEntity::as_select())
+            .get_result(conn)?;
+        Ok(invite_code)
+    })
+}
+
+/// Select all invite codes (including expired and used up ones), ordered by their creation date
+#[instrument(skip(conn), err)]
+pub fn select_invite_codes(conn: &mut SqliteConnection) -> Result<Vec<InviteCodeEntity>> {
+    conn.transaction(|conn| {
+        let invite_codes = InviteCodeDsl::InviteCode
+            .order(InviteCodeDsl::created_at.asc())
+            .load::<InviteCodeEntity>(conn)?;
+        Ok(invite_codes)
+    })
+}
+
+#[instrument(skip(conn), err)]
+pub fn delete_invite_code(
+    conn: &mut SqliteConnection,
+    id: InviteCodeId,
+) -> Result<Option<InviteCodeEntity>> {
+    conn.transaction(|conn| {
+        let invite_code = diesel::delete(InviteCodeDsl::InviteCode)
+            .filter(InviteCodeDsl::id.eq(id))
+            .returning(InviteCodeEntity::as_select())
+            .get_result(conn)
+            .optional()?;
+        Ok(invite_code)
+    })
+}
+
+/// Select an invite code by its hash, if it is neither expired nor used up
+#[instrument(skip(conn, code_hash), err)]
+pub fn select_redeemable_invite_code(
+    conn: &mut SqliteConnection,
+    code_hash: &str,
+    now: NaiveDateTime,
+) -> Result<Option<InviteCodeEntity>> {
+    conn.transaction(|conn| {
+        let invite_code = InviteCodeDsl::InviteCode
+            .filter(InviteCodeDsl::code_hash.eq(code_hash))
+            .filter(
+                InviteCodeDsl::expires_at
+                    .is_null()
+                    .or(InviteCodeDsl::expires_at.gt(now)),
+            )
+            .filter(
+                InviteCodeDsl::max_uses
+                    .is_null()
+                    .or(InviteCodeDsl::uses.nullable().lt(InviteCodeDsl::max_uses)),
+            )
+            .first::<InviteCodeEntity>(conn)
+            .optional()?;
+        Ok(invite_code)
+    })
+}
+
+/// Increase the usage counter of a code, if it is neither expired nor used up. Returns `None` if
+/// the code cannot be redeemed.
+#[instrument(skip(conn, code_hash), err)]
+pub fn redeem_invite_code(
+    conn: &mut SqliteConnection,
+    code_hash: &str,
+    now: NaiveDateTime,
+) -> Result<Option<InviteCodeEntity>> {
+    conn.transaction(|conn| {
+        let invite_code = diesel::update(InviteCodeDsl::InviteCode)
+            .filter(InviteCodeDsl::code_hash.eq(code_hash))
+            .filter(
+                InviteCodeDsl::expires_at
+                    .is_null()
+                    .or(InviteCodeDsl::expires_at.gt(now)),
+            )
+            .filter(
+                InviteCodeDsl::max_uses
+                    .is_null()
+                    .or(InviteCodeDsl::uses.nullable().lt(InviteCodeDsl::max_uses)),
+            )
+            .set(InviteCodeDsl::uses.eq(InviteCodeDsl::uses + 1))
+            .returning(InviteCodeEntity::as_select())
+            .get_result(conn)
+            .optional()?;
+        Ok(invite_code)
+    })
+}
+
+/// Select a code, which reserves a username and can still be redeemed
+#[instrument(skip(conn), err)]
+pub fn select_reserving_invite_code(
+    conn: &mut SqliteConnection,
+    username: &str,
+    now: NaiveDateTime,
+) -> Result<Option<InviteCodeEntity>> {
+    conn.transaction(|conn| {
+        let invite_code = InviteCodeDsl::InviteCode
+            .filter(InviteCodeDsl::username.eq(username))
+            .filter(
+                InviteCodeDsl::expires_at
+                    .is_null()
+                    .or(InviteCodeDsl::expires_at.gt(now)),
+            )
+            .filter(
+                InviteCodeDsl::max_uses
+                    .is_null()
+                    .or(InviteCodeDsl::uses.nullable().lt(InviteCodeDsl::max_uses)),
+            )
+            .first::<InviteCodeEntity>(conn)
+            .optional()?;
+        Ok(invite_code)
+    })
+}
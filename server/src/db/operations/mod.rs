@@ -1,5 +1,10 @@
+pub mod access_token;
+pub mod file_drop;
+pub mod invite;
 pub mod node;
+pub mod public_link;
 pub mod revision;
 pub mod share;
 pub mod token;
+pub mod totp;
 pub mod user;
@@ -1,7 +1,8 @@
+use crate::storage::node::{ChangeCounterMismatch, MetadataUpdate};
 use crate::{db::NodeDsl, storage::node::NodeEntity};
 
 use crabdrive_common::encrypted_metadata::EncryptedMetadata;
-use crabdrive_common::storage::NodeId;
+use crabdrive_common::storage::{NodeId, RevisionId};
 
 use anyhow::{Context, Result};
 use diesel::{
@@ -33,37 +34,70 @@ pub fn select_node(conn: &mut SqliteConnection, node_id: NodeId) -> Result<Optio
     })
 }
 
-/// Insert a new node. Updates the parent metadata, if there is a parent.
+/// Insert a new node. Increases the metadata counter of the parent, but leaves its metadata
+/// untouched. Use [`insert_node_into_parent`] to replace the metadata of the parent as well.
 #[instrument(skip(conn), err)]
-pub fn insert_node(
-    conn: &mut SqliteConnection,
-    node: &NodeEntity,
-    parent_mdata: &EncryptedMetadata,
-) -> Result<()> {
+pub fn insert_node(conn: &mut SqliteConnection, node: &NodeEntity) -> Result<()> {
     // check that the node id is not nil as it may break the path to root function
     if node.id.eq(&NodeId::nil()) {
         return Err(anyhow::anyhow!("illegal node id"));
     }
 
     conn.transaction(|conn| {
-        let node = diesel::insert_into(NodeDsl::Node)
+        diesel::insert_into(NodeDsl::Node)
             .values(node)
-            .returning(NodeEntity::as_select())
-            .get_result(conn)?;
-        // In Parent-Node: Update metadata and increase Metadata-Counter by 1
-        if node.parent_id.is_some() {
+            .execute(conn)?;
+        if let Some(parent_id) = node.parent_id {
             diesel::update(NodeDsl::Node)
-                .filter(NodeDsl::id.eq(node.parent_id.unwrap()))
-                .set((
-                    NodeDsl::metadata.eq(parent_mdata),
-                    NodeDsl::metadata_change_counter.eq(NodeDsl::metadata_change_counter + 1),
-                ))
+                .filter(NodeDsl::id.eq(parent_id))
+                .set(NodeDsl::metadata_change_counter.eq(NodeDsl::metadata_change_counter + 1))
                 .execute(conn)?;
         }
         Ok(())
     })
 }
 
+/// Insert a new node and replace the metadata of its parent. Fails with [`ChangeCounterMismatch`]
+/// (and inserts nothing), if the parent was modified since the client read it.
+#[instrument(skip(conn), err)]
+pub fn insert_node_into_parent(
+    conn: &mut SqliteConnection,
+    node: &NodeEntity,
+    parent: &MetadataUpdate,
+) -> Result<()> {
+    if node.id.eq(&NodeId::nil()) {
+        return Err(anyhow::anyhow!("illegal node id"));
+    }
+
+    conn.transaction(|conn| {
+        compare_and_update_metadata(conn, parent)?;
+        diesel::insert_into(NodeDsl::Node)
+            .values(node)
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+/// Replace the metadata of a node and increase its metadata counter, but only if the counter still
+/// has the expected value. Otherwise nothing is written and [`ChangeCounterMismatch`] is returned.
+#[instrument(skip(conn), err)]
+pub fn compare_and_update_metadata(
+    conn: &mut SqliteConnection,
+    update: &MetadataUpdate,
+) -> Result<NodeEntity> {
+    diesel::update(NodeDsl::Node)
+        .filter(NodeDsl::id.eq(update.node_id))
+        .filter(NodeDsl::metadata_change_counter.eq(update.expected_counter))
+        .set((
+            NodeDsl::metadata.eq(&update.metadata),
+            NodeDsl::metadata_change_counter.eq(NodeDsl::metadata_change_counter + 1),
+        ))
+        .returning(NodeEntity::as_select())
+        .get_result(conn)
+        .optional()?
+        .ok_or_else(|| ChangeCounterMismatch(update.node_id).into())
+}
+
 /// Update a node and increase its metadata counter
 #[instrument(skip(conn), err)]
 pub fn update_node(conn: &mut SqliteConnection, node: &NodeEntity) -> Result<NodeEntity> {
@@ -80,6 +114,24 @@ pub fn update_node(conn: &mut SqliteConnection, node: &NodeEntity) -> Result<Nod
     })
 }
 
+/// Set the active revision of a file and increase its metadata counter
+#[instrument(skip(conn), err)]
+pub fn set_current_revision(
+    conn: &mut SqliteConnection,
+    node_id: NodeId,
+    revision_id: RevisionId,
+) -> Result<NodeEntity> {
+    let node = diesel::update(NodeDsl::Node)
+        .filter(NodeDsl::id.eq(node_id))
+        .set((
+            NodeDsl::current_revision.eq(Some(revision_id)),
+            NodeDsl::metadata_change_counter.eq(NodeDsl::metadata_change_counter + 1),
+        ))
+        .returning(NodeEntity::as_select())
+        .get_result(conn)?;
+    Ok(node)
+}
+
 // TODO: Cascade on Delete? If not, delete associated revisions and children nodes manually.
 #[instrument(skip(conn), err)]
 pub fn delete_node(
@@ -107,40 +159,28 @@ pub fn delete_node(
 }
 
 /// Update the parent node of a node (move the node). Requires the metadata of the old parent and
-/// the metadata of the new parent.
+/// the metadata of the new parent. Fails with [`ChangeCounterMismatch`], if one of the parents was
+/// modified in the meantime.
 #[instrument(skip(conn), err)]
 pub fn move_node(
     conn: &mut SqliteConnection,
     id: NodeId,
-    from: NodeId,
-    from_metadata: EncryptedMetadata,
-    to: NodeId,
-    to_metadata: EncryptedMetadata,
+    from: &MetadataUpdate,
+    to: &MetadataUpdate,
 ) -> Result<()> {
     conn.transaction(|conn| {
-        // Update old parent
-        diesel::update(NodeDsl::Node)
-            .filter(NodeDsl::id.eq(from))
-            .set((
-                NodeDsl::metadata.eq(&from_metadata),
-                NodeDsl::metadata_change_counter.eq(NodeDsl::metadata_change_counter + 1),
-            ))
-            .execute(conn)
-            .context("Failed to update from parent")?;
+        // Update old parent. When moving inside the same folder, the metadata of the new parent
+        // is the final state of the folder.
+        if from.node_id != to.node_id {
+            compare_and_update_metadata(conn, from).context("Failed to update from parent")?;
+        }
         // Update new parent
-        diesel::update(NodeDsl::Node)
-            .filter(NodeDsl::id.eq(to))
-            .set((
-                NodeDsl::metadata.eq(&to_metadata),
-                NodeDsl::metadata_change_counter.eq(NodeDsl::metadata_change_counter + 1),
-            ))
-            .execute(conn)
-            .context("Failed to update to parent")?;
+        compare_and_update_metadata(conn, to).context("Failed to update to parent")?;
         // Update the node itself
         diesel::update(NodeDsl::Node)
             .filter(NodeDsl::id.eq(id))
             .set((
-                NodeDsl::parent_id.eq(Some(to)),
+                NodeDsl::parent_id.eq(Some(to.node_id)),
                 NodeDsl::metadata_change_counter.eq(NodeDsl::metadata_change_counter + 1),
             ))
             .execute(conn)
@@ -0,0 +1,69 @@
+use crate::db::PublicLinkDsl;
+use crate::storage::share::PublicLinkEntity;
+
+use crabdrive_common::storage::PublicLinkId;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper,
+    SqliteConnection,
+};
+use tracing::instrument;
+
+#[instrument(skip(conn), err)]
+pub fn select_public_link(
+    conn: &mut SqliteConnection,
+    id: PublicLinkId,
+) -> Result<Option<PublicLinkEntity>> {
+    conn.transaction(|conn| {
+        let public_link = PublicLinkDsl::PublicLink
+            .filter(PublicLinkDsl::id.eq(id))
+            .first::<PublicLinkEntity>(conn)
+            .optional()?;
+        Ok(public_link)
+    })
+}
+
+#[instrument(skip(conn, public_link), err)]
+pub fn insert_public_link(
+    conn: &mut SqliteConnection,
+    public_link: &PublicLinkEntity,
+) -> Result<PublicLinkEntity> {
+    conn.transaction(|conn| {
+        let public_link = diesel::insert_into(PublicLinkDsl::PublicLink)
+            .values(public_link)
+            .returning(PublicLinkEntity::as_select())
+            .get_result(conn)?;
+        Ok(public_link)
+    })
+}
+
+#[instrument(skip(conn), err)]
+pub fn delete_public_link(
+    conn: &mut SqliteConnection,
+    id: PublicLinkId,
+) -> Result<Option<PublicLinkEntity>> {
+    conn.transaction(|conn| {
+        let public_link = diesel::delete(PublicLinkDsl::PublicLink)
+            .filter(PublicLinkDsl::id.eq(id))
+            .returning(PublicLinkEntity::as_select())
+            .get_result(conn)
+            .optional()?;
+        Ok(public_link)
+    })
+}
+
+/// Delete all expired public links. Returns the amount of deleted links.
+#[instrument(skip(conn), err)]
+pub fn delete_expired_public_links(
+    conn: &mut SqliteConnection,
+    now: NaiveDateTime,
+) -> Result<usize> {
+    conn.transaction(|conn| {
+        let count = diesel::delete(PublicLinkDsl::PublicLink)
+            .filter(PublicLinkDsl::expires_at.le(now))
+            .execute(conn)?;
+        Ok(count)
+    })
+}
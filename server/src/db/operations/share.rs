@@ -1,17 +1,18 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 use crate::db::ShareDsl;
 use crate::db::operations::node::{get_path_between_nodes, select_node};
 use crate::db::operations::user::select_user;
 use crate::storage::share::ShareEntity;
 
-use crabdrive_common::storage::{NodeId, ShareId};
+use crabdrive_common::storage::{NodeId, ShareId, SharePermission};
 use crabdrive_common::user::UserId;
 
 use anyhow::Result;
+use chrono::{NaiveDateTime, Utc};
 use diesel::{
-    Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper,
-    SqliteConnection,
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+    SelectableHelper, SqliteConnection,
 };
 use tracing::instrument;
 
@@ -73,14 +74,78 @@ pub fn get_all_shares_by_node(
     })
 }
 
+/// Get all shares accepted by a user. Shares, which revoked the access on expiry, are skipped.
 #[instrument(skip(conn), err)]
 pub fn get_all_shares_by_user(
     conn: &mut SqliteConnection,
     user_id: UserId,
 ) -> Result<Vec<ShareEntity>> {
+    let now = Utc::now().naive_utc();
     conn.transaction(|conn| {
         let shares = ShareDsl::Share
             .filter(ShareDsl::accepted_by.eq(user_id))
+            .filter(
+                ShareDsl::revoke_on_expiry
+                    .eq(false)
+                    .or(ShareDsl::expires_at.is_null())
+                    .or(ShareDsl::expires_at.gt(now)),
+            )
+            .load::<ShareEntity>(conn)?;
+        Ok(shares)
+    })
+}
+
+/// Get all shares created by a user, newest first. If `node_id` is set, only the shares of that
+/// node are returned.
+#[instrument(skip(conn), err)]
+pub fn get_shares_created_by_user(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    node_id: Option<NodeId>,
+) -> Result<Vec<ShareEntity>> {
+    conn.transaction(|conn| {
+        let mut query = ShareDsl::Share
+            .filter(ShareDsl::shared_by.eq(user_id))
+            .into_boxed();
+        if let Some(node_id) = node_id {
+            query = query.filter(ShareDsl::node_id.eq(node_id));
+        }
+        let shares = query
+            .order(ShareDsl::time_shared.desc())
+            .load::<ShareEntity>(conn)?;
+        Ok(shares)
+    })
+}
+
+/// Delete all shares, which have expired before being accepted. Returns the amount of deleted shares.
+#[instrument(skip(conn), err)]
+pub fn delete_expired_shares(conn: &mut SqliteConnection, now: NaiveDateTime) -> Result<usize> {
+    conn.transaction(|conn| {
+        let count = diesel::delete(ShareDsl::Share)
+            .filter(ShareDsl::accepted_by.is_null())
+            .filter(ShareDsl::expires_at.le(now))
+            .execute(conn)?;
+        Ok(count)
+    })
+}
+
+/// Get all shares sent directly to a user, which were not accepted yet and did not expire
+#[instrument(skip(conn), err)]
+pub fn get_pending_shares_for_user(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    now: NaiveDateTime,
+) -> Result<Vec<ShareEntity>> {
+    conn.transaction(|conn| {
+        let shares = ShareDsl::Share
+            .filter(ShareDsl::shared_with.eq(user_id))
+            .filter(ShareDsl::accepted_by.is_null())
+            .filter(
+                ShareDsl::expires_at
+                    .is_null()
+                    .or(ShareDsl::expires_at.gt(now)),
+            )
+            .order(ShareDsl::time_shared.desc())
             .load::<ShareEntity>(conn)?;
         Ok(shares)
     })
@@ -106,27 +171,34 @@ pub fn get_share_by_node_id_and_accepted_user_id(
 pub fn get_access_list_parent_tree(
     conn: &mut SqliteConnection,
     node_id: NodeId,
-) -> Result<Vec<(UserId, String)>> {
+) -> Result<Vec<(UserId, String, SharePermission)>> {
     let path_to_root = get_path_between_nodes(conn, NodeId::nil(), node_id)?;
 
-    let mut access_list = HashSet::new();
+    // a user may have access through multiple shares, only the highest permission is relevant
+    let mut access_list: HashMap<UserId, (String, SharePermission)> = HashMap::new();
 
     for node in path_to_root {
         let node_access_list = get_access_list_node(conn, node.id)?;
 
-        for entry in node_access_list {
-            access_list.insert(entry);
+        for (user_id, username, permission) in node_access_list {
+            access_list
+                .entry(user_id)
+                .and_modify(|(_, existing)| *existing = (*existing).max(permission))
+                .or_insert((username, permission));
         }
     }
 
-    Ok(access_list.into_iter().collect())
+    Ok(access_list
+        .into_iter()
+        .map(|(user_id, (username, permission))| (user_id, username, permission))
+        .collect())
 }
 
 #[instrument(skip(conn), err)]
 fn get_access_list_node(
     conn: &mut SqliteConnection,
     node_id: NodeId,
-) -> Result<Vec<(UserId, String)>> {
+) -> Result<Vec<(UserId, String, SharePermission)>> {
     let Some(node) = select_node(conn, node_id)? else {
         return Ok(vec![]);
     };
@@ -134,33 +206,44 @@ fn get_access_list_node(
     let owner = select_user(conn, node.owner_id)?
         .ok_or(anyhow::anyhow!("db constraints are not respected"))?;
 
-    let mut access_list = vec![(owner.id, owner.username)];
+    let mut access_list = vec![(owner.id, owner.username, SharePermission::Manage)];
 
     let share_entities = get_all_shares_by_node(conn, node_id)?;
+    let now = Utc::now().naive_utc();
 
     for share_entity in share_entities {
+        // skip pending shares and shares, which revoked the access on expiry
+        if !share_entity.grants_access(now) {
+            continue;
+        }
         let Some(user_id) = share_entity.accepted_by else {
             continue;
         };
 
         let user = select_user(conn, user_id)?
             .ok_or(anyhow::anyhow!("db constraints are not respected"))?;
-        access_list.push((user.id, user.username));
+        access_list.push((user.id, user.username, share_entity.permission));
     }
 
     Ok(access_list)
 }
 
+/// Get the permission of a user on a node. Owners always have [`SharePermission::Manage`], other
+/// users get the highest permission of all accepted shares of the node or one of its parents.
 #[instrument(skip(conn), err)]
-pub fn has_access(conn: &mut SqliteConnection, node_id: NodeId, user_id: UserId) -> Result<bool> {
+pub fn select_permission(
+    conn: &mut SqliteConnection,
+    node_id: NodeId,
+    user_id: UserId,
+) -> Result<Option<SharePermission>> {
     let node = if let Some(node) = select_node(conn, node_id)? {
         node
     } else {
-        return Ok(false);
+        return Ok(None);
     };
 
     if node.owner_id == user_id {
-        return Ok(true);
+        return Ok(Some(SharePermission::Manage));
     }
 
     // this will return the path to between a root node and the current node as the nil node does not exist (probably)
@@ -168,18 +251,33 @@ pub fn has_access(conn: &mut SqliteConnection, node_id: NodeId, user_id: UserId)
 
     let shared_with_user = get_all_shares_by_user(conn, user_id)?;
 
+    let mut permission = None;
+
     for node in path_to_root.iter().rev() {
-        // if the subtree containing the node was moved to the trash it should not be accessible anymore
+        // if the subtree containing the node was moved to the trash, shares above it do not grant
+        // access anymore
         if node.deleted_on.is_some() {
-            return Ok(false);
+            return Ok(permission);
         }
 
-        if shared_with_user
+        for share_entity in shared_with_user
             .iter()
-            .any(|share_entity| share_entity.node_id == node.id)
+            .filter(|share_entity| share_entity.node_id == node.id)
         {
-            return Ok(true);
+            permission = permission.max(Some(share_entity.permission));
         }
     }
-    Ok(false)
+    Ok(permission)
+}
+
+/// Check if a user has at least the given permission on a node
+#[instrument(skip(conn), err)]
+pub fn has_access(
+    conn: &mut SqliteConnection,
+    node_id: NodeId,
+    user_id: UserId,
+    permission: SharePermission,
+) -> Result<bool> {
+    let user_permission = select_permission(conn, node_id, user_id)?;
+    Ok(user_permission.is_some_and(|user_permission| user_permission >= permission))
 }
@@ -1,6 +1,8 @@
 use crate::db::{RefreshTokenDsl, TokenBlacklistDsl};
 use crate::user::{BlacklistedTokenEntity, RefreshTokenEntity, SessionId};
 
+use crabdrive_common::user::UserId;
+
 use anyhow::Result;
 use chrono::NaiveDateTime;
 use diesel::{
@@ -71,6 +73,43 @@ pub fn invalidate_token_family(
     })
 }
 
+#[instrument(skip(conn), err)]
+pub fn select_active_refresh_tokens(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    now: NaiveDateTime,
+) -> Result<Vec<RefreshTokenEntity>> {
+    conn.transaction(|conn| {
+        // Refreshing invalidates the previous token, so there is only one valid token per session
+        let tokens = RefreshTokenDsl::RefreshToken
+            .filter(RefreshTokenDsl::user_id.eq(user_id))
+            .filter(RefreshTokenDsl::invalidated_at.is_null())
+            .filter(RefreshTokenDsl::expires_at.gt(now))
+            .order(RefreshTokenDsl::session_created_at.asc())
+            .load::<RefreshTokenEntity>(conn)?;
+        Ok(tokens)
+    })
+}
+
+#[instrument(skip(conn), err)]
+pub fn invalidate_user_session(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    session_id: SessionId,
+    invalidated_at_time: NaiveDateTime,
+) -> Result<usize> {
+    conn.transaction(|conn| {
+        // Same as `invalidate_token_family`, but makes sure the session belongs to the user
+        let invalidated_count = diesel::update(RefreshTokenDsl::RefreshToken)
+            .filter(RefreshTokenDsl::user_id.eq(user_id))
+            .filter(RefreshTokenDsl::session_id.eq(session_id))
+            .filter(RefreshTokenDsl::invalidated_at.is_null())
+            .set(RefreshTokenDsl::invalidated_at.eq(Some(invalidated_at_time)))
+            .execute(conn)?;
+        Ok(invalidated_count)
+    })
+}
+
 #[instrument(skip(conn), err)]
 pub fn selected_blacklisted_token(
     conn: &mut SqliteConnection,
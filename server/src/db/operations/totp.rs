@@ -0,0 +1,112 @@
+use crate::db::{TotpBackupCodeDsl, UserDsl};
+use crate::user::UserEntity;
+
+use crabdrive_common::user::UserId;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, RunQueryDsl, SelectableHelper,
+    SqliteConnection,
+};
+use tracing::instrument;
+
+#[instrument(skip(conn, totp_secret), err)]
+pub fn update_user_totp(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    totp_secret: Option<&str>,
+    totp_enabled: bool,
+    totp_last_step: Option<i64>,
+    updated_at: NaiveDateTime,
+) -> Result<UserEntity> {
+    conn.transaction(|conn| {
+        let updated = diesel::update(UserDsl::User)
+            .filter(UserDsl::id.eq(user_id))
+            .set((
+                UserDsl::totp_secret.eq(totp_secret),
+                UserDsl::totp_enabled.eq(totp_enabled),
+                UserDsl::totp_last_step.eq(totp_last_step),
+                UserDsl::updated_at.eq(Some(updated_at)),
+            ))
+            .returning(UserEntity::as_select())
+            .get_result(conn)?;
+        Ok(updated)
+    })
+}
+
+/// Mark a time step as used. Returns `false` if the same or a later time step was already used.
+#[instrument(skip(conn), err)]
+pub fn update_totp_last_step(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    step: i64,
+) -> Result<bool> {
+    conn.transaction(|conn| {
+        let updated = diesel::update(UserDsl::User)
+            .filter(UserDsl::id.eq(user_id))
+            .filter(
+                UserDsl::totp_last_step
+                    .is_null()
+                    .or(UserDsl::totp_last_step.lt(step)),
+            )
+            .set(UserDsl::totp_last_step.eq(Some(step)))
+            .execute(conn)?;
+        Ok(updated == 1)
+    })
+}
+
+/// Replace all backup codes of a user
+#[instrument(skip(conn, code_hashes), err)]
+pub fn replace_totp_backup_codes(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    code_hashes: &[String],
+) -> Result<()> {
+    conn.transaction(|conn| {
+        diesel::delete(TotpBackupCodeDsl::TotpBackupCode)
+            .filter(TotpBackupCodeDsl::user_id.eq(user_id))
+            .execute(conn)?;
+
+        let rows: Vec<_> = code_hashes
+            .iter()
+            .map(|code_hash| {
+                (
+                    TotpBackupCodeDsl::user_id.eq(user_id),
+                    TotpBackupCodeDsl::code_hash.eq(code_hash),
+                )
+            })
+            .collect();
+        diesel::insert_into(TotpBackupCodeDsl::TotpBackupCode)
+            .values(&rows)
+            .execute(conn)?;
+
+        Ok(())
+    })
+}
+
+/// Delete a single backup code. Returns `false` if the user has no such code.
+#[instrument(skip(conn, code_hash), err)]
+pub fn delete_totp_backup_code(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    code_hash: &str,
+) -> Result<bool> {
+    conn.transaction(|conn| {
+        let deleted = diesel::delete(TotpBackupCodeDsl::TotpBackupCode)
+            .filter(TotpBackupCodeDsl::user_id.eq(user_id))
+            .filter(TotpBackupCodeDsl::code_hash.eq(code_hash))
+            .execute(conn)?;
+        Ok(deleted == 1)
+    })
+}
+
+#[instrument(skip(conn), err)]
+pub fn delete_totp_backup_codes(conn: &mut SqliteConnection, user_id: UserId) -> Result<usize> {
+    conn.transaction(|conn| {
+        let deleted = diesel::delete(TotpBackupCodeDsl::TotpBackupCode)
+            .filter(TotpBackupCodeDsl::user_id.eq(user_id))
+            .execute(conn)?;
+        Ok(deleted)
+    })
+}
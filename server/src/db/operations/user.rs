@@ -1,13 +1,20 @@
-use crate::db::UserDsl;
+use crate::db::{NodeDsl, RevisionDsl, ShareDsl, UserDsl};
+use crate::storage::revision::RevisionEntity;
 use crate::user::UserEntity;
 
-use crabdrive_common::user::UserId;
+use crabdrive_common::data::DataAmount;
+use crabdrive_common::encryption_key::EncryptionKey;
+use crabdrive_common::storage::{NodeId, RevisionId};
+use crabdrive_common::user::{KdfParams, UserId, UserKeys};
 
 use anyhow::Result;
+use argon2::password_hash::SaltString;
+use chrono::NaiveDateTime;
 use diesel::{
-    Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper,
-    SqliteConnection,
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+    SelectableHelper, SqliteConnection,
 };
+use sha2::{Digest, Sha256};
 use tracing::instrument;
 
 #[instrument(skip(conn), err)]
@@ -35,6 +42,31 @@ pub fn select_user_by_username(
     })
 }
 
+/// Select a page of users, ordered by their creation date
+#[instrument(skip(conn), err)]
+pub fn select_users(
+    conn: &mut SqliteConnection,
+    offset: i64,
+    limit: i64,
+) -> Result<Vec<UserEntity>> {
+    conn.transaction(|conn| {
+        let users = UserDsl::User
+            .order((UserDsl::created_at.asc(), UserDsl::id.asc()))
+            .offset(offset)
+            .limit(limit)
+            .load::<UserEntity>(conn)?;
+        Ok(users)
+    })
+}
+
+#[instrument(skip(conn), err)]
+pub fn count_users(conn: &mut SqliteConnection) -> Result<i64> {
+    conn.transaction(|conn| {
+        let count = UserDsl::User.count().get_result(conn)?;
+        Ok(count)
+    })
+}
+
 #[instrument(skip(conn), err)]
 pub fn insert_user(conn: &mut SqliteConnection, user: &UserEntity) -> Result<()> {
     conn.transaction(|conn| {
@@ -57,6 +89,136 @@ pub fn update_user(conn: &mut SqliteConnection, user: &UserEntity) -> Result<Use
     })
 }
 
+/// Add `amount` to the storage used by a user, unless this exceeds the storage limit of the user.
+/// Returns `false` (and changes nothing) if the limit would be exceeded.
+#[instrument(skip(conn), err)]
+pub fn increase_storage_used(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    amount: DataAmount,
+) -> Result<bool> {
+    let amount: i64 = amount.as_bytes().try_into()?;
+    let updated = diesel::update(UserDsl::User)
+        .filter(UserDsl::id.eq(user_id))
+        .filter((UserDsl::storage_used + amount).le(UserDsl::storage_limit))
+        .set(UserDsl::storage_used.eq(UserDsl::storage_used + amount))
+        .execute(conn)?;
+    Ok(updated == 1)
+}
+
+/// Subtract `amount` from the storage used by a user (e.g. after deleting data, which was counted)
+#[instrument(skip(conn), err)]
+pub fn decrease_storage_used(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    amount: DataAmount,
+) -> Result<()> {
+    let amount: i64 = amount.as_bytes().try_into()?;
+    diesel::update(UserDsl::User)
+        .filter(UserDsl::id.eq(user_id))
+        .set(UserDsl::storage_used.eq(UserDsl::storage_used - amount))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Replace the password hash, the wrapped keys and the KDF parameters of a user. The public key
+/// stays the same, as it is not wrapped.
+#[instrument(skip(conn, password_hash, keys), err)]
+pub fn update_user_credentials(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    password_hash: &str,
+    keys: &UserKeys,
+    kdf_params: &KdfParams,
+    updated_at: NaiveDateTime,
+) -> Result<UserEntity> {
+    conn.transaction(|conn| {
+        let updated = diesel::update(UserDsl::User)
+            .filter(UserDsl::id.eq(user_id))
+            .set((
+                UserDsl::password_hash.eq(password_hash),
+                UserDsl::master_key.eq(&keys.master_key),
+                UserDsl::private_key.eq(&keys.private_key),
+                UserDsl::root_key.eq(&keys.root_key),
+                UserDsl::trash_key.eq(&keys.trash_key),
+                UserDsl::kdf_salt.eq(&kdf_params.salt),
+                UserDsl::kdf_version.eq(kdf_params.version),
+                UserDsl::updated_at.eq(Some(updated_at)),
+            ))
+            .returning(UserEntity::as_select())
+            .get_result(conn)?;
+        Ok(updated)
+    })
+}
+
+/// Store the salt of users, who registered before salts were stored. The client used the hash of
+/// the username as salt for them, so it is stored as such. Returns the amount of updated users.
+#[instrument(skip(conn), err)]
+pub fn backfill_kdf_salts(conn: &mut SqliteConnection) -> Result<usize> {
+    conn.transaction(|conn| {
+        let users = UserDsl::User
+            .filter(UserDsl::kdf_salt.is_null())
+            .select((UserDsl::id, UserDsl::username))
+            .load::<(UserId, String)>(conn)?;
+
+        for (user_id, username) in &users {
+            let digest = Sha256::digest(username.as_bytes());
+            let salt = SaltString::encode_b64(&digest).map_err(|e| anyhow::anyhow!(e))?;
+            diesel::update(UserDsl::User)
+                .filter(UserDsl::id.eq(user_id))
+                .set(UserDsl::kdf_salt.eq(salt.as_str()))
+                .execute(conn)?;
+        }
+        Ok(users.len())
+    })
+}
+
+/// Set the key pair of a user, which is used to share nodes directly with the user
+#[instrument(skip(conn, public_key, private_key), err)]
+pub fn update_user_key_pair(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    public_key: &[u8],
+    private_key: &EncryptionKey,
+    updated_at: NaiveDateTime,
+) -> Result<UserEntity> {
+    conn.transaction(|conn| {
+        let updated = diesel::update(UserDsl::User)
+            .filter(UserDsl::id.eq(user_id))
+            .set((
+                UserDsl::public_key.eq(public_key),
+                UserDsl::private_key.eq(private_key),
+                UserDsl::updated_at.eq(Some(updated_at)),
+            ))
+            .returning(UserEntity::as_select())
+            .get_result(conn)?;
+        Ok(updated)
+    })
+}
+
+/// Set or remove (if `None`) the recovery key of a user
+#[instrument(skip(conn, recovery_password_hash, recovery_master_key), err)]
+pub fn update_user_recovery_key(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    recovery_password_hash: Option<&str>,
+    recovery_master_key: Option<&EncryptionKey>,
+    updated_at: NaiveDateTime,
+) -> Result<UserEntity> {
+    conn.transaction(|conn| {
+        let updated = diesel::update(UserDsl::User)
+            .filter(UserDsl::id.eq(user_id))
+            .set((
+                UserDsl::recovery_password_hash.eq(recovery_password_hash),
+                UserDsl::recovery_master_key.eq(recovery_master_key),
+                UserDsl::updated_at.eq(Some(updated_at)),
+            ))
+            .returning(UserEntity::as_select())
+            .get_result(conn)?;
+        Ok(updated)
+    })
+}
+
 #[instrument(skip(conn), err)]
 pub fn delete_user(conn: &mut SqliteConnection, user_id: UserId) -> Result<UserEntity> {
     conn.transaction(|conn| {
@@ -67,3 +229,66 @@ pub fn delete_user(conn: &mut SqliteConnection, user_id: UserId) -> Result<UserE
         Ok(user)
     })
 }
+
+/// Delete a user together with all of their nodes, revisions and shares (including shares the
+/// user accepted). Returns the deleted user and their revisions, so that the associated chunks
+/// can be deleted. Returns `None` if the user does not exist.
+#[instrument(skip(conn), err)]
+pub fn purge_user(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+) -> Result<Option<(UserEntity, Vec<RevisionEntity>)>> {
+    conn.transaction(|conn| {
+        if select_user(conn, user_id)?.is_none() {
+            return Ok(None);
+        }
+
+        // Nodes always have the same owner as their parent, so this selects complete trees
+        let owned_nodes = NodeDsl::Node
+            .filter(NodeDsl::owner_id.eq(user_id))
+            .select(NodeDsl::id);
+
+        diesel::delete(ShareDsl::Share)
+            .filter(
+                ShareDsl::shared_by
+                    .eq(user_id)
+                    .or(ShareDsl::accepted_by.eq(user_id))
+                    .or(ShareDsl::node_id.eq_any(owned_nodes)),
+            )
+            .execute(conn)?;
+
+        // Root & trash node are referenced by the user
+        diesel::update(UserDsl::User)
+            .filter(UserDsl::id.eq(user_id))
+            .set((
+                UserDsl::root_node.eq(None::<NodeId>),
+                UserDsl::trash_node.eq(None::<NodeId>),
+            ))
+            .execute(conn)?;
+
+        let revisions = RevisionDsl::Revision
+            .filter(RevisionDsl::file_id.eq_any(owned_nodes))
+            .load::<RevisionEntity>(conn)?;
+
+        diesel::update(NodeDsl::Node)
+            .filter(NodeDsl::owner_id.eq(user_id))
+            .set(NodeDsl::current_revision.eq(None::<RevisionId>))
+            .execute(conn)?;
+
+        diesel::delete(RevisionDsl::Revision)
+            .filter(RevisionDsl::file_id.eq_any(owned_nodes))
+            .execute(conn)?;
+
+        // Deleting all nodes in one statement keeps the parent constraints intact
+        diesel::delete(NodeDsl::Node)
+            .filter(NodeDsl::owner_id.eq(user_id))
+            .execute(conn)?;
+
+        let user: UserEntity = diesel::delete(UserDsl::User)
+            .filter(UserDsl::id.eq(user_id))
+            .returning(UserEntity::as_select())
+            .get_result(conn)?;
+
+        Ok(Some((user, revisions)))
+    })
+}
@@ -16,6 +16,14 @@ diesel::table! {
         root_node -> Nullable<Text>,
         trash_key  -> Binary,
         trash_node  -> Nullable<Text>,
+        updated_at -> Nullable<Timestamp>,
+        recovery_password_hash -> Nullable<Text>,
+        recovery_master_key -> Nullable<Binary>,
+        kdf_salt -> Nullable<Text>,
+        kdf_version -> Integer,
+        totp_secret -> Nullable<Text>,
+        totp_enabled -> Bool,
+        totp_last_step -> Nullable<BigInt>,
     }
 }
 
@@ -26,7 +34,8 @@ diesel::table! {
         user_id -> Text,
         session_id -> Text,
         expires_at -> Timestamp,
-        invalidated_at -> Nullable<Timestamp>
+        invalidated_at -> Nullable<Timestamp>,
+        session_created_at -> Nullable<Timestamp>,
     }
 }
 
@@ -74,10 +83,88 @@ diesel::table! {
         time_shared -> Timestamp,
         time_accepted -> Nullable<Timestamp>,
         shared_encryption_key -> Nullable<Binary>,
-        accepted_encryption_key -> Nullable<Binary>
+        accepted_encryption_key -> Nullable<Binary>,
+        permission -> Text,
+        expires_at -> Nullable<Timestamp>,
+        revoke_on_expiry -> Bool,
+        shared_with -> Nullable<Text>,
+        encrypted_share_key -> Nullable<Binary>,
+    }
+}
+
+diesel::table! {
+    #[allow(non_snake_case)]
+    InviteCode(id) {
+        id -> Text,
+        code_hash -> Text,
+        created_by -> Nullable<Text>,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+        max_uses -> Nullable<BigInt>,
+        uses -> BigInt,
+        user_type -> Text,
+        storage_limit -> BigInt,
+        username -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    #[allow(non_snake_case)]
+    TotpBackupCode(user_id, code_hash) {
+        user_id -> Text,
+        code_hash -> Text,
+    }
+}
+
+diesel::table! {
+    #[allow(non_snake_case)]
+    AccessToken(id) {
+        id -> Text,
+        user_id -> Text,
+        name -> Text,
+        token_hash -> Text,
+        scope -> Text,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    #[allow(non_snake_case)]
+    PublicLink(id) {
+        id -> Text,
+        node_id -> Text,
+        created_by -> Text,
+        wrapped_metadata_key -> Binary,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    #[allow(non_snake_case)]
+    FileDrop(id) {
+        id -> Text,
+        node_id -> Text,
+        created_by -> Text,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    #[allow(non_snake_case)]
+    DroppedFile(node_id) {
+        node_id -> Text,
+        file_drop_id -> Nullable<Text>,
+        encrypted_metadata_key -> Binary,
+        uploaded_size -> BigInt,
     }
 }
 
 diesel::joinable!(Revision -> Node (file_id));
 diesel::allow_tables_to_appear_in_same_query!(User, RefreshToken);
 diesel::allow_tables_to_appear_in_same_query!(Revision, Node, User, Share);
+diesel::allow_tables_to_appear_in_same_query!(DroppedFile, Node);
+diesel::allow_tables_to_appear_in_same_query!(DroppedFile, Revision);
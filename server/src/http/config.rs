@@ -1,9 +1,10 @@
 use crate::http::config::confique_auth_config_layer::AuthConfigLayer;
 use crate::http::config::confique_database_config_layer::DatabaseConfigLayer;
 use crate::http::config::confique_log_config_layer::LogConfigLayer;
+use crate::http::config::confique_s3_config_layer::S3ConfigLayer;
 use crate::http::config::confique_server_config_layer::ServerConfigLayer;
 use crate::http::config::confique_storage_config_layer::StorageConfigLayer;
-use crate::{DEFAULT_INVITE_CODE_HASH, DEFAULT_JWT_SECRET};
+use crate::{DEFAULT_INVITE_CODE_HASH, DEFAULT_JWT_SECRET, DEFAULT_PRE_LOGIN_SECRET};
 use confique::Config;
 use serde::{Deserialize, Serialize};
 use tracing_subscriber::{Layer, Registry};
@@ -19,7 +20,7 @@ fn is_valid_log_level(level: &String) -> Result<(), String> {
 fn is_valid_storage_backend(backend: &String) -> Result<(), String> {
     let backend: &str = backend.as_ref();
     match backend {
-        "C3" | "SFS" => Ok(()),
+        "C3" | "SFS" | "S3" => Ok(()),
         _ => Err("Invalid storage backend".to_string()),
     }
 }
@@ -126,9 +127,11 @@ pub struct LogConfig {
 #[derive(Debug, Clone, Serialize, Deserialize, Config)]
 pub struct StorageConfig {
     /// The storage backend to use. Can be one of the following:
+    /// - `C3`
     /// - `SFS`
+    /// - `S3` (see [`StorageConfig::s3`])
     ///
-    /// **Default**: `:temp:`
+    /// **Default**: `C3`
     #[config(env = "CRABDRIVE_STORAGE_BACKEND", validate = is_valid_storage_backend)]
     pub backend: String,
     /// The path to the storage directory. Can be of the following formats:
@@ -158,6 +161,47 @@ pub struct StorageConfig {
     /// **Default**: `2` (2 Chunks)
     #[config(env = "CRABDRIVE_CACHE_AHEAD")]
     pub cache_ahead: u8,
+
+    /// The bucket used by the `S3` backend
+    #[config(nested)]
+    pub s3: S3Config,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Config)]
+pub struct S3Config {
+    /// The URL of the S3-compatible service, e.g. `http://localhost:9000` for a local MinIO. If
+    /// this is not set, AWS is used.
+    ///
+    /// **Default**: None
+    #[config(env = "CRABDRIVE_S3_ENDPOINT")]
+    pub endpoint: Option<String>,
+
+    /// The region of the bucket
+    ///
+    /// **Default**: `us-east-1`
+    #[config(env = "CRABDRIVE_S3_REGION")]
+    pub region: String,
+
+    /// The name of the bucket. It has to exist already.
+    ///
+    /// **Default**: `crabdrive`
+    #[config(env = "CRABDRIVE_S3_BUCKET")]
+    pub bucket: String,
+
+    /// The access key used to sign requests
+    ///
+    /// **Notes**: If the access key is not set, the credentials of the instance (e.g. of an EC2
+    /// instance profile) are used.
+    ///
+    /// **Default**: None
+    #[config(env = "CRABDRIVE_S3_ACCESS_KEY_ID")]
+    pub access_key_id: Option<String>,
+
+    /// The secret belonging to [`S3Config::access_key_id`]
+    ///
+    /// **Default**: None
+    #[config(env = "CRABDRIVE_S3_SECRET_ACCESS_KEY")]
+    pub secret_access_key: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Config)]
@@ -168,6 +212,13 @@ pub struct AuthConfig {
     #[config(env = "JWT_SECRET")]
     pub jwt_secret: String,
 
+    /// The secret used to derive KDF parameters for unknown usernames, so that the pre-login does
+    /// not reveal which users exist
+    ///
+    /// **Default**: `not_so_secret_either`
+    #[config(env = "PRE_LOGIN_SECRET")]
+    pub pre_login_secret: String,
+
     /// the time in seconds that JWTs are valid for in seconds
     ///
     /// **Default**: `86400` (one day)
@@ -177,6 +228,50 @@ pub struct AuthConfig {
     /// invite code used to register
     #[config(env = "INVITE_CODE_HASH")]
     pub invite_code_hash: String,
+
+    /// The time window in seconds, in which the attempt limits for login and registration apply
+    ///
+    /// **Default**: `60` (one minute)
+    #[config(env = "RATE_LIMIT_WINDOW")]
+    pub rate_limit_window: u64,
+
+    /// The maximum amount of login and registration attempts per IP address in one time window
+    ///
+    /// **Default**: `30`
+    #[config(env = "RATE_LIMIT_PER_IP")]
+    pub rate_limit_per_ip: u32,
+
+    /// The maximum amount of login and registration attempts per username in one time window
+    ///
+    /// **Default**: `10`
+    #[config(env = "RATE_LIMIT_PER_USERNAME")]
+    pub rate_limit_per_username: u32,
+
+    /// The time in seconds a client has to wait after a failed attempt. It is doubled with every
+    /// further consecutive failure. `0` disables the backoff.
+    ///
+    /// **Default**: `1`
+    #[config(env = "RATE_LIMIT_BACKOFF_BASE")]
+    pub rate_limit_backoff_base: u64,
+
+    /// The maximum time in seconds a client has to wait after failed attempts
+    ///
+    /// **Default**: `300` (five minutes)
+    #[config(env = "RATE_LIMIT_BACKOFF_MAX")]
+    pub rate_limit_backoff_max: u64,
+
+    /// The amount of consecutive failed attempts after which an account is locked
+    ///
+    /// **Default**: `10`
+    #[config(env = "LOCKOUT_THRESHOLD")]
+    pub lockout_threshold: u32,
+
+    /// The time in seconds an account stays locked. Failed attempts are also forgotten after this
+    /// time.
+    ///
+    /// **Default**: `900` (15 minutes)
+    #[config(env = "LOCKOUT_DURATION")]
+    pub lockout_duration: u64,
 }
 
 type ConfLayer = <AppConfig as Config>::Layer;
@@ -207,6 +302,13 @@ impl AppConfig {
                 dir: Some(":temp:".into()),
                 cache_size: Some(350_000_000),
                 cache_ahead: Some(2),
+                s3: S3ConfigLayer {
+                    endpoint: None,
+                    region: Some("us-east-1".into()),
+                    bucket: Some("crabdrive".into()),
+                    access_key_id: None,
+                    secret_access_key: None,
+                },
             },
             log: LogConfigLayer {
                 minimum_level: Some(if cfg!(debug_assertions) {
@@ -218,8 +320,16 @@ impl AppConfig {
             },
             auth: AuthConfigLayer {
                 jwt_secret: Some(DEFAULT_JWT_SECRET.to_string()),
+                pre_login_secret: Some(DEFAULT_PRE_LOGIN_SECRET.to_string()),
                 jwt_expiration_period: Some(86400),
                 invite_code_hash: Some(DEFAULT_INVITE_CODE_HASH.to_string()),
+                rate_limit_window: Some(60),
+                rate_limit_per_ip: Some(30),
+                rate_limit_per_username: Some(10),
+                rate_limit_backoff_base: Some(1),
+                rate_limit_backoff_max: Some(300),
+                lockout_threshold: Some(10),
+                lockout_duration: Some(900),
             },
         }
     }
@@ -241,6 +351,13 @@ impl AppConfig {
                 dir: ":temp:".into(),
                 cache_ahead: 2,
                 cache_size: 300_000_000,
+                s3: S3Config {
+                    endpoint: None,
+                    region: "us-east-1".into(),
+                    bucket: "crabdrive".into(),
+                    access_key_id: None,
+                    secret_access_key: None,
+                },
             },
             log: LogConfig {
                 minimum_level: "WARN".into(),
@@ -249,7 +366,16 @@ impl AppConfig {
             auth: AuthConfig {
                 invite_code_hash: DEFAULT_INVITE_CODE_HASH.to_string(),
                 jwt_secret: "crabdrive_test".into(),
+                pre_login_secret: "crabdrive_test_pre_login".into(),
                 jwt_expiration_period: 86400,
+                // All test requests come from the same address and many use wrong passwords on purpose
+                rate_limit_window: 60,
+                rate_limit_per_ip: 1000,
+                rate_limit_per_username: 1000,
+                rate_limit_backoff_base: 0,
+                rate_limit_backoff_max: 0,
+                lockout_threshold: 1000,
+                lockout_duration: 900,
             },
         }
     }
@@ -0,0 +1,99 @@
+use crate::http::middleware::current_request_id;
+use crate::storage::vfs::FileSystemError;
+
+use axum::Json;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use crabdrive_common::payloads::error::{ErrorBody, ErrorKind, ErrorResponse};
+use thiserror::Error;
+
+/// An error, which aborts a request handler. It is returned to the client as [`ErrorResponse`].
+///
+/// Expected failures (e.g. a missing permission) are not errors, they are part of the response
+/// payload of the handler.
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error(transparent)]
+    FileSystem(#[from] FileSystemError),
+
+    #[error(transparent)]
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<FileSystemError>() {
+            Ok(error) => return ApiError::FileSystem(error),
+            Err(error) => error,
+        };
+
+        match error.downcast_ref::<diesel::result::Error>() {
+            Some(diesel::result::Error::NotFound) => ApiError::NotFound(error.to_string()),
+            _ => ApiError::Internal(error),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for ApiError {
+    fn from(error: diesel::result::Error) -> Self {
+        anyhow::Error::from(error).into()
+    }
+}
+
+impl ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound(_) | ApiError::FileSystem(FileSystemError::NotFound) => {
+                StatusCode::NOT_FOUND
+            }
+            ApiError::FileSystem(FileSystemError::AlreadyExists) => StatusCode::CONFLICT,
+            ApiError::FileSystem(FileSystemError::TooLarge(_)) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::FileSystem(FileSystemError::Io(_, _)) | ApiError::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    fn kind(&self) -> ErrorKind {
+        match self.status_code() {
+            StatusCode::NOT_FOUND => ErrorKind::NotFound,
+            StatusCode::CONFLICT => ErrorKind::Conflict,
+            StatusCode::PAYLOAD_TOO_LARGE => ErrorKind::PayloadTooLarge,
+            _ => ErrorKind::Internal,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+
+        let details = if status.is_server_error() {
+            tracing::error!("Request failed: {:?}", self);
+
+            if cfg!(debug_assertions) {
+                format!("{self:#}")
+            } else {
+                "Internal Server Error".to_string()
+            }
+        } else {
+            self.to_string()
+        };
+
+        (status, Json(error_response(self.kind(), details))).into_response()
+    }
+}
+
+/// Build the body of an error response for the current request
+pub fn error_response(kind: ErrorKind, details: String) -> ErrorResponse {
+    ErrorResponse {
+        error: ErrorBody {
+            kind,
+            details,
+            request_id: current_request_id(),
+        },
+    }
+}
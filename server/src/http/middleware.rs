@@ -1,9 +1,33 @@
+use crate::http::AppState;
+use crate::http::error::error_response;
+use crate::user::auth::SessionUser;
+
 use axum::{http::Request, middleware::Next, response::Response};
-use std::time::Instant;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
 
+use axum::Json;
 use axum::body::Body;
-use axum::http::{HeaderName, HeaderValue};
-use tracing::{debug, error_span};
+use axum::extract::{ConnectInfo, FromRequestParts, State};
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum::response::IntoResponse;
+use crabdrive_common::payloads::error::ErrorKind;
+use serde::Deserialize;
+use tracing::{debug, error_span, warn};
+
+/// Request bodies larger than this are not inspected by [`rate_limit_middleware`]
+const RATE_LIMIT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// The ID assigned to the current request by [`logging_middleware`]. Returns `None` outside of a
+/// request.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
 
 pub async fn logging_middleware(mut request: Request<Body>, next: Next) -> Response {
     let req_id = nanoid::nanoid!();
@@ -24,7 +48,7 @@ pub async fn logging_middleware(mut request: Request<Body>, next: Next) -> Respo
     let _enter = span.enter();
 
     let response_time_start = Instant::now();
-    let mut response = next.run(request).await;
+    let mut response = REQUEST_ID.scope(req_id.clone(), next.run(request)).await;
     let response_time = Instant::now() - response_time_start;
 
     debug!(
@@ -40,3 +64,98 @@ pub async fn logging_middleware(mut request: Request<Body>, next: Next) -> Respo
 
     response
 }
+
+/// The fields of a request body, which are relevant for rate limiting. All other fields are
+/// ignored.
+#[derive(Deserialize)]
+struct RateLimitedPayload {
+    username: Option<String>,
+    /// The challenge of a login, which waits for the second factor
+    challenge: Option<String>,
+}
+
+/// Limits the attempts per client IP and per username. The username is taken from the JSON body,
+/// from the second factor challenge of a login or, for signed in users confirming their password,
+/// from the session.
+/// Responses with `401 Unauthorized` or `403 Forbidden` count as failed attempts, which delay
+/// further attempts and may lock the username temporarily. Rejected requests receive a
+/// `429 Too Many Requests` with a `Retry-After` header.
+///
+/// Use with [`axum::middleware::from_fn_with_state`] on the routes, that should be protected.
+pub async fn rate_limit_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    // Without connection info (e.g. in tests), all requests share the same limits
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+    let (mut parts, body) = request.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, RATE_LIMIT_BODY_LIMIT).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+    let payload = serde_json::from_slice::<RateLimitedPayload>(&bytes).ok();
+    let username = match payload {
+        Some(RateLimitedPayload {
+            username: Some(username),
+            ..
+        }) => Some(username),
+        // Invalid challenges and sessions are rejected by the handler and count for the IP only
+        Some(RateLimitedPayload {
+            challenge: Some(challenge),
+            ..
+        }) => state
+            .user_repository
+            .verify_second_factor_challenge(&challenge)
+            .ok()
+            .flatten()
+            .map(|user| user.username),
+        _ => SessionUser::from_request_parts(&mut parts, &state)
+            .await
+            .ok()
+            .map(|SessionUser(user)| user.username),
+    };
+    let request = Request::from_parts(parts, Body::from(bytes));
+
+    if let Err(retry_after) = state.rate_limiter.check(ip, username.as_deref()) {
+        warn!("Rate limit exceeded by {ip}");
+        return too_many_requests(retry_after);
+    }
+
+    let response = next.run(request).await;
+
+    match response.status() {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            state.rate_limiter.record_failure(ip, username.as_deref())
+        }
+        status if status.is_success() => {
+            if let Some(username) = &username {
+                state.rate_limiter.record_success(username)
+            }
+        }
+        _ => {}
+    }
+
+    response
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    // Round up, so that clients do not retry too early
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+    let body = error_response(
+        ErrorKind::RateLimit,
+        format!("Too many attempts. Try again in {seconds} seconds."),
+    );
+
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, seconds.to_string())],
+        Json(body),
+    )
+        .into_response()
+}
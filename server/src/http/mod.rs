@@ -1,8 +1,11 @@
 pub mod config;
+pub mod error;
 pub mod middleware;
+pub mod rate_limit;
 pub mod routes;
 pub mod server;
 pub mod state;
 
 pub use config::AppConfig;
+pub use error::ApiError;
 pub use state::AppState;
@@ -0,0 +1,188 @@
+//! Bookkeeping for [`rate_limit_middleware`](crate::http::middleware::rate_limit_middleware).
+//!
+//! Every client IP and every username has its own entry. An entry limits the amount of attempts
+//! per time window, slows down consecutive failures with an exponential backoff, and (for
+//! usernames only) locks the account temporarily after too many failures in a row.
+
+use crate::http::config::AuthConfig;
+
+use dashmap::DashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+enum RateLimitKey {
+    Ip(IpAddr),
+    Username(String),
+}
+
+#[derive(Debug)]
+struct RateLimitEntry {
+    window_start: Instant,
+    attempts: u32,
+    /// Consecutive failures, reset by a successful attempt or a lockout
+    failures: u32,
+    last_failure: Option<Instant>,
+    locked_until: Option<Instant>,
+}
+
+impl RateLimitEntry {
+    fn new(now: Instant) -> Self {
+        Self {
+            window_start: now,
+            attempts: 0,
+            failures: 0,
+            last_failure: None,
+            locked_until: None,
+        }
+    }
+}
+
+pub struct RateLimiter {
+    entries: DashMap<RateLimitKey, RateLimitEntry>,
+    window: Duration,
+    max_attempts_per_ip: u32,
+    max_attempts_per_username: u32,
+    backoff_base: Duration,
+    backoff_max: Duration,
+    lockout_threshold: u32,
+    lockout_duration: Duration,
+}
+
+impl RateLimiter {
+    pub fn new(config: &AuthConfig) -> Self {
+        Self {
+            entries: DashMap::new(),
+            window: Duration::from_secs(config.rate_limit_window),
+            max_attempts_per_ip: config.rate_limit_per_ip,
+            max_attempts_per_username: config.rate_limit_per_username,
+            backoff_base: Duration::from_secs(config.rate_limit_backoff_base),
+            backoff_max: Duration::from_secs(config.rate_limit_backoff_max),
+            lockout_threshold: config.lockout_threshold,
+            lockout_duration: Duration::from_secs(config.lockout_duration),
+        }
+    }
+
+    /// Register an attempt. Returns the time the client has to wait, if the attempt is rejected.
+    /// The IP is checked first: an attempt rejected by the IP limits is not counted, but one
+    /// rejected by the username limits still counts for the IP.
+    pub fn check(&self, ip: IpAddr, username: Option<&str>) -> Result<(), Duration> {
+        let now = Instant::now();
+
+        self.check_key(RateLimitKey::Ip(ip), self.max_attempts_per_ip, now)?;
+        if let Some(username) = username {
+            self.check_key(
+                RateLimitKey::Username(username.to_string()),
+                self.max_attempts_per_username,
+                now,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Record a failed attempt (e.g. a wrong password)
+    pub fn record_failure(&self, ip: IpAddr, username: Option<&str>) {
+        let now = Instant::now();
+
+        self.record_failure_for_key(RateLimitKey::Ip(ip), false, now);
+        if let Some(username) = username {
+            self.record_failure_for_key(RateLimitKey::Username(username.to_string()), true, now);
+        }
+    }
+
+    /// Record a successful attempt, which resets the backoff of the username. The failures of the
+    /// IP are kept, so that signing into an own account does not allow guessing more passwords of
+    /// others.
+    pub fn record_success(&self, username: &str) {
+        if let Some(mut entry) = self
+            .entries
+            .get_mut(&RateLimitKey::Username(username.to_string()))
+        {
+            entry.failures = 0;
+        }
+    }
+
+    /// Remove all entries, which no longer restrict anything. Returns the amount of removed entries.
+    pub fn remove_stale_entries(&self) -> usize {
+        let now = Instant::now();
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| !self.is_stale(entry, now));
+        before - self.entries.len()
+    }
+
+    fn check_key(
+        &self,
+        key: RateLimitKey,
+        max_attempts: u32,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let mut entry = self
+            .entries
+            .entry(key)
+            .or_insert_with(|| RateLimitEntry::new(now));
+
+        if let Some(locked_until) = entry.locked_until {
+            if locked_until > now {
+                return Err(locked_until - now);
+            }
+            entry.locked_until = None;
+        }
+
+        if let Some(last_failure) = entry.last_failure {
+            if now - last_failure >= self.lockout_duration {
+                // Failures are forgotten after a while
+                entry.failures = 0;
+                entry.last_failure = None;
+            } else if entry.failures > 0 {
+                let allowed_at = last_failure + self.backoff(entry.failures);
+                if allowed_at > now {
+                    return Err(allowed_at - now);
+                }
+            }
+        }
+
+        if now - entry.window_start >= self.window {
+            entry.window_start = now;
+            entry.attempts = 0;
+        }
+
+        if entry.attempts >= max_attempts {
+            return Err(entry.window_start + self.window - now);
+        }
+
+        entry.attempts += 1;
+        Ok(())
+    }
+
+    fn record_failure_for_key(&self, key: RateLimitKey, lockout: bool, now: Instant) {
+        let mut entry = self
+            .entries
+            .entry(key)
+            .or_insert_with(|| RateLimitEntry::new(now));
+
+        entry.failures += 1;
+        entry.last_failure = Some(now);
+
+        if lockout && entry.failures >= self.lockout_threshold {
+            entry.locked_until = Some(now + self.lockout_duration);
+            entry.failures = 0;
+        }
+    }
+
+    /// The time to wait after `failures` consecutive failures: `base`, `2 * base`, `4 * base`, ...
+    fn backoff(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.backoff_base
+            .saturating_mul(factor)
+            .min(self.backoff_max)
+    }
+
+    fn is_stale(&self, entry: &RateLimitEntry, now: Instant) -> bool {
+        now - entry.window_start >= self.window
+            && entry.locked_until.is_none_or(|until| until <= now)
+            && entry
+                .last_failure
+                .is_none_or(|last_failure| now - last_failure >= self.lockout_duration)
+    }
+}
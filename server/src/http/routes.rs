@@ -1,26 +1,28 @@
 use crate::http::AppState;
+use crate::http::middleware::rate_limit_middleware;
 
 use crate::request_handler::admin::*;
 use crate::request_handler::auth::*;
 use crate::request_handler::chunk::*;
 use crate::request_handler::file::*;
+use crate::request_handler::file_drop::*;
 use crate::request_handler::folder::*;
 use crate::request_handler::node::*;
+use crate::request_handler::public_link::*;
 
-use crabdrive_common::da;
 use crabdrive_common::routes;
 
 use crate::request_handler::share::{
-    get_accept_share_info, get_accepted_shared_nodes, get_node_share_info, post_accept_share,
-    post_share_node,
+    delete_inbox_share, delete_share, delete_share_user, get_accept_share_info,
+    get_accepted_shared_nodes, get_node_share_info, get_outgoing_shares, get_share_inbox,
+    get_user_public_key, post_accept_share, post_share_node, post_share_node_with_user,
 };
-use axum::Router;
-use axum::extract::DefaultBodyLimit;
-use axum::routing::{get, post};
+use axum::routing::{delete, get, post, put};
+use axum::{Router, middleware};
 use tower_http::compression::CompressionLayer;
 use tower_http::services::{ServeDir, ServeFile};
 
-pub fn routes() -> Router<AppState> {
+pub fn routes(state: &AppState) -> Router<AppState> {
     let frontend_build =
         ServeDir::new("./client/dist").fallback(ServeFile::new("./client/dist/index.html"));
 
@@ -29,8 +31,10 @@ pub fn routes() -> Router<AppState> {
         .layer(CompressionLayer::new())
         .merge(nodes_routes())
         .merge(admin_routes())
-        .merge(auth_routes())
+        .merge(auth_routes(state))
         .merge(share_routes())
+        .merge(public_link_routes())
+        .merge(file_drop_routes())
 }
 
 pub fn nodes_routes() -> Router<AppState> {
@@ -54,39 +58,94 @@ pub fn nodes_routes() -> Router<AppState> {
         .route(routes::node::folder::ROUTE_CREATE, post(post_create_folder))
         .route(routes::node::ROUTE_CHILDREN, get(get_node_children))
         .route(routes::node::ROUTE_VERSIONS, get(get_file_versions))
-        .route(
-            routes::node::ROUTE_CHUNKS,
-            post(post_chunk)
-                .layer(DefaultBodyLimit::max(da!(18 MB).as_bytes() as usize))
-                .get(get_chunk),
-        )
+        .route(routes::node::ROUTE_CHUNKS, post(post_chunk).get(get_chunk))
         .route(
             routes::node::ROUTE_ACCESSIBLE_PATH,
             get(get_accessible_path),
         )
 }
 
-pub fn auth_routes() -> Router<AppState> {
-    Router::new()
+pub fn auth_routes(state: &AppState) -> Router<AppState> {
+    // Routes accepting credentials, which could be used to guess them
+    let rate_limited_routes = Router::new()
         .route(routes::auth::ROUTE_LOGIN, post(post_login))
+        .route(
+            routes::auth::ROUTE_LOGIN_SECOND_FACTOR,
+            post(post_login_second_factor),
+        )
         .route(routes::auth::ROUTE_REGISTER, post(post_register))
+        .route(routes::auth::ROUTE_RECOVERY_KEYS, post(post_recovery_keys))
+        .route(
+            routes::auth::ROUTE_RECOVERY_RESET,
+            post(post_recovery_reset),
+        )
+        .route(routes::auth::ROUTE_PASSWORD, post(post_change_password))
+        .route(routes::auth::ROUTE_PASSWORD_UPGRADE, post(post_upgrade_kdf))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit_middleware,
+        ));
+
+    Router::new()
+        .merge(rate_limited_routes)
+        .route(routes::auth::ROUTE_PRE_LOGIN, post(post_pre_login))
         .route(routes::auth::ROUTE_REFRESH, post(post_refresh))
         .route(routes::auth::ROUTE_LOGOUT, post(post_logout))
         .route(routes::auth::ROUTE_INFO, get(get_user_info))
+        .route(
+            routes::auth::ROUTE_RECOVERY_KEY,
+            put(put_recovery_key).delete(delete_recovery_key),
+        )
+        .route(routes::auth::ROUTE_KEY_PAIR, put(put_key_pair))
+        .route(
+            routes::auth::ROUTE_TOTP,
+            post(post_totp).delete(delete_totp),
+        )
+        .route(routes::auth::ROUTE_TOTP_CONFIRM, post(post_totp_confirm))
+        .route(
+            routes::auth::ROUTE_TOTP_BACKUP_CODES,
+            post(post_totp_backup_codes),
+        )
+        .route(
+            routes::auth::ROUTE_SESSIONS,
+            get(get_sessions).delete(delete_other_sessions),
+        )
+        .route(routes::auth::ROUTE_SESSION_BY_ID, delete(delete_session))
+        .route(
+            routes::auth::ROUTE_ACCESS_TOKENS,
+            get(get_access_tokens).post(post_access_token),
+        )
+        .route(
+            routes::auth::ROUTE_ACCESS_TOKEN_BY_ID,
+            delete(delete_access_token),
+        )
 }
 
 pub fn admin_routes() -> Router<AppState> {
     Router::new()
         .route(
             routes::admin::ROUTE_USER_BY_ID,
-            get(get_user).delete(delete_user),
+            get(get_user).patch(patch_user).delete(delete_user),
+        )
+        .route(routes::admin::ROUTE_USER, get(get_users).post(post_user))
+        .route(
+            routes::admin::ROUTE_INVITE_BY_ID,
+            delete(delete_invite_code),
+        )
+        .route(
+            routes::admin::ROUTE_INVITE,
+            get(get_invite_codes).post(post_invite_code),
         )
-        .route(routes::admin::ROUTE_USER, post(post_user))
 }
 
 pub fn share_routes() -> Router<AppState> {
     Router::new()
         .route(routes::node::share::ROUTE_SHARE_NODE, post(post_share_node))
+        .route(
+            routes::node::share::ROUTE_SHARE_NODE_USER,
+            delete(delete_share_user),
+        )
+        .route(routes::node::share::ROUTE_SHARE_BY_ID, delete(delete_share))
         .route(
             routes::node::share::ROUTE_NODE_SHARE_INFO,
             get(get_node_share_info),
@@ -103,4 +162,61 @@ pub fn share_routes() -> Router<AppState> {
             routes::node::share::ROUTE_ACCEPT_SHARE,
             post(post_accept_share),
         )
+        .route(
+            routes::node::share::ROUTE_SHARE_NODE_WITH_USER,
+            post(post_share_node_with_user),
+        )
+        .route(
+            routes::node::share::ROUTE_USER_PUBLIC_KEY,
+            get(get_user_public_key),
+        )
+        .route(routes::node::share::ROUTE_INBOX, get(get_share_inbox))
+        .route(
+            routes::node::share::ROUTE_OUTGOING_SHARES,
+            get(get_outgoing_shares),
+        )
+        .route(
+            routes::node::share::ROUTE_INBOX_SHARE,
+            delete(delete_inbox_share),
+        )
+}
+
+pub fn public_link_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            routes::node::share::ROUTE_PUBLIC_LINK_NODE,
+            post(post_public_link),
+        )
+        .route(
+            routes::public::ROUTE_PUBLIC_LINK,
+            get(get_public_link).delete(delete_public_link),
+        )
+        .route(
+            routes::public::ROUTE_PUBLIC_VERSIONS,
+            get(get_public_versions),
+        )
+        .route(routes::public::ROUTE_PUBLIC_CHUNKS, get(get_public_chunk))
+}
+
+pub fn file_drop_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            routes::node::share::ROUTE_FILE_DROP_NODE,
+            post(post_file_drop),
+        )
+        .route(
+            routes::node::share::ROUTE_DROPPED_FILES,
+            get(get_dropped_files),
+        )
+        .route(
+            routes::node::share::ROUTE_ADOPT_DROPPED_FILES,
+            post(post_adopt_dropped_files),
+        )
+        .route(
+            routes::drop::ROUTE_FILE_DROP,
+            get(get_file_drop).delete(delete_file_drop),
+        )
+        .route(routes::drop::ROUTE_DROP_FILES, post(post_drop_file))
+        .route(routes::drop::ROUTE_DROP_CHUNKS, post(post_drop_chunk))
+        .route(routes::drop::ROUTE_DROP_COMMIT, post(post_drop_commit))
 }
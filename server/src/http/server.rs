@@ -1,15 +1,20 @@
 use crate::db::operations;
+use crate::http::error::error_response;
 use crate::http::middleware::logging_middleware;
 use crate::http::{AppConfig, AppState, routes};
+use crate::service;
+use crate::service::file_drop::DROPPED_FILE_UPLOAD_TIMEOUT;
 
 use axum::http::StatusCode;
 use axum::http::header::{AUTHORIZATION, CONTENT_TYPE};
 use axum::response::Response;
 use axum::{Router, middleware};
 use bytes::Bytes;
+use crabdrive_common::payloads::error::ErrorKind as ApiErrorKind;
 use http_body_util::Full;
 use std::any::Any;
 use std::io::ErrorKind;
+use std::net::SocketAddr;
 use std::time::Duration;
 use tokio::{task, time};
 use tower_http::catch_panic::CatchPanicLayer;
@@ -44,24 +49,29 @@ async fn graceful_shutdown(state: AppState) {
 
 pub async fn create_app(config: AppConfig) -> (Router, AppState) {
     let state = AppState::new(config).await;
+    (create_router(state.clone()), state)
+}
 
+/// Create the router with all routes and middlewares for the given state
+pub fn create_router(state: AppState) -> Router {
     let cors = CorsLayer::new() // TODO: Make more specific before submission
         .allow_origin(tower_http::cors::Any)
         .allow_methods(tower_http::cors::Any)
         .allow_headers([AUTHORIZATION, CONTENT_TYPE]);
 
-    let router = routes::routes()
-        .with_state(state.clone())
-        .layer(middleware::from_fn(logging_middleware))
+    routes::routes(&state)
+        .with_state(state)
+        // inside of the logging middleware, so that panics are answered with the request ID
         .layer(CatchPanicLayer::custom(handle_panic))
-        .layer(cors);
-
-    (router, state)
+        .layer(middleware::from_fn(logging_middleware))
+        .layer(cors)
 }
 
 pub async fn start(config: AppConfig) -> Result<(), ()> {
     let (app, state) = create_app(config.clone()).await;
     let db_pool = state.db_pool.clone();
+    let rate_limiter = state.rate_limiter.clone();
+    let task_state = state.clone();
 
     let addr = config.addr();
 
@@ -107,13 +117,60 @@ pub async fn start(config: AppConfig) -> Result<(), ()> {
                 .unwrap_or(0);
 
             info!("Removed {count} tokens from blacklist!");
+
+            let count = operations::share::delete_expired_shares(&mut conn, now)
+                .inspect_err(|e| {
+                    error!("Unable to remove expired shares: {e}");
+                })
+                .ok()
+                .unwrap_or(0);
+
+            info!("Removed {count} expired shares!");
+
+            let count = operations::public_link::delete_expired_public_links(&mut conn, now)
+                .inspect_err(|e| {
+                    error!("Unable to remove expired public links: {e}");
+                })
+                .ok()
+                .unwrap_or(0);
+
+            info!("Removed {count} expired public links!");
+
+            let count = operations::file_drop::delete_expired_file_drops(&mut conn, now)
+                .inspect_err(|e| {
+                    error!("Unable to remove expired file drops: {e}");
+                })
+                .ok()
+                .unwrap_or(0);
+
+            info!("Removed {count} expired file drops!");
+
+            // The cleanup of dropped files needs connections of its own
+            drop(conn);
+            let started_before = now - DROPPED_FILE_UPLOAD_TIMEOUT;
+            let count = service::file_drop::delete_stale_dropped_files(&task_state, started_before)
+                .await
+                .inspect_err(|e| {
+                    error!("Unable to remove abandoned dropped files: {e}");
+                })
+                .ok()
+                .unwrap_or(0);
+
+            info!("Removed {count} abandoned dropped files!");
+
+            let count = rate_limiter.remove_stale_entries();
+            info!("Removed {count} stale rate limit entries!");
         }
     });
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(graceful_shutdown(state.clone()))
-        .await
-        .unwrap();
+    // The client address is required for rate limiting
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(graceful_shutdown(state.clone()))
+    .await
+    .unwrap();
 
     Ok(())
 }
@@ -140,12 +197,7 @@ pub fn handle_panic(err: Box<dyn Any + Send + 'static>) -> Response<Full<Bytes>>
         "Internal Server Error".to_string()
     };
 
-    let body = serde_json::json!({
-        "error": {
-            "kind": "panic",
-            "details": client_details,
-        }
-    });
+    let body = error_response(ApiErrorKind::Panic, client_details);
     let body = serde_json::to_string(&body).unwrap();
 
     Response::builder()
@@ -1,31 +1,47 @@
 use crate::db::connection::create_pool;
+use crate::http::rate_limit::RateLimiter;
 use crate::storage::node::NodeRepository;
 use crate::storage::node::persistence::node_repository::NodeRepositoryImpl;
 use crate::storage::revision::RevisionRepository;
 use crate::storage::revision::persistence::revision_repository::RevisionRepositoryImpl;
+use crate::storage::share::persistence::file_drop_repository::{
+    FileDropRepository, FileDropRepositoryImpl,
+};
+use crate::storage::share::persistence::public_link_repository::{
+    PublicLinkRepository, PublicLinkRepositoryImpl,
+};
 use crate::storage::share::persistence::share_repository::ShareRepository;
 use crate::storage::share::persistence::share_repository::ShareRepositoryImpl;
 use crate::storage::vfs::FileRepository;
 use crate::storage::vfs::backend::Sfs;
 use crate::storage::vfs::backend::c3::C3;
+use crate::storage::vfs::backend::s3::S3;
 use crate::user::auth::secrets::Keys;
+use crate::user::persistence::access_token_repository::{
+    AccessTokenRepository, AccessTokenRepositoryImpl,
+};
+use crate::user::persistence::invite_repository::{InviteRepository, InviteRepositoryImpl};
 use crate::user::persistence::user_repository::{UserRepository, UserRepositoryImpl};
 use crate::{db::connection::DbPool, http::AppConfig};
 use std::path::PathBuf;
 use std::sync::Arc;
 use tempfile::TempDir;
-use tokio::sync::RwLock;
 
 #[derive(Clone)]
 pub struct AppState {
     pub config: Arc<AppConfig>,
     pub db_pool: Arc<DbPool>,
-    pub vfs: Arc<RwLock<dyn FileRepository + Send + Sync>>,
+    pub vfs: Arc<dyn FileRepository + Send + Sync>,
     pub node_repository: Arc<dyn NodeRepository + Send + Sync>,
     pub revision_repository: Arc<dyn RevisionRepository + Send + Sync>,
     pub user_repository: Arc<dyn UserRepository + Send + Sync>,
     pub share_repository: Arc<dyn ShareRepository + Send + Sync>,
+    pub public_link_repository: Arc<dyn PublicLinkRepository + Send + Sync>,
+    pub file_drop_repository: Arc<dyn FileDropRepository + Send + Sync>,
+    pub invite_repository: Arc<dyn InviteRepository + Send + Sync>,
+    pub access_token_repository: Arc<dyn AccessTokenRepository + Send + Sync>,
     pub keys: Arc<Keys>,
+    pub rate_limiter: Arc<RateLimiter>,
     _temp_storage: Arc<Option<TempDir>>,
 }
 
@@ -45,27 +61,32 @@ impl AppState {
             (None, path)
         };
 
-        let vfs: Arc<RwLock<dyn FileRepository + Send + Sync>> =
-            match config.storage.backend.as_ref() {
-                "SFS" => Arc::new(RwLock::new(Sfs::new(path))),
-                "C3" => Arc::new(RwLock::new(
-                    C3::new(
-                        path,
-                        Arc::new(pool.clone()),
-                        config.storage.cache_size,
-                        config.storage.cache_ahead,
-                    )
-                    .await,
-                )),
-                _ => panic!("Impossible"),
-            };
+        let vfs: Arc<dyn FileRepository + Send + Sync> = match config.storage.backend.as_ref() {
+            "SFS" => Arc::new(Sfs::new(path)),
+            "C3" => Arc::new(
+                C3::new(
+                    path,
+                    Arc::new(pool.clone()),
+                    config.storage.cache_size,
+                    config.storage.cache_ahead,
+                )
+                .await,
+            ),
+            "S3" => Arc::new(S3::new(&config.storage.s3)),
+            _ => panic!("Impossible"),
+        };
 
         let keys = Keys::new(&config.auth.jwt_secret);
+        let rate_limiter = RateLimiter::new(&config.auth);
 
         let node_repository = NodeRepositoryImpl::new(Arc::new(pool.clone()));
         let revision_repository = RevisionRepositoryImpl::new(Arc::new(pool.clone()));
         let user_repository = UserRepositoryImpl::new(Arc::new(pool.clone()), keys.clone());
         let share_repository = ShareRepositoryImpl::new(Arc::new(pool.clone()));
+        let public_link_repository = PublicLinkRepositoryImpl::new(Arc::new(pool.clone()));
+        let file_drop_repository = FileDropRepositoryImpl::new(Arc::new(pool.clone()));
+        let invite_repository = InviteRepositoryImpl::new(Arc::new(pool.clone()));
+        let access_token_repository = AccessTokenRepositoryImpl::new(Arc::new(pool.clone()));
 
         Self {
             config: Arc::new(config),
@@ -75,7 +96,12 @@ impl AppState {
             revision_repository: Arc::new(revision_repository),
             user_repository: Arc::new(user_repository),
             share_repository: Arc::new(share_repository),
+            public_link_repository: Arc::new(public_link_repository),
+            file_drop_repository: Arc::new(file_drop_repository),
+            invite_repository: Arc::new(invite_repository),
+            access_token_repository: Arc::new(access_token_repository),
             keys: Arc::new(keys),
+            rate_limiter: Arc::new(rate_limiter),
             _temp_storage: Arc::new(temp_dir),
         }
     }
@@ -1,6 +1,7 @@
 mod db;
 mod http;
 mod request_handler;
+mod service;
 mod storage;
 mod user;
 
@@ -16,6 +17,7 @@ use std::io::Write;
 use crate::http::{AppConfig, server};
 
 pub const DEFAULT_JWT_SECRET: &str = "not_so_secret";
+pub const DEFAULT_PRE_LOGIN_SECRET: &str = "not_so_secret_either";
 pub const DEFAULT_INVITE_CODE_HASH: &str = "cf99fdbe0e5915c6b687d2b85c15ab50c9bd4c3752fafce5f46c72c79c5a75cafb4e6514cffc95254176e52411b6f8506aacfce9c32c12437ae575121111e3d9";
 pub const DEFAULT_INVITE_CODE: &str = "crabdrive";
 
@@ -87,6 +89,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         error!("USING DEFAULT_JWT_SECRET. this is not secret so it should be changed")
     }
 
+    if config.auth.pre_login_secret.eq(DEFAULT_PRE_LOGIN_SECRET) {
+        error!("USING DEFAULT_PRE_LOGIN_SECRET. this is not secret so it should be changed")
+    }
+
     if config.auth.invite_code_hash.eq(DEFAULT_INVITE_CODE_HASH) {
         error!("USING default invite code. this is not secret so it should be changed")
     }
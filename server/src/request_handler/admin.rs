@@ -1,45 +1,318 @@
+use crate::http::{ApiError, AppState};
+use crate::storage::vfs::model::FileStatus;
+use crate::user::InviteCodeEntity;
+use crate::user::auth::AdminUser;
+use crate::user::persistence::model::user_entity::UserEntity;
+
 use axum::Json;
-use axum::extract::Path;
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
-use crabdrive_common::payloads::admin::request::user::PostUserRequest;
+use crabdrive_common::da;
+use crabdrive_common::payloads::admin::request::invite::PostInviteCodeRequest;
+use crabdrive_common::payloads::admin::request::user::{
+    GetUserListQuery, PatchUserRequest, PostUserRequest,
+};
+use crabdrive_common::payloads::admin::response::invite::{
+    CreatedInviteCode, DeleteInviteCodeResponse, GetInviteCodesResponse, InviteCodeInfo,
+    PostInviteCodeResponse,
+};
 use crabdrive_common::payloads::admin::response::user::{
-    DeleteUserResponse, GetUserResponse, PostUserResponse, UserInfo,
+    DeleteUserResponse, GetUserListResponse, GetUserResponse, PatchUserResponse, PostUserResponse,
+    UserInfo, UserList,
 };
-use crabdrive_common::user::{UserId, UserType};
+use crabdrive_common::payloads::auth::response::register::RegisterConflictReason;
+use crabdrive_common::user::{InviteCodeId, UserId};
+
+const DEFAULT_PAGE_SIZE: u64 = 50;
+const MAX_PAGE_SIZE: u64 = 500;
 
-//TODO remove this
-pub fn get_example_user_info() -> UserInfo {
+pub fn entity_to_user_info(user: &UserEntity) -> UserInfo {
     UserInfo {
-        username: "admin".to_string(),
-        user_type: UserType::User,
-        storage_limit: None,
-        created_on: Default::default(),
-        updated_on: Default::default(),
+        id: user.id,
+        username: user.username.clone(),
+        user_type: user.user_type,
+        storage_limit: Some(user.storage_limit),
+        storage_used: user.storage_used,
+        created_on: user.created_at,
+        updated_on: user.updated_at.unwrap_or(user.created_at),
+    }
+}
+
+pub fn entity_to_invite_code_info(invite_code: &InviteCodeEntity) -> InviteCodeInfo {
+    InviteCodeInfo {
+        id: invite_code.id,
+        created_by: invite_code.created_by,
+        created_at: invite_code.created_at,
+        expires_at: invite_code.expires_at,
+        max_uses: invite_code.max_uses,
+        uses: invite_code.uses,
+        user_type: invite_code.user_type,
+        storage_limit: invite_code.storage_limit,
+        username: invite_code.username.clone(),
     }
 }
 
-pub async fn get_user(Path(_user_id): Path<UserId>) -> (StatusCode, Json<GetUserResponse>) {
-    //TODO implement
-    (
+pub async fn get_user(
+    AdminUser(_current_user): AdminUser,
+    State(state): State<AppState>,
+    Path(user_id): Path<UserId>,
+) -> Result<(StatusCode, Json<GetUserResponse>), ApiError> {
+    let user = state.user_repository.get_user(user_id)?;
+
+    match user {
+        Some(user) => Ok((
+            StatusCode::OK,
+            Json(GetUserResponse::Ok(entity_to_user_info(&user))),
+        )),
+        None => Ok((StatusCode::NOT_FOUND, Json(GetUserResponse::NotFound))),
+    }
+}
+
+pub async fn get_users(
+    AdminUser(_current_user): AdminUser,
+    State(state): State<AppState>,
+    Query(query): Query<GetUserListQuery>,
+) -> Result<(StatusCode, Json<GetUserListResponse>), ApiError> {
+    let page = query.page.unwrap_or(0);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+
+    if !(1..=MAX_PAGE_SIZE).contains(&per_page) {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(GetUserListResponse::BadRequest(format!(
+                "per_page must be between 1 and {MAX_PAGE_SIZE}"
+            ))),
+        ));
+    }
+
+    // The database only supports signed offsets
+    let Some(offset) = page
+        .checked_mul(per_page)
+        .filter(|offset| i64::try_from(*offset).is_ok())
+    else {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(GetUserListResponse::BadRequest(
+                "page is too large".to_string(),
+            )),
+        ));
+    };
+
+    let users = state.user_repository.list_users(offset, per_page)?;
+    let total = state.user_repository.count_users()?;
+
+    Ok((
         StatusCode::OK,
-        Json(GetUserResponse::Ok(get_example_user_info())),
-    )
+        Json(GetUserListResponse::Ok(UserList {
+            users: users.iter().map(entity_to_user_info).collect(),
+            total,
+            page,
+            per_page,
+        })),
+    ))
 }
 
-pub async fn delete_user(Path(_user_id): Path<UserId>) -> (StatusCode, Json<DeleteUserResponse>) {
-    //TODO implement
-    (
+pub async fn delete_user(
+    AdminUser(_current_user): AdminUser,
+    State(state): State<AppState>,
+    Path(user_id): Path<UserId>,
+) -> Result<(StatusCode, Json<DeleteUserResponse>), ApiError> {
+    let Some((user, revisions)) = state.user_repository.purge_user(user_id)? else {
+        return Ok((StatusCode::NOT_FOUND, Json(DeleteUserResponse::NotFound)));
+    };
+
+    // The database entries are gone, so failing to delete a chunk only leaves garbage on the disk
+    let vfs = &state.vfs;
+    for revision in revisions {
+        let result = match vfs.file_status(&revision.id).await {
+            FileStatus::Staged | FileStatus::Stale => vfs.abort(&revision.id).await,
+            FileStatus::Persisted => vfs.delete_file(&revision.id).await,
+            FileStatus::NotFound => Ok(()),
+        };
+
+        if let Err(e) = result {
+            tracing::warn!(
+                "Failed to delete chunks of revision {} of user {}: {}",
+                revision.id,
+                user.id,
+                e
+            );
+        }
+    }
+
+    Ok((
         StatusCode::OK,
-        Json(DeleteUserResponse::Ok(get_example_user_info())),
-    )
+        Json(DeleteUserResponse::Ok(entity_to_user_info(&user))),
+    ))
 }
 
 pub async fn post_user(
-    Json(_payload): Json<PostUserRequest>,
-) -> (StatusCode, Json<PostUserResponse>) {
-    //TODO implement
-    (
+    AdminUser(current_user): AdminUser,
+    State(state): State<AppState>,
+    Json(payload): Json<PostUserRequest>,
+) -> Result<(StatusCode, Json<PostUserResponse>), ApiError> {
+    if !payload.username.chars().all(char::is_alphanumeric) {
+        return Ok((
+            StatusCode::CONFLICT,
+            Json(PostUserResponse::Conflict(
+                RegisterConflictReason::IllegalUsername,
+            )),
+        ));
+    }
+
+    if !is_username_available(&state, &payload.username)? {
+        return Ok((
+            StatusCode::CONFLICT,
+            Json(PostUserResponse::Conflict(
+                RegisterConflictReason::UsernameTaken,
+            )),
+        ));
+    }
+
+    // The password and the keys are derived on the client of the user, so only the user knows
+    // them. The account is created once the user registers with the code.
+    let (code, invite_code) = state.invite_repository.create_user_invite_code(
+        current_user.id,
+        &payload.username,
+        payload.user_type,
+        payload.storage_limit.unwrap_or(da!(15 GB)),
+    )?;
+
+    Ok((
         StatusCode::CREATED,
-        Json(PostUserResponse::Created(get_example_user_info())),
-    )
+        Json(PostUserResponse::Created(CreatedInviteCode {
+            code,
+            info: entity_to_invite_code_info(&invite_code),
+        })),
+    ))
+}
+
+/// Whether neither a user has the username, nor an invite code reserves it for someone else
+pub fn is_username_available(state: &AppState, username: &str) -> Result<bool, ApiError> {
+    Ok(state
+        .user_repository
+        .get_user_by_username(username)?
+        .is_none()
+        && state
+            .invite_repository
+            .get_reserving_invite_code(username)?
+            .is_none())
+}
+
+pub async fn patch_user(
+    AdminUser(_current_user): AdminUser,
+    State(state): State<AppState>,
+    Path(user_id): Path<UserId>,
+    Json(payload): Json<PatchUserRequest>,
+) -> Result<(StatusCode, Json<PatchUserResponse>), ApiError> {
+    let Some(mut user) = state.user_repository.get_user(user_id)? else {
+        return Ok((StatusCode::NOT_FOUND, Json(PatchUserResponse::NotFound)));
+    };
+
+    if let Some(username) = payload
+        .username
+        .filter(|username| *username != user.username)
+    {
+        if !username.chars().all(char::is_alphanumeric) {
+            return Ok((
+                StatusCode::CONFLICT,
+                Json(PatchUserResponse::Conflict(
+                    RegisterConflictReason::IllegalUsername,
+                )),
+            ));
+        }
+
+        if !is_username_available(&state, &username)? {
+            return Ok((
+                StatusCode::CONFLICT,
+                Json(PatchUserResponse::Conflict(
+                    RegisterConflictReason::UsernameTaken,
+                )),
+            ));
+        }
+
+        user.username = username;
+    }
+
+    if let Some(user_type) = payload.user_type {
+        user.user_type = user_type;
+    }
+
+    if let Some(storage_limit) = payload.storage_limit {
+        user.storage_limit = storage_limit;
+    }
+
+    let user = state.user_repository.update_user(user)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PatchUserResponse::Ok(entity_to_user_info(&user))),
+    ))
+}
+
+pub async fn get_invite_codes(
+    AdminUser(_current_user): AdminUser,
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<GetInviteCodesResponse>), ApiError> {
+    let invite_codes = state.invite_repository.list_invite_codes()?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetInviteCodesResponse::Ok(
+            invite_codes
+                .iter()
+                .map(entity_to_invite_code_info)
+                .collect(),
+        )),
+    ))
+}
+
+pub async fn post_invite_code(
+    AdminUser(current_user): AdminUser,
+    State(state): State<AppState>,
+    Json(payload): Json<PostInviteCodeRequest>,
+) -> Result<(StatusCode, Json<PostInviteCodeResponse>), ApiError> {
+    if payload.max_uses.is_some_and(|max_uses| max_uses < 1) {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostInviteCodeResponse::BadRequest(
+                "max_uses must be at least 1".to_string(),
+            )),
+        ));
+    }
+
+    let (code, invite_code) = state.invite_repository.create_invite_code(
+        current_user.id,
+        payload.expires_at,
+        payload.max_uses,
+        payload.user_type,
+        payload.storage_limit.unwrap_or(da!(15 GB)),
+    )?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(PostInviteCodeResponse::Created(CreatedInviteCode {
+            code,
+            info: entity_to_invite_code_info(&invite_code),
+        })),
+    ))
+}
+
+pub async fn delete_invite_code(
+    AdminUser(_current_user): AdminUser,
+    State(state): State<AppState>,
+    Path(invite_code_id): Path<InviteCodeId>,
+) -> Result<(StatusCode, Json<DeleteInviteCodeResponse>), ApiError> {
+    match state.invite_repository.delete_invite_code(invite_code_id)? {
+        Some(invite_code) => Ok((
+            StatusCode::OK,
+            Json(DeleteInviteCodeResponse::Ok(entity_to_invite_code_info(
+                &invite_code,
+            ))),
+        )),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(DeleteInviteCodeResponse::NotFound),
+        )),
+    }
 }
@@ -1,8 +1,12 @@
-use crate::http::AppState;
+use crate::http::{ApiError, AppState};
+use crate::service::user::{NewUser, register_user};
+use crate::user::AccessTokenEntity;
+use crate::user::auth::{SessionUser, totp};
+use crate::user::persistence::invite_repository::hash_invite_code;
 use crate::user::persistence::model::user_entity::UserEntity;
 
 use axum::Json;
-use axum::extract::State;
+use axum::extract::{Path, State};
 use axum::http::header::SET_COOKIE;
 use axum::http::{HeaderName, StatusCode};
 use axum_extra::TypedHeader;
@@ -10,53 +14,192 @@ use axum_extra::extract::CookieJar;
 use axum_extra::extract::cookie::{Cookie, SameSite};
 use axum_extra::headers::Authorization;
 use axum_extra::headers::authorization::Bearer;
-use crabdrive_common::da;
-use crabdrive_common::encrypted_metadata::EncryptedMetadata;
-use crabdrive_common::payloads::auth::request::login::PostLoginRequest;
+use crabdrive_common::payloads::auth::request::access_token::PostAccessTokenRequest;
+use crabdrive_common::payloads::auth::request::keys::PutKeyPairRequest;
+use crabdrive_common::payloads::auth::request::login::{
+    PostLoginRequest, PostLoginSecondFactorRequest, PostPreLoginRequest,
+};
+use crabdrive_common::payloads::auth::request::password::PostChangePasswordRequest;
+use crabdrive_common::payloads::auth::request::recovery::{
+    PostRecoveryKeysRequest, PostRecoveryResetRequest, PutRecoveryKeyRequest,
+};
 use crabdrive_common::payloads::auth::request::register::PostRegisterRequest;
+use crabdrive_common::payloads::auth::request::totp::TotpCodeRequest;
+use crabdrive_common::payloads::auth::response::access_token::{
+    AccessTokenInfo, CreatedAccessToken, DeleteAccessTokenResponse, GetAccessTokensResponse,
+    PostAccessTokenResponse,
+};
 use crabdrive_common::payloads::auth::response::info::{GetSelfInfoResponse, SelfUserInfo};
-use crabdrive_common::payloads::auth::response::login::LoginDeniedReason::Username;
-use crabdrive_common::payloads::auth::response::login::{LoginSuccess, PostLoginResponse};
-use sha2::Digest;
+use crabdrive_common::payloads::auth::response::keys::PutKeyPairResponse;
+use crabdrive_common::payloads::auth::response::login::LoginDeniedReason::{
+    SecondFactor, Username,
+};
+use crabdrive_common::payloads::auth::response::login::{
+    LoginSuccess, PostLoginResponse, PostPreLoginResponse, SecondFactorChallenge,
+};
+use crabdrive_common::payloads::auth::response::password::PostChangePasswordResponse;
+use crabdrive_common::payloads::auth::response::recovery::{
+    DeleteRecoveryKeyResponse, PostRecoveryKeysResponse, PostRecoveryResetResponse,
+    PutRecoveryKeyResponse, RecoveryKeys,
+};
 
 use crabdrive_common::payloads::auth::response::refresh::{PostRefreshResponse, RefreshBody};
 use crabdrive_common::payloads::auth::response::register::{
     PostRegisterResponse, RegisterConflictReason,
 };
+use crabdrive_common::payloads::auth::response::session::{
+    DeleteOtherSessionsResponse, DeleteSessionResponse, GetSessionsResponse, SessionInfo,
+};
+use crabdrive_common::payloads::auth::response::totp::{
+    DeleteTotpResponse, PostTotpBackupCodesResponse, PostTotpConfirmResponse, PostTotpResponse,
+    TotpEnrolment,
+};
 use crabdrive_common::routes::auth::ROUTE_REFRESH;
-use crabdrive_common::storage::{NodeId, NodeType};
-use crabdrive_common::user::UserKeys;
+use crabdrive_common::user::{AccessTokenId, KdfParams, SessionId, UserKeys};
 
-pub async fn post_login(
-    State(state): State<AppState>,
-    Json(payload): Json<PostLoginRequest>,
-) -> (
+use argon2::password_hash::SaltString;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// The response to a login, which sets the refresh token cookie if it succeeded
+type LoginResponse = (
     StatusCode,
     [(HeaderName, String); 1],
     Json<PostLoginResponse>,
-) {
+);
+
+/// Salts shorter than this (in bytes) are rejected
+const MIN_SALT_LENGTH: usize = 16;
+
+/// Made up, but stable KDF parameters for unknown usernames. They look like the parameters of a
+/// user, who registered or changed their password with the current client.
+fn unknown_user_kdf_params(secret: &str, username: &str) -> KdfParams {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(username.as_bytes());
+    let digest = mac.finalize().into_bytes();
+    let salt = SaltString::encode_b64(&digest[..MIN_SALT_LENGTH]).expect("Failed to encode salt");
+    KdfParams::new(KdfParams::CURRENT_VERSION, Some(salt.as_str().to_string()))
+}
+
+/// Check the KDF parameters of new credentials. Returns why they are rejected, if they are.
+fn validate_kdf_params(kdf_params: &KdfParams) -> Result<(), String> {
+    if kdf_params.version != KdfParams::CURRENT_VERSION {
+        return Err(format!(
+            "KDF version {} is not supported, use version {}",
+            kdf_params.version,
+            KdfParams::CURRENT_VERSION
+        ));
+    }
+
+    let Some(salt) = &kdf_params.salt else {
+        return Err("The KDF parameters are missing a salt".to_string());
+    };
+
+    // The salt has to be accepted by argon2 on the client
+    let mut buffer = [0; 64];
+    let salt_length = SaltString::from_b64(salt)
+        .and_then(|salt| salt.decode_b64(&mut buffer).map(|bytes| bytes.len()))
+        .map_err(|_| "The salt is not valid Base64 (without padding)".to_string())?;
+
+    if salt_length < MIN_SALT_LENGTH {
+        return Err(format!(
+            "The salt has to be at least {MIN_SALT_LENGTH} bytes long"
+        ));
+    }
+    Ok(())
+}
+
+pub async fn post_pre_login(
+    State(state): State<AppState>,
+    Json(payload): Json<PostPreLoginRequest>,
+) -> Result<(StatusCode, Json<PostPreLoginResponse>), ApiError> {
+    let kdf_params = match state
+        .user_repository
+        .get_user_by_username(&payload.username)?
+    {
+        Some(user) => user.kdf_params(),
+        None => unknown_user_kdf_params(&state.config.auth.pre_login_secret, &payload.username),
+    };
+
+    Ok((StatusCode::OK, Json(PostPreLoginResponse::Ok(kdf_params))))
+}
+
+pub async fn post_login(
+    State(state): State<AppState>,
+    Json(payload): Json<PostLoginRequest>,
+) -> Result<LoginResponse, ApiError> {
     let username = payload.username;
     let password = payload.password;
 
     let user_entity = state
         .user_repository
-        .authenticate_user(&username, &password)
-        .expect("db error");
+        .authenticate_user(&username, &password)?;
 
     if user_entity.is_none() {
-        return (
+        return Ok((
             StatusCode::UNAUTHORIZED,
             [(SET_COOKIE, "".to_string())],
             Json(PostLoginResponse::Unauthorized(Username)),
-        );
+        ));
     }
 
     let user_entity = user_entity.unwrap();
 
-    let (rtoken, jwt) = state
+    // Refresh tokens are only issued after the second factor was entered
+    if user_entity.totp_enabled {
+        let challenge = state
+            .user_repository
+            .create_second_factor_challenge(user_entity.id)?;
+
+        return Ok((
+            StatusCode::OK,
+            [(SET_COOKIE, "".to_string())],
+            Json(PostLoginResponse::SecondFactorRequired(
+                SecondFactorChallenge { challenge },
+            )),
+        ));
+    }
+
+    create_login_session(&state, user_entity)
+}
+
+pub async fn post_login_second_factor(
+    State(state): State<AppState>,
+    Json(payload): Json<PostLoginSecondFactorRequest>,
+) -> Result<LoginResponse, ApiError> {
+    let Some(user_entity) = state
         .user_repository
-        .create_session(user_entity.id)
-        .expect("Failed to create session!");
+        .verify_second_factor_challenge(&payload.challenge)?
+    else {
+        return Ok((
+            StatusCode::UNAUTHORIZED,
+            [(SET_COOKIE, "".to_string())],
+            Json(PostLoginResponse::Unauthorized(SecondFactor)),
+        ));
+    };
+
+    if !state
+        .user_repository
+        .verify_second_factor(&user_entity, &payload.code)?
+    {
+        return Ok((
+            StatusCode::UNAUTHORIZED,
+            [(SET_COOKIE, "".to_string())],
+            Json(PostLoginResponse::Unauthorized(SecondFactor)),
+        ));
+    }
+
+    create_login_session(&state, user_entity)
+}
+
+/// Start a new session for an authenticated user. Sets the refresh token cookie.
+fn create_login_session(
+    state: &AppState,
+    user_entity: UserEntity,
+) -> Result<LoginResponse, ApiError> {
+    let (rtoken, jwt) = state.user_repository.create_session(user_entity.id)?;
 
     let cookie = Cookie::build(("refresh_token", rtoken))
         .http_only(true)
@@ -78,7 +221,7 @@ pub async fn post_login(
         ))
     };
 
-    (
+    Ok((
         StatusCode::OK,
         [(SET_COOKIE, cookie)],
         Json(PostLoginResponse::Ok(LoginSuccess::new(
@@ -89,89 +232,107 @@ pub async fn post_login(
             user_entity.encryption_uninitialized,
             keys,
         ))),
-    )
+    ))
 }
 
 pub async fn post_register(
     State(state): State<AppState>,
     Json(payload): Json<PostRegisterRequest>,
-) -> (StatusCode, Json<PostRegisterResponse>) {
+) -> Result<(StatusCode, Json<PostRegisterResponse>), ApiError> {
     let username = payload.username;
     let password = payload.password;
     let invite_code = payload.invite_code;
     let keys = payload.keys;
+    let recovery_key = payload.recovery_key;
+    let kdf_params = payload.kdf_params;
+
+    if let Err(error) = validate_kdf_params(&kdf_params) {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostRegisterResponse::BadRequest(error)),
+        ));
+    }
 
     //TODO maybe check for weird characters in usernames
 
     if !username.chars().all(char::is_alphanumeric) {
-        return (
+        return Ok((
             StatusCode::CONFLICT,
             Json(PostRegisterResponse::Conflict(
                 RegisterConflictReason::IllegalUsername,
             )),
-        );
+        ));
     }
 
-    let invite_code_hash = format!("{:02x}", sha2::Sha512::digest(invite_code.as_bytes()));
+    // Codes created by admins take precedence. The code from the config is only a fallback to
+    // bootstrap the first accounts.
+    let invite = state
+        .invite_repository
+        .get_redeemable_invite_code(&invite_code)?;
 
-    if !invite_code_hash.eq(&state.config.auth.invite_code_hash) {
-        return (
+    if invite.is_none() && !hash_invite_code(&invite_code).eq(&state.config.auth.invite_code_hash) {
+        return Ok((
             StatusCode::FORBIDDEN,
             Json(PostRegisterResponse::Unauthorized),
-        );
+        ));
     }
 
-    if state
-        .user_repository
-        .get_user_by_username(&username)
-        .expect("db error")
-        .is_some()
+    // Codes created for a single account only accept its username
+    if invite
+        .as_ref()
+        .and_then(|invite| invite.username.as_ref())
+        .is_some_and(|reserved| *reserved != username)
     {
-        return (
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(PostRegisterResponse::Unauthorized),
+        ));
+    }
+
+    let reserved_for_other = state
+        .invite_repository
+        .get_reserving_invite_code(&username)?
+        .is_some_and(|reserving| {
+            invite
+                .as_ref()
+                .is_none_or(|invite| invite.id != reserving.id)
+        });
+
+    if reserved_for_other
+        || state
+            .user_repository
+            .get_user_by_username(&username)?
+            .is_some()
+    {
+        return Ok((
             StatusCode::CONFLICT,
             Json(PostRegisterResponse::Conflict(
                 RegisterConflictReason::UsernameTaken,
             )),
-        );
+        ));
     }
 
-    let mut user = state
-        .user_repository
-        .create_user(&username, &password, da!(15 GB), keys)
-        .expect("db error when creating user");
-
-    // Create Root & Trash node with uninitialized encryption
-    // The client will initiliaze the metadata on first sign-in.
-
-    let root_node = state
-        .node_repository
-        .create_node(
-            None,
-            EncryptedMetadata::nil(),
-            user.id,
-            NodeType::Folder,
-            NodeId::random(),
-        )
-        .expect("db error when creating root node");
-
-    user.root_node = Some(root_node.id);
-
-    let trash_node = state
-        .node_repository
-        .create_node(
-            None,
-            EncryptedMetadata::nil(),
-            user.id,
-            NodeType::Folder,
-            NodeId::random(),
-        )
-        .expect("db error when creating root node");
-
-    user.trash_node = Some(trash_node.id);
-
-    state.user_repository.update_user(user).expect("DB Error!");
-
-    (StatusCode::CREATED, Json(PostRegisterResponse::Created))
+    let new_user = NewUser {
+        username,
+        password,
+        keys,
+        kdf_params,
+        recovery_key,
+    };
+    let invite_code = invite.is_some().then_some(invite_code.as_str());
+
+    if register_user(&state, new_user, invite_code)
+        .await?
+        .is_none()
+    {
+        // The code was used up in the meantime
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(PostRegisterResponse::Unauthorized),
+        ));
+    }
+
+    Ok((StatusCode::CREATED, Json(PostRegisterResponse::Created)))
 }
 
 pub async fn get_user_info(
@@ -183,21 +344,19 @@ pub async fn get_user_info(
         storage_limit: user.storage_limit,
         username: user.username,
         storage_used: user.storage_used,
+        totp_enabled: user.totp_enabled,
     }))
 }
 
 pub async fn post_logout(
     State(state): State<AppState>,
-    _user: UserEntity,
+    SessionUser(_user): SessionUser,
     TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
-) -> (StatusCode, [(HeaderName, String); 1]) {
+) -> Result<(StatusCode, [(HeaderName, String); 1]), ApiError> {
     tracing::debug!("Logging out!");
     let jwt = auth.token().to_string();
-    state
-        .user_repository
-        .close_session(&jwt)
-        .expect("Failed to close session");
-    (StatusCode::OK, [(SET_COOKIE, "".to_string())])
+    state.user_repository.close_session(&jwt)?;
+    Ok((StatusCode::OK, [(SET_COOKIE, "".to_string())]))
 }
 
 pub async fn post_refresh(
@@ -257,3 +416,478 @@ pub async fn post_refresh(
         Json(PostRefreshResponse::Ok(RefreshBody { bearer_token: jwt })),
     )
 }
+
+pub async fn post_change_password(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Json(payload): Json<PostChangePasswordRequest>,
+) -> Result<(StatusCode, Json<PostChangePasswordResponse>), ApiError> {
+    if let Err(error) = validate_kdf_params(&payload.kdf_params) {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostChangePasswordResponse::BadRequest(error)),
+        ));
+    }
+
+    if state
+        .user_repository
+        .authenticate_user(&user.username, &payload.old_password)?
+        .is_none()
+    {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(PostChangePasswordResponse::Unauthorized),
+        ));
+    }
+
+    state.user_repository.change_password(
+        user.id,
+        &payload.new_password,
+        payload.keys,
+        payload.kdf_params,
+    )?;
+
+    // Other sessions and access tokens may have been created by someone who knows the old password
+    let current_session = state.user_repository.get_session_id(auth.token())?;
+    state
+        .user_repository
+        .revoke_other_sessions(user.id, current_session)?;
+    state
+        .access_token_repository
+        .delete_access_tokens(user.id)?;
+
+    Ok((StatusCode::OK, Json(PostChangePasswordResponse::Ok)))
+}
+
+/// Replace the password with the same password, derived with stronger parameters. Unlike
+/// `post_change_password`, this keeps the other sessions, so it is only possible once for every
+/// newer version of the parameters.
+pub async fn post_upgrade_kdf(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+    Json(payload): Json<PostChangePasswordRequest>,
+) -> Result<(StatusCode, Json<PostChangePasswordResponse>), ApiError> {
+    if let Err(error) = validate_kdf_params(&payload.kdf_params) {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostChangePasswordResponse::BadRequest(error)),
+        ));
+    }
+
+    // Otherwise the upgrade could be used to change the password without revoking the sessions
+    let stored_kdf_params = user.kdf_params();
+    if !stored_kdf_params.needs_upgrade() || payload.kdf_params.version <= stored_kdf_params.version
+    {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostChangePasswordResponse::BadRequest(
+                "The key derivation parameters are up to date already".to_string(),
+            )),
+        ));
+    }
+
+    if state
+        .user_repository
+        .authenticate_user(&user.username, &payload.old_password)?
+        .is_none()
+    {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(PostChangePasswordResponse::Unauthorized),
+        ));
+    }
+
+    state.user_repository.change_password(
+        user.id,
+        &payload.new_password,
+        payload.keys,
+        payload.kdf_params,
+    )?;
+
+    Ok((StatusCode::OK, Json(PostChangePasswordResponse::Ok)))
+}
+
+pub async fn put_recovery_key(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+    Json(payload): Json<PutRecoveryKeyRequest>,
+) -> Result<(StatusCode, Json<PutRecoveryKeyResponse>), ApiError> {
+    if state
+        .user_repository
+        .authenticate_user(&user.username, &payload.password)?
+        .is_none()
+    {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(PutRecoveryKeyResponse::Unauthorized),
+        ));
+    }
+
+    state.user_repository.set_recovery_key(
+        user.id,
+        &payload.recovery_key.recovery_password,
+        payload.recovery_key.master_key,
+    )?;
+
+    Ok((StatusCode::OK, Json(PutRecoveryKeyResponse::Ok)))
+}
+
+pub async fn delete_recovery_key(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+) -> Result<(StatusCode, Json<DeleteRecoveryKeyResponse>), ApiError> {
+    if user.recovery_password_hash.is_none() {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(DeleteRecoveryKeyResponse::NotFound),
+        ));
+    }
+
+    state.user_repository.delete_recovery_key(user.id)?;
+
+    Ok((StatusCode::OK, Json(DeleteRecoveryKeyResponse::Ok)))
+}
+
+/// Set the key pair of a user, who registered before key pairs were generated
+pub async fn put_key_pair(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+    Json(payload): Json<PutKeyPairRequest>,
+) -> Result<(StatusCode, Json<PutKeyPairResponse>), ApiError> {
+    if !user.public_key.is_empty() {
+        return Ok((StatusCode::CONFLICT, Json(PutKeyPairResponse::Conflict)));
+    }
+
+    if payload.public_key.is_empty() || payload.private_key.key().is_empty() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PutKeyPairResponse::BadRequest),
+        ));
+    }
+
+    state
+        .user_repository
+        .set_key_pair(user.id, payload.public_key, payload.private_key)?;
+
+    Ok((StatusCode::OK, Json(PutKeyPairResponse::Ok)))
+}
+
+pub async fn post_recovery_keys(
+    State(state): State<AppState>,
+    Json(payload): Json<PostRecoveryKeysRequest>,
+) -> Result<(StatusCode, Json<PostRecoveryKeysResponse>), ApiError> {
+    let Some(user) = state
+        .user_repository
+        .authenticate_recovery(&payload.username, &payload.recovery_password)?
+    else {
+        return Ok((
+            StatusCode::UNAUTHORIZED,
+            Json(PostRecoveryKeysResponse::Unauthorized),
+        ));
+    };
+
+    // `authenticate_recovery` only returns users with a recovery key
+    let recovery_master_key = user.recovery_master_key.unwrap();
+
+    Ok((
+        StatusCode::OK,
+        Json(PostRecoveryKeysResponse::Ok(RecoveryKeys {
+            recovery_master_key,
+            keys: UserKeys::new(
+                user.public_key,
+                user.private_key,
+                user.master_key,
+                user.root_key,
+                user.trash_key,
+            ),
+        })),
+    ))
+}
+
+pub async fn post_recovery_reset(
+    State(state): State<AppState>,
+    Json(payload): Json<PostRecoveryResetRequest>,
+) -> Result<(StatusCode, Json<PostRecoveryResetResponse>), ApiError> {
+    if let Err(error) = validate_kdf_params(&payload.kdf_params) {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostRecoveryResetResponse::BadRequest(error)),
+        ));
+    }
+
+    let Some(user) = state
+        .user_repository
+        .authenticate_recovery(&payload.username, &payload.recovery_password)?
+    else {
+        return Ok((
+            StatusCode::UNAUTHORIZED,
+            Json(PostRecoveryResetResponse::Unauthorized),
+        ));
+    };
+
+    state.user_repository.change_password(
+        user.id,
+        &payload.new_password,
+        payload.keys,
+        payload.kdf_params,
+    )?;
+
+    // The master key did not change, so the recovery key stays valid
+    state.user_repository.revoke_all_sessions(user.id)?;
+    state
+        .access_token_repository
+        .delete_access_tokens(user.id)?;
+
+    Ok((StatusCode::OK, Json(PostRecoveryResetResponse::Ok)))
+}
+
+pub async fn get_sessions(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> Result<(StatusCode, Json<GetSessionsResponse>), ApiError> {
+    let current_session = state.user_repository.get_session_id(auth.token())?;
+
+    let sessions = state
+        .user_repository
+        .list_sessions(user.id)?
+        .into_iter()
+        .map(|token| SessionInfo {
+            id: token.session_id,
+            created_at: token.session_created_at,
+            expires_at: token.expires_at,
+            current: token.session_id == current_session,
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(GetSessionsResponse::Ok(sessions))))
+}
+
+pub async fn delete_session(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+    Path(session_id): Path<SessionId>,
+) -> Result<(StatusCode, Json<DeleteSessionResponse>), ApiError> {
+    let revoked = state.user_repository.revoke_session(user.id, session_id)?;
+
+    if !revoked {
+        return Ok((StatusCode::NOT_FOUND, Json(DeleteSessionResponse::NotFound)));
+    }
+
+    Ok((StatusCode::OK, Json(DeleteSessionResponse::Ok)))
+}
+
+pub async fn delete_other_sessions(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> Result<(StatusCode, Json<DeleteOtherSessionsResponse>), ApiError> {
+    let current_session = state.user_repository.get_session_id(auth.token())?;
+
+    let revoked_count = state
+        .user_repository
+        .revoke_other_sessions(user.id, current_session)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(DeleteOtherSessionsResponse::Ok(revoked_count)),
+    ))
+}
+
+pub async fn post_totp(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+) -> Result<(StatusCode, Json<PostTotpResponse>), ApiError> {
+    if user.totp_enabled {
+        return Ok((StatusCode::CONFLICT, Json(PostTotpResponse::Conflict)));
+    }
+
+    let secret = state.user_repository.start_totp_enrolment(user.id)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PostTotpResponse::Ok(TotpEnrolment {
+            provisioning_uri: totp::provisioning_uri(&user.username, &secret),
+            secret,
+        })),
+    ))
+}
+
+pub async fn post_totp_confirm(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+    Json(payload): Json<TotpCodeRequest>,
+) -> Result<(StatusCode, Json<PostTotpConfirmResponse>), ApiError> {
+    if user.totp_enabled || user.totp_secret.is_none() {
+        return Ok((
+            StatusCode::CONFLICT,
+            Json(PostTotpConfirmResponse::Conflict),
+        ));
+    }
+
+    // Proves that the authenticator app was set up correctly
+    if !state
+        .user_repository
+        .verify_totp_code(&user, &payload.code)?
+    {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(PostTotpConfirmResponse::Unauthorized),
+        ));
+    }
+
+    let backup_codes = state.user_repository.enable_totp(user.id)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PostTotpConfirmResponse::Ok(backup_codes)),
+    ))
+}
+
+pub async fn delete_totp(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+    Json(payload): Json<TotpCodeRequest>,
+) -> Result<(StatusCode, Json<DeleteTotpResponse>), ApiError> {
+    if !user.totp_enabled {
+        return Ok((StatusCode::NOT_FOUND, Json(DeleteTotpResponse::NotFound)));
+    }
+
+    if !state
+        .user_repository
+        .verify_second_factor(&user, &payload.code)?
+    {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(DeleteTotpResponse::Unauthorized),
+        ));
+    }
+
+    state.user_repository.disable_totp(user.id)?;
+
+    Ok((StatusCode::OK, Json(DeleteTotpResponse::Ok)))
+}
+
+pub async fn post_totp_backup_codes(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+    Json(payload): Json<TotpCodeRequest>,
+) -> Result<(StatusCode, Json<PostTotpBackupCodesResponse>), ApiError> {
+    if !user.totp_enabled {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(PostTotpBackupCodesResponse::NotFound),
+        ));
+    }
+
+    if !state
+        .user_repository
+        .verify_second_factor(&user, &payload.code)?
+    {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(PostTotpBackupCodesResponse::Unauthorized),
+        ));
+    }
+
+    let backup_codes = state.user_repository.regenerate_backup_codes(user.id)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PostTotpBackupCodesResponse::Ok(backup_codes)),
+    ))
+}
+
+const MAX_ACCESS_TOKEN_NAME_LENGTH: usize = 100;
+
+fn entity_to_access_token_info(access_token: &AccessTokenEntity) -> AccessTokenInfo {
+    AccessTokenInfo {
+        id: access_token.id,
+        name: access_token.name.clone(),
+        scope: access_token.scope,
+        created_at: access_token.created_at,
+        expires_at: access_token.expires_at,
+        last_used_at: access_token.last_used_at,
+    }
+}
+
+pub async fn post_access_token(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+    Json(payload): Json<PostAccessTokenRequest>,
+) -> Result<(StatusCode, Json<PostAccessTokenResponse>), ApiError> {
+    let name = payload.name.trim();
+
+    if name.is_empty() || name.chars().count() > MAX_ACCESS_TOKEN_NAME_LENGTH {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostAccessTokenResponse::BadRequest(format!(
+                "name must be between 1 and {MAX_ACCESS_TOKEN_NAME_LENGTH} characters"
+            ))),
+        ));
+    }
+
+    if payload
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now().naive_utc())
+    {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostAccessTokenResponse::BadRequest(
+                "expires_at must be in the future".to_string(),
+            )),
+        ));
+    }
+
+    let (token, access_token) = state.access_token_repository.create_access_token(
+        user.id,
+        name,
+        payload.scope,
+        payload.expires_at,
+    )?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(PostAccessTokenResponse::Created(CreatedAccessToken {
+            token,
+            info: entity_to_access_token_info(&access_token),
+        })),
+    ))
+}
+
+pub async fn get_access_tokens(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+) -> Result<(StatusCode, Json<GetAccessTokensResponse>), ApiError> {
+    let access_tokens = state
+        .access_token_repository
+        .list_access_tokens(user.id)?
+        .iter()
+        .map(entity_to_access_token_info)
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(GetAccessTokensResponse::Ok(access_tokens)),
+    ))
+}
+
+pub async fn delete_access_token(
+    State(state): State<AppState>,
+    SessionUser(user): SessionUser,
+    Path(access_token_id): Path<AccessTokenId>,
+) -> Result<(StatusCode, Json<DeleteAccessTokenResponse>), ApiError> {
+    let deleted = state
+        .access_token_repository
+        .delete_access_token(user.id, access_token_id)?;
+
+    if deleted.is_none() {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(DeleteAccessTokenResponse::NotFound),
+        ));
+    }
+
+    Ok((StatusCode::OK, Json(DeleteAccessTokenResponse::Ok)))
+}
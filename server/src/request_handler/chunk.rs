@@ -1,148 +1,274 @@
-use crate::http::AppState;
-use crate::storage::vfs::FileChunk;
+use crate::http::{ApiError, AppState};
+use crate::service::file::{self, StoreChunkError};
+use crate::storage::node::persistence::model::node_entity::NodeEntity;
+use crate::storage::revision::RevisionEntity;
 use crate::storage::vfs::model::FileSystemError;
+use crate::storage::vfs::{ChunkContents, ChunkReader};
+use crate::user::auth::ReadWriteUser;
 use crate::user::persistence::model::user_entity::UserEntity;
 use axum::Json;
 use axum::body::Body;
 use axum::extract::{Path, State};
-use axum::http::{Response, StatusCode};
+use axum::http::{HeaderMap, Response, StatusCode};
 use axum::response::IntoResponse;
-use crabdrive_common::data::{DataAmount, DataUnit};
-use crabdrive_common::storage::{ChunkIndex, NodeId, RevisionId};
-use std::ops::Add;
+use axum_extra::headers::{
+    AcceptRanges, CacheControl, ContentLength, ContentRange, ContentType, ETag, HeaderMapExt,
+    IfNoneMatch, IfRange, Range,
+};
+use crabdrive_common::da;
+use crabdrive_common::data::DataAmount;
+use crabdrive_common::storage::{ChunkIndex, NodeId, RevisionId, SharePermission};
+use futures_util::TryStreamExt;
+use std::io::SeekFrom;
+use std::ops::Bound;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::{ReaderStream, StreamReader};
+
+/// The maximum size of an uploaded chunk. Chunks are usually 16 MB, the remainder is reserved for
+/// the overhead of the encryption.
+pub const MAX_CHUNK_SIZE: DataAmount = da!(18 MB);
+
+/// The size of the buffer for streaming a chunk from disk
+const CHUNK_READ_BUFFER_SIZE: usize = da!(64 KiB).as_bytes() as usize;
+
+/// How long clients may cache a chunk. Chunks of committed revisions never change.
+const CHUNK_MAX_AGE: Duration = Duration::from_secs(365 * 24 * 60 * 60);
 
 pub async fn post_chunk(
-    current_user: UserEntity,
+    ReadWriteUser(current_user): ReadWriteUser,
     State(state): State<AppState>,
     Path((node_id, revision_id, chunk_index)): Path<(NodeId, RevisionId, ChunkIndex)>,
-    chunk: axum::body::Bytes,
-) -> (StatusCode, Json<()>) {
-    let size = chunk.len() as f64;
-
-    let file_chunk = FileChunk {
-        index: chunk_index,
-        data: chunk,
-    };
-
-    let node_entity = state.node_repository.get_node(node_id).expect("db error");
-    let revision_entity = state
-        .revision_repository
-        .get_revision(revision_id)
-        .expect("db error");
+    body: Body,
+) -> Result<(StatusCode, Json<()>), ApiError> {
+    let node_entity = state.node_repository.get_node(node_id)?;
+    let revision_entity = state.revision_repository.get_revision(revision_id)?;
     if revision_entity.is_none() || node_entity.is_none() {
-        return (StatusCode::NOT_FOUND, Json(()));
+        return Ok((StatusCode::NOT_FOUND, Json(())));
     }
 
     let (revision_entity, node_entity) = (revision_entity.unwrap(), node_entity.unwrap());
 
     if node_entity.id != revision_entity.file_id {
-        return (StatusCode::NOT_FOUND, Json(()));
+        return Ok((StatusCode::NOT_FOUND, Json(())));
     }
 
-    if !state
+    let Some(permission) = state
         .node_repository
-        .has_access(node_entity.id, current_user.id)
-        .expect("db error")
-    {
-        return (StatusCode::NOT_FOUND, Json(()));
-    }
-
-    let Some(mut owning_user) = state
-        .user_repository
-        .get_user(node_entity.owner_id)
-        .expect("db error")
+        .get_permission(node_entity.id, current_user.id)?
     else {
-        panic!("db constraints not respected");
+        return Ok((StatusCode::NOT_FOUND, Json(())));
     };
 
-    let owning_user_new_storage_used = owning_user
-        .storage_used
-        .add(DataAmount::new(size, DataUnit::Byte));
-
-    if owning_user_new_storage_used > owning_user.storage_limit {
-        return (StatusCode::PAYLOAD_TOO_LARGE, Json(()));
+    if permission < SharePermission::Edit {
+        return Ok((StatusCode::FORBIDDEN, Json(())));
     }
 
+    let status = store_chunk(&state, &node_entity, &revision_entity, chunk_index, body).await?;
+    Ok((status, Json(())))
+}
+
+/// Store a chunk of a revision, which is streamed from the request body to the file system. The
+/// size of the chunk is added to the storage used by the owner of the node, uploads exceeding the
+/// storage limit of the owner are rejected.
+pub async fn store_chunk(
+    state: &AppState,
+    node_entity: &NodeEntity,
+    revision_entity: &RevisionEntity,
+    chunk_index: ChunkIndex,
+    body: Body,
+) -> Result<StatusCode, ApiError> {
+    let revision_id = revision_entity.id;
+
     if revision_entity.chunk_count < chunk_index || chunk_index <= 0 {
-        return (StatusCode::BAD_REQUEST, Json(()));
+        return Ok(StatusCode::BAD_REQUEST);
     }
 
-    if state
-        .vfs
-        .read()
-        .await
-        .chunk_exists(&revision_id, chunk_index)
-        .await
-    {
-        return (StatusCode::BAD_REQUEST, Json(()));
+    if state.vfs.chunk_exists(&revision_id, chunk_index).await {
+        return Ok(StatusCode::BAD_REQUEST);
     }
 
-    let result = state
-        .vfs
-        .write()
-        .await
-        .write_chunk(&revision_id, file_chunk)
-        .await;
-
-    match result {
-        Ok(_) => {
-            owning_user.storage_used = owning_user_new_storage_used;
-            state
-                .user_repository
-                .update_user(owning_user)
-                .expect("db error");
-
-            (StatusCode::CREATED, Json(()))
+    let contents = body_reader(body);
+    match file::store_chunk(
+        state,
+        node_entity.owner_id,
+        revision_entity,
+        chunk_index,
+        contents,
+        MAX_CHUNK_SIZE,
+    )
+    .await
+    {
+        Ok(_) => Ok(StatusCode::CREATED),
+        Err(StoreChunkError::StorageLimitExceeded) => Ok(StatusCode::PAYLOAD_TOO_LARGE),
+        // the same chunk was uploaded concurrently
+        Err(StoreChunkError::FileSystem(FileSystemError::AlreadyExists)) => {
+            Ok(StatusCode::BAD_REQUEST)
+        }
+        Err(StoreChunkError::FileSystem(FileSystemError::Io(
+            std::io::ErrorKind::ConnectionAborted,
+            e,
+        ))) => {
+            tracing::debug!("Chunk upload was aborted: {e}");
+            Ok(StatusCode::BAD_REQUEST)
         }
-        Err(FileSystemError::AlreadyExists) => (StatusCode::BAD_REQUEST, Json(())),
-        Err(FileSystemError::NotFound) => (StatusCode::NOT_FOUND, Json(())),
-        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(())),
+        Err(StoreChunkError::FileSystem(e)) => Err(e.into()),
+        Err(StoreChunkError::Other(e)) => Err(e.into()),
     }
 }
 
+/// Read the body of a request as the contents of a chunk. Errors while receiving the body (e.g.
+/// when the client disconnects) are reported as [`std::io::ErrorKind::ConnectionAborted`].
+fn body_reader(body: Body) -> ChunkReader {
+    let stream = body
+        .into_data_stream()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::ConnectionAborted, e));
+    Box::pin(StreamReader::new(stream))
+}
+
 pub async fn get_chunk(
     current_user: UserEntity,
     State(state): State<AppState>,
     Path((node_id, revision_id, chunk_index)): Path<(NodeId, RevisionId, ChunkIndex)>,
-) -> Response<Body> {
-    let node_entity = state.node_repository.get_node(node_id).expect("db error");
-    let revision_entity = state
-        .revision_repository
-        .get_revision(revision_id)
-        .expect("db error");
+    headers: HeaderMap,
+) -> Result<Response<Body>, ApiError> {
+    let node_entity = state.node_repository.get_node(node_id)?;
+    let revision_entity = state.revision_repository.get_revision(revision_id)?;
 
     if revision_entity.is_none() || node_entity.is_none() {
-        return StatusCode::NOT_FOUND.into_response();
+        return Ok(StatusCode::NOT_FOUND.into_response());
     }
 
     let (revision_entity, node_entity) = (revision_entity.unwrap(), node_entity.unwrap());
 
     if node_entity.id != revision_entity.file_id {
-        return StatusCode::NOT_FOUND.into_response();
+        return Ok(StatusCode::NOT_FOUND.into_response());
     }
 
     if !state
         .node_repository
-        .has_access(node_entity.id, current_user.id)
-        .expect("db error")
+        .has_access(node_entity.id, current_user.id, SharePermission::View)?
+    {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+
+    let contents = state.vfs.open_chunk(&revision_id, chunk_index).await?;
+
+    Ok(chunk_response(revision_id, chunk_index, contents, &headers).await?)
+}
+
+/// Respond with the contents of a chunk of a committed revision. Chunks, which are not in memory
+/// already, are streamed from disk instead of being read completely.
+///
+/// As committed chunks never change, the response can be cached forever. Clients can revalidate
+/// it with `If-None-Match` and resume interrupted downloads with `Range` (a single byte range).
+pub async fn chunk_response(
+    revision_id: RevisionId,
+    chunk_index: ChunkIndex,
+    contents: ChunkContents,
+    request_headers: &HeaderMap,
+) -> Result<Response<Body>, FileSystemError> {
+    let size = contents.size().as_bytes();
+    let etag: ETag = format!("\"{revision_id}-{chunk_index}\"")
+        .parse()
+        .expect("ETag of chunk is invalid");
+
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(etag.clone());
+    headers.typed_insert(AcceptRanges::bytes());
+    headers.typed_insert(
+        CacheControl::new()
+            .with_private()
+            .with_max_age(CHUNK_MAX_AGE)
+            .with_immutable(),
+    );
+
+    if request_headers
+        .typed_get::<IfNoneMatch>()
+        .is_some_and(|if_none_match| !if_none_match.precondition_passes(&etag))
     {
-        return StatusCode::NOT_FOUND.into_response();
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
     }
 
-    let result = state
-        .vfs
-        .read()
-        .await
-        .read_chunk(&revision_id, chunk_index)
-        .await;
+    // If the client has the start of another chunk, it needs the whole chunk instead of the range
+    let range = request_headers.typed_get::<Range>().filter(|_| {
+        request_headers
+            .typed_get::<IfRange>()
+            .is_none_or(|if_range| !if_range.is_modified(Some(&etag), None))
+    });
+
+    let (status, start, length) = match range.map(|range| satisfiable_range(&range, size)) {
+        None | Some(Ok(None)) => (StatusCode::OK, 0, size),
+        Some(Ok(Some((start, end)))) => {
+            let content_range =
+                ContentRange::bytes(start..=end, size).expect("Content range is invalid");
+            headers.typed_insert(content_range);
+            (StatusCode::PARTIAL_CONTENT, start, end - start + 1)
+        }
+        Some(Err(_)) => {
+            headers.typed_insert(ContentRange::unsatisfied_bytes(size));
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response());
+        }
+    };
 
-    if let Ok(data) = result {
-        return (StatusCode::OK, data.data).into_response();
+    headers.typed_insert(ContentType::octet_stream());
+    headers.typed_insert(ContentLength(length));
+
+    let body = match contents {
+        ChunkContents::Buffered(bytes) => {
+            Body::from(bytes.slice(start as usize..(start + length) as usize))
+        }
+        ChunkContents::File { mut file, .. } => {
+            if start > 0 {
+                file.seek(SeekFrom::Start(start)).await?;
+            }
+            Body::from_stream(ReaderStream::with_capacity(
+                file.take(length),
+                CHUNK_READ_BUFFER_SIZE,
+            ))
+        }
+        ChunkContents::Stream { mut contents, .. } => {
+            // streams cannot seek, so the bytes before the range are skipped
+            tokio::io::copy(&mut (&mut contents).take(start), &mut tokio::io::sink()).await?;
+            Body::from_stream(ReaderStream::with_capacity(
+                contents.take(length),
+                CHUNK_READ_BUFFER_SIZE,
+            ))
+        }
+    };
+
+    Ok((status, headers, body).into_response())
+}
+
+/// The requested byte range cannot be served
+struct UnsatisfiableRange;
+
+/// Get the first and last byte of the range requested from a chunk of `size` bytes. Requests for
+/// multiple ranges are answered with the whole chunk (`Ok(None)`), as the client is usually only
+/// interested in the part it is missing.
+fn satisfiable_range(range: &Range, size: u64) -> Result<Option<(u64, u64)>, UnsatisfiableRange> {
+    let mut ranges = range.satisfiable_ranges(size);
+    let (Some((start, end)), None) = (ranges.next(), ranges.next()) else {
+        return Ok(None);
+    };
+
+    let start = match start {
+        Bound::Included(start) => start,
+        Bound::Excluded(start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    if start >= size {
+        return Err(UnsatisfiableRange);
     }
 
-    match result.err().unwrap() {
-        FileSystemError::AlreadyExists => StatusCode::BAD_REQUEST.into_response(),
-        FileSystemError::NotFound => StatusCode::NOT_FOUND.into_response(),
-        _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    let end = match end {
+        Bound::Included(end) => end.min(size - 1),
+        Bound::Excluded(end) => end.saturating_sub(1).min(size - 1),
+        Bound::Unbounded => size - 1,
+    };
+    if end < start {
+        return Err(UnsatisfiableRange);
     }
+
+    Ok(Some((start, end)))
 }
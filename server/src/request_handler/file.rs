@@ -1,11 +1,12 @@
-use crate::http::AppState;
+use crate::http::{ApiError, AppState};
 use crate::request_handler::node::{entity_to_encrypted_node, entity_to_file_revision};
-use crate::storage::node::persistence::model::node_entity::NodeEntity;
+use crate::service::file::{self, NewFile};
+use crate::storage::node::{ChangeCounterMismatch, MetadataUpdate};
+use crate::user::auth::ReadWriteUser;
 use crate::user::persistence::model::user_entity::UserEntity;
 use axum::Json;
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
-use chrono::Utc;
 use crabdrive_common::payloads::node::request::file::{
     PostCreateFileRequest, PostUpdateFileRequest,
 };
@@ -15,293 +16,241 @@ use crabdrive_common::payloads::node::response::file::CommitFileError::{
 use crabdrive_common::payloads::node::response::file::{
     GetVersionsResponse, PostCommitFileResponse, PostCreateFileResponse, PostUpdateFileResponse,
 };
-use crabdrive_common::storage::NodeType;
 use crabdrive_common::storage::{NodeId, RevisionId};
+use crabdrive_common::storage::{NodeType, SharePermission};
 use crabdrive_common::uuid::UUID;
 
 #[axum::debug_handler]
 pub async fn post_create_file(
-    current_user: UserEntity,
+    ReadWriteUser(current_user): ReadWriteUser,
     State(state): State<AppState>,
     Path(parent_id): Path<NodeId>,
     Json(payload): Json<PostCreateFileRequest>,
-) -> (StatusCode, Json<PostCreateFileResponse>) {
+) -> Result<(StatusCode, Json<PostCreateFileResponse>), ApiError> {
     if payload.node_id.eq(&UUID::nil()) {
-        return (
+        return Ok((
             StatusCode::BAD_REQUEST,
             Json(PostCreateFileResponse::BadRequest),
-        );
+        ));
     }
 
-    let parent_node = state.node_repository.get_node(parent_id).expect("db error");
+    let parent_node = state.node_repository.get_node(parent_id)?;
 
     if parent_node.is_none() {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostCreateFileResponse::NotFound),
-        );
+        ));
     }
 
     let parent_node = parent_node.unwrap();
 
-    if !state
+    let Some(permission) = state
         .node_repository
-        .has_access(parent_node.id, current_user.id)
-        .expect("db error")
-    {
-        return (
+        .get_permission(parent_node.id, current_user.id)?
+    else {
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostCreateFileResponse::NotFound),
-        );
+        ));
+    };
+
+    if permission < SharePermission::Edit {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(PostCreateFileResponse::Forbidden),
+        ));
     }
 
     // a file cannot have children
     if parent_node.node_type != NodeType::Folder {
-        return (
+        return Ok((
             StatusCode::BAD_REQUEST,
             Json(PostCreateFileResponse::BadRequest),
-        );
-    }
-
-    //TODO this is not thread safe
-    if parent_node.metadata_change_counter != payload.parent_metadata_version {
-        tracing::warn!(
-            "Parent metadata mismatch (got {}, expected {})",
-            payload.parent_metadata_version,
-            parent_node.metadata_change_counter
-        );
-        return (StatusCode::CONFLICT, Json(PostCreateFileResponse::Conflict));
+        ));
     }
 
-    //update the parent
-    state
-        .node_repository
-        .update_node(&NodeEntity {
+    //create the node and update the parent
+    let result = file::create_file(
+        &state,
+        MetadataUpdate {
+            node_id: parent_id,
             metadata: payload.parent_metadata,
-            metadata_change_counter: parent_node.metadata_change_counter,
-            ..parent_node
-        })
-        .expect("db error");
-
-    //create the node
-    let node = state
-        .node_repository
-        .create_node(
-            Some(parent_id),
-            payload.node_metadata,
+            expected_counter: payload.parent_metadata_version,
+        },
+        NewFile {
+            id: payload.node_id,
+            metadata: payload.node_metadata,
             // a node should always have the same owner as its parent
-            parent_node.owner_id,
-            NodeType::File,
-            payload.node_id,
-        )
-        .expect("db error");
-
-    let revision = state
-        .revision_repository
-        .create_revision(
-            node.id,
-            Utc::now().naive_utc(),
-            payload.file_iv,
-            payload.chunk_count,
-        )
-        .expect("db error");
-
-    let node_with_revision = NodeEntity {
-        current_revision: Some(revision.id),
-        ..node
+            owner: parent_node.owner_id,
+            iv: payload.file_iv,
+            chunk_count: payload.chunk_count,
+        },
+    )
+    .await;
+
+    let node = match result {
+        Ok((node, _)) => node,
+        Err(e) if e.is::<ChangeCounterMismatch>() => {
+            tracing::warn!(
+                "Parent metadata mismatch (got {}, expected {})",
+                payload.parent_metadata_version,
+                parent_node.metadata_change_counter
+            );
+            return Ok((StatusCode::CONFLICT, Json(PostCreateFileResponse::Conflict)));
+        }
+        Err(e) => return Err(e.into()),
     };
 
-    state
-        .node_repository
-        .update_node(&node_with_revision)
-        .expect("db error");
-
-    {
-        let mut vfs = state.vfs.write().await;
-
-        vfs.create_file(&revision.id)
-            .await
-            .expect("how does that happen? - File system error!");
-    }
-
-    let response_node =
-        entity_to_encrypted_node(node_with_revision.clone(), &state).expect("db error");
-    (
+    let response_node = entity_to_encrypted_node(node, &state)?;
+    Ok((
         StatusCode::CREATED,
         Json(PostCreateFileResponse::Created(response_node)),
-    )
+    ))
 }
 
 #[axum::debug_handler]
 pub async fn post_update_file(
-    current_user: UserEntity,
+    ReadWriteUser(current_user): ReadWriteUser,
     State(state): State<AppState>,
     Path(file_id): Path<NodeId>,
     Json(payload): Json<PostUpdateFileRequest>,
-) -> (StatusCode, Json<PostUpdateFileResponse>) {
-    let node_entity = state.node_repository.get_node(file_id).expect("db error");
+) -> Result<(StatusCode, Json<PostUpdateFileResponse>), ApiError> {
+    let node_entity = state.node_repository.get_node(file_id)?;
 
     if node_entity.is_none() {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostUpdateFileResponse::NotFound),
-        );
+        ));
     }
 
     let node_entity = node_entity.unwrap();
 
-    if !state
+    let Some(permission) = state
         .node_repository
-        .has_access(node_entity.id, current_user.id)
-        .expect("db error")
-    {
-        return (
+        .get_permission(node_entity.id, current_user.id)?
+    else {
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostUpdateFileResponse::NotFound),
-        );
+        ));
+    };
+
+    if permission < SharePermission::Edit {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(PostUpdateFileResponse::Forbidden),
+        ));
     }
 
     if node_entity.node_type != NodeType::File {
-        return (
+        return Ok((
             StatusCode::BAD_REQUEST,
             Json(PostUpdateFileResponse::BadRequest),
-        );
+        ));
     }
 
-    let revision = state
-        .revision_repository
-        .create_revision(
-            file_id,
-            Utc::now().naive_utc(),
-            payload.file_iv,
-            payload.chunk_count,
-        )
-        .expect("db error");
-
-    {
-        let mut vfs = state.vfs.write().await;
-
-        vfs.create_file(&revision.id)
-            .await
-            .expect("how does that happen?");
-    }
+    let revision =
+        file::create_revision(&state, file_id, payload.file_iv, payload.chunk_count).await?;
 
-    (
+    Ok((
         StatusCode::OK,
         Json(PostUpdateFileResponse::Ok(entity_to_file_revision(
             revision,
         ))),
-    )
+    ))
 }
 
 #[axum::debug_handler]
 pub async fn post_commit_file(
-    current_user: UserEntity,
+    ReadWriteUser(current_user): ReadWriteUser,
     State(state): State<AppState>,
     Path((file_id, revision_id)): Path<(NodeId, RevisionId)>,
-) -> (StatusCode, Json<PostCommitFileResponse>) {
-    let revision = state
-        .revision_repository
-        .get_revision(revision_id)
-        .expect("db error");
+) -> Result<(StatusCode, Json<PostCommitFileResponse>), ApiError> {
+    let revision = state.revision_repository.get_revision(revision_id)?;
 
-    let node_entity = state.node_repository.get_node(file_id).expect("db error");
+    let node_entity = state.node_repository.get_node(file_id)?;
     if revision.is_none() || node_entity.is_none() {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostCommitFileResponse::NotFound),
-        );
+        ));
     }
 
-    let (mut revision, mut node_entity) = (revision.unwrap(), node_entity.unwrap());
+    let (revision, node_entity) = (revision.unwrap(), node_entity.unwrap());
 
     // check if node belongs to user and if the revision belongs to the node
-    if !state
+    let permission = state
         .node_repository
-        .has_access(node_entity.id, current_user.id)
-        .expect("db error")
-        || revision.file_id != node_entity.id
-    {
-        return (
+        .get_permission(node_entity.id, current_user.id)?;
+    let Some(permission) = permission.filter(|_| revision.file_id == node_entity.id) else {
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostCommitFileResponse::NotFound),
-        );
+        ));
+    };
+
+    if permission < SharePermission::Edit {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(PostCommitFileResponse::Forbidden),
+        ));
     }
 
     if revision.upload_ended_on.is_some() {
-        return (
+        return Ok((
             StatusCode::BAD_REQUEST,
             Json(PostCommitFileResponse::BadRequest(AlreadyCommitted)),
-        );
+        ));
     }
 
     let mut missing_chunks = vec![];
     for i in 1..revision.chunk_count {
-        if !state.vfs.read().await.chunk_exists(&revision_id, i).await {
+        if !state.vfs.chunk_exists(&revision_id, i).await {
             missing_chunks.push(i);
         }
     }
     if !missing_chunks.is_empty() {
-        return (
+        return Ok((
             StatusCode::BAD_REQUEST,
             Json(PostCommitFileResponse::BadRequest(MissingChunks(
                 missing_chunks,
             ))),
-        );
+        ));
     }
 
-    revision.upload_ended_on = Some(Utc::now().naive_utc());
+    let node_entity = file::commit_revision(&state, revision).await?;
+    let node = entity_to_encrypted_node(node_entity, &state)?;
 
-    node_entity.current_revision = Some(revision.id);
-
-    state
-        .revision_repository
-        .update_revision(revision)
-        .expect("db error");
-
-    state
-        .node_repository
-        .update_node(&node_entity)
-        .expect("db error");
-
-    let node = entity_to_encrypted_node(node_entity, &state).expect("db error");
-
-    {
-        let mut vfs = state.vfs.write().await;
-        vfs.commit_file(&revision_id).await.unwrap()
-    }
-
-    (StatusCode::OK, Json(PostCommitFileResponse::Ok(node)))
+    Ok((StatusCode::OK, Json(PostCommitFileResponse::Ok(node))))
 }
 
 pub async fn get_file_versions(
     current_user: UserEntity,
     State(state): State<AppState>,
     Path(file_id): Path<NodeId>,
-) -> (StatusCode, Json<GetVersionsResponse>) {
-    let node_entity = state.node_repository.get_node(file_id).expect("db error");
+) -> Result<(StatusCode, Json<GetVersionsResponse>), ApiError> {
+    let node_entity = state.node_repository.get_node(file_id)?;
 
     if node_entity.is_none() {
-        return (StatusCode::NOT_FOUND, Json(GetVersionsResponse::NotFound));
+        return Ok((StatusCode::NOT_FOUND, Json(GetVersionsResponse::NotFound)));
     }
 
-    if !state
-        .node_repository
-        .has_access(node_entity.unwrap().id, current_user.id)
-        .expect("db error")
-    {
-        return (StatusCode::NOT_FOUND, Json(GetVersionsResponse::NotFound));
+    if !state.node_repository.has_access(
+        node_entity.unwrap().id,
+        current_user.id,
+        SharePermission::View,
+    )? {
+        return Ok((StatusCode::NOT_FOUND, Json(GetVersionsResponse::NotFound)));
     }
 
-    let version_entities = state
-        .revision_repository
-        .get_revision_history(file_id)
-        .expect("db error");
+    let version_entities = state.revision_repository.get_revision_history(file_id)?;
 
     let versions = version_entities
         .iter()
         .map(|&entity| entity_to_file_revision(entity))
         .collect();
 
-    (StatusCode::OK, Json(GetVersionsResponse::Ok(versions)))
+    Ok((StatusCode::OK, Json(GetVersionsResponse::Ok(versions))))
 }
@@ -0,0 +1,445 @@
+//! File drops allow anyone with the link to upload files into a folder, without being able to see
+//! anything else in it. The uploader encrypts the metadata key of each file with the public key of
+//! the folder owner instead of adding it to the metadata of the folder, so the folder is never
+//! touched by the uploader. Dropped files are hidden from the children of the folder until the
+//! owner adopts them.
+
+use crate::http::{ApiError, AppState};
+use crate::request_handler::chunk::store_chunk;
+use crate::request_handler::node::{entity_to_encrypted_node, entity_to_file_revision};
+use crate::service::file::{self, NewFile};
+use crate::service::file_drop;
+use crate::storage::node::persistence::model::node_entity::NodeEntity;
+use crate::storage::node::{ChangeCounterMismatch, MetadataUpdate};
+use crate::storage::revision::RevisionEntity;
+use crate::storage::share::FileDropEntity;
+use crate::user::auth::ReadWriteUser;
+use crate::user::persistence::model::user_entity::UserEntity;
+use axum::Json;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use chrono::Utc;
+use crabdrive_common::payloads::node::request::file_drop::{
+    PostAdoptDroppedFilesRequest, PostDropFileRequest, PostFileDropRequest,
+};
+use crabdrive_common::payloads::node::response::file::CommitFileError::{
+    AlreadyCommitted, MissingChunks,
+};
+use crabdrive_common::payloads::node::response::file_drop::{
+    DeleteFileDropResponse, DroppedFile, FileDropInfo, GetDroppedFilesResponse,
+    GetFileDropResponse, PostAdoptDroppedFilesResponse, PostDropCommitResponse,
+    PostDropFileResponse, PostFileDropResponse,
+};
+use crabdrive_common::storage::{ChunkIndex, FileDropId, NodeId, NodeType, RevisionId};
+use crabdrive_common::uuid::UUID;
+use std::collections::HashSet;
+
+pub async fn post_file_drop(
+    ReadWriteUser(current_user): ReadWriteUser,
+    State(state): State<AppState>,
+    Path(node_id): Path<NodeId>,
+    Json(payload): Json<PostFileDropRequest>,
+) -> Result<(StatusCode, Json<PostFileDropResponse>), ApiError> {
+    let Some(node) = state.node_repository.get_node(node_id)? else {
+        return Ok((StatusCode::NOT_FOUND, Json(PostFileDropResponse::NotFound)));
+    };
+
+    if state
+        .node_repository
+        .get_permission(node.id, current_user.id)?
+        .is_none()
+    {
+        return Ok((StatusCode::NOT_FOUND, Json(PostFileDropResponse::NotFound)));
+    }
+
+    // the metadata keys of dropped files are encrypted for the owner, nobody else could adopt them
+    if node.owner_id != current_user.id {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostFileDropResponse::BadRequest(
+                "file drops can only be created by the owner of the folder".to_string(),
+            )),
+        ));
+    }
+
+    if node.node_type != NodeType::Folder {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostFileDropResponse::BadRequest(
+                "file drops can only be created for folders".to_string(),
+            )),
+        ));
+    }
+
+    if node.deleted_on.is_some() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostFileDropResponse::BadRequest(
+                "Cannot create a file drop for a folder that is in the trash".to_string(),
+            )),
+        ));
+    }
+
+    if current_user.public_key.is_empty() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostFileDropResponse::BadRequest(
+                "a key pair is required to create a file drop".to_string(),
+            )),
+        ));
+    }
+
+    if payload
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now().naive_utc())
+    {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostFileDropResponse::BadRequest(
+                "expires_at must be in the future".to_string(),
+            )),
+        ));
+    }
+
+    let file_drop = state.file_drop_repository.create_file_drop(
+        node.id,
+        current_user.id,
+        payload.expires_at,
+    )?;
+
+    Ok((StatusCode::OK, Json(PostFileDropResponse::Ok(file_drop.id))))
+}
+
+/// Revoke a file drop of a folder you own. Files dropped so far can still be adopted.
+pub async fn delete_file_drop(
+    ReadWriteUser(current_user): ReadWriteUser,
+    State(state): State<AppState>,
+    Path(drop_id): Path<FileDropId>,
+) -> Result<(StatusCode, Json<DeleteFileDropResponse>), ApiError> {
+    let Some(file_drop) = state.file_drop_repository.get_file_drop(drop_id)? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(DeleteFileDropResponse::NotFound),
+        ));
+    };
+
+    let owner_id = state
+        .node_repository
+        .get_node(file_drop.node_id)?
+        .ok_or_else(|| ApiError::NotFound(format!("Node {} does not exist", file_drop.node_id)))?
+        .owner_id;
+
+    if owner_id != current_user.id {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(DeleteFileDropResponse::BadRequest(
+                "cannot revoke a file drop of a folder that you do not own".to_string(),
+            )),
+        ));
+    }
+
+    state.file_drop_repository.delete_file_drop(file_drop.id)?;
+
+    Ok((StatusCode::OK, Json(DeleteFileDropResponse::Ok)))
+}
+
+/// Get the file drop and the folder it belongs to. Returns `None` if the drop does not exist, has
+/// expired or if the folder was moved to the trash.
+fn get_file_drop_folder(
+    state: &AppState,
+    drop_id: FileDropId,
+) -> Result<Option<(FileDropEntity, NodeEntity)>, ApiError> {
+    let Some(file_drop) = state.file_drop_repository.get_file_drop(drop_id)? else {
+        return Ok(None);
+    };
+
+    if file_drop.is_expired(Utc::now().naive_utc()) {
+        return Ok(None);
+    }
+
+    let path = state.node_repository.get_path_to_root(file_drop.node_id)?;
+    if path.iter().any(|node| node.deleted_on.is_some()) {
+        return Ok(None);
+    }
+
+    let folder = path.into_iter().find(|node| node.id == file_drop.node_id);
+
+    Ok(folder.map(|folder| (file_drop, folder)))
+}
+
+/// Get a file, which was dropped through the given drop and was not adopted yet, together with
+/// one of its revisions
+fn get_dropped_revision(
+    state: &AppState,
+    drop_id: FileDropId,
+    node_id: NodeId,
+    revision_id: RevisionId,
+) -> Result<Option<(NodeEntity, RevisionEntity)>, ApiError> {
+    if get_file_drop_folder(state, drop_id)?.is_none() {
+        return Ok(None);
+    }
+
+    let dropped_file = state.file_drop_repository.get_dropped_file(node_id)?;
+    if dropped_file.is_none_or(|dropped_file| dropped_file.file_drop_id != Some(drop_id)) {
+        return Ok(None);
+    }
+
+    let node = state.node_repository.get_node(node_id)?;
+    let revision = state.revision_repository.get_revision(revision_id)?;
+
+    // uploaders can only touch the revision they created
+    Ok(node
+        .zip(revision)
+        .filter(|(node, revision)| node.current_revision == Some(revision.id)))
+}
+
+pub async fn get_file_drop(
+    State(state): State<AppState>,
+    Path(drop_id): Path<FileDropId>,
+) -> Result<(StatusCode, Json<GetFileDropResponse>), ApiError> {
+    let expired = state
+        .file_drop_repository
+        .get_file_drop(drop_id)?
+        .is_some_and(|file_drop| file_drop.is_expired(Utc::now().naive_utc()));
+
+    if expired {
+        return Ok((StatusCode::GONE, Json(GetFileDropResponse::Expired)));
+    }
+
+    let Some((file_drop, folder)) = get_file_drop_folder(&state, drop_id)? else {
+        return Ok((StatusCode::NOT_FOUND, Json(GetFileDropResponse::NotFound)));
+    };
+
+    let owner = state
+        .user_repository
+        .get_user(folder.owner_id)?
+        .ok_or_else(|| ApiError::NotFound(format!("User {} does not exist", folder.owner_id)))?;
+
+    let info = FileDropInfo {
+        public_key: owner.public_key,
+        expires_at: file_drop.expires_at,
+    };
+
+    Ok((StatusCode::OK, Json(GetFileDropResponse::Ok(info))))
+}
+
+pub async fn post_drop_file(
+    State(state): State<AppState>,
+    Path(drop_id): Path<FileDropId>,
+    Json(payload): Json<PostDropFileRequest>,
+) -> Result<(StatusCode, Json<PostDropFileResponse>), ApiError> {
+    if payload.node_id.eq(&UUID::nil()) || payload.encrypted_metadata_key.is_empty() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostDropFileResponse::BadRequest),
+        ));
+    }
+
+    let Some((file_drop, folder)) = get_file_drop_folder(&state, drop_id)? else {
+        return Ok((StatusCode::NOT_FOUND, Json(PostDropFileResponse::NotFound)));
+    };
+
+    if state.node_repository.get_node(payload.node_id)?.is_some() {
+        return Ok((StatusCode::CONFLICT, Json(PostDropFileResponse::Conflict)));
+    }
+
+    let revision = file_drop::create_dropped_file(
+        &state,
+        &file_drop,
+        payload.encrypted_metadata_key,
+        NewFile {
+            id: payload.node_id,
+            metadata: payload.node_metadata,
+            owner: folder.owner_id,
+            iv: payload.file_iv,
+            chunk_count: payload.chunk_count,
+        },
+    )
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(PostDropFileResponse::Created(entity_to_file_revision(
+            revision,
+        ))),
+    ))
+}
+
+pub async fn post_drop_chunk(
+    State(state): State<AppState>,
+    Path((drop_id, node_id, revision_id, chunk_index)): Path<(
+        FileDropId,
+        NodeId,
+        RevisionId,
+        ChunkIndex,
+    )>,
+    body: Body,
+) -> Result<(StatusCode, Json<()>), ApiError> {
+    let Some((node, revision)) = get_dropped_revision(&state, drop_id, node_id, revision_id)?
+    else {
+        return Ok((StatusCode::NOT_FOUND, Json(())));
+    };
+
+    if revision.upload_ended_on.is_some() {
+        return Ok((StatusCode::BAD_REQUEST, Json(())));
+    }
+
+    let status = store_chunk(&state, &node, &revision, chunk_index, body).await?;
+    Ok((status, Json(())))
+}
+
+pub async fn post_drop_commit(
+    State(state): State<AppState>,
+    Path((drop_id, node_id, revision_id)): Path<(FileDropId, NodeId, RevisionId)>,
+) -> Result<(StatusCode, Json<PostDropCommitResponse>), ApiError> {
+    let Some((_, revision)) = get_dropped_revision(&state, drop_id, node_id, revision_id)? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(PostDropCommitResponse::NotFound),
+        ));
+    };
+
+    if revision.upload_ended_on.is_some() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostDropCommitResponse::BadRequest(AlreadyCommitted)),
+        ));
+    }
+
+    let mut missing_chunks = vec![];
+    for i in 1..=revision.chunk_count {
+        if !state.vfs.chunk_exists(&revision_id, i).await {
+            missing_chunks.push(i);
+        }
+    }
+    if !missing_chunks.is_empty() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostDropCommitResponse::BadRequest(MissingChunks(
+                missing_chunks,
+            ))),
+        ));
+    }
+
+    file::commit_revision(&state, revision).await?;
+
+    Ok((StatusCode::OK, Json(PostDropCommitResponse::Ok)))
+}
+
+/// Get the files dropped into a folder you own, which were not adopted yet
+pub async fn get_dropped_files(
+    current_user: UserEntity,
+    State(state): State<AppState>,
+    Path(folder_id): Path<NodeId>,
+) -> Result<(StatusCode, Json<GetDroppedFilesResponse>), ApiError> {
+    let folder = state.node_repository.get_node(folder_id)?;
+    if folder.is_none_or(|folder| folder.owner_id != current_user.id) {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(GetDroppedFilesResponse::NotFound),
+        ));
+    }
+
+    let dropped_files = state
+        .file_drop_repository
+        .get_dropped_files(folder_id)?
+        .into_iter()
+        .filter_map(|dropped_file| {
+            // the file may have been deleted since the dropped files were listed
+            let node = match state.node_repository.get_node(dropped_file.node_id) {
+                Ok(Some(node)) => node,
+                Ok(None) => return None,
+                Err(err) => return Some(Err(err)),
+            };
+
+            Some(
+                entity_to_encrypted_node(node, &state).map(|node| DroppedFile {
+                    node,
+                    encrypted_metadata_key: dropped_file.encrypted_metadata_key,
+                }),
+            )
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetDroppedFilesResponse::Ok(dropped_files)),
+    ))
+}
+
+/// Adopt dropped files after their metadata keys were added to the metadata of the folder. Only
+/// completely uploaded files can be adopted.
+pub async fn post_adopt_dropped_files(
+    ReadWriteUser(current_user): ReadWriteUser,
+    State(state): State<AppState>,
+    Path(folder_id): Path<NodeId>,
+    Json(payload): Json<PostAdoptDroppedFilesRequest>,
+) -> Result<(StatusCode, Json<PostAdoptDroppedFilesResponse>), ApiError> {
+    let Some(folder) = state
+        .node_repository
+        .get_node(folder_id)?
+        .filter(|folder| folder.owner_id == current_user.id)
+    else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(PostAdoptDroppedFilesResponse::NotFound),
+        ));
+    };
+
+    let dropped_files: HashSet<NodeId> = state
+        .file_drop_repository
+        .get_dropped_files(folder.id)?
+        .into_iter()
+        .map(|dropped_file| dropped_file.node_id)
+        .collect();
+
+    for node_id in &payload.node_ids {
+        if !dropped_files.contains(node_id) {
+            return Ok((
+                StatusCode::BAD_REQUEST,
+                Json(PostAdoptDroppedFilesResponse::BadRequest(format!(
+                    "{node_id} is not a dropped file in this folder"
+                ))),
+            ));
+        }
+
+        let revision_id = state
+            .node_repository
+            .get_node(*node_id)?
+            .and_then(|node| node.current_revision);
+        let revision = match revision_id {
+            Some(revision_id) => state.revision_repository.get_revision(revision_id)?,
+            None => None,
+        };
+        let committed = revision.is_some_and(|revision| revision.upload_ended_on.is_some());
+
+        if !committed {
+            return Ok((
+                StatusCode::BAD_REQUEST,
+                Json(PostAdoptDroppedFilesResponse::BadRequest(format!(
+                    "the upload of {node_id} is not finished"
+                ))),
+            ));
+        }
+    }
+
+    let folder_update = MetadataUpdate {
+        node_id: folder.id,
+        metadata: payload.parent_metadata,
+        expected_counter: payload.parent_metadata_version,
+    };
+
+    match file_drop::adopt_dropped_files(&state, folder_update, &payload.node_ids).await {
+        Ok(_) => {}
+        Err(e) if e.is::<ChangeCounterMismatch>() => {
+            return Ok((
+                StatusCode::CONFLICT,
+                Json(PostAdoptDroppedFilesResponse::Conflict),
+            ));
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok((StatusCode::OK, Json(PostAdoptDroppedFilesResponse::Ok)))
+}
@@ -1,91 +1,89 @@
-use crate::http::AppState;
+use crate::http::{ApiError, AppState};
 use crate::request_handler::node::entity_to_encrypted_node;
-use crate::storage::node::persistence::model::node_entity::NodeEntity;
-use crate::user::persistence::model::user_entity::UserEntity;
+use crate::storage::node::{ChangeCounterMismatch, MetadataUpdate};
+use crate::user::auth::ReadWriteUser;
 use axum::Json;
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
 use crabdrive_common::payloads::node::request::folder::PostCreateFolderRequest;
 use crabdrive_common::payloads::node::response::folder::PostCreateFolderResponse;
-use crabdrive_common::storage::{NodeId, NodeType};
+use crabdrive_common::storage::{NodeId, NodeType, SharePermission};
 use crabdrive_common::uuid::UUID;
 
 pub async fn post_create_folder(
-    current_user: UserEntity,
+    ReadWriteUser(current_user): ReadWriteUser,
     State(state): State<AppState>,
     Path(parent_id): Path<NodeId>,
     Json(payload): Json<PostCreateFolderRequest>,
-) -> (StatusCode, Json<PostCreateFolderResponse>) {
+) -> Result<(StatusCode, Json<PostCreateFolderResponse>), ApiError> {
     if payload.node_id.eq(&UUID::nil()) {
-        return (
+        return Ok((
             StatusCode::BAD_REQUEST,
             Json(PostCreateFolderResponse::BadRequest),
-        );
+        ));
     }
 
-    let parent_node = state.node_repository.get_node(parent_id).expect("db error");
+    let parent_node = state.node_repository.get_node(parent_id)?;
 
     if parent_node.is_none() {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostCreateFolderResponse::NotFound),
-        );
+        ));
     }
 
     let parent_node = parent_node.unwrap();
 
-    if !state
+    let Some(permission) = state
         .node_repository
-        .has_access(parent_node.id, current_user.id)
-        .expect("db error")
-    {
-        return (
+        .get_permission(parent_node.id, current_user.id)?
+    else {
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostCreateFolderResponse::NotFound),
-        );
+        ));
+    };
+
+    if permission < SharePermission::Edit {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(PostCreateFolderResponse::Forbidden),
+        ));
     }
 
     // a file cannot have children
     if parent_node.node_type != NodeType::Folder {
-        return (
+        return Ok((
             StatusCode::BAD_REQUEST,
             Json(PostCreateFolderResponse::BadRequest),
-        );
-    }
-
-    //TODO this is not thread safe
-    if parent_node.metadata_change_counter != payload.parent_metadata_version {
-        return (
-            StatusCode::CONFLICT,
-            Json(PostCreateFolderResponse::Conflict),
-        );
+        ));
     }
 
-    //update the parent
-    state
-        .node_repository
-        .update_node(&NodeEntity {
+    //create the node and update the parent
+    let node = match state.node_repository.create_node_in_parent(
+        MetadataUpdate {
+            node_id: parent_id,
             metadata: payload.parent_metadata,
-            metadata_change_counter: parent_node.metadata_change_counter,
-            ..parent_node
-        })
-        .expect("db error");
-
-    //create the node
-    let node = state
-        .node_repository
-        .create_node(
-            Some(parent_id),
-            payload.node_metadata,
-            parent_node.owner_id,
-            NodeType::Folder,
-            payload.node_id,
-        )
-        .expect("db error");
+            expected_counter: payload.parent_metadata_version,
+        },
+        payload.node_metadata,
+        parent_node.owner_id,
+        NodeType::Folder,
+        payload.node_id,
+    ) {
+        Ok(node) => node,
+        Err(e) if e.is::<ChangeCounterMismatch>() => {
+            return Ok((
+                StatusCode::CONFLICT,
+                Json(PostCreateFolderResponse::Conflict),
+            ));
+        }
+        Err(e) => return Err(e.into()),
+    };
 
-    let response_node = entity_to_encrypted_node(node, &state).expect("db error");
-    (
+    let response_node = entity_to_encrypted_node(node, &state)?;
+    Ok((
         StatusCode::CREATED,
         Json(PostCreateFolderResponse::Created(response_node)),
-    )
+    ))
 }
@@ -2,6 +2,8 @@ pub mod admin;
 pub mod auth;
 pub mod chunk;
 pub mod file;
+pub mod file_drop;
 pub mod folder;
 pub mod node;
+pub mod public_link;
 pub mod share;
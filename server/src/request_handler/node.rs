@@ -1,7 +1,10 @@
-use crate::http::AppState;
+use crate::http::{ApiError, AppState};
 use crate::storage::node::persistence::model::node_entity::NodeEntity;
+use crate::storage::node::{ChangeCounterMismatch, MetadataUpdate};
 use crate::storage::revision::persistence::model::revision_entity::RevisionEntity;
+use crate::user::auth::ReadWriteUser;
 use crate::user::persistence::model::user_entity::UserEntity;
+use anyhow::Context;
 use axum::Json;
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
@@ -14,43 +17,45 @@ use crabdrive_common::payloads::node::response::node::{
     PatchNodeResponse, PostMoveNodeOutOfTrashResponse, PostMoveNodeResponse,
     PostMoveNodeToTrashResponse,
 };
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 
 use crabdrive_common::storage::{EncryptedNode, NodeId};
-use crabdrive_common::storage::{FileRevision, NodeType};
+use crabdrive_common::storage::{FileRevision, NodeType, SharePermission};
 
 pub async fn delete_node(
-    current_user: UserEntity,
+    ReadWriteUser(current_user): ReadWriteUser,
     State(state): State<AppState>,
     Path(node_id): Path<NodeId>,
     Json(payload): Json<DeleteNodeRequest>,
-) -> (StatusCode, Json<DeleteNodeResponse>) {
-    let node = state.node_repository.get_node(node_id).expect("db error");
+) -> Result<(StatusCode, Json<DeleteNodeResponse>), ApiError> {
+    let node = state.node_repository.get_node(node_id)?;
 
     if node.is_none() {
-        return (StatusCode::NOT_FOUND, Json(DeleteNodeResponse::NotFound));
+        return Ok((StatusCode::NOT_FOUND, Json(DeleteNodeResponse::NotFound)));
     }
 
     let node = node.unwrap();
 
     if node.owner_id != current_user.id {
-        return (StatusCode::NOT_FOUND, Json(DeleteNodeResponse::NotFound));
+        return Ok((StatusCode::NOT_FOUND, Json(DeleteNodeResponse::NotFound)));
     }
 
-    // will panic when trying to delete root node but thats ok as the client prevents it
+    // the root and trash nodes have no parent and cannot be deleted
+    let Some(parent_id) = node.parent_id else {
+        return Ok((StatusCode::NOT_FOUND, Json(DeleteNodeResponse::NotFound)));
+    };
     let parent = state
         .node_repository
-        .get_node(node.parent_id.unwrap())
-        .expect("db error")
-        .expect("db constraints");
+        .get_node(parent_id)?
+        .ok_or_else(|| ApiError::NotFound(format!("Node {parent_id} does not exist")))?;
 
     if parent.metadata_change_counter != payload.parent_change_count {
-        return (StatusCode::CONFLICT, Json(DeleteNodeResponse::Conflict));
+        return Ok((StatusCode::CONFLICT, Json(DeleteNodeResponse::Conflict)));
     }
 
     match state.node_repository.purge_tree_from_trash(node_id) {
-        Ok(_) => (StatusCode::OK, Json(DeleteNodeResponse::Ok)),
-        Err(_) => (StatusCode::CONFLICT, Json(DeleteNodeResponse::Conflict)),
+        Ok(_) => Ok((StatusCode::OK, Json(DeleteNodeResponse::Ok))),
+        Err(_) => Ok((StatusCode::CONFLICT, Json(DeleteNodeResponse::Conflict))),
     }
 }
 
@@ -58,318 +63,306 @@ pub async fn get_node(
     current_user: UserEntity,
     State(state): State<AppState>,
     Path(node_id): Path<NodeId>,
-) -> (StatusCode, Json<GetNodeResponse>) {
-    let node_entity = state.node_repository.get_node(node_id).expect("db error");
+) -> Result<(StatusCode, Json<GetNodeResponse>), ApiError> {
+    let node_entity = state.node_repository.get_node(node_id)?;
 
     if node_entity.is_none() {
-        return (StatusCode::NOT_FOUND, Json(GetNodeResponse::NotFound));
+        return Ok((StatusCode::NOT_FOUND, Json(GetNodeResponse::NotFound)));
     }
     let node_entity = node_entity.unwrap();
 
     if !state
         .node_repository
-        .has_access(node_entity.id, current_user.id)
-        .expect("db error")
+        .has_access(node_entity.id, current_user.id, SharePermission::View)?
     {
-        return (StatusCode::NOT_FOUND, Json(GetNodeResponse::NotFound));
+        return Ok((StatusCode::NOT_FOUND, Json(GetNodeResponse::NotFound)));
     }
 
-    let node = entity_to_encrypted_node(node_entity, &state).unwrap();
+    let node = entity_to_encrypted_node(node_entity, &state)?;
 
-    (StatusCode::OK, Json(GetNodeResponse::Ok(node)))
+    Ok((StatusCode::OK, Json(GetNodeResponse::Ok(node))))
 }
 
 pub async fn patch_node(
-    current_user: UserEntity,
+    ReadWriteUser(current_user): ReadWriteUser,
     State(state): State<AppState>,
     Path(node_id): Path<NodeId>,
     Json(payload): Json<PatchNodeRequest>,
-) -> (StatusCode, Json<PatchNodeResponse>) {
+) -> Result<(StatusCode, Json<PatchNodeResponse>), ApiError> {
     // TODO very janky
 
-    let node = state.node_repository.get_node(node_id).expect("db error");
+    let node = state.node_repository.get_node(node_id)?;
 
     if node.is_none() {
-        return (StatusCode::NOT_FOUND, Json(PatchNodeResponse::NotFound));
+        return Ok((StatusCode::NOT_FOUND, Json(PatchNodeResponse::NotFound)));
     }
 
     let node_entity = node.unwrap();
 
-    if !state
+    let Some(permission) = state
         .node_repository
-        .has_access(node_entity.id, current_user.id)
-        .expect("db error")
-    {
-        return (StatusCode::NOT_FOUND, Json(PatchNodeResponse::NotFound));
-    }
+        .get_permission(node_entity.id, current_user.id)?
+    else {
+        return Ok((StatusCode::NOT_FOUND, Json(PatchNodeResponse::NotFound)));
+    };
 
-    // TODO this should happen in one transaction as it it could lead to updates being lost
-    // (which is bad)
-    if node_entity.metadata_change_counter != payload.node_change_count {
-        return (StatusCode::CONFLICT, Json(PatchNodeResponse::Conflict));
+    if permission < SharePermission::Edit {
+        return Ok((StatusCode::FORBIDDEN, Json(PatchNodeResponse::Forbidden)));
     }
 
-    let updated_node = NodeEntity {
+    let updated_node_entity = match state.node_repository.update_node_metadata(MetadataUpdate {
+        node_id: node_entity.id,
         metadata: payload.node_metadata,
-        metadata_change_counter: node_entity.metadata_change_counter,
-        ..node_entity
+        expected_counter: payload.node_change_count,
+    }) {
+        Ok(node) => node,
+        Err(e) if e.is::<ChangeCounterMismatch>() => {
+            return Ok((StatusCode::CONFLICT, Json(PatchNodeResponse::Conflict)));
+        }
+        Err(e) => return Err(e.into()),
     };
 
-    let updated_node_entity = state
-        .node_repository
-        .update_node(&updated_node)
-        .expect("db error");
-
-    let updated_node = entity_to_encrypted_node(updated_node_entity, &state).expect("db error");
-    (StatusCode::OK, Json(PatchNodeResponse::Ok(updated_node)))
+    let updated_node = entity_to_encrypted_node(updated_node_entity, &state)?;
+    Ok((StatusCode::OK, Json(PatchNodeResponse::Ok(updated_node))))
 }
 
 pub async fn post_move_node(
-    current_user: UserEntity,
+    ReadWriteUser(current_user): ReadWriteUser,
     State(state): State<AppState>,
     Path(node_id): Path<NodeId>,
     Json(payload): Json<PostMoveNodeRequest>,
-) -> (StatusCode, Json<PostMoveNodeResponse>) {
-    let node = state.node_repository.get_node(node_id).expect("db error");
+) -> Result<(StatusCode, Json<PostMoveNodeResponse>), ApiError> {
+    let node = state.node_repository.get_node(node_id)?;
 
     if node.is_none() {
-        return (StatusCode::NOT_FOUND, Json(PostMoveNodeResponse::NotFound));
+        return Ok((StatusCode::NOT_FOUND, Json(PostMoveNodeResponse::NotFound)));
     }
     let node = node.unwrap();
 
     if !state
         .node_repository
-        .has_access(node.id, current_user.id)
-        .expect("db error")
+        .has_access(node.id, current_user.id, SharePermission::View)?
     {
-        return (StatusCode::NOT_FOUND, Json(PostMoveNodeResponse::NotFound));
+        return Ok((StatusCode::NOT_FOUND, Json(PostMoveNodeResponse::NotFound)));
     }
 
-    let to_node = state
-        .node_repository
-        .get_node(payload.to_node_id)
-        .expect("db error");
+    let to_node = state.node_repository.get_node(payload.to_node_id)?;
 
-    let from_node = state
-        .node_repository
-        .get_node(node.parent_id.expect("node to be moved has no parent"))
-        .expect("db error");
+    // the root and trash nodes have no parent and cannot be moved
+    let from_node = match node.parent_id {
+        Some(parent_id) => state.node_repository.get_node(parent_id)?,
+        None => None,
+    };
 
     if to_node.is_none() || from_node.is_none() {
-        return (StatusCode::NOT_FOUND, Json(PostMoveNodeResponse::NotFound));
+        return Ok((StatusCode::NOT_FOUND, Json(PostMoveNodeResponse::NotFound)));
     }
 
     let (to_node, from_node) = (to_node.unwrap(), from_node.unwrap());
 
     if to_node.node_type != NodeType::Folder {
-        return (
+        return Ok((
             StatusCode::BAD_REQUEST,
             Json(PostMoveNodeResponse::BadRequest),
-        );
+        ));
     }
 
-    let access = [
+    let permissions = [
         state
             .node_repository
-            .has_access(to_node.id, current_user.id)
-            .expect("db error"),
+            .get_permission(to_node.id, current_user.id)?,
         state
             .node_repository
-            .has_access(from_node.id, current_user.id)
-            .expect("db error"),
+            .get_permission(from_node.id, current_user.id)?,
         state
             .node_repository
-            .has_access(node.id, current_user.id)
-            .expect("db error"),
+            .get_permission(node.id, current_user.id)?,
     ];
 
-    if access.iter().any(|x| !x) {
-        return (StatusCode::NOT_FOUND, Json(PostMoveNodeResponse::NotFound));
+    if permissions.iter().any(|x| x.is_none()) {
+        return Ok((StatusCode::NOT_FOUND, Json(PostMoveNodeResponse::NotFound)));
     }
 
-    if from_node.metadata_change_counter != payload.from_node_change_counter
-        || to_node.metadata_change_counter != payload.to_node_change_counter
+    if permissions
+        .iter()
+        .any(|x| x.is_some_and(|permission| permission < SharePermission::Edit))
     {
-        // TODO - This is not thread-safe
-        return (StatusCode::CONFLICT, Json(PostMoveNodeResponse::Conflict));
+        return Ok((StatusCode::FORBIDDEN, Json(PostMoveNodeResponse::Forbidden)));
     }
 
-    state
-        .node_repository
-        .move_node(
-            node_id,
-            from_node.id,
-            payload.from_node_metadata,
-            to_node.id,
-            payload.to_node_metadata,
-        )
-        .expect("db error");
-    (StatusCode::OK, Json(PostMoveNodeResponse::Ok))
+    match state.node_repository.move_node(
+        node_id,
+        MetadataUpdate {
+            node_id: from_node.id,
+            metadata: payload.from_node_metadata,
+            expected_counter: payload.from_node_change_counter,
+        },
+        MetadataUpdate {
+            node_id: to_node.id,
+            metadata: payload.to_node_metadata,
+            expected_counter: payload.to_node_change_counter,
+        },
+    ) {
+        Ok(_) => Ok((StatusCode::OK, Json(PostMoveNodeResponse::Ok))),
+        Err(e) if e.is::<ChangeCounterMismatch>() => {
+            Ok((StatusCode::CONFLICT, Json(PostMoveNodeResponse::Conflict)))
+        }
+        Err(e) => Err(e.into()),
+    }
 }
 
 pub async fn post_move_node_to_trash(
-    current_user: UserEntity,
+    ReadWriteUser(current_user): ReadWriteUser,
     State(state): State<AppState>,
     Path(node_id): Path<NodeId>,
     Json(payload): Json<PostMoveNodeToTrashRequest>,
-) -> (StatusCode, Json<PostMoveNodeToTrashResponse>) {
-    let node = state.node_repository.get_node(node_id).expect("db error");
+) -> Result<(StatusCode, Json<PostMoveNodeToTrashResponse>), ApiError> {
+    let node = state.node_repository.get_node(node_id)?;
 
     if node.is_none() {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostMoveNodeToTrashResponse::NotFound),
-        );
+        ));
     }
 
     let node = node.unwrap();
 
     if node.owner_id != current_user.id {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostMoveNodeToTrashResponse::NotFound),
-        );
+        ));
     }
 
-    let from_node = state
-        .node_repository
-        .get_node(node.parent_id.expect("node has no parent"))
-        .expect("db error");
+    // the root and trash nodes have no parent and cannot be moved
+    let from_node = match node.parent_id {
+        Some(parent_id) => state.node_repository.get_node(parent_id)?,
+        None => None,
+    };
 
-    let trash_node = state
-        .node_repository
-        .get_node(payload.to_node_id)
-        .expect("db error");
+    let trash_node = state.node_repository.get_node(payload.to_node_id)?;
 
     if from_node.is_none() || trash_node.is_none() {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostMoveNodeToTrashResponse::NotFound),
-        );
+        ));
     }
 
     let from_node = from_node.unwrap();
     let trash_node = trash_node.unwrap();
 
     if trash_node.node_type != NodeType::Folder {
-        return (
+        return Ok((
             StatusCode::BAD_REQUEST,
             Json(PostMoveNodeToTrashResponse::BadRequest),
-        );
-    }
-
-    if trash_node.metadata_change_counter != payload.to_node_change_counter
-        || from_node.metadata_change_counter != payload.from_node_change_counter
-    {
-        return (
-            StatusCode::CONFLICT,
-            Json(PostMoveNodeToTrashResponse::Conflict),
-        );
+        ));
     }
 
     if from_node.owner_id != current_user.id || trash_node.owner_id != current_user.id {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostMoveNodeToTrashResponse::NotFound),
-        );
+        ));
     }
 
     match state.node_repository.move_node_to_trash(
         node_id,
-        from_node.id,
-        payload.from_node_metadata,
-        trash_node.id,
-        payload.to_node_metadata,
+        MetadataUpdate {
+            node_id: from_node.id,
+            metadata: payload.from_node_metadata,
+            expected_counter: payload.from_node_change_counter,
+        },
+        MetadataUpdate {
+            node_id: trash_node.id,
+            metadata: payload.to_node_metadata,
+            expected_counter: payload.to_node_change_counter,
+        },
     ) {
-        Ok(_) => (StatusCode::OK, Json(PostMoveNodeToTrashResponse::Ok)),
-        Err(_) => (
+        Ok(_) => Ok((StatusCode::OK, Json(PostMoveNodeToTrashResponse::Ok))),
+        Err(_) => Ok((
             StatusCode::CONFLICT,
             Json(PostMoveNodeToTrashResponse::Conflict),
-        ),
+        )),
     }
 }
 
 pub async fn post_move_node_out_of_trash(
-    current_user: UserEntity,
+    ReadWriteUser(current_user): ReadWriteUser,
     State(state): State<AppState>,
     Path(node_id): Path<NodeId>,
     Json(payload): Json<PostMoveNodeOutOfTrashRequest>,
-) -> (StatusCode, Json<PostMoveNodeOutOfTrashResponse>) {
-    let node = state.node_repository.get_node(node_id).expect("db error");
+) -> Result<(StatusCode, Json<PostMoveNodeOutOfTrashResponse>), ApiError> {
+    let node = state.node_repository.get_node(node_id)?;
 
     if node.is_none() {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostMoveNodeOutOfTrashResponse::NotFound),
-        );
+        ));
     }
 
     let node = node.unwrap();
 
     if node.owner_id != current_user.id {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostMoveNodeOutOfTrashResponse::NotFound),
-        );
+        ));
     }
 
     if node.deleted_on.is_none() {
-        return (
+        return Ok((
             StatusCode::CONFLICT,
             Json(PostMoveNodeOutOfTrashResponse::Conflict),
-        );
+        ));
     }
 
-    let from_trash = state
-        .node_repository
-        .get_node(node.parent_id.expect("node has no parent"))
-        .expect("db error");
+    // the root and trash nodes have no parent and cannot be moved
+    let from_trash = match node.parent_id {
+        Some(parent_id) => state.node_repository.get_node(parent_id)?,
+        None => None,
+    };
 
-    let to_node = state
-        .node_repository
-        .get_node(payload.to_node_id)
-        .expect("db error");
+    let to_node = state.node_repository.get_node(payload.to_node_id)?;
 
     if from_trash.is_none() || to_node.is_none() {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostMoveNodeOutOfTrashResponse::NotFound),
-        );
+        ));
     }
 
     let from_trash = from_trash.unwrap();
     let to_node = to_node.unwrap();
 
     if from_trash.owner_id != current_user.id || to_node.owner_id != current_user.id {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostMoveNodeOutOfTrashResponse::NotFound),
-        );
-    }
-
-    if to_node.metadata_change_counter != payload.to_node_change_counter {
-        return (
-            StatusCode::CONFLICT,
-            Json(PostMoveNodeOutOfTrashResponse::Conflict),
-        );
-    }
-
-    if from_trash.metadata_change_counter != payload.from_node_change_counter {
-        return (
-            StatusCode::CONFLICT,
-            Json(PostMoveNodeOutOfTrashResponse::Conflict),
-        );
+        ));
     }
 
     match state.node_repository.move_node_out_of_trash(
         node_id,
-        from_trash.id,
-        payload.from_node_metadata,
-        to_node.id,
-        payload.to_node_metadata,
+        MetadataUpdate {
+            node_id: from_trash.id,
+            metadata: payload.from_node_metadata,
+            expected_counter: payload.from_node_change_counter,
+        },
+        MetadataUpdate {
+            node_id: to_node.id,
+            metadata: payload.to_node_metadata,
+            expected_counter: payload.to_node_change_counter,
+        },
     ) {
-        Ok(_) => (StatusCode::OK, Json(PostMoveNodeOutOfTrashResponse::Ok)),
-        Err(_) => panic!("db error"),
+        Ok(_) => Ok((StatusCode::OK, Json(PostMoveNodeOutOfTrashResponse::Ok))),
+        Err(e) if e.is::<ChangeCounterMismatch>() => Ok((
+            StatusCode::CONFLICT,
+            Json(PostMoveNodeOutOfTrashResponse::Conflict),
+        )),
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -377,88 +370,90 @@ pub async fn get_node_children(
     current_user: UserEntity,
     State(state): State<AppState>,
     Path(parent_id): Path<NodeId>,
-) -> (StatusCode, Json<GetNodeChildrenResponse>) {
-    let node = state.node_repository.get_node(parent_id).expect("db error");
+) -> Result<(StatusCode, Json<GetNodeChildrenResponse>), ApiError> {
+    let node = state.node_repository.get_node(parent_id)?;
 
     if node.as_ref().is_none() {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(GetNodeChildrenResponse::NotFound),
-        );
+        ));
     }
 
     let node = node.unwrap();
 
     if !state
         .node_repository
-        .has_access(node.id, current_user.id)
-        .expect("db error")
+        .has_access(node.id, current_user.id, SharePermission::View)?
     {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(GetNodeChildrenResponse::NotFound),
-        );
+        ));
     }
 
     if node.node_type != NodeType::Folder {
-        return (
+        return Ok((
             StatusCode::BAD_REQUEST,
             Json(GetNodeChildrenResponse::BadRequest),
-        );
+        ));
     }
 
-    let children = state.node_repository.get_children(parent_id).unwrap();
+    // dropped files cannot be decrypted before the owner adopted them
+    let dropped_files: HashSet<NodeId> = state
+        .file_drop_repository
+        .get_dropped_files(parent_id)?
+        .into_iter()
+        .map(|dropped_file| dropped_file.node_id)
+        .collect();
 
-    let children = children
-        .iter()
-        .map(|entity| entity_to_encrypted_node(entity.clone(), &state).unwrap());
+    let children = state
+        .node_repository
+        .get_children(parent_id)?
+        .into_iter()
+        .filter(|entity| !dropped_files.contains(&entity.id))
+        .map(|entity| entity_to_encrypted_node(entity, &state))
+        .collect::<anyhow::Result<Vec<_>>>()?;
 
-    (
-        StatusCode::OK,
-        Json(GetNodeChildrenResponse::Ok(children.collect())),
-    )
+    Ok((StatusCode::OK, Json(GetNodeChildrenResponse::Ok(children))))
 }
 
 pub async fn get_accessible_path(
     current_user: UserEntity,
     State(state): State<AppState>,
     Path(node_id): Path<NodeId>,
-) -> (StatusCode, Json<GetAccessiblePathResponse>) {
+) -> Result<(StatusCode, Json<GetAccessiblePathResponse>), ApiError> {
     if !state
         .node_repository
-        .has_access(node_id, current_user.id)
-        .expect("db error")
+        .has_access(node_id, current_user.id, SharePermission::View)?
     {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(GetAccessiblePathResponse::NotFound),
-        );
+        ));
     }
 
-    let path = state
-        .node_repository
-        .get_path_to_root(node_id)
-        .expect("db error");
+    let path = state.node_repository.get_path_to_root(node_id)?;
     let mut path_list = VecDeque::from(path);
 
     // there is always at least one element as we checked that the user has access to the last node so this cannot panic
-    while !state
-        .node_repository
-        .has_access(path_list[0].id, current_user.id)
-        .expect("db error")
-    {
+    while !state.node_repository.has_access(
+        path_list[0].id,
+        current_user.id,
+        SharePermission::View,
+    )? {
         path_list.pop_front();
     }
 
-    let encrypted_node_path: Vec<EncryptedNode> = path_list
-        .iter()
-        .map(|entity| entity_to_encrypted_node(entity.clone(), &state).unwrap())
-        .collect();
+    let encrypted_node_path = path_list
+        .into_iter()
+        .map(|entity| entity_to_encrypted_node(entity, &state))
+        .collect::<anyhow::Result<Vec<EncryptedNode>>>()?;
 
-    (
+    Ok((
         StatusCode::OK,
         Json(GetAccessiblePathResponse::Ok(encrypted_node_path)),
-    )
+    ))
 }
 
 pub fn entity_to_encrypted_node(
@@ -470,7 +465,9 @@ pub fn entity_to_encrypted_node(
             let entity = state
                 .revision_repository
                 .get_revision(id)?
-                .expect("data is not consistent");
+                // mapped to a not found response by the request handlers
+                .ok_or(diesel::result::Error::NotFound)
+                .with_context(|| format!("Revision {id} does not exist"))?;
             Some(entity_to_file_revision(entity))
         }
         None => None,
@@ -0,0 +1,246 @@
+//! Public download links allow downloading a single file without an account. The key to decrypt
+//! the metadata of the file is part of the url fragment and never sent to the server, just like
+//! for share links. Except for creating and revoking a link, the routes do not authenticate the
+//! user.
+
+use crate::http::{ApiError, AppState};
+use crate::request_handler::chunk::chunk_response;
+use crate::request_handler::node::entity_to_file_revision;
+use crate::storage::node::persistence::model::node_entity::NodeEntity;
+use crate::storage::share::PublicLinkEntity;
+use crate::user::auth::ReadWriteUser;
+use axum::Json;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, Response, StatusCode};
+use axum::response::IntoResponse;
+use chrono::Utc;
+use crabdrive_common::payloads::node::request::public_link::PostPublicLinkRequest;
+use crabdrive_common::payloads::node::response::public_link::{
+    DeletePublicLinkResponse, GetPublicLinkResponse, GetPublicVersionsResponse,
+    PostPublicLinkResponse, PublicNode,
+};
+use crabdrive_common::storage::{ChunkIndex, NodeId, NodeType, PublicLinkId, RevisionId};
+use crabdrive_common::storage::{FileRevision, SharePermission};
+
+pub async fn post_public_link(
+    ReadWriteUser(current_user): ReadWriteUser,
+    State(state): State<AppState>,
+    Path(node_id): Path<NodeId>,
+    Json(payload): Json<PostPublicLinkRequest>,
+) -> Result<(StatusCode, Json<PostPublicLinkResponse>), ApiError> {
+    let Some(node) = state.node_repository.get_node(node_id)? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(PostPublicLinkResponse::NotFound),
+        ));
+    };
+
+    let Some(permission) = state
+        .node_repository
+        .get_permission(node.id, current_user.id)?
+    else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(PostPublicLinkResponse::NotFound),
+        ));
+    };
+
+    if permission < SharePermission::Manage {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostPublicLinkResponse::BadRequest(
+                "cannot create a public link for a file that you do not own or manage".to_string(),
+            )),
+        ));
+    }
+
+    if node.node_type != NodeType::File {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostPublicLinkResponse::BadRequest(
+                "public links can only be created for files".to_string(),
+            )),
+        ));
+    }
+
+    if node.deleted_on.is_some() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostPublicLinkResponse::BadRequest(
+                "Cannot share a node that is in the trash".to_string(),
+            )),
+        ));
+    }
+
+    if payload
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now().naive_utc())
+    {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostPublicLinkResponse::BadRequest(
+                "expires_at must be in the future".to_string(),
+            )),
+        ));
+    }
+
+    let public_link = state.public_link_repository.create_public_link(
+        node.id,
+        current_user.id,
+        payload.wrapped_metadata_key,
+        payload.expires_at,
+    )?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PostPublicLinkResponse::Ok(public_link.id)),
+    ))
+}
+
+/// Revoke a public link of a node you own or manage
+pub async fn delete_public_link(
+    ReadWriteUser(current_user): ReadWriteUser,
+    State(state): State<AppState>,
+    Path(link_id): Path<PublicLinkId>,
+) -> Result<(StatusCode, Json<DeletePublicLinkResponse>), ApiError> {
+    let Some(public_link) = state.public_link_repository.get_public_link(link_id)? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(DeletePublicLinkResponse::NotFound),
+        ));
+    };
+
+    if !state.node_repository.has_access(
+        public_link.node_id,
+        current_user.id,
+        SharePermission::Manage,
+    )? {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(DeletePublicLinkResponse::BadRequest(
+                "cannot revoke a public link of a file that you do not own or manage".to_string(),
+            )),
+        ));
+    }
+
+    state
+        .public_link_repository
+        .delete_public_link(public_link.id)?;
+
+    Ok((StatusCode::OK, Json(DeletePublicLinkResponse::Ok)))
+}
+
+/// Get the public link and the node it belongs to. Returns `None` if the link does not exist, has
+/// expired or if the node was moved to the trash.
+fn get_public_link_node(
+    state: &AppState,
+    link_id: PublicLinkId,
+) -> Result<Option<(PublicLinkEntity, NodeEntity)>, ApiError> {
+    let Some(public_link) = state.public_link_repository.get_public_link(link_id)? else {
+        return Ok(None);
+    };
+
+    if public_link.is_expired(Utc::now().naive_utc()) {
+        return Ok(None);
+    }
+
+    // the link stops working while the file (or one of its parents) is in the trash
+    let path = state
+        .node_repository
+        .get_path_to_root(public_link.node_id)?;
+    if path.iter().any(|node| node.deleted_on.is_some()) {
+        return Ok(None);
+    }
+
+    let node = path.into_iter().find(|node| node.id == public_link.node_id);
+
+    Ok(node.map(|node| (public_link, node)))
+}
+
+pub async fn get_public_link(
+    State(state): State<AppState>,
+    Path(link_id): Path<PublicLinkId>,
+) -> Result<(StatusCode, Json<GetPublicLinkResponse>), ApiError> {
+    let expired = state
+        .public_link_repository
+        .get_public_link(link_id)?
+        .is_some_and(|public_link| public_link.is_expired(Utc::now().naive_utc()));
+
+    if expired {
+        return Ok((StatusCode::GONE, Json(GetPublicLinkResponse::Expired)));
+    }
+
+    let Some((public_link, node)) = get_public_link_node(&state, link_id)? else {
+        return Ok((StatusCode::NOT_FOUND, Json(GetPublicLinkResponse::NotFound)));
+    };
+
+    let current_revision = match node.current_revision {
+        Some(revision_id) => {
+            let revision = state
+                .revision_repository
+                .get_revision(revision_id)?
+                .ok_or_else(|| {
+                    ApiError::NotFound(format!("Revision {revision_id} does not exist"))
+                })?;
+            Some(entity_to_file_revision(revision))
+        }
+        None => None,
+    };
+
+    let public_node = PublicNode {
+        id: node.id,
+        encrypted_metadata: node.metadata,
+        current_revision,
+        wrapped_metadata_key: public_link.wrapped_metadata_key,
+    };
+
+    Ok((StatusCode::OK, Json(GetPublicLinkResponse::Ok(public_node))))
+}
+
+pub async fn get_public_versions(
+    State(state): State<AppState>,
+    Path(link_id): Path<PublicLinkId>,
+) -> Result<(StatusCode, Json<GetPublicVersionsResponse>), ApiError> {
+    let Some((_, node)) = get_public_link_node(&state, link_id)? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(GetPublicVersionsResponse::NotFound),
+        ));
+    };
+
+    let versions: Vec<FileRevision> = state
+        .revision_repository
+        .get_revision_history(node.id)?
+        .into_iter()
+        .map(entity_to_file_revision)
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(GetPublicVersionsResponse::Ok(versions)),
+    ))
+}
+
+pub async fn get_public_chunk(
+    State(state): State<AppState>,
+    Path((link_id, revision_id, chunk_index)): Path<(PublicLinkId, RevisionId, ChunkIndex)>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, ApiError> {
+    let Some((_, node)) = get_public_link_node(&state, link_id)? else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    let Some(revision) = state.revision_repository.get_revision(revision_id)? else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    // only committed revisions of the linked file can be downloaded
+    if revision.file_id != node.id || revision.upload_ended_on.is_none() {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+
+    let contents = state.vfs.open_chunk(&revision_id, chunk_index).await?;
+
+    Ok(chunk_response(revision_id, chunk_index, contents, &headers).await?)
+}
@@ -1,146 +1,399 @@
-use crate::http::AppState;
+use crate::http::{ApiError, AppState};
 use crate::request_handler::node::entity_to_encrypted_node;
+use crate::storage::node::NodeEntity;
+use crate::user::auth::ReadWriteUser;
 use crate::user::persistence::model::user_entity::UserEntity;
+use anyhow::anyhow;
 use axum::Json;
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use crabdrive_common::encryption_key::EncryptionKey;
 use crabdrive_common::payloads::node::request::share::{
-    PostAcceptShareRequest, PostShareNodeRequest,
+    GetOutgoingSharesQuery, PostAcceptShareRequest, PostShareNodeRequest,
+    PostShareNodeWithUserRequest,
 };
 use crabdrive_common::payloads::node::response::share::{
-    GetAcceptShareInfoResponse, GetAcceptedSharedResponse, GetNodeShareInfo,
-    PostAcceptShareResponse, PostShareNodeResponse, ShareEncryptionInfo,
+    DeleteShareResponse, GetAcceptShareInfoResponse, GetAcceptedSharedResponse, GetNodeShareInfo,
+    GetOutgoingSharesResponse, GetShareInboxResponse, GetUserPublicKeyResponse, OutgoingShare,
+    PostAcceptShareResponse, PostShareNodeResponse, ShareEncryptionInfo, ShareOffer, ShareUser,
 };
-use crabdrive_common::storage::{EncryptedNode, NodeId, ShareId};
+use crabdrive_common::storage::{EncryptedNode, NodeId, ShareId, SharePermission};
+use crabdrive_common::user::UserId;
 use tracing::error;
 
-pub async fn post_share_node(
-    current_user: UserEntity,
-    State(state): State<AppState>,
-    Path(node_id): Path<NodeId>,
-    Json(payload): Json<PostShareNodeRequest>,
-) -> (StatusCode, Json<PostShareNodeResponse>) {
-    let node = state.node_repository.get_node(node_id).expect("db error");
+/// Check whether the current user may share the node. Returns the node, if the user is allowed
+/// to share it, otherwise the response for the client.
+fn check_can_share(
+    state: &AppState,
+    current_user: &UserEntity,
+    node_id: NodeId,
+    expires_at: Option<NaiveDateTime>,
+) -> Result<Result<NodeEntity, (StatusCode, Json<PostShareNodeResponse>)>, ApiError> {
+    let node = state.node_repository.get_node(node_id)?;
 
     if node.is_none() {
-        return (StatusCode::NOT_FOUND, Json(PostShareNodeResponse::NotFound));
+        return Ok(Err((
+            StatusCode::NOT_FOUND,
+            Json(PostShareNodeResponse::NotFound),
+        )));
     }
 
     let node = node.unwrap();
 
-    if node.owner_id != current_user.id {
-        return (
+    // owners and users with the manage permission are allowed to share a node
+    if !state
+        .node_repository
+        .has_access(node.id, current_user.id, SharePermission::Manage)?
+    {
+        return Ok(Err((
             StatusCode::BAD_REQUEST,
             Json(PostShareNodeResponse::BadRequest(
-                "cannot share a file that you do not own".to_string(),
+                "cannot share a file that you do not own or manage".to_string(),
             )),
-        );
+        )));
     }
 
     if node.parent_id.is_none() {
-        return (
+        return Ok(Err((
             StatusCode::BAD_REQUEST,
             Json(PostShareNodeResponse::BadRequest(
                 "Cannot share a root node".to_string(),
             )),
-        );
+        )));
     }
 
     if node.deleted_on.is_some() {
-        return (
+        return Ok(Err((
             StatusCode::BAD_REQUEST,
             Json(PostShareNodeResponse::BadRequest(
                 "Cannot share a node that is in the trash".to_string(),
             )),
-        );
+        )));
     }
 
-    let share_entity = state
-        .share_repository
-        .create_share(
-            node.id,
-            current_user.id,
-            payload.wrapped_metadata_key.clone(),
-        )
-        .expect("db error");
+    if expires_at.is_some_and(|expires_at| expires_at <= Utc::now().naive_utc()) {
+        return Ok(Err((
+            StatusCode::BAD_REQUEST,
+            Json(PostShareNodeResponse::BadRequest(
+                "expires_at must be in the future".to_string(),
+            )),
+        )));
+    }
+
+    Ok(Ok(node))
+}
+
+pub async fn post_share_node(
+    ReadWriteUser(current_user): ReadWriteUser,
+    State(state): State<AppState>,
+    Path(node_id): Path<NodeId>,
+    Json(payload): Json<PostShareNodeRequest>,
+) -> Result<(StatusCode, Json<PostShareNodeResponse>), ApiError> {
+    let node = match check_can_share(&state, &current_user, node_id, payload.expires_at)? {
+        Ok(node) => node,
+        Err(response) => return Ok(response),
+    };
+
+    let share_entity = state.share_repository.create_share(
+        node.id,
+        current_user.id,
+        payload.wrapped_metadata_key.clone(),
+        payload.permission,
+        payload.expires_at,
+        payload.revoke_on_expiry,
+    )?;
 
-    (
+    Ok((
         StatusCode::OK,
         Json(PostShareNodeResponse::Ok(share_entity.id)),
-    )
+    ))
 }
 
-pub async fn get_accept_share_info(
+/// Share a node with a user directly. The share shows up in the inbox of the user and can only be
+/// accepted by them.
+pub async fn post_share_node_with_user(
+    ReadWriteUser(current_user): ReadWriteUser,
+    State(state): State<AppState>,
+    Path(node_id): Path<NodeId>,
+    Json(payload): Json<PostShareNodeWithUserRequest>,
+) -> Result<(StatusCode, Json<PostShareNodeResponse>), ApiError> {
+    let node = match check_can_share(&state, &current_user, node_id, payload.expires_at)? {
+        Ok(node) => node,
+        Err(response) => return Ok(response),
+    };
+
+    let Some(recipient) = state
+        .user_repository
+        .get_user_by_username(&payload.username)?
+    else {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostShareNodeResponse::BadRequest(
+                "the user does not exist".to_string(),
+            )),
+        ));
+    };
+
+    if recipient.id == current_user.id {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostShareNodeResponse::BadRequest(
+                "cannot share a node with yourself".to_string(),
+            )),
+        ));
+    }
+
+    // the share key could not have been encrypted for the user
+    if recipient.public_key.is_empty() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostShareNodeResponse::BadRequest(
+                "the user cannot receive shares yet".to_string(),
+            )),
+        ));
+    }
+
+    if state
+        .node_repository
+        .has_access(node.id, recipient.id, payload.permission)?
+    {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(PostShareNodeResponse::BadRequest(
+                "the user can already access the node".to_string(),
+            )),
+        ));
+    }
+
+    let share_entity = state.share_repository.create_direct_share(
+        node.id,
+        current_user.id,
+        (recipient.id, payload.encrypted_share_key),
+        payload.wrapped_metadata_key,
+        payload.permission,
+        (payload.expires_at, payload.revoke_on_expiry),
+    )?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PostShareNodeResponse::Ok(share_entity.id)),
+    ))
+}
+
+pub async fn get_user_public_key(
     _current_user: UserEntity,
     State(state): State<AppState>,
-    Path(share_id): Path<ShareId>,
-) -> (StatusCode, Json<GetAcceptShareInfoResponse>) {
-    let share_entity = state
+    Path(username): Path<String>,
+) -> Result<(StatusCode, Json<GetUserPublicKeyResponse>), ApiError> {
+    let user = state.user_repository.get_user_by_username(&username)?;
+
+    match user {
+        Some(user) if !user.public_key.is_empty() => Ok((
+            StatusCode::OK,
+            Json(GetUserPublicKeyResponse::Ok(user.public_key)),
+        )),
+        _ => Ok((
+            StatusCode::NOT_FOUND,
+            Json(GetUserPublicKeyResponse::NotFound),
+        )),
+    }
+}
+
+/// Get all shares, which were sent directly to the current user and are waiting to be accepted
+pub async fn get_share_inbox(
+    current_user: UserEntity,
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<GetShareInboxResponse>), ApiError> {
+    let pending_shares = state
         .share_repository
-        .get_share(share_id)
-        .expect("db error");
+        .get_pending_shares_for_user(current_user.id)?;
+
+    let offers = pending_shares
+        .into_iter()
+        .map(|share_entity| {
+            let node = state
+                .node_repository
+                .get_node(share_entity.node_id)?
+                .ok_or_else(|| {
+                    ApiError::NotFound(format!("Node {} does not exist", share_entity.node_id))
+                })?;
+            let shared_by = state
+                .user_repository
+                .get_user(share_entity.shared_by)?
+                .ok_or_else(|| {
+                    ApiError::NotFound(format!("User {} does not exist", share_entity.shared_by))
+                })?;
+
+            let wrapped_metadata_key = share_entity.shared_encryption_key.ok_or_else(|| {
+                anyhow!(
+                    "Pending share {} is missing its encryption key",
+                    share_entity.id
+                )
+            })?;
+            let encrypted_share_key = share_entity.encrypted_share_key.ok_or_else(|| {
+                anyhow!(
+                    "Direct share {} is missing its encrypted key",
+                    share_entity.id
+                )
+            })?;
+
+            Ok(ShareOffer {
+                share_id: share_entity.id,
+                node_id: node.id,
+                shared_by: shared_by.username,
+                time_shared: share_entity.time_shared,
+                permission: share_entity.permission,
+                expires_at: share_entity.expires_at,
+                wrapped_metadata_key,
+                encrypted_share_key,
+                encrypted_metadata: node.metadata,
+            })
+        })
+        .collect::<Result<_, ApiError>>()?;
+
+    Ok((StatusCode::OK, Json(GetShareInboxResponse::Ok(offers))))
+}
+
+fn get_share_user(state: &AppState, user_id: UserId) -> Result<ShareUser, ApiError> {
+    let user = state
+        .user_repository
+        .get_user(user_id)?
+        .ok_or_else(|| ApiError::NotFound(format!("User {user_id} does not exist")))?;
+    Ok(ShareUser {
+        id: user.id,
+        username: user.username,
+    })
+}
+
+/// Get all shares created by the current user, including pending and expired ones
+pub async fn get_outgoing_shares(
+    current_user: UserEntity,
+    State(state): State<AppState>,
+    Query(query): Query<GetOutgoingSharesQuery>,
+) -> Result<(StatusCode, Json<GetOutgoingSharesResponse>), ApiError> {
+    let share_entities = state
+        .share_repository
+        .get_shares_created_by_user(current_user.id, query.node_id)?;
+
+    let shares = share_entities
+        .into_iter()
+        .map(|share_entity| {
+            Ok(OutgoingShare {
+                share_id: share_entity.id,
+                node_id: share_entity.node_id,
+                time_shared: share_entity.time_shared,
+                permission: share_entity.permission,
+                expires_at: share_entity.expires_at,
+                revoke_on_expiry: share_entity.revoke_on_expiry,
+                shared_with: share_entity
+                    .shared_with
+                    .map(|user_id| get_share_user(&state, user_id))
+                    .transpose()?,
+                pending: share_entity.accepted_by.is_none(),
+                accepted_by: share_entity
+                    .accepted_by
+                    .map(|user_id| get_share_user(&state, user_id))
+                    .transpose()?,
+                time_accepted: share_entity.time_accepted,
+            })
+        })
+        .collect::<Result<_, ApiError>>()?;
+
+    Ok((StatusCode::OK, Json(GetOutgoingSharesResponse::Ok(shares))))
+}
+
+/// Decline a share in the inbox of the current user
+pub async fn delete_inbox_share(
+    ReadWriteUser(current_user): ReadWriteUser,
+    State(state): State<AppState>,
+    Path(share_id): Path<ShareId>,
+) -> Result<(StatusCode, Json<DeleteShareResponse>), ApiError> {
+    let Some(share_entity) = state.share_repository.get_share(share_id)? else {
+        return Ok((StatusCode::NOT_FOUND, Json(DeleteShareResponse::NotFound)));
+    };
+
+    if share_entity.shared_with != Some(current_user.id) || share_entity.accepted_by.is_some() {
+        return Ok((StatusCode::NOT_FOUND, Json(DeleteShareResponse::NotFound)));
+    }
+
+    state.share_repository.delete_share(share_entity.id)?;
+
+    Ok((StatusCode::OK, Json(DeleteShareResponse::Ok)))
+}
+
+pub async fn get_accept_share_info(
+    current_user: UserEntity,
+    State(state): State<AppState>,
+    Path(share_id): Path<ShareId>,
+) -> Result<(StatusCode, Json<GetAcceptShareInfoResponse>), ApiError> {
+    let share_entity = state.share_repository.get_share(share_id)?;
 
     if share_entity.is_none() {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(GetAcceptShareInfoResponse::NotFound),
-        );
+        ));
     }
 
     let share_entity = share_entity.unwrap();
 
-    if share_entity.accepted_by.is_some() {
-        return (
+    if share_entity.accepted_by.is_some() || !share_entity.is_addressed_to(current_user.id) {
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(GetAcceptShareInfoResponse::NotFound),
-        );
+        ));
+    }
+
+    if share_entity.is_expired(Utc::now().naive_utc()) {
+        return Ok((StatusCode::GONE, Json(GetAcceptShareInfoResponse::Expired)));
     }
 
+    let wrapped_metadata_key = share_entity.shared_encryption_key.ok_or_else(|| {
+        anyhow!(
+            "Pending share {} is missing its encryption key",
+            share_entity.id
+        )
+    })?;
     let response = GetAcceptShareInfoResponse::Ok(ShareEncryptionInfo {
         node_id: share_entity.node_id,
-        wrapped_metadata_key: share_entity
-            .shared_encryption_key
-            .expect("share entity that has not been accepted is missing encryption key"),
+        wrapped_metadata_key,
     });
 
-    (StatusCode::OK, Json(response))
+    Ok((StatusCode::OK, Json(response)))
 }
 
 pub async fn get_node_share_info(
     current_user: UserEntity,
     State(state): State<AppState>,
     Path(node_id): Path<NodeId>,
-) -> (StatusCode, Json<GetNodeShareInfo>) {
+) -> Result<(StatusCode, Json<GetNodeShareInfo>), ApiError> {
     if !state
         .node_repository
-        .has_access(node_id, current_user.id)
-        .expect("db error")
+        .has_access(node_id, current_user.id, SharePermission::View)?
     {
-        return (StatusCode::NOT_FOUND, Json(GetNodeShareInfo::NotFound));
+        return Ok((StatusCode::NOT_FOUND, Json(GetNodeShareInfo::NotFound)));
     }
 
     let Some(share_entity) = state
         .share_repository
-        .get_share_by_node_id_and_accepted_user_id(node_id, current_user.id)
-        .expect("db error")
+        .get_share_by_node_id_and_accepted_user_id(node_id, current_user.id)?
     else {
-        return (StatusCode::NOT_FOUND, Json(GetNodeShareInfo::NotFound));
+        return Ok((StatusCode::NOT_FOUND, Json(GetNodeShareInfo::NotFound)));
     };
 
     let Some(wrapped_key) = share_entity.accepted_encryption_key else {
         error!("A user has accepted a share but there is not key in the db???????");
-        return (StatusCode::NOT_FOUND, Json(GetNodeShareInfo::NotFound));
+        return Ok((StatusCode::NOT_FOUND, Json(GetNodeShareInfo::NotFound)));
     };
 
-    (
+    Ok((
         StatusCode::OK,
         Json(GetNodeShareInfo::Ok(ShareEncryptionInfo {
             node_id,
             wrapped_metadata_key: wrapped_key,
         })),
-    )
+    ))
 }
 
 pub async fn post_accept_share(
@@ -148,83 +401,165 @@ pub async fn post_accept_share(
     State(state): State<AppState>,
     Path(share_id): Path<ShareId>,
     Json(payload): Json<PostAcceptShareRequest>,
-) -> (StatusCode, Json<PostAcceptShareResponse>) {
-    let Some(mut share_entity) = state
-        .share_repository
-        .get_share(share_id)
-        .expect("db error")
-    else {
-        return (
+) -> Result<(StatusCode, Json<PostAcceptShareResponse>), ApiError> {
+    let Some(mut share_entity) = state.share_repository.get_share(share_id)? else {
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostAcceptShareResponse::NotFound),
-        );
+        ));
     };
 
-    // cannot accept a share that is already accessible (owned/ access to parent)
-    if state
-        .node_repository
-        .has_access(share_entity.node_id, current_user.id)
-        .expect("db error")
-    {
-        return (
+    if !share_entity.is_addressed_to(current_user.id) {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(PostAcceptShareResponse::NotFound),
+        ));
+    }
+
+    // cannot accept a share that is already accessible (owned/ access to parent), unless the share
+    // grants a higher permission
+    if state.node_repository.has_access(
+        share_entity.node_id,
+        current_user.id,
+        share_entity.permission,
+    )? {
+        return Ok((
             StatusCode::BAD_REQUEST,
             Json(PostAcceptShareResponse::BadRequest(
                 "share is already accessible".to_string(),
             )),
-        );
+        ));
     }
 
     if share_entity.accepted_by.is_some() {
-        return (
+        return Ok((
             StatusCode::NOT_FOUND,
             Json(PostAcceptShareResponse::NotFound),
-        );
+        ));
+    }
+
+    let now = Utc::now().naive_utc();
+
+    if share_entity.is_expired(now) {
+        return Ok((StatusCode::GONE, Json(PostAcceptShareResponse::Expired)));
     }
 
     share_entity.accepted_by = Some(current_user.id);
-    share_entity.time_accepted = Some(Utc::now().naive_utc());
+    share_entity.time_accepted = Some(now);
     share_entity.accepted_encryption_key = Some(payload.new_wrapped_metadata_key);
 
-    // this key is not required anymore and should be deleted
+    // these keys are not required anymore and should be deleted
     share_entity.shared_encryption_key = None;
+    share_entity.encrypted_share_key = None;
 
-    state
-        .share_repository
-        .update_share(share_entity)
-        .expect("db error");
+    state.share_repository.update_share(share_entity)?;
 
-    (StatusCode::OK, Json(PostAcceptShareResponse::Ok))
+    Ok((StatusCode::OK, Json(PostAcceptShareResponse::Ok)))
 }
 
 pub async fn get_accepted_shared_nodes(
     current_user: UserEntity,
     State(state): State<AppState>,
-) -> (StatusCode, Json<GetAcceptedSharedResponse>) {
+) -> Result<(StatusCode, Json<GetAcceptedSharedResponse>), ApiError> {
     let accepted_shares = state
         .share_repository
-        .get_accepted_shares_by_user(current_user.id)
-        .expect("db error");
+        .get_accepted_shares_by_user(current_user.id)?;
 
     let nodes: Vec<(EncryptionKey, EncryptedNode)> = accepted_shares
         .iter()
         .map(|share_entity| {
             let node = state
                 .node_repository
-                .get_node(share_entity.node_id)
-                .expect("db error");
-            if node.is_none() {
-                unreachable!("violating db constraints");
-            }
+                .get_node(share_entity.node_id)?
+                .ok_or_else(|| {
+                    ApiError::NotFound(format!("Node {} does not exist", share_entity.node_id))
+                })?;
 
-            if share_entity.accepted_encryption_key.is_none() {
-                unreachable!("a node is marked as accepted but there is no key")
-            }
+            let accepted_encryption_key =
+                share_entity
+                    .accepted_encryption_key
+                    .clone()
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Accepted share {} is missing its encryption key",
+                            share_entity.id
+                        )
+                    })?;
 
-            let node = entity_to_encrypted_node(node.unwrap(), &state).expect("db error");
+            let node = entity_to_encrypted_node(node, &state)?;
 
-            (share_entity.accepted_encryption_key.clone().unwrap(), node)
+            Ok((accepted_encryption_key, node))
         })
-        .collect();
+        .collect::<Result<_, ApiError>>()?;
+
+    Ok((StatusCode::OK, Json(GetAcceptedSharedResponse::Ok(nodes))))
+}
+
+/// Revoke a share of a node you own or manage. Pending share links can no longer be accepted and the user,
+/// who accepted the share, loses access.
+pub async fn delete_share(
+    ReadWriteUser(current_user): ReadWriteUser,
+    State(state): State<AppState>,
+    Path(share_id): Path<ShareId>,
+) -> Result<(StatusCode, Json<DeleteShareResponse>), ApiError> {
+    let Some(share_entity) = state.share_repository.get_share(share_id)? else {
+        return Ok((StatusCode::NOT_FOUND, Json(DeleteShareResponse::NotFound)));
+    };
+
+    let node = state
+        .node_repository
+        .get_node(share_entity.node_id)?
+        .ok_or_else(|| {
+            ApiError::NotFound(format!("Node {} does not exist", share_entity.node_id))
+        })?;
+
+    if !state
+        .node_repository
+        .has_access(node.id, current_user.id, SharePermission::Manage)?
+    {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(DeleteShareResponse::BadRequest(
+                "cannot revoke a share of a node that you do not own or manage".to_string(),
+            )),
+        ));
+    }
+
+    state.share_repository.delete_share(share_entity.id)?;
+
+    Ok((StatusCode::OK, Json(DeleteShareResponse::Ok)))
+}
+
+/// Remove the access of a user, who accepted a share of a node you own or manage
+pub async fn delete_share_user(
+    ReadWriteUser(current_user): ReadWriteUser,
+    State(state): State<AppState>,
+    Path((node_id, user_id)): Path<(NodeId, UserId)>,
+) -> Result<(StatusCode, Json<DeleteShareResponse>), ApiError> {
+    let Some(node) = state.node_repository.get_node(node_id)? else {
+        return Ok((StatusCode::NOT_FOUND, Json(DeleteShareResponse::NotFound)));
+    };
+
+    if !state
+        .node_repository
+        .has_access(node.id, current_user.id, SharePermission::Manage)?
+    {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(DeleteShareResponse::BadRequest(
+                "cannot unshare a node that you do not own or manage".to_string(),
+            )),
+        ));
+    }
+
+    let Some(share_entity) = state
+        .share_repository
+        .get_share_by_node_id_and_accepted_user_id(node_id, user_id)?
+    else {
+        return Ok((StatusCode::NOT_FOUND, Json(DeleteShareResponse::NotFound)));
+    };
+
+    state.share_repository.delete_share(share_entity.id)?;
 
-    (StatusCode::OK, Json(GetAcceptedSharedResponse::Ok(nodes)))
+    Ok((StatusCode::OK, Json(DeleteShareResponse::Ok)))
 }
@@ -0,0 +1,164 @@
+use crate::db::operations::file_drop::increase_dropped_file_size;
+use crate::db::operations::node::{insert_node_into_parent, set_current_revision};
+use crate::db::operations::revision::{insert_revision, update_revision};
+use crate::db::operations::user::increase_storage_used;
+use crate::http::AppState;
+use crate::service::UnitOfWork;
+use crate::storage::node::{MetadataUpdate, NodeEntity};
+use crate::storage::revision::RevisionEntity;
+use crate::storage::vfs::{ChunkReader, FileSystemError};
+
+use crabdrive_common::data::DataAmount;
+use crabdrive_common::encrypted_metadata::EncryptedMetadata;
+use crabdrive_common::iv::IV;
+use crabdrive_common::storage::{ChunkIndex, NodeId, NodeType, RevisionId};
+use crabdrive_common::user::UserId;
+
+use anyhow::Result;
+use chrono::Utc;
+use thiserror::Error;
+
+/// A file and its first revision, which are about to be created
+pub struct NewFile {
+    pub id: NodeId,
+    pub metadata: EncryptedMetadata,
+    pub owner: UserId,
+    pub iv: IV,
+    pub chunk_count: ChunkIndex,
+}
+
+impl NewFile {
+    pub(crate) fn into_entities(self, parent_id: NodeId) -> (NodeEntity, RevisionEntity) {
+        let node = NodeEntity {
+            id: self.id,
+            parent_id: Some(parent_id),
+            owner_id: self.owner,
+            metadata: self.metadata,
+            deleted_on: None,
+            metadata_change_counter: 0,
+            current_revision: None,
+            node_type: NodeType::File,
+        };
+        let revision = new_revision(self.id, self.iv, self.chunk_count);
+        (node, revision)
+    }
+}
+
+fn new_revision(file_id: NodeId, iv: IV, chunk_count: ChunkIndex) -> RevisionEntity {
+    RevisionEntity {
+        id: RevisionId::random(),
+        file_id,
+        upload_started_on: Utc::now().naive_utc(),
+        upload_ended_on: None,
+        iv,
+        chunk_count,
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum StoreChunkError {
+    #[error("The storage limit of the owner would be exceeded")]
+    StorageLimitExceeded,
+
+    #[error(transparent)]
+    FileSystem(#[from] FileSystemError),
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Create a file with its first revision in a folder and replace the metadata of the folder. Fails
+/// with [`ChangeCounterMismatch`](crate::storage::node::ChangeCounterMismatch) if the folder was
+/// modified concurrently.
+pub async fn create_file(
+    state: &AppState,
+    parent: MetadataUpdate,
+    file: NewFile,
+) -> Result<(NodeEntity, RevisionEntity)> {
+    let (node, revision) = file.into_entities(parent.node_id);
+
+    let mut uow = UnitOfWork::begin(state);
+    uow.create_file(&revision.id).await?;
+
+    let (node, revision) = uow.execute(|conn| {
+        insert_node_into_parent(conn, &node, &parent)?;
+        let revision = insert_revision(conn, &revision)?;
+        let node = set_current_revision(conn, node.id, revision.id)?;
+        Ok((node, revision))
+    })?;
+
+    uow.commit().await?;
+    Ok((node, revision))
+}
+
+/// Create a new (unfinished) revision of a file
+pub async fn create_revision(
+    state: &AppState,
+    file_id: NodeId,
+    iv: IV,
+    chunk_count: ChunkIndex,
+) -> Result<RevisionEntity> {
+    let revision = new_revision(file_id, iv, chunk_count);
+
+    let mut uow = UnitOfWork::begin(state);
+    uow.create_file(&revision.id).await?;
+    let revision = uow.execute(|conn| insert_revision(conn, &revision))?;
+    uow.commit().await?;
+
+    Ok(revision)
+}
+
+/// Store a chunk of an unfinished revision and add its size to the storage used by the `owner` of
+/// the file. The chunk is written while it is received and may not exceed `max_size`.
+///
+/// For files uploaded through a file drop, the size is also recorded for the dropped file, so that
+/// it can be given back if the upload is abandoned.
+pub async fn store_chunk(
+    state: &AppState,
+    owner: UserId,
+    revision: &RevisionEntity,
+    index: ChunkIndex,
+    contents: ChunkReader,
+    max_size: DataAmount,
+) -> Result<(), StoreChunkError> {
+    let mut uow = UnitOfWork::begin(state);
+    let size = uow
+        .write_chunk(&revision.id, index, contents, max_size)
+        .await?;
+
+    let within_limit = uow.execute(|conn| {
+        if !increase_storage_used(conn, owner, size)? {
+            return Ok(false);
+        }
+        increase_dropped_file_size(conn, revision.file_id, size)?;
+        Ok(true)
+    })?;
+    if !within_limit {
+        uow.rollback().await;
+        return Err(StoreChunkError::StorageLimitExceeded);
+    }
+
+    uow.commit().await?;
+    Ok(())
+}
+
+/// Finish the upload of a revision and make it the active revision of its file
+pub async fn commit_revision(state: &AppState, revision: RevisionEntity) -> Result<NodeEntity> {
+    let mut uow = UnitOfWork::begin(state);
+    uow.persist_file(&revision.id).await?;
+
+    let node = uow.execute(|conn| {
+        update_revision(
+            conn,
+            &RevisionEntity {
+                upload_ended_on: Some(Utc::now().naive_utc()),
+                ..revision
+            },
+        )?;
+        set_current_revision(conn, revision.file_id, revision.id)
+    })?;
+
+    uow.commit().await?;
+
+    Ok(node)
+}
@@ -0,0 +1,101 @@
+use crate::db::operations::file_drop::{
+    delete_dropped_files, delete_uncommitted_dropped_file, insert_dropped_file,
+    select_stale_dropped_revisions,
+};
+use crate::db::operations::node::{compare_and_update_metadata, insert_node, set_current_revision};
+use crate::db::operations::revision::insert_revision;
+use crate::http::AppState;
+use crate::service::UnitOfWork;
+use crate::service::file::NewFile;
+use crate::storage::node::MetadataUpdate;
+use crate::storage::revision::RevisionEntity;
+use crate::storage::share::{DroppedFileEntity, FileDropEntity};
+
+use crabdrive_common::da;
+use crabdrive_common::storage::NodeId;
+
+use anyhow::{Context, Result};
+use chrono::{NaiveDateTime, TimeDelta};
+
+/// How long an upload through a file drop may take. Files, which are not committed within this
+/// time, are deleted by [`delete_stale_dropped_files`].
+pub const DROPPED_FILE_UPLOAD_TIMEOUT: TimeDelta = TimeDelta::days(1);
+
+/// Create a file with its first revision through a file drop. The metadata of the folder stays
+/// untouched, the owner adds the file to it when adopting it.
+pub async fn create_dropped_file(
+    state: &AppState,
+    file_drop: &FileDropEntity,
+    encrypted_metadata_key: Vec<u8>,
+    file: NewFile,
+) -> Result<RevisionEntity> {
+    let (node, revision) = file.into_entities(file_drop.node_id);
+    let dropped_file = DroppedFileEntity {
+        node_id: node.id,
+        file_drop_id: Some(file_drop.id),
+        encrypted_metadata_key,
+        uploaded_size: da!(0 B),
+    };
+
+    let mut uow = UnitOfWork::begin(state);
+    uow.create_file(&revision.id).await?;
+
+    let revision = uow.execute(|conn| {
+        insert_node(conn, &node)?;
+        insert_dropped_file(conn, &dropped_file)?;
+        let revision = insert_revision(conn, &revision)?;
+        set_current_revision(conn, node.id, revision.id)?;
+        Ok(revision)
+    })?;
+
+    uow.commit().await?;
+    Ok(revision)
+}
+
+/// Add dropped files to their folder, by replacing the metadata of the folder. Fails with
+/// [`ChangeCounterMismatch`](crate::storage::node::ChangeCounterMismatch) if the folder was
+/// modified concurrently.
+pub async fn adopt_dropped_files(
+    state: &AppState,
+    folder: MetadataUpdate,
+    node_ids: &[NodeId],
+) -> Result<()> {
+    let mut uow = UnitOfWork::begin(state);
+
+    uow.execute(|conn| {
+        compare_and_update_metadata(conn, &folder)?;
+        delete_dropped_files(conn, node_ids)
+    })?;
+
+    uow.commit().await
+}
+
+/// Delete dropped files, whose upload started before `started_before` and was never committed.
+/// The storage they used is given back to the owner of the folder. Returns the amount of deleted
+/// files.
+pub async fn delete_stale_dropped_files(
+    state: &AppState,
+    started_before: NaiveDateTime,
+) -> Result<usize> {
+    let revisions = {
+        let mut conn = state.db_pool.get().context("Failed to get db connection")?;
+        select_stale_dropped_revisions(&mut conn, started_before)?
+    };
+
+    let mut count = 0;
+    for revision in revisions {
+        let mut uow = UnitOfWork::begin(state);
+        // The upload may have been committed in the meantime
+        if !uow.execute(|conn| delete_uncommitted_dropped_file(conn, revision.id))? {
+            continue;
+        }
+        uow.commit().await?;
+        count += 1;
+
+        if let Err(e) = state.vfs.abort(&revision.id).await {
+            // Nothing in the database references the data anymore, so it only takes up space
+            tracing::warn!("Failed to delete the chunks of {}: {}", revision.id, e);
+        }
+    }
+    Ok(count)
+}
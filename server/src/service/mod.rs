@@ -0,0 +1,9 @@
+//! Operations, which span multiple repositories and the file system. Each of them runs in a
+//! [`UnitOfWork`], so that a failure partway through does not leave inconsistent data behind.
+
+pub mod file;
+pub mod file_drop;
+pub mod unit_of_work;
+pub mod user;
+
+pub use unit_of_work::UnitOfWork;
@@ -0,0 +1,197 @@
+use crate::db::connection::DbPool;
+use crate::http::AppState;
+use crate::storage::vfs::{ChunkReader, FileKey, FileRepository, FileSystemError};
+
+use crabdrive_common::data::DataAmount;
+use crabdrive_common::storage::ChunkIndex;
+
+use anyhow::{Context, Result, bail};
+use diesel::SqliteConnection;
+use diesel::connection::{AnsiTransactionManager, TransactionManager};
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use std::sync::Arc;
+
+type Vfs = Arc<dyn FileRepository + Send + Sync>;
+
+/// Undoes a change to the file system
+#[derive(Debug)]
+enum Compensation {
+    /// Remove a file from the staging area
+    AbortFile(FileKey),
+    /// Remove a chunk from a staged file
+    DeleteChunk(FileKey, ChunkIndex),
+    /// Remove a file from the permanent storage
+    DeleteFile(FileKey),
+}
+
+impl Compensation {
+    async fn run(self, vfs: &Vfs) {
+        let result = match &self {
+            Compensation::AbortFile(key) => vfs.abort(key).await,
+            Compensation::DeleteChunk(key, index) => vfs.delete_chunk(key, *index).await,
+            Compensation::DeleteFile(key) => vfs.delete_file(key).await,
+        };
+
+        if let Err(e) = result {
+            // Nothing in the database references the data, so it only takes up space
+            tracing::warn!("Failed to undo {:?}: {}", self, e);
+        }
+    }
+}
+
+/// Groups the database writes and file system operations, which belong to one request, so that
+/// they are applied completely or not at all:
+///
+/// - File system operations are executed immediately. If the unit of work fails, they are undone
+///   in reverse order.
+/// - Database operations (see [`UnitOfWork::execute`]) share one transaction, which is started by
+///   the first of them. File system operations before that neither hold a pooled connection nor
+///   the database lock.
+/// - Files are persisted (see [`UnitOfWork::persist_file`]) before the transaction is started, as
+///   this may copy their whole contents. If the unit of work fails afterwards, they are deleted.
+///
+/// A unit of work, which is dropped without calling [`UnitOfWork::commit`] (e.g. by returning
+/// early with an error), is rolled back.
+pub struct UnitOfWork {
+    db_pool: Arc<DbPool>,
+    /// The connection running the transaction, once it was started
+    conn: Option<PooledConnection<ConnectionManager<SqliteConnection>>>,
+    vfs: Vfs,
+    compensations: Vec<Compensation>,
+}
+
+impl UnitOfWork {
+    pub fn begin(state: &AppState) -> Self {
+        Self {
+            db_pool: state.db_pool.clone(),
+            conn: None,
+            vfs: state.vfs.clone(),
+            compensations: Vec::new(),
+        }
+    }
+
+    /// Run database operations inside the transaction of the unit of work. The transaction locks
+    /// the database for other writers until the unit of work is finished.
+    pub fn execute<T>(
+        &mut self,
+        operations: impl FnOnce(&mut SqliteConnection) -> Result<T>,
+    ) -> Result<T> {
+        let conn = match &mut self.conn {
+            Some(conn) => conn,
+            None => {
+                let mut conn = self.db_pool.get().context("Failed to get db connection")?;
+                AnsiTransactionManager::begin_transaction_sql(&mut *conn, "BEGIN IMMEDIATE")
+                    .context("Failed to begin transaction")?;
+                self.conn.insert(conn)
+            }
+        };
+
+        operations(conn)
+    }
+
+    /// Create a file in the staging area. It is removed again, if the unit of work fails.
+    pub async fn create_file(&mut self, key: &FileKey) -> Result<(), FileSystemError> {
+        self.vfs.create_file(key).await?;
+        self.compensations.push(Compensation::AbortFile(*key));
+        Ok(())
+    }
+
+    /// Write a chunk into a staged file, while its contents are received. It is deleted again, if
+    /// the unit of work fails.
+    pub async fn write_chunk(
+        &mut self,
+        key: &FileKey,
+        index: ChunkIndex,
+        contents: ChunkReader,
+        max_size: DataAmount,
+    ) -> Result<DataAmount, FileSystemError> {
+        let size = self
+            .vfs
+            .write_chunk_stream(key, index, contents, max_size)
+            .await?;
+        self.compensations
+            .push(Compensation::DeleteChunk(*key, index));
+        Ok(size)
+    }
+
+    /// Move a staged file into the permanent storage. It is deleted again, if the unit of work
+    /// fails. Has to be called before the first database operation, so that other writers are not
+    /// locked out while the file is copied.
+    pub async fn persist_file(&mut self, key: &FileKey) -> Result<()> {
+        if self.conn.is_some() {
+            bail!("Cannot persist file {key} while the transaction is running");
+        }
+
+        self.vfs
+            .commit_file(key)
+            .await
+            .with_context(|| format!("Failed to persist file {key}"))?;
+        self.compensations.push(Compensation::DeleteFile(*key));
+        Ok(())
+    }
+
+    /// Commit the database transaction. If this fails, the file system changes are undone.
+    pub async fn commit(mut self) -> Result<()> {
+        if let Some(mut conn) = self.conn.take() {
+            let result = AnsiTransactionManager::commit_transaction(&mut *conn);
+            // return the connection to the pool before touching the file system again
+            drop(conn);
+
+            if let Err(e) = result {
+                self.compensate().await;
+                return Err(e).context("Failed to commit transaction");
+            }
+        }
+
+        self.compensations.clear();
+        Ok(())
+    }
+
+    /// Roll back the database transaction and undo all file system changes
+    pub async fn rollback(mut self) {
+        self.rollback_transaction();
+        self.compensate().await;
+    }
+
+    fn rollback_transaction(&mut self) {
+        let Some(mut conn) = self.conn.take() else {
+            return;
+        };
+
+        if let Err(e) = AnsiTransactionManager::rollback_transaction(&mut *conn) {
+            tracing::error!("Failed to roll back transaction: {}", e);
+        }
+    }
+
+    async fn compensate(&mut self) {
+        while let Some(compensation) = self.compensations.pop() {
+            compensation.run(&self.vfs).await;
+        }
+    }
+}
+
+impl Drop for UnitOfWork {
+    fn drop(&mut self) {
+        self.rollback_transaction();
+
+        if self.compensations.is_empty() {
+            return;
+        }
+
+        let compensations = std::mem::take(&mut self.compensations);
+        let vfs = self.vfs.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move {
+                    for compensation in compensations.into_iter().rev() {
+                        compensation.run(&vfs).await;
+                    }
+                });
+            }
+            Err(_) => tracing::warn!(
+                "Cannot undo {} file system changes outside of a runtime",
+                compensations.len()
+            ),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use crate::db::operations::invite::redeem_invite_code;
+use crate::db::operations::node::insert_node;
+use crate::db::operations::user::{insert_user, update_user, update_user_recovery_key};
+use crate::http::AppState;
+use crate::service::UnitOfWork;
+use crate::storage::node::NodeEntity;
+use crate::user::UserEntity;
+use crate::user::persistence::invite_repository::hash_invite_code;
+use crate::user::persistence::user_repository::hash_password;
+
+use crabdrive_common::da;
+use crabdrive_common::encrypted_metadata::EncryptedMetadata;
+use crabdrive_common::payloads::auth::request::recovery::RecoveryKey;
+use crabdrive_common::storage::{NodeId, NodeType};
+use crabdrive_common::user::{KdfParams, UserId, UserKeys, UserType};
+
+use anyhow::Result;
+use chrono::Utc;
+
+/// An account, which is about to be registered
+pub struct NewUser {
+    pub username: String,
+    /// The password derived by the client, it is hashed again before it is stored
+    pub password: String,
+    pub keys: UserKeys,
+    pub kdf_params: KdfParams,
+    pub recovery_key: Option<RecoveryKey>,
+}
+
+/// Create a user with their root and trash folder. If `invite_code` is given, it is redeemed in
+/// the same transaction and determines the type and storage limit of the user. Returns `None` (and
+/// creates nothing) if the code cannot be redeemed anymore.
+pub async fn register_user(
+    state: &AppState,
+    new_user: NewUser,
+    invite_code: Option<&str>,
+) -> Result<Option<UserEntity>> {
+    let now = Utc::now().naive_utc();
+    let mut user = UserEntity {
+        user_type: UserType::User,
+        id: UserId::random(),
+        created_at: now,
+        username: new_user.username,
+        password_hash: hash_password(&new_user.password),
+        storage_limit: da!(15 GB),
+        storage_used: da!(0 B),
+        encryption_uninitialized: false,
+        master_key: new_user.keys.master_key,
+        private_key: new_user.keys.private_key,
+        public_key: new_user.keys.public_key,
+        root_key: new_user.keys.root_key,
+        root_node: None,
+        trash_key: new_user.keys.trash_key,
+        trash_node: None,
+        updated_at: None,
+        recovery_password_hash: None,
+        recovery_master_key: None,
+        kdf_salt: new_user.kdf_params.salt,
+        kdf_version: new_user.kdf_params.version,
+        totp_secret: None,
+        totp_enabled: false,
+        totp_last_step: None,
+    };
+
+    // The client initializes the metadata of both folders on the first sign-in
+    let root_node = empty_folder(user.id);
+    let trash_node = empty_folder(user.id);
+    let recovery_key = new_user
+        .recovery_key
+        .map(|key| (hash_password(&key.recovery_password), key.master_key));
+
+    let mut uow = UnitOfWork::begin(state);
+
+    let user = uow.execute(|conn| {
+        if let Some(code) = invite_code {
+            // The code may have been used up since it was checked
+            let Some(invite) = redeem_invite_code(conn, &hash_invite_code(code), now)? else {
+                return Ok(None);
+            };
+            user.user_type = invite.user_type;
+            user.storage_limit = invite.storage_limit;
+        }
+
+        insert_user(conn, &user)?;
+        insert_node(conn, &root_node)?;
+        insert_node(conn, &trash_node)?;
+
+        user.root_node = Some(root_node.id);
+        user.trash_node = Some(trash_node.id);
+        let mut user = update_user(conn, &user)?;
+
+        if let Some((recovery_password_hash, recovery_master_key)) = &recovery_key {
+            user = update_user_recovery_key(
+                conn,
+                user.id,
+                Some(recovery_password_hash),
+                Some(recovery_master_key),
+                now,
+            )?;
+        }
+        Ok(Some(user))
+    })?;
+
+    if user.is_some() {
+        uow.commit().await?;
+    } else {
+        uow.rollback().await;
+    }
+    Ok(user)
+}
+
+fn empty_folder(owner: UserId) -> NodeEntity {
+    NodeEntity {
+        id: NodeId::random(),
+        parent_id: None,
+        owner_id: owner,
+        metadata: EncryptedMetadata::nil(),
+        deleted_on: None,
+        metadata_change_counter: 0,
+        current_revision: None,
+        node_type: NodeType::Folder,
+    }
+}
@@ -1,4 +1,4 @@
 pub mod persistence;
 
 pub use persistence::model::node_entity::NodeEntity;
-pub use persistence::node_repository::NodeRepository;
+pub use persistence::node_repository::{ChangeCounterMismatch, MetadataUpdate, NodeRepository};
@@ -1,22 +1,39 @@
 use crate::db::NodeDsl;
 use crate::db::connection::DbPool;
 use crate::db::operations::node::{
-    delete_node, get_all_children, get_path_between_nodes, insert_node, move_node, select_node,
-    update_node,
+    compare_and_update_metadata, delete_node, get_all_children, get_path_between_nodes,
+    insert_node, insert_node_into_parent, move_node, select_node, update_node,
 };
-use crate::db::operations::share::{get_access_list_parent_tree, has_access};
+use crate::db::operations::share::{get_access_list_parent_tree, has_access, select_permission};
 use crate::storage::node::persistence::model::node_entity::NodeEntity;
 use crate::storage::revision::persistence::model::revision_entity::RevisionEntity;
 use anyhow::{Context, Ok, Result};
 use chrono::NaiveDateTime;
 use crabdrive_common::encrypted_metadata::EncryptedMetadata;
-use crabdrive_common::storage::{NodeId, NodeType};
+use crabdrive_common::storage::{NodeId, NodeType, SharePermission};
 use crabdrive_common::user::UserId;
 use diesel::Connection;
 use diesel::ExpressionMethods;
 use diesel::QueryDsl;
 use diesel::RunQueryDsl;
+use diesel::SqliteConnection;
 use std::sync::Arc;
+use thiserror::Error;
+
+/// New metadata for a node, which the client derived from the version `expected_counter` of the
+/// node. It is only written if nobody else modified the node in the meantime.
+#[derive(Debug, Clone)]
+pub struct MetadataUpdate {
+    pub node_id: NodeId,
+    pub metadata: EncryptedMetadata,
+    pub expected_counter: i64,
+}
+
+/// A [`MetadataUpdate`] lost a race: the metadata counter of the node did not have the expected
+/// value anymore. Repositories return it wrapped in an [`anyhow::Error`].
+#[derive(Error, Debug)]
+#[error("Node {0} was modified concurrently")]
+pub struct ChangeCounterMismatch(pub NodeId);
 
 pub trait NodeRepository {
     fn create_node(
@@ -28,10 +45,26 @@ pub trait NodeRepository {
         node_id: NodeId,
     ) -> Result<NodeEntity>;
 
+    /// Create a node and replace the metadata of its parent (which has to contain the key of the
+    /// new node) in one step. Fails with [`ChangeCounterMismatch`] if the parent was modified
+    /// concurrently.
+    fn create_node_in_parent(
+        &self,
+        parent: MetadataUpdate,
+        encrypted_metadata: EncryptedMetadata,
+        owner: UserId,
+        node_type: NodeType,
+        node_id: NodeId,
+    ) -> Result<NodeEntity>;
+
     fn get_node(&self, id: NodeId) -> Result<Option<NodeEntity>>;
 
     fn update_node(&self, node: &NodeEntity) -> Result<NodeEntity>;
 
+    /// Replace the metadata of a node. Fails with [`ChangeCounterMismatch`] if the node was
+    /// modified concurrently.
+    fn update_node_metadata(&self, update: MetadataUpdate) -> Result<NodeEntity>;
+
     /// Returns a list of all nodes it deleted so that the associated chunks can be deleted
     fn purge_tree(&self, id: NodeId) -> Result<Vec<NodeEntity>>;
 
@@ -44,14 +77,9 @@ pub trait NodeRepository {
     /// - the node cannot be moved into one of its own children
     /// - the to_node must be a folder
     /// - a file cannot be moved into another file
-    fn move_node(
-        &self,
-        id: NodeId,
-        from: NodeId,
-        from_metadata: EncryptedMetadata,
-        to: NodeId,
-        to_metadata: EncryptedMetadata,
-    ) -> Result<()>;
+    /// - neither parent may have been modified since the client read it, otherwise this fails with
+    ///   [`ChangeCounterMismatch`]
+    fn move_node(&self, id: NodeId, from: MetadataUpdate, to: MetadataUpdate) -> Result<()>;
 
     /// Get all children of a node
     fn get_children(&self, parent_id: NodeId) -> Result<Vec<NodeEntity>>;
@@ -62,19 +90,15 @@ pub trait NodeRepository {
     fn move_node_to_trash(
         &self,
         id: NodeId,
-        from: NodeId,
-        from_metadata: EncryptedMetadata,
-        to_trash: NodeId,
-        to_trash_metadata: EncryptedMetadata,
+        from: MetadataUpdate,
+        to_trash: MetadataUpdate,
     ) -> Result<()>;
 
     fn move_node_out_of_trash(
         &self,
         id: NodeId,
-        from_trash: NodeId,
-        from_trash_metadata: EncryptedMetadata,
-        to: NodeId,
-        to_metadata: EncryptedMetadata,
+        from_trash: MetadataUpdate,
+        to: MetadataUpdate,
     ) -> Result<()>;
 
     fn purge_tree_from_trash(&self, id: NodeId) -> Result<(Vec<NodeEntity>, Vec<RevisionEntity>)>;
@@ -89,11 +113,14 @@ pub trait NodeRepository {
     /// Get the path from a node to the root or trash node
     fn get_path_to_root(&self, node: NodeId) -> Result<Vec<NodeEntity>>;
 
-    /// Check if a user has access to the node (own nodes & shared nodes)
-    fn has_access(&self, id: NodeId, user: UserId) -> Result<bool>;
+    /// Check if a user has at least the given permission on the node (own nodes & shared nodes)
+    fn has_access(&self, id: NodeId, user: UserId, permission: SharePermission) -> Result<bool>;
 
-    /// Get a list of tuples `(UserId, Username)`, on which users have access to a node
-    fn get_access_list(&self, node: NodeId) -> Result<Vec<(UserId, String)>>;
+    /// Get the permission of a user on the node, or `None` if the user has no access
+    fn get_permission(&self, id: NodeId, user: UserId) -> Result<Option<SharePermission>>;
+
+    /// Get a list of tuples `(UserId, Username, SharePermission)`, on which users have access to a node
+    fn get_access_list(&self, node: NodeId) -> Result<Vec<(UserId, String, SharePermission)>>;
 }
 
 pub struct NodeRepositoryImpl {
@@ -128,17 +155,33 @@ impl NodeRepository for NodeRepositoryImpl {
             node_type,
         };
 
-        if let Some(parent_id) = parent {
-            let parent_node = select_node(&mut conn, parent_id)
-                .context("Failed to select parent node")?
-                .context("Parent node not found")?;
+        insert_node(&mut conn, &node).context("Failed to insert node")?;
 
-            insert_node(&mut conn, &node, &parent_node.metadata)
-                .context("Failed to insert node")?;
-        } else {
-            insert_node(&mut conn, &node, &encrypted_metadata)
-                .context("Failed to insert root node")?;
-        }
+        Ok(node)
+    }
+
+    fn create_node_in_parent(
+        &self,
+        parent: MetadataUpdate,
+        encrypted_metadata: EncryptedMetadata,
+        owner: UserId,
+        node_type: NodeType,
+        node_id: NodeId,
+    ) -> Result<NodeEntity> {
+        let mut conn = self.db_pool.get().context("Failed to get db connection")?;
+
+        let node = NodeEntity {
+            id: node_id,
+            parent_id: Some(parent.node_id),
+            owner_id: owner,
+            metadata: encrypted_metadata,
+            deleted_on: None,
+            metadata_change_counter: 0,
+            current_revision: None,
+            node_type,
+        };
+
+        insert_node_into_parent(&mut conn, &node, &parent)?;
 
         Ok(node)
     }
@@ -155,6 +198,11 @@ impl NodeRepository for NodeRepositoryImpl {
             .context("Failed to update node")
     }
 
+    fn update_node_metadata(&self, update: MetadataUpdate) -> Result<NodeEntity> {
+        let mut conn = self.db_pool.get().context("Failed to get db connection")?;
+        compare_and_update_metadata(&mut conn, &update)
+    }
+
     fn purge_tree(&self, id: NodeId) -> Result<Vec<NodeEntity>> {
         let mut deleted_nodes = Vec::new();
 
@@ -199,31 +247,9 @@ impl NodeRepository for NodeRepositoryImpl {
         Ok(deleted_nodes)
     }
 
-    fn move_node(
-        &self,
-        id: NodeId,
-        from: NodeId,
-        from_metadata: EncryptedMetadata,
-        to: NodeId,
-        to_metadata: EncryptedMetadata,
-    ) -> Result<()> {
+    fn move_node(&self, id: NodeId, from: MetadataUpdate, to: MetadataUpdate) -> Result<()> {
         let mut conn = self.db_pool.get().context("Failed to get db connection")?;
-
-        let to_node = select_node(&mut conn, to)
-            .context("Failed to select to_node")?
-            .context("to_node not found")?;
-
-        if to_node.node_type != NodeType::Folder {
-            anyhow::bail!("Cannot move a node into a folder");
-        }
-
-        // node cannot be moved into one of its own children (i.e. to_node cannot be in the subtree of id)
-        let path = get_path_between_nodes(&mut conn, id, to)?;
-        if !path.is_empty() && path.first().map(|n| n.id) == Some(id) {
-            anyhow::bail!("Cannot move a node into one of its own children");
-        }
-
-        move_node(&mut conn, id, from, from_metadata, to, to_metadata)
+        conn.immediate_transaction(|conn| checked_move_node(conn, id, &from, &to))
     }
 
     fn get_children(&self, parent_id: NodeId) -> Result<Vec<NodeEntity>> {
@@ -245,19 +271,17 @@ impl NodeRepository for NodeRepositoryImpl {
     fn move_node_to_trash(
         &self,
         id: NodeId,
-        from: NodeId,
-        from_metadata: EncryptedMetadata,
-        to_trash: NodeId,
-        to_trash_metadata: EncryptedMetadata,
+        from: MetadataUpdate,
+        to_trash: MetadataUpdate,
     ) -> Result<()> {
-        self.move_node(id, from, from_metadata, to_trash, to_trash_metadata)?;
-
         let mut conn = self
             .db_pool
             .get()
             .context("Failed to get database connection")?;
 
-        conn.transaction(|conn| {
+        conn.immediate_transaction(|conn| {
+            checked_move_node(conn, id, &from, &to_trash)?;
+
             let now = chrono::Local::now().naive_local();
 
             diesel::update(NodeDsl::Node)
@@ -267,27 +291,23 @@ impl NodeRepository for NodeRepositoryImpl {
                 .context("Failed to set deleted_on timestamp")?;
 
             Ok(())
-        })?;
-
-        Ok(())
+        })
     }
 
     fn move_node_out_of_trash(
         &self,
         id: NodeId,
-        from_trash: NodeId,
-        from_trash_metadata: EncryptedMetadata,
-        to: NodeId,
-        to_metadata: EncryptedMetadata,
+        from_trash: MetadataUpdate,
+        to: MetadataUpdate,
     ) -> Result<()> {
-        self.move_node(id, from_trash, from_trash_metadata, to, to_metadata)?;
-
         let mut conn = self
             .db_pool
             .get()
             .context("Failed to get database connection")?;
 
-        conn.transaction(|conn| {
+        conn.immediate_transaction(|conn| {
+            checked_move_node(conn, id, &from_trash, &to)?;
+
             diesel::update(NodeDsl::Node)
                 .filter(NodeDsl::id.eq(id))
                 .set(NodeDsl::deleted_on.eq(None::<NaiveDateTime>))
@@ -295,9 +315,7 @@ impl NodeRepository for NodeRepositoryImpl {
                 .context("Failed to clear deleted_on timestamp")?;
 
             Ok(())
-        })?;
-
-        Ok(())
+        })
     }
 
     fn purge_tree_from_trash(&self, id: NodeId) -> Result<(Vec<NodeEntity>, Vec<RevisionEntity>)> {
@@ -405,13 +423,46 @@ impl NodeRepository for NodeRepositoryImpl {
         get_path_between_nodes(&mut conn, NodeId::nil(), node)
     }
 
-    fn has_access(&self, id: NodeId, user: UserId) -> Result<bool> {
+    fn has_access(&self, id: NodeId, user: UserId, permission: SharePermission) -> Result<bool> {
+        let mut conn = self.db_pool.get().context("Failed to get db connection")?;
+        has_access(&mut conn, id, user, permission)
+    }
+
+    fn get_permission(&self, id: NodeId, user: UserId) -> Result<Option<SharePermission>> {
         let mut conn = self.db_pool.get().context("Failed to get db connection")?;
-        has_access(&mut conn, id, user)
+        select_permission(&mut conn, id, user)
     }
 
-    fn get_access_list(&self, node: NodeId) -> Result<Vec<(UserId, String)>> {
+    fn get_access_list(&self, node: NodeId) -> Result<Vec<(UserId, String, SharePermission)>> {
         let mut conn = self.db_pool.get().context("Failed to get db connection")?;
         get_access_list_parent_tree(&mut conn, node)
     }
 }
+
+/// Moves the node and checks the constraints of [`NodeRepository::move_node`]. Has to run inside a
+/// transaction, which is rolled back if a constraint is violated.
+fn checked_move_node(
+    conn: &mut SqliteConnection,
+    id: NodeId,
+    from: &MetadataUpdate,
+    to: &MetadataUpdate,
+) -> Result<()> {
+    let to_node = select_node(conn, to.node_id)
+        .context("Failed to select to_node")?
+        .context("to_node not found")?;
+    let path = get_path_between_nodes(conn, id, to.node_id)?;
+
+    // a client with an outdated view of the tree gets a conflict, even if its move is invalid
+    move_node(conn, id, from, to)?;
+
+    if to_node.node_type != NodeType::Folder {
+        anyhow::bail!("Cannot move a node into a folder");
+    }
+
+    // node cannot be moved into one of its own children (i.e. to_node cannot be in the subtree of id)
+    if !path.is_empty() && path.first().map(|n| n.id) == Some(id) {
+        anyhow::bail!("Cannot move a node into one of its own children");
+    }
+
+    Ok(())
+}
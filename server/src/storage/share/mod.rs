@@ -1,3 +1,6 @@
 pub mod persistence;
 
+pub use persistence::model::dropped_file_entity::DroppedFileEntity;
+pub use persistence::model::file_drop_entity::FileDropEntity;
+pub use persistence::model::public_link_entity::PublicLinkEntity;
 pub use persistence::model::share_entity::ShareEntity;
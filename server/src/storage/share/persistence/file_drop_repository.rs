@@ -0,0 +1,113 @@
+use crate::db::connection::DbPool;
+use crate::db::operations::file_drop::*;
+use crate::storage::share::{DroppedFileEntity, FileDropEntity};
+
+use crabdrive_common::da;
+use crabdrive_common::storage::{FileDropId, NodeId};
+use crabdrive_common::user::UserId;
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{NaiveDateTime, Utc};
+
+pub trait FileDropRepository {
+    /// Get a file drop by ID
+    fn get_file_drop(&self, id: FileDropId) -> Result<Option<FileDropEntity>>;
+    /// Create a new file drop, which allows uploading files into the folder without an account
+    fn create_file_drop(
+        &self,
+        node_id: NodeId,
+        created_by: UserId,
+        expires_at: Option<NaiveDateTime>,
+    ) -> Result<FileDropEntity>;
+    /// Delete a file drop. Files, which were dropped already, are kept.
+    fn delete_file_drop(&self, id: FileDropId) -> Result<Option<FileDropEntity>>;
+    /// Remember that a node was uploaded through a file drop
+    fn create_dropped_file(
+        &self,
+        node_id: NodeId,
+        file_drop_id: FileDropId,
+        encrypted_metadata_key: Vec<u8>,
+    ) -> Result<DroppedFileEntity>;
+    /// Get a dropped file. Returns `None` if the node was not dropped or was adopted already.
+    fn get_dropped_file(&self, node_id: NodeId) -> Result<Option<DroppedFileEntity>>;
+    /// Get all files dropped into a folder, which were not adopted yet
+    fn get_dropped_files(&self, folder_id: NodeId) -> Result<Vec<DroppedFileEntity>>;
+    /// Mark dropped files as adopted. Returns the amount of adopted files.
+    fn adopt_dropped_files(&self, node_ids: &[NodeId]) -> Result<usize>;
+}
+
+pub struct FileDropRepositoryImpl {
+    db_pool: Arc<DbPool>,
+}
+
+impl FileDropRepositoryImpl {
+    pub fn new(db_pool: Arc<DbPool>) -> Self {
+        Self { db_pool }
+    }
+}
+
+impl FileDropRepository for FileDropRepositoryImpl {
+    fn get_file_drop(&self, id: FileDropId) -> Result<Option<FileDropEntity>> {
+        let mut conn = self.db_pool.get()?;
+        select_file_drop(&mut conn, id)
+    }
+
+    fn create_file_drop(
+        &self,
+        node_id: NodeId,
+        created_by: UserId,
+        expires_at: Option<NaiveDateTime>,
+    ) -> Result<FileDropEntity> {
+        let mut conn = self.db_pool.get()?;
+
+        let file_drop = FileDropEntity {
+            id: FileDropId::random(),
+            node_id,
+            created_by,
+            created_at: Utc::now().naive_utc(),
+            expires_at,
+        };
+
+        insert_file_drop(&mut conn, &file_drop)
+    }
+
+    fn delete_file_drop(&self, id: FileDropId) -> Result<Option<FileDropEntity>> {
+        let mut conn = self.db_pool.get()?;
+        delete_file_drop(&mut conn, id)
+    }
+
+    fn create_dropped_file(
+        &self,
+        node_id: NodeId,
+        file_drop_id: FileDropId,
+        encrypted_metadata_key: Vec<u8>,
+    ) -> Result<DroppedFileEntity> {
+        let mut conn = self.db_pool.get()?;
+
+        let dropped_file = DroppedFileEntity {
+            node_id,
+            file_drop_id: Some(file_drop_id),
+            encrypted_metadata_key,
+            uploaded_size: da!(0 B),
+        };
+
+        insert_dropped_file(&mut conn, &dropped_file)
+    }
+
+    fn get_dropped_file(&self, node_id: NodeId) -> Result<Option<DroppedFileEntity>> {
+        let mut conn = self.db_pool.get()?;
+        select_dropped_file(&mut conn, node_id)
+    }
+
+    fn get_dropped_files(&self, folder_id: NodeId) -> Result<Vec<DroppedFileEntity>> {
+        let mut conn = self.db_pool.get()?;
+        get_dropped_files_in_folder(&mut conn, folder_id)
+    }
+
+    fn adopt_dropped_files(&self, node_ids: &[NodeId]) -> Result<usize> {
+        let mut conn = self.db_pool.get()?;
+        delete_dropped_files(&mut conn, node_ids)
+    }
+}
@@ -1,2 +1,4 @@
+pub mod file_drop_repository;
 pub mod model;
+pub mod public_link_repository;
 pub mod share_repository;
@@ -0,0 +1,21 @@
+use crabdrive_common::data::DataAmount;
+use crabdrive_common::storage::{FileDropId, NodeId};
+
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A file uploaded through a file drop. The row is removed once the owner of the folder adopted
+/// the file by adding its metadata key to the folder.
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Insertable, AsChangeset, Clone)]
+#[diesel(table_name = crate::db::schema::DroppedFile)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct DroppedFileEntity {
+    pub node_id: NodeId,
+    /// `None` if the drop was revoked or has expired since
+    pub file_drop_id: Option<FileDropId>,
+    /// The metadata key of the node, encrypted with the public key of the folder owner
+    pub encrypted_metadata_key: Vec<u8>,
+    /// The size of the chunks uploaded so far, which counts against the storage limit of the
+    /// folder owner
+    pub uploaded_size: DataAmount,
+}
@@ -0,0 +1,25 @@
+use crabdrive_common::storage::{FileDropId, NodeId};
+use crabdrive_common::user::UserId;
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Insertable, AsChangeset, Clone)]
+#[diesel(table_name = crate::db::schema::FileDrop)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct FileDropEntity {
+    pub id: FileDropId,
+    /// The folder, which receives the dropped files
+    pub node_id: NodeId,
+    pub created_by: UserId,
+    pub created_at: NaiveDateTime,
+    /// No files can be dropped after this point in time (UTC)
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+impl FileDropEntity {
+    pub fn is_expired(&self, now: NaiveDateTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
@@ -1 +1,4 @@
+pub mod dropped_file_entity;
+pub mod file_drop_entity;
+pub mod public_link_entity;
 pub mod share_entity;
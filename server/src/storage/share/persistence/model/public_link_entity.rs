@@ -0,0 +1,27 @@
+use crabdrive_common::encryption_key::EncryptionKey;
+use crabdrive_common::storage::{NodeId, PublicLinkId};
+use crabdrive_common::user::UserId;
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Insertable, AsChangeset, Clone)]
+#[diesel(table_name = crate::db::schema::PublicLink)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct PublicLinkEntity {
+    pub id: PublicLinkId,
+    pub node_id: NodeId,
+    pub created_by: UserId,
+    /// The metadata key of the node, wrapped with the key in the url fragment
+    pub wrapped_metadata_key: EncryptionKey,
+    pub created_at: NaiveDateTime,
+    /// The link can no longer be used after this point in time (UTC)
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+impl PublicLinkEntity {
+    pub fn is_expired(&self, now: NaiveDateTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
@@ -2,7 +2,7 @@ use crate::storage::node::NodeEntity;
 use crate::user::UserEntity;
 
 use crabdrive_common::encryption_key::EncryptionKey;
-use crabdrive_common::storage::{NodeId, ShareId};
+use crabdrive_common::storage::{NodeId, ShareId, SharePermission};
 use crabdrive_common::user::UserId;
 
 use chrono::NaiveDateTime;
@@ -22,6 +22,7 @@ use serde::{Deserialize, Serialize};
 )]
 #[diesel(table_name = crate::db::schema::Share)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
 #[diesel(belongs_to(UserEntity, foreign_key = shared_by))]
 #[diesel(belongs_to(NodeEntity, foreign_key = node_id))]
 pub struct ShareEntity {
@@ -33,4 +34,31 @@ pub struct ShareEntity {
     pub time_accepted: Option<NaiveDateTime>,
     pub shared_encryption_key: Option<EncryptionKey>,
     pub accepted_encryption_key: Option<EncryptionKey>,
+    pub permission: SharePermission,
+    /// Time after which the share link can no longer be accepted
+    pub expires_at: Option<NaiveDateTime>,
+    /// Whether the access of the user who accepted the share ends, once the share has expired
+    pub revoke_on_expiry: bool,
+    /// The user the share was sent to directly. Only this user can accept the share.
+    pub shared_with: Option<UserId>,
+    /// The key in the url of a share link, encrypted with the public key of `shared_with`
+    pub encrypted_share_key: Option<Vec<u8>>,
+}
+
+impl ShareEntity {
+    pub fn is_expired(&self, now: NaiveDateTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Whether `user_id` may see and accept the share. Share links can be accepted by anyone with
+    /// the link.
+    pub fn is_addressed_to(&self, user_id: UserId) -> bool {
+        self.shared_with
+            .is_none_or(|shared_with| shared_with == user_id)
+    }
+
+    /// Whether the share (still) grants access to the user who accepted it
+    pub fn grants_access(&self, now: NaiveDateTime) -> bool {
+        self.accepted_by.is_some() && !(self.revoke_on_expiry && self.is_expired(now))
+    }
 }
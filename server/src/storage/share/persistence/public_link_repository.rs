@@ -0,0 +1,70 @@
+use crate::db::connection::DbPool;
+use crate::db::operations::public_link::*;
+use crate::storage::share::PublicLinkEntity;
+
+use crabdrive_common::encryption_key::EncryptionKey;
+use crabdrive_common::storage::{NodeId, PublicLinkId};
+use crabdrive_common::user::UserId;
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{NaiveDateTime, Utc};
+
+pub trait PublicLinkRepository {
+    /// Get a public link by ID
+    fn get_public_link(&self, id: PublicLinkId) -> Result<Option<PublicLinkEntity>>;
+    /// Create a new public link, which allows downloading the node without an account
+    fn create_public_link(
+        &self,
+        node_id: NodeId,
+        created_by: UserId,
+        wrapped_metadata_key: EncryptionKey,
+        expires_at: Option<NaiveDateTime>,
+    ) -> Result<PublicLinkEntity>;
+    /// Delete a public link. Returns `None` if there is no such link.
+    fn delete_public_link(&self, id: PublicLinkId) -> Result<Option<PublicLinkEntity>>;
+}
+
+pub struct PublicLinkRepositoryImpl {
+    db_pool: Arc<DbPool>,
+}
+
+impl PublicLinkRepositoryImpl {
+    pub fn new(db_pool: Arc<DbPool>) -> Self {
+        Self { db_pool }
+    }
+}
+
+impl PublicLinkRepository for PublicLinkRepositoryImpl {
+    fn get_public_link(&self, id: PublicLinkId) -> Result<Option<PublicLinkEntity>> {
+        let mut conn = self.db_pool.get()?;
+        select_public_link(&mut conn, id)
+    }
+
+    fn create_public_link(
+        &self,
+        node_id: NodeId,
+        created_by: UserId,
+        wrapped_metadata_key: EncryptionKey,
+        expires_at: Option<NaiveDateTime>,
+    ) -> Result<PublicLinkEntity> {
+        let mut conn = self.db_pool.get()?;
+
+        let public_link = PublicLinkEntity {
+            id: PublicLinkId::random(),
+            node_id,
+            created_by,
+            wrapped_metadata_key,
+            created_at: Utc::now().naive_utc(),
+            expires_at,
+        };
+
+        insert_public_link(&mut conn, &public_link)
+    }
+
+    fn delete_public_link(&self, id: PublicLinkId) -> Result<Option<PublicLinkEntity>> {
+        let mut conn = self.db_pool.get()?;
+        delete_public_link(&mut conn, id)
+    }
+}
@@ -3,13 +3,13 @@ use crate::db::operations::share::*;
 use crate::storage::share::ShareEntity;
 
 use crabdrive_common::encryption_key::EncryptionKey;
-use crabdrive_common::storage::{NodeId, ShareId};
+use crabdrive_common::storage::{NodeId, ShareId, SharePermission};
 use crabdrive_common::user::UserId;
 
 use std::sync::Arc;
 
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 
 pub trait ShareRepository {
     /// Get a share entry by ID
@@ -20,12 +20,27 @@ pub trait ShareRepository {
         node_id: NodeId,
         shared_by: UserId,
         key: EncryptionKey,
+        permission: SharePermission,
+        expires_at: Option<NaiveDateTime>,
+        revoke_on_expiry: bool,
+    ) -> Result<ShareEntity>;
+    /// Create a new share entry, which can only be accepted by the recipient. The recipient is given
+    /// with the share key, encrypted with the public key of the recipient. The expiry is given with
+    /// whether the access of the recipient ends at that time.
+    fn create_direct_share(
+        &self,
+        node_id: NodeId,
+        shared_by: UserId,
+        recipient: (UserId, Vec<u8>),
+        key: EncryptionKey,
+        permission: SharePermission,
+        expiry: (Option<NaiveDateTime>, bool),
     ) -> Result<ShareEntity>;
     /// Delete a share entry
     fn delete_share(&self, share_id: ShareId) -> Result<ShareEntity>;
     /// Update a share entry
     fn update_share(&self, entity: ShareEntity) -> Result<ShareEntity>;
-    /// Get all share entries by User ID
+    /// Get all share entries accepted by a user, which still grant access
     fn get_accepted_shares_by_user(&self, user_id: UserId) -> Result<Vec<ShareEntity>>;
     /// Get all share entries for a Node
     fn get_shares_by_node_id(&self, node_id: NodeId) -> Result<Vec<ShareEntity>>;
@@ -35,6 +50,14 @@ pub trait ShareRepository {
         node_id: NodeId,
         user_id: UserId,
     ) -> Result<Option<ShareEntity>>;
+    /// Get all share entries sent directly to a user, which were not accepted and did not expire
+    fn get_pending_shares_for_user(&self, user_id: UserId) -> Result<Vec<ShareEntity>>;
+    /// Get the shares created by the user, optionally only the shares of a single node
+    fn get_shares_created_by_user(
+        &self,
+        user_id: UserId,
+        node_id: Option<NodeId>,
+    ) -> Result<Vec<ShareEntity>>;
 }
 
 pub struct ShareRepositoryImpl {
@@ -58,6 +81,39 @@ impl ShareRepository for ShareRepositoryImpl {
         node_id: NodeId,
         shared_by: UserId,
         key: EncryptionKey,
+        permission: SharePermission,
+        expires_at: Option<NaiveDateTime>,
+        revoke_on_expiry: bool,
+    ) -> Result<ShareEntity> {
+        let mut conn = self.db_pool.get()?;
+
+        let share_entity = ShareEntity {
+            id: ShareId::random(),
+            node_id,
+            shared_by,
+            accepted_by: None,
+            time_shared: Utc::now().naive_utc(),
+            time_accepted: None,
+            shared_encryption_key: Some(key),
+            accepted_encryption_key: None,
+            permission,
+            expires_at,
+            revoke_on_expiry,
+            shared_with: None,
+            encrypted_share_key: None,
+        };
+
+        insert_share(&mut conn, &share_entity)
+    }
+
+    fn create_direct_share(
+        &self,
+        node_id: NodeId,
+        shared_by: UserId,
+        (shared_with, encrypted_share_key): (UserId, Vec<u8>),
+        key: EncryptionKey,
+        permission: SharePermission,
+        (expires_at, revoke_on_expiry): (Option<NaiveDateTime>, bool),
     ) -> Result<ShareEntity> {
         let mut conn = self.db_pool.get()?;
 
@@ -70,6 +126,11 @@ impl ShareRepository for ShareRepositoryImpl {
             time_accepted: None,
             shared_encryption_key: Some(key),
             accepted_encryption_key: None,
+            permission,
+            expires_at,
+            revoke_on_expiry,
+            shared_with: Some(shared_with),
+            encrypted_share_key: Some(encrypted_share_key),
         };
 
         insert_share(&mut conn, &share_entity)
@@ -103,4 +164,18 @@ impl ShareRepository for ShareRepositoryImpl {
         let mut conn = self.db_pool.get()?;
         get_share_by_node_id_and_accepted_user_id(&mut conn, node_id, user_id)
     }
+
+    fn get_pending_shares_for_user(&self, user_id: UserId) -> Result<Vec<ShareEntity>> {
+        let mut conn = self.db_pool.get()?;
+        get_pending_shares_for_user(&mut conn, user_id, Utc::now().naive_utc())
+    }
+
+    fn get_shares_created_by_user(
+        &self,
+        user_id: UserId,
+        node_id: Option<NodeId>,
+    ) -> Result<Vec<ShareEntity>> {
+        let mut conn = self.db_pool.get()?;
+        get_shares_created_by_user(&mut conn, user_id, node_id)
+    }
 }
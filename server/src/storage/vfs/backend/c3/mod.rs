@@ -3,15 +3,20 @@ pub mod utils;
 
 use crate::db::connection::DbPool;
 use crate::db::operations::revision::get_all_uncommitted_revisions;
-use crate::storage::vfs::{FileChunk, FileKey, FileRepository, FileStatus, FileSystemError};
+use crate::storage::vfs::backend::utils::{open_chunk_file, write_stream_to_file};
+use crate::storage::vfs::{
+    ChunkContents, ChunkReader, FileChunk, FileKey, FileRepository, FileStatus, FileSystemError,
+};
 use model::{CachedChunk, FileTransfer, FileTransferState};
 
+use crabdrive_common::data::DataAmount;
 use crabdrive_common::storage::ChunkIndex;
 use crabdrive_common::uuid::UUID;
 
 use std::sync::Arc;
 use std::{path::PathBuf, time::Duration};
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
 
 use async_trait::async_trait;
 use dashmap::DashMap;
@@ -25,8 +30,9 @@ pub struct C3 {
     staging_path: PathBuf,
     /// The base path for the persitent directory
     persistent_path: PathBuf,
-    /// List of all active transfers
-    transfers: Arc<DashMap<UUID, FileTransfer>>,
+    /// List of all active transfers. The transfers are reference counted, so that the map does not
+    /// stay locked while a transfer is in use.
+    transfers: Arc<DashMap<UUID, Arc<FileTransfer>>>,
     cache: Arc<Cache<(UUID, ChunkIndex), CachedChunk>>,
     /// Configuration option for caching behavior.
     cache_ahead: u8,
@@ -72,7 +78,7 @@ impl C3 {
             for id in open_transfers {
                 let path = utils::shard_path(id, &staging_path);
                 let transfer = FileTransfer::new(path);
-                transfers.insert(id, transfer);
+                transfers.insert(id, Arc::new(transfer));
             }
         }
 
@@ -100,7 +106,7 @@ impl C3 {
     /// Garbage-Collects all staled transfers (received no uploads in the last 10 minutes) and
     /// updates the reference counter on each transfer.
     fn spawn_gc(&self) {
-        let mut this = self.clone(); // Welcome back Java :D
+        let this = self.clone(); // Welcome back Java :D
 
         tokio::spawn(async move {
             // Run every minute
@@ -131,6 +137,13 @@ impl C3 {
         });
     }
 
+    fn get_transfer(&self, key: &FileKey) -> Result<Arc<FileTransfer>, FileSystemError> {
+        self.transfers
+            .get(key)
+            .map(|transfer| Arc::clone(&transfer))
+            .ok_or(FileSystemError::NotFound)
+    }
+
     fn spawn_prefetch(&self, key: UUID, original_index: i64) {
         let cache = Arc::clone(&self.cache);
         let cache_ahead = self.cache_ahead;
@@ -216,7 +229,7 @@ impl FileRepository for C3 {
     }
 
     #[instrument(skip(self), err)]
-    async fn create_file(&mut self, key: &FileKey) -> Result<(), FileSystemError> {
+    async fn create_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
         let FileStatus::NotFound = self.file_status(key).await else {
             tracing::warn!("Revision already stored in VFS!");
             return Err(FileSystemError::AlreadyExists);
@@ -228,19 +241,15 @@ impl FileRepository for C3 {
         tracing::trace!("File chunks will be staged in {}", path.display());
 
         let transfer = FileTransfer::new(path);
-        self.transfers.insert(*key, transfer);
+        self.transfers.insert(*key, Arc::new(transfer));
 
         Ok(())
     }
 
     #[instrument(skip(self, contents), err)]
-    async fn write_chunk(
-        &mut self,
-        key: &FileKey,
-        contents: FileChunk,
-    ) -> Result<(), FileSystemError> {
-        let transfer = self.transfers.get(key).ok_or(FileSystemError::NotFound)?;
-        transfer.set_state(FileTransferState::Writing).await;
+    async fn write_chunk(&self, key: &FileKey, contents: FileChunk) -> Result<(), FileSystemError> {
+        let transfer = self.get_transfer(key)?;
+        let _writing = transfer.start_writing().await;
 
         let mut path = transfer.path.clone();
         utils::push_index(&mut path, contents.index);
@@ -249,15 +258,43 @@ impl FileRepository for C3 {
             .reference
             .store(0, std::sync::atomic::Ordering::SeqCst);
 
-        let result = fs::write(path, contents.data).await;
+        write_new_file(&path, &contents.data).await
+    }
+
+    #[instrument(skip(self, contents), err)]
+    async fn write_chunk_stream(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+        contents: ChunkReader,
+        max_size: DataAmount,
+    ) -> Result<DataAmount, FileSystemError> {
+        let transfer = self.get_transfer(key)?;
+        let _writing = transfer.start_writing().await;
 
-        transfer.try_set_state(FileTransferState::Ready).ok();
-        result.map_err(|e| e.into())
+        let mut path = transfer.path.clone();
+        utils::push_index(&mut path, index);
+
+        transfer
+            .reference
+            .store(0, std::sync::atomic::Ordering::SeqCst);
+
+        write_stream_to_file(&path, contents, max_size).await
     }
 
     #[instrument(skip(self), err)]
-    async fn commit_file(&mut self, key: &FileKey) -> Result<(), FileSystemError> {
-        let transfer = self.transfers.get(key).ok_or(FileSystemError::NotFound)?;
+    async fn delete_chunk(&self, key: &FileKey, index: ChunkIndex) -> Result<(), FileSystemError> {
+        let transfer = self.get_transfer(key)?;
+
+        let mut path = transfer.path.clone();
+        utils::push_index(&mut path, index);
+
+        fs::remove_file(path).await.map_err(|e| e.into())
+    }
+
+    #[instrument(skip(self), err)]
+    async fn commit_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        let transfer = self.get_transfer(key)?;
         transfer.set_state(FileTransferState::Comitting).await;
 
         transfer
@@ -297,7 +334,7 @@ impl FileRepository for C3 {
     }
 
     #[instrument(skip(self), err)]
-    async fn abort(&mut self, key: &FileKey) -> Result<(), FileSystemError> {
+    async fn abort(&self, key: &FileKey) -> Result<(), FileSystemError> {
         if !matches!(
             self.file_status(key).await,
             FileStatus::Staged | FileStatus::Stale
@@ -306,7 +343,7 @@ impl FileRepository for C3 {
             return Err(FileSystemError::NotFound);
         };
 
-        let transfer = self.transfers.get(key).ok_or(FileSystemError::NotFound)?;
+        let transfer = self.get_transfer(key)?;
         transfer.set_state(FileTransferState::Aborting).await;
         transfer
             .reference
@@ -323,7 +360,7 @@ impl FileRepository for C3 {
     }
 
     #[instrument(skip(self), err)]
-    async fn delete_file(&mut self, key: &FileKey) -> Result<(), FileSystemError> {
+    async fn delete_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
         if self.file_status(key).await != FileStatus::Persisted {
             tracing::warn!("Attempted to delete non-existing file!");
             return Err(FileSystemError::NotFound);
@@ -393,4 +430,45 @@ impl FileRepository for C3 {
             data: bytes.bytes,
         })
     }
+
+    #[instrument(skip(self), err)]
+    async fn open_chunk(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+    ) -> Result<ChunkContents, FileSystemError> {
+        if let Some(cached) = self.cache.get(&(*key, index)).await {
+            if cached.cached_at < 1 && cached.cached_at != -1 {
+                self.spawn_prefetch(*key, index);
+            }
+            return Ok(ChunkContents::Buffered(cached.bytes));
+        }
+
+        tracing::trace!("Cache missed - Streaming chunk from disk");
+        let path = utils::shard_path_with_index(*key, &self.persistent_path, index);
+        let contents = open_chunk_file(&path).await?;
+
+        // The chunk itself is not cached, but if the file is downloaded completely, the following
+        // chunks can be served from memory
+        self.spawn_prefetch(*key, index + 1);
+
+        Ok(contents)
+    }
+}
+
+/// Write a chunk into a new file. Fails with [`FileSystemError::AlreadyExists`], if the chunk was
+/// written already (e.g. by a concurrent upload of the same chunk).
+async fn write_new_file(path: &PathBuf, data: &[u8]) -> Result<(), FileSystemError> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .await
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => FileSystemError::AlreadyExists,
+            _ => e.into(),
+        })?;
+
+    file.write_all(data).await?;
+    Ok(())
 }
@@ -118,6 +118,24 @@ impl FileTransfer {
             tokio::task::yield_now().await;
         }
     }
+
+    /// Register a chunk writer. The writer checks out again when the returned guard is dropped,
+    /// so that a cancelled write does not block committing or aborting the transfer forever.
+    pub async fn start_writing(&self) -> WritingGuard<'_> {
+        self.set_state(FileTransferState::Writing).await;
+        WritingGuard { transfer: self }
+    }
+}
+
+/// A chunk writer of a [`FileTransfer`], see [`FileTransfer::start_writing()`]
+pub struct WritingGuard<'a> {
+    transfer: &'a FileTransfer,
+}
+
+impl Drop for WritingGuard<'_> {
+    fn drop(&mut self) {
+        self.transfer.try_set_state(FileTransferState::Ready).ok();
+    }
 }
 
 #[derive(Clone)]
@@ -1,4 +1,6 @@
 pub mod c3;
+pub mod s3;
 pub mod sfs;
+pub mod utils;
 
 pub use sfs::Sfs;
@@ -0,0 +1,353 @@
+use crate::http::config::S3Config;
+use crate::storage::vfs::{
+    ChunkContents, ChunkReader, FileChunk, FileKey, FileRepository, FileStatus, FileSystemError,
+};
+
+use crabdrive_common::da;
+use crabdrive_common::data::DataAmount;
+use crabdrive_common::storage::ChunkIndex;
+use crabdrive_common::uuid::UUID;
+
+use async_trait::async_trait;
+use bytes::BytesMut;
+use futures_util::TryStreamExt;
+use object_store::aws::{AmazonS3, AmazonS3Builder, S3CopyIfNotExists};
+use object_store::path::Path;
+use object_store::{ObjectStore, PutMode, PutPayload, WriteMultipart};
+use tokio::io::AsyncReadExt;
+use tokio_util::io::StreamReader;
+use tracing::{debug, instrument};
+
+/// Name of the object marking a file as being uploaded
+const TRANSFER_MARKER: &str = "transfer";
+/// Name of the object marking a file as completely persisted. It is written after all chunks have
+/// been copied, so a failed commit never leaves behind a file which looks complete.
+const COMMIT_MARKER: &str = "committed";
+/// How many objects are copied or deleted at the same time
+const CONCURRENT_REQUESTS: usize = 8;
+/// The size of the parts chunks are streamed in. This is the smallest part size S3 accepts (except
+/// for the last part).
+const PART_SIZE: usize = 5 * 1024 * 1024;
+/// How many parts of a chunk are uploaded at the same time. Together with [`PART_SIZE`], this
+/// limits the memory a chunk upload needs.
+const CONCURRENT_PARTS: usize = 2;
+
+/// Stores chunks as objects in an S3-compatible bucket. The layout mirrors [`super::c3::C3`]:
+///
+/// - `stage/{key}/{index}` contains the chunks of files which are still being uploaded
+/// - `pers/{key}/{index}` contains the chunks of committed files
+///
+/// Object storages cannot rename objects, so committing a file copies its chunks from the staging
+/// prefix into the persistent one.
+///
+/// Streamed chunks are uploaded to a temporary object in multiple parts first. Multipart uploads,
+/// which are interrupted (e.g. by a restart), stay in the bucket until they are aborted, so the
+/// bucket should have a lifecycle rule removing incomplete multipart uploads.
+pub struct S3 {
+    store: AmazonS3,
+}
+
+impl S3 {
+    #[instrument(skip(config), fields(bucket = %config.bucket))]
+    pub fn new(config: &S3Config) -> Self {
+        let mut builder = AmazonS3Builder::new()
+            .with_bucket_name(&config.bucket)
+            .with_region(&config.region)
+            // Streamed chunks are moved into place with a multipart upload, which copies the
+            // temporary object and is only completed if the chunk does not exist yet
+            .with_copy_if_not_exists(S3CopyIfNotExists::Multipart);
+
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder
+                .with_allow_http(endpoint.starts_with("http://"))
+                .with_endpoint(endpoint);
+        }
+        if let Some(access_key_id) = &config.access_key_id {
+            builder = builder.with_access_key_id(access_key_id);
+        }
+        if let Some(secret_access_key) = &config.secret_access_key {
+            builder = builder.with_secret_access_key(secret_access_key);
+        }
+
+        let store = builder
+            .build()
+            .expect("Failed to start: S3 configuration is invalid.");
+
+        tracing::info!("Files will be stored in the bucket {}", config.bucket);
+        Self { store }
+    }
+
+    fn staging_prefix(key: &FileKey) -> Path {
+        Path::from_iter(["stage".to_string(), key.to_string()])
+    }
+
+    fn persistent_prefix(key: &FileKey) -> Path {
+        Path::from_iter(["pers".to_string(), key.to_string()])
+    }
+
+    fn staged(key: &FileKey, name: impl ToString) -> Path {
+        S3::staging_prefix(key).child(name.to_string())
+    }
+
+    fn persisted(key: &FileKey, name: impl ToString) -> Path {
+        S3::persistent_prefix(key).child(name.to_string())
+    }
+
+    /// Name of the temporary object a chunk is streamed into. It is not a valid chunk index, so
+    /// it is never committed.
+    fn temporary(index: ChunkIndex) -> String {
+        format!(".{index}.{}.part", UUID::random())
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool, FileSystemError> {
+        match self.store.head(path).await {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Upload a chunk into the staging area, unless it exists already
+    async fn put_chunk(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+        payload: PutPayload,
+    ) -> Result<(), FileSystemError> {
+        self.ensure_transfer(key).await?;
+        self.store
+            .put_opts(&S3::staged(key, index), payload, PutMode::Create.into())
+            .await?;
+        Ok(())
+    }
+
+    /// Fail with [`FileSystemError::NotFound`] if the file is not being uploaded
+    async fn ensure_transfer(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        if !self.exists(&S3::staged(key, TRANSFER_MARKER)).await? {
+            return Err(FileSystemError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Upload `contents` to `path` in multiple parts. The upload is aborted if reading the
+    /// contents fails or they exceed `max_size`.
+    async fn put_stream(
+        &self,
+        path: &Path,
+        contents: ChunkReader,
+        max_size: DataAmount,
+    ) -> Result<DataAmount, FileSystemError> {
+        let upload = self.store.put_multipart(path).await?;
+        let mut writer = WriteMultipart::new_with_chunk_size(upload, PART_SIZE);
+
+        // Read one byte more than allowed, otherwise an oversized chunk is indistinguishable from
+        // one with exactly the maximum size
+        let mut contents = contents.take(max_size.as_bytes() + 1);
+        let mut buffer = BytesMut::with_capacity(64 * 1024);
+        let mut size = 0;
+        let result = loop {
+            buffer.clear();
+            match contents.read_buf(&mut buffer).await {
+                Ok(0) => break Ok(()),
+                Ok(read) => size += read as u64,
+                Err(e) => break Err(e.into()),
+            }
+            if size > max_size.as_bytes() {
+                break Err(FileSystemError::TooLarge(max_size));
+            }
+            if let Err(e) = writer.wait_for_capacity(CONCURRENT_PARTS).await {
+                break Err(e.into());
+            }
+            writer.write(&buffer);
+        };
+
+        if let Err(e) = result {
+            writer.abort().await.ok();
+            return Err(e);
+        }
+        writer.finish().await?;
+        Ok(da!(size))
+    }
+
+    /// Delete all objects below `prefix`
+    async fn delete_prefix(&self, prefix: Path) -> Result<(), FileSystemError> {
+        // Deleted one by one, as not every S3-compatible storage supports bulk deletions
+        self.store
+            .list(Some(&prefix))
+            .map_ok(|object| async move {
+                match self.store.delete(&object.location).await {
+                    Err(object_store::Error::NotFound { .. }) => Ok(()),
+                    result => result,
+                }
+            })
+            .try_buffer_unordered(CONCURRENT_REQUESTS)
+            .try_collect::<()>()
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FileRepository for S3 {
+    async fn chunk_exists(&self, key: &FileKey, index: ChunkIndex) -> bool {
+        self.exists(&S3::staged(key, index)).await.unwrap_or(false)
+    }
+
+    async fn file_status(&self, key: &FileKey) -> FileStatus {
+        if self
+            .exists(&S3::persisted(key, COMMIT_MARKER))
+            .await
+            .unwrap_or(false)
+        {
+            FileStatus::Persisted
+        } else if self
+            .exists(&S3::staged(key, TRANSFER_MARKER))
+            .await
+            .unwrap_or(false)
+        {
+            FileStatus::Staged
+        } else {
+            FileStatus::NotFound
+        }
+    }
+
+    #[instrument(skip(self), fields(key = %key))]
+    async fn create_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        self.store
+            .put_opts(
+                &S3::staged(key, TRANSFER_MARKER),
+                PutPayload::new(),
+                PutMode::Create.into(),
+            )
+            .await?;
+        debug!("Chunks will be stored in {}", S3::staging_prefix(key));
+        Ok(())
+    }
+
+    #[instrument(skip(self, contents), fields(key = %key))]
+    async fn write_chunk(&self, key: &FileKey, contents: FileChunk) -> Result<(), FileSystemError> {
+        let size = da!(contents.data.len());
+        self.put_chunk(key, contents.index, contents.data.into())
+            .await?;
+        debug!("Wrote chunk {} (Size: {})", contents.index, size);
+        Ok(())
+    }
+
+    #[instrument(skip(self, contents), fields(key = %key))]
+    async fn write_chunk_stream(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+        contents: ChunkReader,
+        max_size: DataAmount,
+    ) -> Result<DataAmount, FileSystemError> {
+        self.ensure_transfer(key).await?;
+
+        // Streamed multipart uploads always overwrite their target, so the chunk is streamed into
+        // a temporary object, which is then copied into place unless the chunk exists already
+        let temporary = S3::staged(key, S3::temporary(index));
+        let size = self.put_stream(&temporary, contents, max_size).await?;
+        let result = self
+            .store
+            .copy_if_not_exists(&temporary, &S3::staged(key, index))
+            .await;
+        // A left over temporary object is removed with the staging area at the latest
+        self.store.delete(&temporary).await.ok();
+        result?;
+
+        debug!("Wrote chunk {} (Size: {})", index, size);
+        Ok(size)
+    }
+
+    #[instrument(skip(self), fields(key = %key))]
+    async fn delete_chunk(&self, key: &FileKey, index: ChunkIndex) -> Result<(), FileSystemError> {
+        Ok(self.store.delete(&S3::staged(key, index)).await?)
+    }
+
+    #[instrument(skip(self), fields(key = %key))]
+    async fn commit_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        self.ensure_transfer(key).await?;
+
+        let chunks = self
+            .store
+            .list(Some(&S3::staging_prefix(key)))
+            .try_filter_map(|object| async move {
+                Ok(object
+                    .location
+                    .filename()
+                    .and_then(|name| name.parse::<ChunkIndex>().ok()))
+            })
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        futures_util::stream::iter(chunks.iter().map(Ok))
+            .try_for_each_concurrent(CONCURRENT_REQUESTS, |index| async move {
+                self.store
+                    .copy(&S3::staged(key, index), &S3::persisted(key, index))
+                    .await
+            })
+            .await?;
+        self.store
+            .put(&S3::persisted(key, COMMIT_MARKER), PutPayload::new())
+            .await?;
+        debug!(
+            "Copied {} chunks of {} into the persistent area",
+            chunks.len(),
+            key
+        );
+
+        self.delete_prefix(S3::staging_prefix(key)).await
+    }
+
+    #[instrument(skip(self), fields(key = %key))]
+    async fn abort(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        self.ensure_transfer(key).await?;
+        debug!("Transfer {} aborted", key);
+        self.delete_prefix(S3::staging_prefix(key)).await
+    }
+
+    #[instrument(skip(self), fields(key = %key))]
+    async fn delete_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        if self.file_status(key).await != FileStatus::Persisted {
+            return Err(FileSystemError::NotFound);
+        }
+        // Remove the marker first, so the file is gone even if deleting some chunk fails
+        self.store
+            .delete(&S3::persisted(key, COMMIT_MARKER))
+            .await?;
+        self.delete_prefix(S3::persistent_prefix(key)).await
+    }
+
+    #[instrument(skip(self), fields(key = %key))]
+    async fn read_chunk(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+    ) -> Result<FileChunk, FileSystemError> {
+        let data = self
+            .store
+            .get(&S3::persisted(key, index))
+            .await?
+            .bytes()
+            .await?;
+        Ok(FileChunk { index, data })
+    }
+
+    #[instrument(skip(self), fields(key = %key))]
+    async fn open_chunk(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+    ) -> Result<ChunkContents, FileSystemError> {
+        let object = self.store.get(&S3::persisted(key, index)).await?;
+        let size = da!(object.meta.size);
+        let contents = object
+            .into_stream()
+            .map_err(|e| std::io::Error::other(e.to_string()));
+
+        Ok(ChunkContents::Stream {
+            contents: Box::pin(StreamReader::new(contents)),
+            size,
+        })
+    }
+}
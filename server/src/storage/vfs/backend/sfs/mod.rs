@@ -1,6 +1,7 @@
-use crate::storage::vfs::{FileChunk, FileKey, FileRepository};
+use crate::storage::vfs::backend::utils::{open_chunk_file, write_stream_to_file};
+use crate::storage::vfs::{ChunkContents, ChunkReader, FileChunk, FileKey, FileRepository};
 use bytes::BytesMut;
-use crabdrive_common::{da, storage::ChunkIndex};
+use crabdrive_common::{da, data::DataAmount, storage::ChunkIndex};
 use std::{
     fs::OpenOptions,
     io::{Read, Write},
@@ -9,12 +10,13 @@ use tracing::{debug, error, instrument};
 
 use crate::storage::vfs::model::{FileStatus, FileSystemError};
 use crabdrive_common::storage::RevisionId;
-use std::{collections::HashMap, path::PathBuf};
+use dashmap::DashMap;
+use std::path::PathBuf;
 
 /// S(tupid)imple File System
 pub struct Sfs {
     storage_dir: PathBuf,
-    sessions: HashMap<RevisionId, PathBuf>,
+    sessions: DashMap<RevisionId, PathBuf>,
 }
 
 impl Sfs {
@@ -23,7 +25,7 @@ impl Sfs {
         tracing::info!("Files will be stored in {}", storage_dir.display());
         Self {
             storage_dir,
-            sessions: HashMap::new(),
+            sessions: DashMap::new(),
         }
     }
 }
@@ -54,7 +56,7 @@ impl FileRepository for Sfs {
     }
 
     #[instrument(skip(self), fields(key = %key))]
-    async fn create_file(&mut self, key: &FileKey) -> Result<(), FileSystemError> {
+    async fn create_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
         let session = *key;
         let mut pathbuf = self.storage_dir.clone();
         pathbuf.push(key.to_string());
@@ -65,25 +67,24 @@ impl FileRepository for Sfs {
     }
 
     #[instrument(skip(self, contents), fields(key = %key))]
-    async fn write_chunk(
-        &mut self,
-        key: &FileKey,
-        contents: FileChunk,
-    ) -> Result<(), FileSystemError> {
-        if !self.sessions.contains_key(key) {
+    async fn write_chunk(&self, key: &FileKey, contents: FileChunk) -> Result<(), FileSystemError> {
+        let Some(path) = self.sessions.get(key) else {
             error!("Invalid session");
             return Err(FileSystemError::NotFound);
-        }
-
-        let path = self.sessions.get(key).unwrap();
+        };
 
         let mut pathbuf = path.clone();
+        drop(path);
         pathbuf.push(contents.index.to_string());
         pathbuf.set_extension("bin");
         let mut file_handle = OpenOptions::new()
             .write(true)
             .create_new(true)
-            .open(&pathbuf)?;
+            .open(&pathbuf)
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::AlreadyExists => FileSystemError::AlreadyExists,
+                _ => e.into(),
+            })?;
         file_handle.write_all(&contents.data)?;
 
         debug!(
@@ -96,10 +97,52 @@ impl FileRepository for Sfs {
         Ok(())
     }
 
+    #[instrument(skip(self, contents), fields(key = %key))]
+    async fn write_chunk_stream(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+        contents: ChunkReader,
+        max_size: DataAmount,
+    ) -> Result<DataAmount, FileSystemError> {
+        let Some(path) = self.sessions.get(key) else {
+            error!("Invalid session");
+            return Err(FileSystemError::NotFound);
+        };
+
+        let mut pathbuf = path.clone();
+        drop(path);
+        pathbuf.push(index.to_string());
+        pathbuf.set_extension("bin");
+
+        let size = write_stream_to_file(&pathbuf, contents, max_size).await?;
+
+        debug!(
+            "Wrote chunk {} (Size: {}) to {}",
+            index,
+            size,
+            pathbuf.display()
+        );
+
+        Ok(size)
+    }
+
+    #[instrument(skip(self), fields(key = %key))]
+    async fn delete_chunk(&self, key: &FileKey, index: ChunkIndex) -> Result<(), FileSystemError> {
+        let Some(path) = self.sessions.get(key) else {
+            error!("Invalid session");
+            return Err(FileSystemError::NotFound);
+        };
+
+        let mut pathbuf = path.clone();
+        pathbuf.push(index.to_string());
+        pathbuf.set_extension("bin");
+        Ok(std::fs::remove_file(&pathbuf)?)
+    }
+
     #[instrument(skip(self), fields(key = %key))]
-    async fn commit_file(&mut self, key: &FileKey) -> Result<(), FileSystemError> {
-        if self.sessions.contains_key(key) {
-            self.sessions.remove(key);
+    async fn commit_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        if self.sessions.remove(key).is_some() {
             debug!("Session {} removed", key);
             Ok(())
         } else {
@@ -108,10 +151,16 @@ impl FileRepository for Sfs {
         }
     }
 
-    async fn abort(&mut self, _: &FileKey) -> Result<(), FileSystemError> {
-        unimplemented!("SFS does not implement this functionality.")
+    #[instrument(skip(self), fields(key = %key))]
+    async fn abort(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        let Some((_, path)) = self.sessions.remove(key) else {
+            error!("Invalid session");
+            return Err(FileSystemError::NotFound);
+        };
+        debug!("Session {} aborted", key);
+        Ok(std::fs::remove_dir_all(&path)?)
     }
-    async fn delete_file(&mut self, key: &FileKey) -> Result<(), FileSystemError> {
+    async fn delete_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
         if self.file_status(key).await != FileStatus::Persisted {
             return Err(FileSystemError::NotFound);
         }
@@ -155,4 +204,22 @@ impl FileRepository for Sfs {
             data: bytes.freeze(),
         })
     }
+
+    #[instrument(skip(self), fields(key = %key))]
+    async fn open_chunk(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+    ) -> Result<ChunkContents, FileSystemError> {
+        if self.file_status(key).await != FileStatus::Persisted {
+            return Err(FileSystemError::NotFound);
+        }
+
+        let mut pathbuf = self.storage_dir.clone();
+        pathbuf.push(key.to_string());
+        pathbuf.push(index.to_string());
+        pathbuf.set_extension("bin");
+
+        open_chunk_file(&pathbuf).await
+    }
 }
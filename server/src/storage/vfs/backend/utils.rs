@@ -0,0 +1,62 @@
+use crate::storage::vfs::{ChunkContents, ChunkReader, FileSystemError};
+
+use crabdrive_common::da;
+use crabdrive_common::data::DataAmount;
+
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Write the contents of a chunk into a new file at `path`, while they are received.
+///
+/// The contents go into a temporary file next to `path`, which is moved into place once the whole
+/// chunk was written. The temporary file is removed if anything fails in between, including the
+/// future being dropped (e.g. because the client disconnected). Fails with
+/// [`FileSystemError::AlreadyExists`], if there is a file at `path` already.
+pub async fn write_stream_to_file(
+    path: &Path,
+    contents: ChunkReader,
+    max_size: DataAmount,
+) -> Result<DataAmount, FileSystemError> {
+    let dir = path.parent().ok_or(FileSystemError::NotFound)?;
+    let (file, temp_path) = tempfile::Builder::new()
+        .prefix(".")
+        .suffix(".part")
+        .tempfile_in(dir)?
+        .into_parts();
+    let mut file = File::from_std(file);
+
+    // Read one byte more than allowed, otherwise an oversized chunk is indistinguishable from one
+    // with exactly the maximum size
+    let mut contents = contents.take(max_size.as_bytes() + 1);
+    let size = tokio::io::copy(&mut contents, &mut file).await?;
+    if size > max_size.as_bytes() {
+        return Err(FileSystemError::TooLarge(max_size));
+    }
+
+    file.flush().await?;
+    drop(file);
+
+    temp_path
+        .persist_noclobber(path)
+        .map_err(|e| match e.error.kind() {
+            std::io::ErrorKind::AlreadyExists => FileSystemError::AlreadyExists,
+            _ => e.error.into(),
+        })?;
+
+    Ok(da!(size))
+}
+
+/// Open the file of a stored chunk for reading
+pub async fn open_chunk_file(path: &Path) -> Result<ChunkContents, FileSystemError> {
+    let file = File::open(path).await.map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => FileSystemError::NotFound,
+        _ => e.into(),
+    })?;
+    let size = file.metadata().await?.len();
+
+    Ok(ChunkContents::File {
+        file,
+        size: da!(size),
+    })
+}
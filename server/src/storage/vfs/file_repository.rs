@@ -1,4 +1,7 @@
-use crate::storage::vfs::model::{FileChunk, FileKey, FileStatus, FileSystemError};
+use crate::storage::vfs::model::{
+    ChunkContents, ChunkReader, FileChunk, FileKey, FileStatus, FileSystemError,
+};
+use crabdrive_common::data::DataAmount;
 use crabdrive_common::storage::ChunkIndex;
 
 #[async_trait::async_trait]
@@ -7,23 +10,40 @@ pub trait FileRepository {
     /// Check if a file exists
     async fn file_status(&self, key: &FileKey) -> FileStatus;
     /// Create a new file with the given key in the staging area.
-    async fn create_file(&mut self, key: &FileKey) -> Result<(), FileSystemError>;
+    async fn create_file(&self, key: &FileKey) -> Result<(), FileSystemError>;
     /// Write a new chunk into a file key
-    async fn write_chunk(
-        &mut self,
+    async fn write_chunk(&self, key: &FileKey, contents: FileChunk) -> Result<(), FileSystemError>;
+    /// Write a new chunk into a file key while its contents are received, without buffering the
+    /// whole chunk in memory. Chunks exceeding `max_size` are rejected with
+    /// [`FileSystemError::TooLarge`]. Nothing is stored if reading the contents fails.
+    ///
+    /// Returns the size of the written chunk.
+    async fn write_chunk_stream(
+        &self,
         key: &FileKey,
-        contents: FileChunk,
-    ) -> Result<(), FileSystemError>;
+        index: ChunkIndex,
+        contents: ChunkReader,
+        max_size: DataAmount,
+    ) -> Result<DataAmount, FileSystemError>;
+    /// Remove a chunk from a file in the staging area
+    async fn delete_chunk(&self, key: &FileKey, index: ChunkIndex) -> Result<(), FileSystemError>;
     /// Transfer a file from the staging area into the permanent storage area
-    async fn commit_file(&mut self, key: &FileKey) -> Result<(), FileSystemError>;
+    async fn commit_file(&self, key: &FileKey) -> Result<(), FileSystemError>;
     /// Abort the file upload of a file
-    async fn abort(&mut self, key: &FileKey) -> Result<(), FileSystemError>;
+    async fn abort(&self, key: &FileKey) -> Result<(), FileSystemError>;
     /// Delete a file and all it's chunk contents permanently
-    async fn delete_file(&mut self, key: &FileKey) -> Result<(), FileSystemError>;
+    async fn delete_file(&self, key: &FileKey) -> Result<(), FileSystemError>;
     /// Get the contents of a file
     async fn read_chunk(
         &self,
         key: &FileKey,
         index: ChunkIndex,
     ) -> Result<FileChunk, FileSystemError>;
+    /// Get the contents of a chunk without reading it into memory first, unless it is in memory
+    /// already. This should be preferred over [`FileRepository::read_chunk`] for large downloads.
+    async fn open_chunk(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+    ) -> Result<ChunkContents, FileSystemError>;
 }
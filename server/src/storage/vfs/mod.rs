@@ -3,6 +3,8 @@ pub mod file_repository;
 pub mod model;
 
 pub use file_repository::FileRepository;
+pub use model::ChunkContents;
+pub use model::ChunkReader;
 pub use model::FileChunk;
 pub use model::FileKey;
 pub use model::FileStatus;
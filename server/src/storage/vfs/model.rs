@@ -1,9 +1,10 @@
-use axum::Json;
-use axum::http::StatusCode;
-use axum::response::{IntoResponse, Response};
 use bytes::Bytes;
+use crabdrive_common::data::{DataAmount, DataUnit};
 use crabdrive_common::storage::{ChunkIndex, RevisionId};
+use std::pin::Pin;
 use thiserror::Error;
+use tokio::fs::File;
+use tokio::io::AsyncRead;
 
 #[derive(Error, Debug, Clone)]
 pub enum FileSystemError {
@@ -13,6 +14,9 @@ pub enum FileSystemError {
     #[error("File already exists.")]
     AlreadyExists,
 
+    #[error("Chunk exceeds the maximum size of {0}.")]
+    TooLarge(DataAmount),
+
     #[error("IO Error: {1} ({0})")]
     Io(std::io::ErrorKind, String),
 }
@@ -23,20 +27,19 @@ impl From<std::io::Error> for FileSystemError {
     }
 }
 
-impl From<std::sync::Arc<FileSystemError>> for FileSystemError {
-    fn from(arc_err: std::sync::Arc<FileSystemError>) -> Self {
-        (*arc_err).clone()
+impl From<object_store::Error> for FileSystemError {
+    fn from(value: object_store::Error) -> Self {
+        match value {
+            object_store::Error::NotFound { .. } => FileSystemError::NotFound,
+            object_store::Error::AlreadyExists { .. } => FileSystemError::AlreadyExists,
+            _ => FileSystemError::Io(std::io::ErrorKind::Other, value.to_string()),
+        }
     }
 }
 
-impl IntoResponse for FileSystemError {
-    fn into_response(self) -> Response {
-        match self {
-            FileSystemError::NotFound => (StatusCode::NOT_FOUND, Json(())),
-            FileSystemError::AlreadyExists => (StatusCode::CONFLICT, Json(())),
-            FileSystemError::Io(_, _) => (StatusCode::INTERNAL_SERVER_ERROR, Json(())),
-        }
-        .into_response()
+impl From<std::sync::Arc<FileSystemError>> for FileSystemError {
+    fn from(arc_err: std::sync::Arc<FileSystemError>) -> Self {
+        (*arc_err).clone()
     }
 }
 
@@ -62,3 +65,29 @@ pub struct FileChunk {
     /// usually 16MB, however it may be smaller if this is the last (or only) chunk.
     pub data: Bytes,
 }
+
+/// The contents of a stored chunk
+pub enum ChunkContents {
+    /// The chunk is in memory already (e.g. because it was cached)
+    Buffered(Bytes),
+    /// The chunk is still on disk and needs to be read from the file
+    File { file: File, size: DataAmount },
+    /// The chunk is received from a remote storage (e.g. an object storage) while it is read
+    Stream {
+        contents: ChunkReader,
+        size: DataAmount,
+    },
+}
+
+impl ChunkContents {
+    pub fn size(&self) -> DataAmount {
+        match self {
+            ChunkContents::Buffered(bytes) => DataAmount::new(bytes.len() as f64, DataUnit::Byte),
+            ChunkContents::File { size, .. } | ChunkContents::Stream { size, .. } => *size,
+        }
+    }
+}
+
+/// The contents of a chunk, which are read while the chunk is written (e.g. directly from the
+/// body of a request)
+pub type ChunkReader = Pin<Box<dyn AsyncRead + Send>>;
@@ -0,0 +1,437 @@
+use crate::DEFAULT_INVITE_CODE;
+use crate::storage::vfs::model::FileStatus;
+use crate::test::utils::TestContext;
+
+use crabdrive_common::da;
+use crabdrive_common::payloads::admin::{
+    request::invite::*, request::user::*, response::invite::*, response::user::*,
+};
+use crabdrive_common::payloads::auth::request::register::PostRegisterRequest;
+use crabdrive_common::payloads::auth::response::register::RegisterConflictReason;
+use crabdrive_common::payloads::node::{request::share::*, response::share::*};
+use crabdrive_common::routes;
+use crabdrive_common::storage::SharePermission;
+use crabdrive_common::user::{UserKeys, UserType};
+use crabdrive_common::uuid::UUID;
+
+use axum::http::StatusCode;
+use pretty_assertions::assert_eq;
+
+#[tokio::test]
+pub async fn test_get_user() {
+    let ctx = TestContext::new(2).await;
+    let admin = ctx.get_user(0);
+    admin.set_user_type(UserType::Admin);
+    let user = ctx.get_user(1);
+
+    let response = admin.get(routes::admin::user_by_id(user.id)).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let GetUserResponse::Ok(info) = response.json() else {
+        panic!("Expected Ok with user info");
+    };
+
+    assert_eq!(info.id, user.id);
+    assert_eq!(info.username, user.username);
+    assert_eq!(info.user_type, UserType::User);
+    assert_eq!(info.storage_limit, Some(da!(128 MiB)));
+}
+
+#[tokio::test]
+pub async fn test_get_invalid_user() {
+    let ctx = TestContext::new(1).await;
+    let admin = ctx.get_user(0);
+    admin.set_user_type(UserType::Admin);
+
+    let response = admin.get(routes::admin::user_by_id(UUID::random())).await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+pub async fn test_list_users() {
+    let ctx = TestContext::new(3).await;
+    let admin = ctx.get_user(0);
+    admin.set_user_type(UserType::Admin);
+
+    let response = admin.get(routes::admin::user_list(0, 2)).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let GetUserListResponse::Ok(first_page) = response.json() else {
+        panic!("Expected Ok with user list");
+    };
+    assert_eq!(first_page.total, 3);
+    assert_eq!(first_page.users.len(), 2);
+
+    let response = admin.get(routes::admin::user_list(1, 2)).await;
+    let GetUserListResponse::Ok(second_page) = response.json() else {
+        panic!("Expected Ok with user list");
+    };
+    assert_eq!(second_page.total, 3);
+    assert_eq!(second_page.users.len(), 1);
+
+    let mut listed: Vec<_> = first_page
+        .users
+        .iter()
+        .chain(second_page.users.iter())
+        .map(|user| user.id)
+        .collect();
+    let mut expected: Vec<_> = ctx.users.iter().map(|user| user.id).collect();
+    listed.sort();
+    expected.sort();
+    assert_eq!(listed, expected);
+}
+
+#[tokio::test]
+pub async fn test_admin_routes_without_admin() {
+    let ctx = TestContext::new(2).await;
+    let user = ctx.get_user(0);
+    let other = ctx.get_user(1);
+
+    let response = user.get(routes::admin::user_by_id(other.id)).await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let response = user.get(routes::admin::user()).await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let response = user.delete(routes::admin::user_by_id(other.id)).await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+    assert!(other.fetch_node_from_db(other.get_root()).is_some());
+
+    // Restricted users are no admins either
+    user.set_user_type(UserType::Restricted);
+    let response = user.get(routes::admin::user()).await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+pub async fn test_admin_routes_without_token() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let response = ctx.server.get(&routes::admin::user_by_id(user.id)).await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+}
+
+fn register_request(username: &str, invite_code: &str) -> PostRegisterRequest {
+    PostRegisterRequest {
+        username: username.to_string(),
+        password: TestContext::random_text(),
+        keys: UserKeys::nil(),
+        invite_code: invite_code.to_string(),
+        recovery_key: None,
+        kdf_params: TestContext::random_kdf_params(),
+    }
+}
+
+#[tokio::test]
+pub async fn test_post_user() {
+    let ctx = TestContext::new(1).await;
+    let admin = ctx.get_user(0);
+    admin.set_user_type(UserType::Admin);
+
+    let payload = PostUserRequest {
+        username: TestContext::random_text(),
+        user_type: UserType::Restricted,
+        storage_limit: Some(da!(1 GB)),
+    };
+
+    let response = admin.post(routes::admin::user()).json(&payload).await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+
+    let PostUserResponse::Created(invite) = response.json() else {
+        panic!("Expected Created with invite code");
+    };
+
+    assert_eq!(invite.info.username.as_ref(), Some(&payload.username));
+    assert_eq!(invite.info.max_uses, Some(1));
+    assert_eq!(invite.info.user_type, UserType::Restricted);
+    assert_eq!(invite.info.storage_limit, da!(1 GB));
+
+    // The account does not exist before the user registered
+    assert!(
+        ctx.state
+            .user_repository
+            .get_user_by_username(&payload.username)
+            .unwrap()
+            .is_none()
+    );
+
+    // The username is reserved
+    let conflict = admin.post(routes::admin::user()).json(&payload).await;
+    assert_eq!(conflict.status_code(), StatusCode::CONFLICT);
+    let PostUserResponse::Conflict(reason) = conflict.json() else {
+        panic!("Expected Conflict");
+    };
+    assert_eq!(reason, RegisterConflictReason::UsernameTaken);
+
+    let response = ctx
+        .server
+        .post(&routes::auth::register())
+        .json(&register_request(&payload.username, DEFAULT_INVITE_CODE))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CONFLICT);
+
+    // The code cannot be used for other usernames
+    let response = ctx
+        .server
+        .post(&routes::auth::register())
+        .json(&register_request(&TestContext::random_text(), &invite.code))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let response = ctx
+        .server
+        .post(&routes::auth::register())
+        .json(&register_request(&payload.username, &invite.code))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+
+    let user = ctx
+        .state
+        .user_repository
+        .get_user_by_username(&payload.username)
+        .unwrap()
+        .expect("User was not created");
+    assert_eq!(user.user_type, UserType::Restricted);
+    assert_eq!(user.storage_limit, da!(1 GB));
+    assert!(!user.encryption_uninitialized);
+
+    let conflict = admin.post(routes::admin::user()).json(&payload).await;
+    assert_eq!(conflict.status_code(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+pub async fn test_list_users_with_invalid_page() {
+    let ctx = TestContext::new(1).await;
+    let admin = ctx.get_user(0);
+    admin.set_user_type(UserType::Admin);
+
+    for (page, per_page) in [(0, 0), (0, 501), (u64::MAX, 2), (u64::MAX / 2, 2)] {
+        let response = admin.get(routes::admin::user_list(page, per_page)).await;
+        assert_eq!(
+            response.status_code(),
+            StatusCode::BAD_REQUEST,
+            "page {page}, per_page {per_page}"
+        );
+    }
+}
+
+#[tokio::test]
+pub async fn test_patch_user() {
+    let ctx = TestContext::new(2).await;
+    let admin = ctx.get_user(0);
+    admin.set_user_type(UserType::Admin);
+    let user = ctx.get_user(1);
+
+    let payload = PatchUserRequest {
+        user_type: Some(UserType::Admin),
+        storage_limit: Some(da!(2 GB)),
+        ..Default::default()
+    };
+
+    let response = admin
+        .patch(routes::admin::user_by_id(user.id))
+        .json(&payload)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let PatchUserResponse::Ok(info) = response.json() else {
+        panic!("Expected Ok with user info");
+    };
+
+    assert_eq!(info.username, user.username);
+    assert_eq!(info.user_type, UserType::Admin);
+    assert_eq!(info.storage_limit, Some(da!(2 GB)));
+    assert!(info.updated_on >= info.created_on);
+}
+
+#[tokio::test]
+pub async fn test_patch_user_with_taken_username() {
+    let ctx = TestContext::new(2).await;
+    let admin = ctx.get_user(0);
+    admin.set_user_type(UserType::Admin);
+    let user = ctx.get_user(1);
+
+    let payload = PatchUserRequest {
+        username: Some(admin.username.clone()),
+        ..Default::default()
+    };
+
+    let response = admin
+        .patch(routes::admin::user_by_id(user.id))
+        .json(&payload)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+pub async fn test_delete_user() {
+    let ctx = TestContext::new(3).await;
+    let admin = ctx.get_user(0);
+    admin.set_user_type(UserType::Admin);
+    let user = ctx.get_user(1);
+    let other = ctx.get_user(2);
+
+    let folder = user.generate_random_folder().await;
+    let file = user.generate_file_in(folder.id).await;
+    let revision = file.active_revision.clone().unwrap();
+
+    // A node shared by the deleted user ...
+    let share_response = user
+        .post(routes::node::share::share(folder.id))
+        .json(&PostShareNodeRequest {
+            wrapped_metadata_key: user.keys.master_key.clone(),
+            permission: SharePermission::Edit,
+            expires_at: None,
+            revoke_on_expiry: false,
+        })
+        .await;
+    let PostShareNodeResponse::Ok(share_id) = share_response.json() else {
+        panic!("Expected Ok");
+    };
+    other
+        .post(routes::node::share::accept_share(share_id))
+        .json(&PostAcceptShareRequest {
+            new_wrapped_metadata_key: other.keys.master_key.clone(),
+        })
+        .await
+        .assert_status_ok();
+
+    // ... and a node accepted by the deleted user
+    let other_folder = other.generate_random_folder().await;
+    let share_response = other
+        .post(routes::node::share::share(other_folder.id))
+        .json(&PostShareNodeRequest {
+            wrapped_metadata_key: other.keys.master_key.clone(),
+            permission: SharePermission::Edit,
+            expires_at: None,
+            revoke_on_expiry: false,
+        })
+        .await;
+    let PostShareNodeResponse::Ok(share_id) = share_response.json() else {
+        panic!("Expected Ok");
+    };
+    user.post(routes::node::share::accept_share(share_id))
+        .json(&PostAcceptShareRequest {
+            new_wrapped_metadata_key: user.keys.master_key.clone(),
+        })
+        .await
+        .assert_status_ok();
+
+    let response = admin.delete(routes::admin::user_by_id(user.id)).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let DeleteUserResponse::Ok(info) = response.json() else {
+        panic!("Expected Ok with user info");
+    };
+    assert_eq!(info.id, user.id);
+
+    assert!(user.fetch_node_from_db(folder.id).is_none());
+    assert!(user.fetch_node_from_db(file.id).is_none());
+    assert!(user.fetch_node_from_db(user.get_root()).is_none());
+    assert_eq!(
+        ctx.state.vfs.file_status(&revision.id).await,
+        FileStatus::NotFound
+    );
+
+    // The other user keeps their own node, but loses the shared one
+    assert!(other.fetch_node_from_db(other_folder.id).is_some());
+    let GetAcceptedSharedResponse::Ok(shared) = other
+        .get(routes::node::share::get_accepted_shared())
+        .await
+        .json();
+    assert!(shared.is_empty());
+
+    // The token of the deleted user is no longer valid
+    user.get(routes::auth::info())
+        .await
+        .assert_status_unauthorized();
+
+    let response = admin.get(routes::admin::user_by_id(user.id)).await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    let response = admin.delete(routes::admin::user_by_id(user.id)).await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+pub async fn test_create_list_and_revoke_invite_code() {
+    let ctx = TestContext::new(1).await;
+    let admin = ctx.get_user(0);
+    admin.set_user_type(UserType::Admin);
+
+    let payload = PostInviteCodeRequest {
+        expires_at: None,
+        max_uses: Some(3),
+        user_type: UserType::Restricted,
+        storage_limit: Some(da!(1 GB)),
+    };
+
+    let response = admin.post(routes::admin::invite()).json(&payload).await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+
+    let PostInviteCodeResponse::Created(created) = response.json() else {
+        panic!("Expected Created with invite code");
+    };
+    assert!(!created.code.is_empty());
+    assert_eq!(created.info.created_by, Some(admin.id));
+    assert_eq!(created.info.max_uses, Some(3));
+    assert_eq!(created.info.uses, 0);
+    assert_eq!(created.info.user_type, UserType::Restricted);
+    assert_eq!(created.info.storage_limit, da!(1 GB));
+
+    let response = admin.get(routes::admin::invite()).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let GetInviteCodesResponse::Ok(codes) = response.json();
+    assert_eq!(codes, vec![created.info.clone()]);
+
+    let response = admin
+        .delete(routes::admin::invite_by_id(created.info.id))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let response = admin.get(routes::admin::invite()).await;
+    let GetInviteCodesResponse::Ok(codes) = response.json();
+    assert!(codes.is_empty());
+
+    let response = admin
+        .delete(routes::admin::invite_by_id(created.info.id))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+pub async fn test_create_invite_code_with_invalid_max_uses() {
+    let ctx = TestContext::new(1).await;
+    let admin = ctx.get_user(0);
+    admin.set_user_type(UserType::Admin);
+
+    let payload = PostInviteCodeRequest {
+        expires_at: None,
+        max_uses: Some(0),
+        user_type: UserType::User,
+        storage_limit: None,
+    };
+
+    let response = admin.post(routes::admin::invite()).json(&payload).await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+pub async fn test_invite_codes_without_admin() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let payload = PostInviteCodeRequest {
+        expires_at: None,
+        max_uses: None,
+        user_type: UserType::Admin,
+        storage_limit: None,
+    };
+
+    let response = user.post(routes::admin::invite()).json(&payload).await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let response = user.get(routes::admin::invite()).await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+}
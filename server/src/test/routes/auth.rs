@@ -1,19 +1,38 @@
+use std::net::{IpAddr, SocketAddr};
 use std::time::Duration;
 
 use axum_extra::extract::cookie::Cookie;
+use crabdrive_common::encrypted_metadata::EncryptedMetadata;
+use crabdrive_common::encryption_key::EncryptionKey;
 use crabdrive_common::payloads::auth::response::refresh::PostRefreshResponse;
-use crabdrive_common::user::UserKeys;
+use crabdrive_common::payloads::node::request::folder::PostCreateFolderRequest;
+use crabdrive_common::user::{AccessTokenScope, KdfParams, UserKeys, UserType};
+use crabdrive_common::uuid::UUID;
 
 use crabdrive_common::payloads::auth::{
-    request::{login::*, register::*},
-    response::{info::*, login::*, register::*},
+    request::{access_token::*, keys::*, login::*, password::*, recovery::*, register::*, totp::*},
+    response::{
+        access_token::*, info::*, keys::*, login::*, password::*, recovery::*, register::*,
+        session::*, totp::*,
+    },
 };
 use crabdrive_common::routes;
 
-use crate::test::utils::TestContext;
+use crate::db::operations::user::backfill_kdf_salts;
+use crate::http::AppConfig;
+use crate::http::config::AuthConfig;
+use crate::http::server::create_app;
+use crate::service::user::{NewUser, register_user};
+use crate::test::utils::{TestContext, TestUserEntity};
+use crate::user::auth::totp;
 
 use crate::DEFAULT_INVITE_CODE;
-use axum::http::StatusCode;
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::header::{RETRY_AFTER, SET_COOKIE};
+use axum::http::{Request, StatusCode};
+use axum_test::{TestResponse, TestServer};
+use chrono::{TimeDelta, Utc};
 
 #[tokio::test]
 pub async fn test_register() {
@@ -24,6 +43,8 @@ pub async fn test_register() {
         password: TestContext::random_text(),
         keys: UserKeys::nil(),
         invite_code: DEFAULT_INVITE_CODE.to_string(),
+        recovery_key: None,
+        kdf_params: TestContext::random_kdf_params(),
     };
 
     let register_response = ctx
@@ -44,6 +65,8 @@ pub async fn test_register_with_conflicting_username() {
         password: TestContext::random_text(),
         keys: UserKeys::nil(),
         invite_code: DEFAULT_INVITE_CODE.to_string(),
+        recovery_key: None,
+        kdf_params: TestContext::random_kdf_params(),
     };
 
     let register_response = ctx
@@ -87,7 +110,7 @@ pub async fn test_login() {
     let login_responese: PostLoginResponse = login_request.json();
     let login_responese = match login_responese {
         PostLoginResponse::Ok(login_success) => login_success,
-        PostLoginResponse::Unauthorized(_) => panic!("Server returned wrong status code!"),
+        _ => panic!("Server returned wrong status code!"),
     };
 
     assert!(ctx.validate_jwt(&login_responese.bearer_token));
@@ -131,6 +154,8 @@ pub async fn test_register_account_and_login() {
         password: password.clone(),
         keys: keys.clone(),
         invite_code: DEFAULT_INVITE_CODE.to_string(),
+        recovery_key: None,
+        kdf_params: TestContext::random_kdf_params(),
     };
 
     let register_response = ctx
@@ -157,7 +182,7 @@ pub async fn test_register_account_and_login() {
     let login_responese: PostLoginResponse = login_request.json();
     let login_responese = match login_responese {
         PostLoginResponse::Ok(login_success) => login_success,
-        PostLoginResponse::Unauthorized(_) => panic!("Server returned wrong status code!"),
+        _ => panic!("Server returned wrong status code!"),
     };
 
     assert!(ctx.validate_jwt(&login_responese.bearer_token));
@@ -181,6 +206,7 @@ pub async fn test_user_info() {
             username: user.username.clone(),
             storage_limit: crabdrive_common::da!(128 MiB), // In test environments: 128 MiB limit, otherwise 15GB
             storage_used: crabdrive_common::da!(0 B),
+            totp_enabled: false,
         }
     );
 }
@@ -320,3 +346,1667 @@ pub async fn test_refresh_token_reusal_in_grace_period() {
     );
     assert!(refresh_token.http_only().expect("Failed to check cookie"));
 }
+
+fn register_request(invite_code: &str) -> PostRegisterRequest {
+    PostRegisterRequest {
+        username: TestContext::random_text(),
+        password: TestContext::random_text(),
+        keys: UserKeys::nil(),
+        invite_code: invite_code.to_string(),
+        recovery_key: None,
+        kdf_params: TestContext::random_kdf_params(),
+    }
+}
+
+#[tokio::test]
+pub async fn test_register_with_invite_code() {
+    let ctx = TestContext::new(1).await;
+    let admin = ctx.get_user(0);
+
+    let (code, invite) = ctx
+        .state
+        .invite_repository
+        .create_invite_code(
+            admin.id,
+            None,
+            None,
+            UserType::Restricted,
+            crabdrive_common::da!(1 GB),
+        )
+        .unwrap();
+
+    let register_body = register_request(&code);
+    let register_response = ctx
+        .server
+        .post(&routes::auth::register())
+        .json(&register_body)
+        .await;
+
+    assert_eq!(register_response.status_code(), StatusCode::CREATED);
+
+    let user = ctx
+        .state
+        .user_repository
+        .get_user_by_username(&register_body.username)
+        .unwrap()
+        .expect("User was not created");
+    assert_eq!(user.user_type, UserType::Restricted);
+    assert_eq!(user.storage_limit, crabdrive_common::da!(1 GB));
+
+    let codes = ctx.state.invite_repository.list_invite_codes().unwrap();
+    assert_eq!(codes.len(), 1);
+    assert_eq!(codes[0].id, invite.id);
+    assert_eq!(codes[0].uses, 1);
+}
+
+#[tokio::test]
+pub async fn test_register_with_used_up_invite_code() {
+    let ctx = TestContext::new(1).await;
+    let admin = ctx.get_user(0);
+
+    let (code, _) = ctx
+        .state
+        .invite_repository
+        .create_invite_code(
+            admin.id,
+            None,
+            Some(1),
+            UserType::User,
+            crabdrive_common::da!(1 GB),
+        )
+        .unwrap();
+
+    let register_response = ctx
+        .server
+        .post(&routes::auth::register())
+        .json(&register_request(&code))
+        .await;
+    assert_eq!(register_response.status_code(), StatusCode::CREATED);
+
+    let register_response = ctx
+        .server
+        .post(&routes::auth::register())
+        .json(&register_request(&code))
+        .await;
+    assert_eq!(register_response.status_code(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+pub async fn test_failed_registration_keeps_invite_code() {
+    let ctx = TestContext::new(1).await;
+    let admin = ctx.get_user(0);
+
+    let (code, _) = ctx
+        .state
+        .invite_repository
+        .create_invite_code(
+            admin.id,
+            None,
+            Some(1),
+            UserType::User,
+            crabdrive_common::da!(1 GB),
+        )
+        .unwrap();
+
+    // The username is taken, so creating the user fails after the code was redeemed
+    let request = register_request(&code);
+    let new_user = NewUser {
+        username: admin.username.clone(),
+        password: request.password,
+        keys: request.keys,
+        kdf_params: request.kdf_params,
+        recovery_key: None,
+    };
+    assert!(
+        register_user(&ctx.state, new_user, Some(&code))
+            .await
+            .is_err()
+    );
+
+    let codes = ctx.state.invite_repository.list_invite_codes().unwrap();
+    assert_eq!(codes[0].uses, 0);
+
+    let register_response = ctx
+        .server
+        .post(&routes::auth::register())
+        .json(&register_request(&code))
+        .await;
+    assert_eq!(register_response.status_code(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+pub async fn test_register_with_expired_invite_code() {
+    let ctx = TestContext::new(1).await;
+    let admin = ctx.get_user(0);
+
+    let (code, _) = ctx
+        .state
+        .invite_repository
+        .create_invite_code(
+            admin.id,
+            Some(Utc::now().naive_utc() - TimeDelta::minutes(1)),
+            None,
+            UserType::User,
+            crabdrive_common::da!(1 GB),
+        )
+        .unwrap();
+
+    let register_response = ctx
+        .server
+        .post(&routes::auth::register())
+        .json(&register_request(&code))
+        .await;
+    assert_eq!(register_response.status_code(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+pub async fn test_register_with_revoked_invite_code() {
+    let ctx = TestContext::new(1).await;
+    let admin = ctx.get_user(0);
+
+    let (code, invite) = ctx
+        .state
+        .invite_repository
+        .create_invite_code(
+            admin.id,
+            None,
+            None,
+            UserType::User,
+            crabdrive_common::da!(1 GB),
+        )
+        .unwrap();
+
+    ctx.state
+        .invite_repository
+        .delete_invite_code(invite.id)
+        .unwrap()
+        .expect("Invite code not found");
+
+    let register_response = ctx
+        .server
+        .post(&routes::auth::register())
+        .json(&register_request(&code))
+        .await;
+    assert_eq!(register_response.status_code(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+pub async fn test_register_with_wrong_invite_code() {
+    let ctx = TestContext::new(0).await;
+
+    let register_response = ctx
+        .server
+        .post(&routes::auth::register())
+        .json(&register_request(&TestContext::random_text()))
+        .await;
+    assert_eq!(register_response.status_code(), StatusCode::FORBIDDEN);
+}
+
+async fn get_sessions(user: &TestUserEntity) -> Vec<SessionInfo> {
+    let request = user.get(routes::auth::sessions()).await;
+    request.assert_status_ok();
+
+    let GetSessionsResponse::Ok(sessions) = request.json::<GetSessionsResponse>();
+    sessions
+}
+
+#[tokio::test]
+pub async fn test_list_sessions() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    ctx.state.user_repository.create_session(user1.id).unwrap();
+
+    let sessions = get_sessions(user1).await;
+
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions.iter().filter(|session| session.current).count(), 1);
+    assert!(sessions.iter().all(|session| session.created_at.is_some()));
+}
+
+#[tokio::test]
+pub async fn test_revoke_session() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    let (refresh_token, jwt) = ctx.state.user_repository.create_session(user1.id).unwrap();
+
+    let other_session = get_sessions(user1)
+        .await
+        .into_iter()
+        .find(|session| !session.current)
+        .expect("Second session is missing");
+
+    let request = user1
+        .delete(routes::auth::session_by_id(other_session.id))
+        .await;
+    request.assert_status_ok();
+
+    // The JWT of the revoked session is rejected, even though it did not expire yet
+    ctx.server
+        .get(&routes::auth::info())
+        .authorization_bearer(&jwt)
+        .await
+        .assert_status_unauthorized();
+
+    // Refresh tokens are revoked immediately, without a grace period
+    ctx.server
+        .post(&routes::auth::refresh())
+        .add_cookie(Cookie::new("refresh_token", refresh_token))
+        .await
+        .assert_status_unauthorized();
+
+    user1.get(routes::auth::info()).await.assert_status_ok();
+    assert_eq!(get_sessions(user1).await.len(), 1);
+
+    let request = user1
+        .delete(routes::auth::session_by_id(other_session.id))
+        .await;
+    request.assert_status_not_found();
+}
+
+#[tokio::test]
+pub async fn test_revoke_session_of_other_user() {
+    let ctx = TestContext::new(2).await;
+    let user1 = ctx.get_user(0);
+    let user2 = ctx.get_user(1);
+
+    let session = get_sessions(user2)
+        .await
+        .into_iter()
+        .next()
+        .expect("Session is missing");
+
+    let request = user1.delete(routes::auth::session_by_id(session.id)).await;
+    request.assert_status_not_found();
+
+    user2.get(routes::auth::info()).await.assert_status_ok();
+}
+
+#[tokio::test]
+pub async fn test_revoke_other_sessions() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    let (_, jwt1) = ctx.state.user_repository.create_session(user1.id).unwrap();
+    let (_, jwt2) = ctx.state.user_repository.create_session(user1.id).unwrap();
+
+    let request = user1.delete(routes::auth::sessions()).await;
+    request.assert_status_ok();
+    request.assert_json(&DeleteOtherSessionsResponse::Ok(2));
+
+    for jwt in [jwt1, jwt2] {
+        ctx.server
+            .get(&routes::auth::info())
+            .authorization_bearer(&jwt)
+            .await
+            .assert_status_unauthorized();
+    }
+
+    user1.get(routes::auth::info()).await.assert_status_ok();
+
+    let sessions = get_sessions(user1).await;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+}
+
+async fn pre_login(ctx: &TestContext, username: &str) -> PostPreLoginResponse {
+    ctx.server
+        .post(&routes::auth::pre_login())
+        .json(&PostPreLoginRequest {
+            username: username.to_string(),
+        })
+        .await
+        .json::<PostPreLoginResponse>()
+}
+
+async fn login(ctx: &TestContext, username: &str, password: &str) -> PostLoginResponse {
+    let login_body = PostLoginRequest {
+        username: username.to_string(),
+        password: password.to_string(),
+    };
+
+    ctx.server
+        .post(&routes::auth::login())
+        .json(&login_body)
+        .await
+        .json::<PostLoginResponse>()
+}
+
+#[tokio::test]
+pub async fn test_put_key_pair() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    let key_pair = PutKeyPairRequest {
+        public_key: vec![1, 2, 3],
+        private_key: EncryptionKey::random(),
+    };
+
+    // the key pair of a user cannot be replaced
+    let request = user1.put(routes::auth::key_pair()).json(&key_pair).await;
+    assert_eq!(request.status_code(), StatusCode::CONFLICT);
+    request.assert_json(&PutKeyPairResponse::Conflict);
+
+    // users who registered without a key pair can set one
+    let mut user_entity = user1.fetch_user_from_db();
+    user_entity.public_key = vec![];
+    user_entity.private_key = EncryptionKey::nil();
+    ctx.state.user_repository.update_user(user_entity).unwrap();
+
+    let request = user1
+        .put(routes::auth::key_pair())
+        .json(&PutKeyPairRequest {
+            public_key: vec![],
+            private_key: EncryptionKey::random(),
+        })
+        .await;
+    assert_eq!(request.status_code(), StatusCode::BAD_REQUEST);
+
+    let request = user1.put(routes::auth::key_pair()).json(&key_pair).await;
+    request.assert_status_ok();
+    request.assert_json(&PutKeyPairResponse::Ok);
+
+    let user_entity = user1.fetch_user_from_db();
+    assert_eq!(user_entity.public_key, key_pair.public_key);
+    assert_eq!(user_entity.private_key, key_pair.private_key);
+}
+
+#[tokio::test]
+pub async fn test_change_password() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    let (_, other_jwt) = ctx.state.user_repository.create_session(user1.id).unwrap();
+    let access_token = create_access_token(user1, AccessTokenScope::ReadWrite).await;
+
+    let new_password = TestContext::random_text();
+    let new_keys = UserKeys {
+        public_key: user1.keys.public_key.clone(),
+        ..UserKeys::random()
+    };
+    let kdf_params = TestContext::random_kdf_params();
+
+    let request = user1
+        .post(routes::auth::password())
+        .json(&PostChangePasswordRequest {
+            old_password: user1.password.clone(),
+            new_password: new_password.clone(),
+            keys: new_keys.clone(),
+            kdf_params: kdf_params.clone(),
+        })
+        .await;
+    request.assert_status_ok();
+    request.assert_json(&PostChangePasswordResponse::Ok);
+
+    match login(&ctx, &user1.username, &new_password).await {
+        PostLoginResponse::Ok(login_success) => {
+            assert_eq!(login_success.user_keys, Some(new_keys))
+        }
+        _ => panic!("Login with the new password failed"),
+    }
+
+    assert!(matches!(
+        login(&ctx, &user1.username, &user1.password).await,
+        PostLoginResponse::Unauthorized(_)
+    ));
+
+    assert_eq!(
+        pre_login(&ctx, &user1.username).await,
+        PostPreLoginResponse::Ok(kdf_params)
+    );
+
+    // Other sessions and access tokens are revoked, the current session stays valid
+    ctx.server
+        .get(&routes::auth::info())
+        .authorization_bearer(&other_jwt)
+        .await
+        .assert_status_unauthorized();
+    ctx.server
+        .get(&routes::auth::info())
+        .authorization_bearer(&access_token.token)
+        .await
+        .assert_status_unauthorized();
+    user1.get(routes::auth::info()).await.assert_status_ok();
+    assert!(
+        ctx.state
+            .access_token_repository
+            .list_access_tokens(user1.id)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+pub async fn test_change_password_with_wrong_old_password() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    let request = user1
+        .post(routes::auth::password())
+        .json(&PostChangePasswordRequest {
+            old_password: TestContext::random_text(),
+            new_password: TestContext::random_text(),
+            keys: UserKeys::random(),
+            kdf_params: TestContext::random_kdf_params(),
+        })
+        .await;
+    request.assert_status_forbidden();
+    request.assert_json(&PostChangePasswordResponse::Unauthorized);
+
+    match login(&ctx, &user1.username, &user1.password).await {
+        PostLoginResponse::Ok(login_success) => {
+            assert_eq!(login_success.user_keys, Some(user1.keys.clone()))
+        }
+        _ => panic!("Password was changed"),
+    }
+}
+
+fn random_recovery_key() -> RecoveryKey {
+    RecoveryKey {
+        recovery_password: TestContext::random_text(),
+        master_key: EncryptionKey::random(),
+    }
+}
+
+async fn get_recovery_keys(
+    ctx: &TestContext,
+    username: &str,
+    recovery_password: &str,
+) -> PostRecoveryKeysResponse {
+    ctx.server
+        .post(&routes::auth::recovery_keys())
+        .json(&PostRecoveryKeysRequest {
+            username: username.to_string(),
+            recovery_password: recovery_password.to_string(),
+        })
+        .await
+        .json::<PostRecoveryKeysResponse>()
+}
+
+#[tokio::test]
+pub async fn test_recover_account() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    let recovery_key = random_recovery_key();
+    let request = user1
+        .put(routes::auth::recovery_key())
+        .json(&PutRecoveryKeyRequest {
+            password: user1.password.clone(),
+            recovery_key: recovery_key.clone(),
+        })
+        .await;
+    request.assert_status_ok();
+
+    assert_eq!(
+        get_recovery_keys(&ctx, &user1.username, &TestContext::random_text()).await,
+        PostRecoveryKeysResponse::Unauthorized
+    );
+    assert_eq!(
+        get_recovery_keys(&ctx, &user1.username, &recovery_key.recovery_password).await,
+        PostRecoveryKeysResponse::Ok(RecoveryKeys {
+            recovery_master_key: recovery_key.master_key.clone(),
+            keys: user1.keys.clone(),
+        })
+    );
+
+    let new_password = TestContext::random_text();
+    let new_keys = UserKeys {
+        public_key: user1.keys.public_key.clone(),
+        ..UserKeys::random()
+    };
+
+    let request = ctx
+        .server
+        .post(&routes::auth::recovery_reset())
+        .json(&PostRecoveryResetRequest {
+            username: user1.username.clone(),
+            recovery_password: recovery_key.recovery_password.clone(),
+            new_password: new_password.clone(),
+            keys: new_keys.clone(),
+            kdf_params: TestContext::random_kdf_params(),
+        })
+        .await;
+    request.assert_status_ok();
+    request.assert_json(&PostRecoveryResetResponse::Ok);
+
+    match login(&ctx, &user1.username, &new_password).await {
+        PostLoginResponse::Ok(login_success) => {
+            assert_eq!(login_success.user_keys, Some(new_keys))
+        }
+        _ => panic!("Login with the new password failed"),
+    }
+
+    // All sessions are revoked
+    user1
+        .get(routes::auth::info())
+        .await
+        .assert_status_unauthorized();
+
+    // The recovery key can be used again
+    assert!(matches!(
+        get_recovery_keys(&ctx, &user1.username, &recovery_key.recovery_password).await,
+        PostRecoveryKeysResponse::Ok(_)
+    ));
+}
+
+#[tokio::test]
+pub async fn test_recover_account_with_wrong_recovery_password() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    // Users without recovery key cannot be recovered
+    let reset_request = PostRecoveryResetRequest {
+        username: user1.username.clone(),
+        recovery_password: TestContext::random_text(),
+        new_password: TestContext::random_text(),
+        keys: UserKeys::random(),
+        kdf_params: TestContext::random_kdf_params(),
+    };
+    let request = ctx
+        .server
+        .post(&routes::auth::recovery_reset())
+        .json(&reset_request)
+        .await;
+    request.assert_status_unauthorized();
+
+    ctx.state
+        .user_repository
+        .set_recovery_key(
+            user1.id,
+            &TestContext::random_text(),
+            EncryptionKey::random(),
+        )
+        .unwrap();
+
+    let request = ctx
+        .server
+        .post(&routes::auth::recovery_reset())
+        .json(&reset_request)
+        .await;
+    request.assert_status_unauthorized();
+    request.assert_json(&PostRecoveryResetResponse::Unauthorized);
+
+    user1.get(routes::auth::info()).await.assert_status_ok();
+}
+
+#[tokio::test]
+pub async fn test_set_recovery_key_with_wrong_password() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    let recovery_key = random_recovery_key();
+    let request = user1
+        .put(routes::auth::recovery_key())
+        .json(&PutRecoveryKeyRequest {
+            password: TestContext::random_text(),
+            recovery_key: recovery_key.clone(),
+        })
+        .await;
+    request.assert_status_forbidden();
+    request.assert_json(&PutRecoveryKeyResponse::Unauthorized);
+
+    assert_eq!(
+        get_recovery_keys(&ctx, &user1.username, &recovery_key.recovery_password).await,
+        PostRecoveryKeysResponse::Unauthorized
+    );
+}
+
+#[tokio::test]
+pub async fn test_register_with_recovery_key_and_delete_it() {
+    let ctx = TestContext::new(1).await;
+
+    let recovery_key = random_recovery_key();
+    let register_body = PostRegisterRequest {
+        recovery_key: Some(recovery_key.clone()),
+        ..register_request(DEFAULT_INVITE_CODE)
+    };
+    ctx.server
+        .post(&routes::auth::register())
+        .json(&register_body)
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let PostRecoveryKeysResponse::Ok(recovery_keys) = get_recovery_keys(
+        &ctx,
+        &register_body.username,
+        &recovery_key.recovery_password,
+    )
+    .await
+    else {
+        panic!("Recovery key was not set");
+    };
+    assert_eq!(recovery_keys.recovery_master_key, recovery_key.master_key);
+
+    let PostLoginResponse::Ok(login_success) =
+        login(&ctx, &register_body.username, &register_body.password).await
+    else {
+        panic!("Login failed");
+    };
+
+    let request = ctx
+        .server
+        .delete(&routes::auth::recovery_key())
+        .authorization_bearer(&login_success.bearer_token)
+        .await;
+    request.assert_status_ok();
+
+    let request = ctx
+        .server
+        .delete(&routes::auth::recovery_key())
+        .authorization_bearer(&login_success.bearer_token)
+        .await;
+    request.assert_status_not_found();
+
+    assert_eq!(
+        get_recovery_keys(
+            &ctx,
+            &register_body.username,
+            &recovery_key.recovery_password
+        )
+        .await,
+        PostRecoveryKeysResponse::Unauthorized
+    );
+}
+
+#[tokio::test]
+pub async fn test_pre_login() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    assert_eq!(
+        pre_login(&ctx, &user1.username).await,
+        PostPreLoginResponse::Ok(user1.entity.kdf_params())
+    );
+
+    // Unknown users get parameters, which do not change between requests
+    let username = TestContext::random_text();
+    let PostPreLoginResponse::Ok(kdf_params) = pre_login(&ctx, &username).await;
+    assert!(!kdf_params.needs_upgrade());
+    assert_eq!(
+        pre_login(&ctx, &username).await,
+        PostPreLoginResponse::Ok(kdf_params.clone())
+    );
+
+    let PostPreLoginResponse::Ok(other_kdf_params) =
+        pre_login(&ctx, &TestContext::random_text()).await;
+    assert_ne!(kdf_params, other_kdf_params);
+}
+
+#[tokio::test]
+pub async fn test_register_with_kdf_params() {
+    let ctx = TestContext::new(1).await;
+
+    let kdf_params = TestContext::random_kdf_params();
+    let register_body = PostRegisterRequest {
+        kdf_params: kdf_params.clone(),
+        ..register_request(DEFAULT_INVITE_CODE)
+    };
+    ctx.server
+        .post(&routes::auth::register())
+        .json(&register_body)
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    assert_eq!(
+        pre_login(&ctx, &register_body.username).await,
+        PostPreLoginResponse::Ok(kdf_params)
+    );
+}
+
+#[tokio::test]
+pub async fn test_pre_login_of_legacy_user() {
+    let ctx = TestContext::new(0).await;
+
+    // Created before salts were stored, the client hashed the username instead
+    let user = ctx
+        .state
+        .user_repository
+        .create_user(
+            "CrabdriveIsBetterThanMega",
+            &TestContext::random_text(),
+            crabdrive_common::da!(1 GB),
+            UserKeys::nil(),
+            KdfParams::legacy(),
+        )
+        .unwrap();
+    let mut conn = ctx.state.db_pool.get().unwrap();
+    assert_eq!(backfill_kdf_salts(&mut conn).unwrap(), 1);
+    assert_eq!(backfill_kdf_salts(&mut conn).unwrap(), 0);
+
+    let legacy_kdf_params = KdfParams::new(
+        KdfParams::LEGACY_VERSION,
+        Some("C2NciVB5nXQCCcxR+riz8iJc39GysynTxyMRNPxUnVk".to_string()),
+    );
+    assert_eq!(
+        pre_login(&ctx, &user.username).await,
+        PostPreLoginResponse::Ok(legacy_kdf_params.clone())
+    );
+    assert!(legacy_kdf_params.needs_upgrade());
+}
+
+#[tokio::test]
+pub async fn test_pre_login_of_unknown_user() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+    let username = TestContext::random_text();
+
+    // The parameters cannot be told apart from those of a user
+    let PostPreLoginResponse::Ok(user_kdf_params) = pre_login(&ctx, &user1.username).await;
+    let PostPreLoginResponse::Ok(kdf_params) = pre_login(&ctx, &username).await;
+    assert_eq!(kdf_params.version, user_kdf_params.version);
+    assert_eq!(
+        kdf_params.salt.as_ref().map(String::len),
+        user_kdf_params.salt.as_ref().map(String::len)
+    );
+
+    // The salt is derived from its own secret
+    let mut config = AppConfig::test();
+    config.auth.jwt_secret = TestContext::random_text();
+    let other_ctx = TestContext::with_config(0, config).await;
+    assert_eq!(
+        pre_login(&other_ctx, &username).await,
+        PostPreLoginResponse::Ok(kdf_params.clone())
+    );
+
+    let mut config = AppConfig::test();
+    config.auth.pre_login_secret = TestContext::random_text();
+    let other_ctx = TestContext::with_config(0, config).await;
+    assert_ne!(
+        pre_login(&other_ctx, &username).await,
+        PostPreLoginResponse::Ok(kdf_params)
+    );
+}
+
+fn invalid_kdf_params() -> Vec<KdfParams> {
+    let salt = TestContext::random_kdf_params().salt;
+    vec![
+        KdfParams::legacy(),
+        KdfParams::new(KdfParams::LEGACY_VERSION, salt.clone()),
+        KdfParams::new(KdfParams::CURRENT_VERSION + 1, salt),
+        KdfParams::new(KdfParams::CURRENT_VERSION, None),
+        // not Base64
+        KdfParams::new(KdfParams::CURRENT_VERSION, Some("crab drive=".repeat(3))),
+        // too short
+        KdfParams::new(KdfParams::CURRENT_VERSION, Some("Y3JhYmRyaXZl".to_string())),
+    ]
+}
+
+#[tokio::test]
+pub async fn test_invalid_kdf_params_are_rejected() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    let recovery_key = random_recovery_key();
+    user1
+        .put(routes::auth::recovery_key())
+        .json(&PutRecoveryKeyRequest {
+            password: user1.password.clone(),
+            recovery_key: recovery_key.clone(),
+        })
+        .await
+        .assert_status_ok();
+
+    for kdf_params in invalid_kdf_params() {
+        let request = ctx
+            .server
+            .post(&routes::auth::register())
+            .json(&PostRegisterRequest {
+                kdf_params: kdf_params.clone(),
+                ..register_request(DEFAULT_INVITE_CODE)
+            })
+            .await;
+        request.assert_status_bad_request();
+        assert!(matches!(
+            request.json::<PostRegisterResponse>(),
+            PostRegisterResponse::BadRequest(_)
+        ));
+
+        let change_password_request = PostChangePasswordRequest {
+            old_password: user1.password.clone(),
+            new_password: TestContext::random_text(),
+            keys: user1.keys.clone(),
+            kdf_params: kdf_params.clone(),
+        };
+        for route in [routes::auth::password(), routes::auth::password_upgrade()] {
+            let request = user1.post(route).json(&change_password_request).await;
+            request.assert_status_bad_request();
+            assert!(matches!(
+                request.json::<PostChangePasswordResponse>(),
+                PostChangePasswordResponse::BadRequest(_)
+            ));
+        }
+
+        let request = ctx
+            .server
+            .post(&routes::auth::recovery_reset())
+            .json(&PostRecoveryResetRequest {
+                username: user1.username.clone(),
+                recovery_password: recovery_key.recovery_password.clone(),
+                new_password: TestContext::random_text(),
+                keys: user1.keys.clone(),
+                kdf_params,
+            })
+            .await;
+        request.assert_status_bad_request();
+        assert!(matches!(
+            request.json::<PostRecoveryResetResponse>(),
+            PostRecoveryResetResponse::BadRequest(_)
+        ));
+    }
+
+    // Nothing was changed
+    assert_eq!(
+        pre_login(&ctx, &user1.username).await,
+        PostPreLoginResponse::Ok(user1.entity.kdf_params())
+    );
+    assert!(matches!(
+        login(&ctx, &user1.username, &user1.password).await,
+        PostLoginResponse::Ok(_)
+    ));
+}
+
+/// Store the password of `user` as if it was derived with legacy parameters
+fn store_legacy_kdf_params(ctx: &TestContext, user: &TestUserEntity) {
+    let legacy_kdf_params = KdfParams::new(
+        KdfParams::LEGACY_VERSION,
+        TestContext::random_kdf_params().salt,
+    );
+    ctx.state
+        .user_repository
+        .change_password(
+            user.id,
+            &user.password,
+            user.keys.clone(),
+            legacy_kdf_params,
+        )
+        .unwrap();
+}
+
+#[tokio::test]
+pub async fn test_upgrade_kdf() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    // Up-to-date parameters cannot be upgraded
+    let upgrade_request = PostChangePasswordRequest {
+        old_password: user1.password.clone(),
+        new_password: TestContext::random_text(),
+        keys: user1.keys.clone(),
+        kdf_params: TestContext::random_kdf_params(),
+    };
+    let request = user1
+        .post(routes::auth::password_upgrade())
+        .json(&upgrade_request)
+        .await;
+    request.assert_status_bad_request();
+    assert!(matches!(
+        request.json::<PostChangePasswordResponse>(),
+        PostChangePasswordResponse::BadRequest(_)
+    ));
+
+    store_legacy_kdf_params(&ctx, user1);
+    let (_, other_jwt) = ctx.state.user_repository.create_session(user1.id).unwrap();
+
+    let new_password = TestContext::random_text();
+    let kdf_params = TestContext::random_kdf_params();
+    let request = user1
+        .post(routes::auth::password_upgrade())
+        .json(&PostChangePasswordRequest {
+            old_password: user1.password.clone(),
+            new_password: new_password.clone(),
+            keys: user1.keys.clone(),
+            kdf_params: kdf_params.clone(),
+        })
+        .await;
+    request.assert_status_ok();
+
+    assert_eq!(
+        pre_login(&ctx, &user1.username).await,
+        PostPreLoginResponse::Ok(kdf_params)
+    );
+    assert!(matches!(
+        login(&ctx, &user1.username, &new_password).await,
+        PostLoginResponse::Ok(_)
+    ));
+
+    // Unlike a password change, the upgrade keeps all sessions
+    ctx.server
+        .get(&routes::auth::info())
+        .authorization_bearer(&other_jwt)
+        .await
+        .assert_status_ok();
+
+    // The parameters are up to date now, so the password cannot be changed this way anymore
+    let request = user1
+        .post(routes::auth::password_upgrade())
+        .json(&PostChangePasswordRequest {
+            old_password: new_password.clone(),
+            ..upgrade_request
+        })
+        .await;
+    request.assert_status_bad_request();
+    assert!(matches!(
+        login(&ctx, &user1.username, &new_password).await,
+        PostLoginResponse::Ok(_)
+    ));
+}
+
+#[tokio::test]
+pub async fn test_upgrade_kdf_with_wrong_password() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+    store_legacy_kdf_params(&ctx, user1);
+    let kdf_params = ctx
+        .state
+        .user_repository
+        .get_user(user1.id)
+        .unwrap()
+        .unwrap()
+        .kdf_params();
+
+    let request = user1
+        .post(routes::auth::password_upgrade())
+        .json(&PostChangePasswordRequest {
+            old_password: TestContext::random_text(),
+            new_password: TestContext::random_text(),
+            keys: user1.keys.clone(),
+            kdf_params: TestContext::random_kdf_params(),
+        })
+        .await;
+    request.assert_status_forbidden();
+
+    assert_eq!(
+        pre_login(&ctx, &user1.username).await,
+        PostPreLoginResponse::Ok(kdf_params)
+    );
+}
+
+/// The TOTP code of the current time step, shifted by `step_offset`
+fn totp_code(secret: &str, step_offset: i64) -> String {
+    let secret = totp::base32_decode(secret).unwrap();
+    totp::generate_code(
+        &secret,
+        totp::time_step(Utc::now().timestamp()) + step_offset,
+    )
+}
+
+/// Enrol and confirm TOTP. Returns the secret and the backup codes. The code of the current time
+/// step is used up afterward.
+async fn enable_totp(user: &TestUserEntity) -> (String, BackupCodes) {
+    let request = user.post(routes::auth::totp()).await;
+    request.assert_status_ok();
+    let PostTotpResponse::Ok(enrolment) = request.json::<PostTotpResponse>() else {
+        panic!("Failed to start TOTP enrolment");
+    };
+
+    let request = user
+        .post(routes::auth::totp_confirm())
+        .json(&TotpCodeRequest {
+            code: totp_code(&enrolment.secret, 0),
+        })
+        .await;
+    request.assert_status_ok();
+    let PostTotpConfirmResponse::Ok(backup_codes) = request.json::<PostTotpConfirmResponse>()
+    else {
+        panic!("Failed to confirm TOTP enrolment");
+    };
+
+    (enrolment.secret, backup_codes)
+}
+
+/// Log in with the password. Returns the challenge for the second factor.
+async fn login_first_factor(ctx: &TestContext, user: &TestUserEntity) -> String {
+    match login(ctx, &user.username, &user.password).await {
+        PostLoginResponse::SecondFactorRequired(challenge) => challenge.challenge,
+        _ => panic!("Expected the second factor to be required"),
+    }
+}
+
+async fn login_second_factor(ctx: &TestContext, challenge: &str, code: &str) -> TestResponse {
+    ctx.server
+        .post(&routes::auth::login_second_factor())
+        .json(&PostLoginSecondFactorRequest {
+            challenge: challenge.to_string(),
+            code: code.to_string(),
+        })
+        .await
+}
+
+#[tokio::test]
+pub async fn test_totp_login() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    let (secret, backup_codes) = enable_totp(user1).await;
+    assert_eq!(backup_codes.len(), 10);
+
+    let GetSelfInfoResponse::Ok(info) = user1
+        .get(routes::auth::info())
+        .await
+        .json::<GetSelfInfoResponse>();
+    assert!(info.totp_enabled);
+
+    // The password alone does not start a session
+    let request = ctx
+        .server
+        .post(&routes::auth::login())
+        .json(&PostLoginRequest {
+            username: user1.username.clone(),
+            password: user1.password.clone(),
+        })
+        .await;
+    request.assert_status_ok();
+    assert_eq!(request.header(SET_COOKIE), "");
+    let PostLoginResponse::SecondFactorRequired(challenge) = request.json::<PostLoginResponse>()
+    else {
+        panic!("Expected the second factor to be required");
+    };
+
+    // The code of the current time step was used for the confirmation
+    let request = login_second_factor(&ctx, &challenge.challenge, &totp_code(&secret, 1)).await;
+    request.assert_status_ok();
+    let PostLoginResponse::Ok(login_success) = request.json::<PostLoginResponse>() else {
+        panic!("Login with the second factor failed");
+    };
+
+    assert!(ctx.validate_jwt(&login_success.bearer_token));
+    assert!(request.maybe_cookie("refresh_token").is_some());
+}
+
+#[tokio::test]
+pub async fn test_totp_login_with_wrong_code() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    let (secret, _) = enable_totp(user1).await;
+    let challenge = login_first_factor(&ctx, user1).await;
+
+    login_second_factor(&ctx, &challenge, "000000")
+        .await
+        .assert_status_unauthorized();
+    // Codes can only be used once. The time step may have passed since the confirmation, so the
+    // code is replayed for the step which was used.
+    let used_step = user1.fetch_user_from_db().totp_last_step.unwrap();
+    let used_code = totp::generate_code(&totp::base32_decode(&secret).unwrap(), used_step);
+    login_second_factor(&ctx, &challenge, &used_code)
+        .await
+        .assert_status_unauthorized();
+    // Bearer tokens are no valid challenge
+    login_second_factor(&ctx, &user1.token, &totp_code(&secret, 1))
+        .await
+        .assert_status_unauthorized();
+}
+
+#[tokio::test]
+pub async fn test_totp_login_with_backup_code() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    let (_, backup_codes) = enable_totp(user1).await;
+    let challenge = login_first_factor(&ctx, user1).await;
+
+    // Backup codes are accepted in uppercase and with separators
+    let backup_code = backup_codes[0].to_uppercase();
+    let backup_code = format!("{}-{}", &backup_code[..5], &backup_code[5..]);
+    login_second_factor(&ctx, &challenge, &backup_code)
+        .await
+        .assert_status_ok();
+
+    login_second_factor(&ctx, &challenge, &backup_codes[0])
+        .await
+        .assert_status_unauthorized();
+    login_second_factor(&ctx, &challenge, &backup_codes[1])
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+pub async fn test_confirm_totp_with_wrong_code() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    user1.post(routes::auth::totp()).await.assert_status_ok();
+    user1
+        .post(routes::auth::totp_confirm())
+        .json(&TotpCodeRequest {
+            code: "000000".to_string(),
+        })
+        .await
+        .assert_status_forbidden();
+
+    // The enrolment is not complete, so the password is enough
+    assert!(matches!(
+        login(&ctx, &user1.username, &user1.password).await,
+        PostLoginResponse::Ok(_)
+    ));
+}
+
+#[tokio::test]
+pub async fn test_disable_totp() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    let (_, backup_codes) = enable_totp(user1).await;
+
+    // Enrolling again requires disabling first
+    user1
+        .post(routes::auth::totp())
+        .await
+        .assert_status(StatusCode::CONFLICT);
+
+    user1
+        .delete(routes::auth::totp())
+        .json(&TotpCodeRequest {
+            code: "000000".to_string(),
+        })
+        .await
+        .assert_status_forbidden();
+
+    let request = user1
+        .delete(routes::auth::totp())
+        .json(&TotpCodeRequest {
+            code: backup_codes[0].clone(),
+        })
+        .await;
+    request.assert_status_ok();
+    assert_eq!(request.json::<DeleteTotpResponse>(), DeleteTotpResponse::Ok);
+
+    assert!(matches!(
+        login(&ctx, &user1.username, &user1.password).await,
+        PostLoginResponse::Ok(_)
+    ));
+}
+
+#[tokio::test]
+pub async fn test_regenerate_backup_codes() {
+    let ctx = TestContext::new(1).await;
+    let user1 = ctx.get_user(0);
+
+    let (_, old_backup_codes) = enable_totp(user1).await;
+
+    let request = user1
+        .post(routes::auth::totp_backup_codes())
+        .json(&TotpCodeRequest {
+            code: old_backup_codes[0].clone(),
+        })
+        .await;
+    request.assert_status_ok();
+    let PostTotpBackupCodesResponse::Ok(backup_codes) =
+        request.json::<PostTotpBackupCodesResponse>()
+    else {
+        panic!("Failed to regenerate backup codes");
+    };
+
+    let challenge = login_first_factor(&ctx, user1).await;
+    login_second_factor(&ctx, &challenge, &old_backup_codes[1])
+        .await
+        .assert_status_unauthorized();
+    login_second_factor(&ctx, &challenge, &backup_codes[0])
+        .await
+        .assert_status_ok();
+}
+
+fn rate_limited_config(configure: impl FnOnce(&mut AuthConfig)) -> AppConfig {
+    let mut config = AppConfig::test();
+    configure(&mut config.auth);
+    config
+}
+
+fn assert_too_many_requests(response: &TestResponse, min_retry_after: u64, max_retry_after: u64) {
+    assert_eq!(response.status_code(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response
+        .header(RETRY_AFTER)
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(
+        (min_retry_after..=max_retry_after).contains(&retry_after),
+        "Unexpected Retry-After: {retry_after}"
+    );
+}
+
+async fn login_response(ctx: &TestContext, username: &str, password: &str) -> TestResponse {
+    ctx.server
+        .post(&routes::auth::login())
+        .json(&PostLoginRequest {
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+        .await
+}
+
+#[tokio::test]
+pub async fn test_login_rate_limit_per_username() {
+    let config = rate_limited_config(|auth| auth.rate_limit_per_username = 3);
+    let ctx = TestContext::with_config(2, config).await;
+    let user = ctx.get_user(0);
+    let other_user = ctx.get_user(1);
+
+    for _ in 0..3 {
+        login_response(&ctx, &user.username, &TestContext::random_text())
+            .await
+            .assert_status_unauthorized();
+    }
+
+    // The correct password is rejected as well
+    let response = login_response(&ctx, &user.username, &user.password).await;
+    assert_too_many_requests(&response, 1, 60);
+
+    // Other users are not affected
+    login_response(&ctx, &other_user.username, &other_user.password)
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+pub async fn test_login_rate_limit_per_ip() {
+    let config = rate_limited_config(|auth| auth.rate_limit_per_ip = 3);
+    let ctx = TestContext::with_config(1, config).await;
+    let user = ctx.get_user(0);
+
+    for _ in 0..3 {
+        login_response(
+            &ctx,
+            &TestContext::random_text(),
+            &TestContext::random_text(),
+        )
+        .await
+        .assert_status_unauthorized();
+    }
+
+    let response = login_response(&ctx, &user.username, &user.password).await;
+    assert_too_many_requests(&response, 1, 60);
+}
+
+#[tokio::test]
+pub async fn test_login_backoff() {
+    let config = rate_limited_config(|auth| {
+        auth.rate_limit_backoff_base = 30;
+        auth.rate_limit_backoff_max = 50;
+    });
+    let ctx = TestContext::with_config(1, config).await;
+    let user = ctx.get_user(0);
+
+    login_response(&ctx, &user.username, &TestContext::random_text())
+        .await
+        .assert_status_unauthorized();
+
+    let response = login_response(&ctx, &user.username, &user.password).await;
+    assert_too_many_requests(&response, 1, 30);
+
+    // The backoff is doubled after each failure, up to the maximum
+    let limiter = &ctx.state.rate_limiter;
+    let ip = std::net::Ipv4Addr::UNSPECIFIED.into();
+    limiter.record_failure(ip, Some(&user.username));
+    let retry_after = limiter.check(ip, Some(&user.username)).unwrap_err();
+    assert!(retry_after > Duration::from_secs(30) && retry_after <= Duration::from_secs(50));
+}
+
+#[tokio::test]
+pub async fn test_login_lockout() {
+    let config = rate_limited_config(|auth| {
+        auth.lockout_threshold = 3;
+        auth.lockout_duration = 600;
+    });
+    let ctx = TestContext::with_config(1, config).await;
+    let user = ctx.get_user(0);
+
+    for _ in 0..3 {
+        login_response(&ctx, &user.username, &TestContext::random_text())
+            .await
+            .assert_status_unauthorized();
+    }
+
+    let response = login_response(&ctx, &user.username, &user.password).await;
+    assert_too_many_requests(&response, 590, 600);
+}
+
+#[tokio::test]
+pub async fn test_successful_login_resets_failures() {
+    let config = rate_limited_config(|auth| auth.lockout_threshold = 3);
+    let ctx = TestContext::with_config(1, config).await;
+    let user = ctx.get_user(0);
+
+    for _ in 0..2 {
+        login_response(&ctx, &user.username, &TestContext::random_text())
+            .await
+            .assert_status_unauthorized();
+    }
+    login_response(&ctx, &user.username, &user.password)
+        .await
+        .assert_status_ok();
+    login_response(&ctx, &user.username, &TestContext::random_text())
+        .await
+        .assert_status_unauthorized();
+
+    login_response(&ctx, &user.username, &user.password)
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+pub async fn test_successful_login_keeps_ip_failures() {
+    let config = rate_limited_config(|auth| {
+        auth.rate_limit_backoff_base = 30;
+        auth.rate_limit_backoff_max = 50;
+    });
+    let ctx = TestContext::with_config(1, config).await;
+    let user = ctx.get_user(0);
+
+    let limiter = &ctx.state.rate_limiter;
+    let ip = std::net::Ipv4Addr::UNSPECIFIED.into();
+    limiter.record_failure(ip, Some(&TestContext::random_text()));
+    limiter.record_success(&user.username);
+
+    // The IP still has to wait, other usernames cannot be guessed right away
+    let retry_after = limiter.check(ip, None).unwrap_err();
+    assert!(retry_after > Duration::from_secs(20) && retry_after <= Duration::from_secs(30));
+}
+
+#[tokio::test]
+pub async fn test_register_rate_limit() {
+    let config = rate_limited_config(|auth| auth.rate_limit_per_ip = 2);
+    let ctx = TestContext::with_config(0, config).await;
+
+    for _ in 0..2 {
+        let response = ctx
+            .server
+            .post(&routes::auth::register())
+            .json(&register_request(&TestContext::random_text()))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+    }
+
+    let response = ctx
+        .server
+        .post(&routes::auth::register())
+        .json(&register_request(DEFAULT_INVITE_CODE))
+        .await;
+    assert_too_many_requests(&response, 1, 60);
+}
+
+#[tokio::test]
+pub async fn test_change_password_rate_limit() {
+    let config = rate_limited_config(|auth| auth.rate_limit_per_username = 3);
+    let ctx = TestContext::with_config(1, config).await;
+    let user = ctx.get_user(0);
+
+    let change_password = |old_password: String| {
+        user.post(routes::auth::password())
+            .json(&PostChangePasswordRequest {
+                old_password,
+                new_password: TestContext::random_text(),
+                keys: UserKeys::random(),
+                kdf_params: TestContext::random_kdf_params(),
+            })
+    };
+
+    for _ in 0..3 {
+        change_password(TestContext::random_text())
+            .await
+            .assert_status_forbidden();
+    }
+
+    // The attempts count for the account of the session, which is also used by the login
+    let response = change_password(user.password.clone()).await;
+    assert_too_many_requests(&response, 1, 60);
+    let response = login_response(&ctx, &user.username, &user.password).await;
+    assert_too_many_requests(&response, 1, 60);
+}
+
+/// A second server using the database of `ctx`, which takes the client IP from the
+/// `x-client-ip` header. It has its own rate limits.
+async fn server_with_client_ips(ctx: &TestContext) -> TestServer {
+    let (router, _) = create_app(ctx.state.config.as_ref().clone()).await;
+    let router = router.layer(axum::middleware::map_request(
+        |mut request: Request<Body>| async move {
+            let ip: IpAddr = request.headers()["x-client-ip"]
+                .to_str()
+                .unwrap()
+                .parse()
+                .unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::new(ip, 0)));
+            request
+        },
+    ));
+    TestServer::new(router).expect("Failed to create test server!")
+}
+
+#[tokio::test]
+pub async fn test_second_factor_rate_limit_per_account() {
+    let config = rate_limited_config(|auth| auth.lockout_threshold = 3);
+    let ctx = TestContext::with_config(1, config).await;
+    let user = ctx.get_user(0);
+    let (secret, _) = enable_totp(user).await;
+
+    let PostLoginResponse::SecondFactorRequired(challenge) =
+        login(&ctx, &user.username, &user.password).await
+    else {
+        panic!("Expected the second factor to be required");
+    };
+
+    let server = server_with_client_ips(&ctx).await;
+    let login_second_factor = |ip: &str, code: String| {
+        server
+            .post(&routes::auth::login_second_factor())
+            .add_header("x-client-ip", ip)
+            .json(&PostLoginSecondFactorRequest {
+                challenge: challenge.challenge.clone(),
+                code,
+            })
+    };
+
+    // Every attempt comes from another IP, so only the limit of the account applies
+    for ip in ["10.0.0.1", "10.0.0.2", "10.0.0.3"] {
+        login_second_factor(ip, TestContext::random_text())
+            .await
+            .assert_status_unauthorized();
+    }
+
+    let response = login_second_factor("10.0.0.4", totp_code(&secret, 1)).await;
+    assert_too_many_requests(&response, 1, 900);
+}
+
+async fn create_access_token(user: &TestUserEntity, scope: AccessTokenScope) -> CreatedAccessToken {
+    let response = user
+        .post(routes::auth::access_tokens())
+        .json(&PostAccessTokenRequest {
+            name: TestContext::random_text(),
+            scope,
+            expires_at: None,
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+
+    match response.json::<PostAccessTokenResponse>() {
+        PostAccessTokenResponse::Created(created) => created,
+        _ => panic!("Invalid response"),
+    }
+}
+
+async fn get_access_tokens(user: &TestUserEntity) -> Vec<AccessTokenInfo> {
+    match user
+        .get(routes::auth::access_tokens())
+        .await
+        .json::<GetAccessTokensResponse>()
+    {
+        GetAccessTokensResponse::Ok(access_tokens) => access_tokens,
+    }
+}
+
+fn create_folder_request() -> PostCreateFolderRequest {
+    PostCreateFolderRequest {
+        parent_metadata_version: 0,
+        parent_metadata: EncryptedMetadata::random(),
+        node_metadata: EncryptedMetadata::random(),
+        node_id: UUID::random(),
+    }
+}
+
+#[tokio::test]
+pub async fn test_access_token() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let created = create_access_token(user, AccessTokenScope::ReadWrite).await;
+    assert!(created.token.starts_with("crabdrive_pat_"));
+    assert!(created.info.last_used_at.is_none());
+
+    let info_response = ctx
+        .server
+        .get(&routes::auth::info())
+        .authorization_bearer(&created.token)
+        .await;
+    info_response.assert_status_ok();
+    match info_response.json::<GetSelfInfoResponse>() {
+        GetSelfInfoResponse::Ok(info) => assert_eq!(info.user_id, user.id),
+    }
+
+    ctx.server
+        .post(&routes::node::folder::create(user.get_root()))
+        .authorization_bearer(&created.token)
+        .json(&create_folder_request())
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let access_tokens = get_access_tokens(user).await;
+    assert_eq!(access_tokens.len(), 1);
+    assert_eq!(access_tokens[0].id, created.info.id);
+    assert_eq!(access_tokens[0].name, created.info.name);
+    assert!(access_tokens[0].last_used_at.is_some());
+}
+
+#[tokio::test]
+pub async fn test_read_only_access_token() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let created = create_access_token(user, AccessTokenScope::Read).await;
+
+    ctx.server
+        .get(&routes::auth::info())
+        .authorization_bearer(&created.token)
+        .await
+        .assert_status_ok();
+
+    ctx.server
+        .post(&routes::node::folder::create(user.get_root()))
+        .authorization_bearer(&created.token)
+        .json(&create_folder_request())
+        .await
+        .assert_status_forbidden();
+}
+
+#[tokio::test]
+pub async fn test_access_token_cannot_manage_account() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let created = create_access_token(user, AccessTokenScope::ReadWrite).await;
+
+    ctx.server
+        .get(&routes::auth::sessions())
+        .authorization_bearer(&created.token)
+        .await
+        .assert_status_forbidden();
+
+    ctx.server
+        .post(&routes::auth::access_tokens())
+        .authorization_bearer(&created.token)
+        .json(&PostAccessTokenRequest {
+            name: TestContext::random_text(),
+            scope: AccessTokenScope::ReadWrite,
+            expires_at: None,
+        })
+        .await
+        .assert_status_forbidden();
+}
+
+#[tokio::test]
+pub async fn test_revoke_access_token() {
+    let ctx = TestContext::new(2).await;
+    let user = ctx.get_user(0);
+    let other_user = ctx.get_user(1);
+
+    let created = create_access_token(user, AccessTokenScope::ReadWrite).await;
+
+    // Tokens of other users cannot be revoked
+    other_user
+        .delete(routes::auth::access_token_by_id(created.info.id))
+        .await
+        .assert_status_not_found();
+
+    let response = user
+        .delete(routes::auth::access_token_by_id(created.info.id))
+        .await;
+    response.assert_status_ok();
+    assert!(matches!(
+        response.json::<DeleteAccessTokenResponse>(),
+        DeleteAccessTokenResponse::Ok
+    ));
+
+    ctx.server
+        .get(&routes::auth::info())
+        .authorization_bearer(&created.token)
+        .await
+        .assert_status_unauthorized();
+
+    user.delete(routes::auth::access_token_by_id(created.info.id))
+        .await
+        .assert_status_not_found();
+    assert!(get_access_tokens(user).await.is_empty());
+}
+
+#[tokio::test]
+pub async fn test_expired_access_token() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let response = user
+        .post(routes::auth::access_tokens())
+        .json(&PostAccessTokenRequest {
+            name: TestContext::random_text(),
+            scope: AccessTokenScope::ReadWrite,
+            expires_at: Some((Utc::now() - TimeDelta::hours(1)).naive_utc()),
+        })
+        .await;
+    response.assert_status_bad_request();
+
+    let (token, _) = ctx
+        .state
+        .access_token_repository
+        .create_access_token(
+            user.id,
+            "expired",
+            AccessTokenScope::ReadWrite,
+            Some((Utc::now() - TimeDelta::seconds(1)).naive_utc()),
+        )
+        .unwrap();
+
+    ctx.server
+        .get(&routes::auth::info())
+        .authorization_bearer(&token)
+        .await
+        .assert_status_unauthorized();
+}
+
+#[tokio::test]
+pub async fn test_create_access_token_without_name() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let response = user
+        .post(routes::auth::access_tokens())
+        .json(&PostAccessTokenRequest {
+            name: "  ".to_string(),
+            scope: AccessTokenScope::Read,
+            expires_at: None,
+        })
+        .await;
+    response.assert_status_bad_request();
+}
@@ -1,15 +1,28 @@
-use crate::test::utils::TestContext;
+use crate::http::AppConfig;
+use crate::request_handler::chunk::MAX_CHUNK_SIZE;
+use crate::test::utils::{PausedCommitVfs, TestBucket, TestContext, WriteTrackingVfs};
+use crate::user::persistence::model::user_entity::UserEntity;
 
+use crabdrive_common::da;
 use crabdrive_common::encrypted_metadata::EncryptedMetadata;
 use crabdrive_common::iv::IV;
+use crabdrive_common::payloads::error::{ErrorKind, ErrorResponse};
+use crabdrive_common::payloads::node::request::folder::PostCreateFolderRequest;
 use crabdrive_common::payloads::node::{request::file::*, response::file::*};
 use crabdrive_common::routes;
 use crabdrive_common::storage::NodeType;
+use crabdrive_common::user::UserType;
 use crabdrive_common::uuid::UUID;
 
 use axum::http::StatusCode;
+use axum::http::header::{
+    ACCEPT_RANGES, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_NONE_MATCH, IF_RANGE,
+    RANGE,
+};
 use pretty_assertions::assert_eq;
 
+use std::sync::Arc;
+
 #[tokio::test]
 pub async fn test_create_file() {
     let ctx = TestContext::new(1).await;
@@ -136,6 +149,7 @@ pub async fn test_create_file_and_upload_single_chunk() {
     let created_node = match request.json::<PostCreateFileResponse>() {
         PostCreateFileResponse::Created(encrypted_node) => encrypted_node,
         PostCreateFileResponse::NotFound => panic!("Wrong status code!"),
+        PostCreateFileResponse::Forbidden => panic!("Wrong status code!"),
         PostCreateFileResponse::BadRequest => panic!("Wrong status code!"),
         PostCreateFileResponse::Conflict => panic!("Wrong status code!"),
     };
@@ -195,6 +209,7 @@ pub async fn test_create_file_and_upload_chunks() {
     let created_node = match request.json::<PostCreateFileResponse>() {
         PostCreateFileResponse::Created(encrypted_node) => encrypted_node,
         PostCreateFileResponse::NotFound => panic!("Wrong status code!"),
+        PostCreateFileResponse::Forbidden => panic!("Wrong status code!"),
         PostCreateFileResponse::BadRequest => panic!("Wrong status code!"),
         PostCreateFileResponse::Conflict => panic!("Wrong status code!"),
     };
@@ -231,8 +246,6 @@ pub async fn test_create_file_and_upload_chunks() {
         let bytes_from_vfs = ctx
             .state
             .vfs
-            .read()
-            .await
             .read_chunk(&current_revision, i as i64)
             .await
             .expect("Failed to read from VFS!");
@@ -273,6 +286,7 @@ pub async fn test_create_file_and_upload_wrong_chunk_indexes() {
     let created_node = match request.json::<PostCreateFileResponse>() {
         PostCreateFileResponse::Created(encrypted_node) => encrypted_node,
         PostCreateFileResponse::NotFound => panic!("Wrong status code!"),
+        PostCreateFileResponse::Forbidden => panic!("Wrong status code!"),
         PostCreateFileResponse::BadRequest => panic!("Wrong status code!"),
         PostCreateFileResponse::Conflict => panic!("Wrong status code!"),
     };
@@ -331,6 +345,7 @@ pub async fn test_upload_chunks_to_invalid_revision() {
     let created_node = match request.json::<PostCreateFileResponse>() {
         PostCreateFileResponse::Created(encrypted_node) => encrypted_node,
         PostCreateFileResponse::NotFound => panic!("Wrong status code!"),
+        PostCreateFileResponse::Forbidden => panic!("Wrong status code!"),
         PostCreateFileResponse::BadRequest => panic!("Wrong status code!"),
         PostCreateFileResponse::Conflict => panic!("Wrong status code!"),
     };
@@ -389,6 +404,7 @@ async fn test_double_commit() {
     let created_node = match request.json::<PostCreateFileResponse>() {
         PostCreateFileResponse::Created(encrypted_node) => encrypted_node,
         PostCreateFileResponse::NotFound => panic!("Wrong status code!"),
+        PostCreateFileResponse::Forbidden => panic!("Wrong status code!"),
         PostCreateFileResponse::BadRequest => panic!("Wrong status code!"),
         PostCreateFileResponse::Conflict => panic!("Wrong status code!"),
     };
@@ -427,6 +443,7 @@ async fn test_double_commit() {
         PostCommitFileResponse::Ok(_) => panic!("Wrong status code!"),
         PostCommitFileResponse::BadRequest(commit_file_error) => commit_file_error,
         PostCommitFileResponse::NotFound => panic!("Wrong status code!"),
+        PostCommitFileResponse::Forbidden => panic!("Wrong status code!"),
     };
 
     assert_eq!(commit_err, CommitFileError::AlreadyCommitted);
@@ -457,6 +474,203 @@ pub async fn test_download_file() {
     }
 }
 
+#[tokio::test]
+pub async fn test_download_file_repeatedly() {
+    let ctx = TestContext::new(1).await;
+
+    let user1 = ctx.get_user(0);
+
+    let file1 = user1.generate_file_with_chunks(4).await;
+    let revision1 = file1.active_revision.expect("No revision with file!");
+
+    // the first download streams the chunks from disk, later ones may be served from the cache
+    for _ in 0..3 {
+        for i in 0..4 {
+            let chunk = &revision1.chunks[i];
+
+            let request = user1
+                .get(routes::node::chunks(file1.id, revision1.id, i as i64))
+                .await;
+
+            assert_eq!(request.status_code(), StatusCode::OK);
+
+            let body = request.as_bytes();
+            assert_eq!(
+                request.header("content-length").to_str().unwrap(),
+                body.len().to_string()
+            );
+            TestContext::validate_checksum(&chunk.checksum, body);
+        }
+    }
+}
+
+#[tokio::test]
+pub async fn test_s3_upload_download_file() {
+    let bucket = TestBucket::start().await;
+    let mut config = AppConfig::test();
+    config.storage.backend = "S3".into();
+    config.storage.s3 = bucket.config.clone();
+    let ctx = TestContext::with_config(1, config).await;
+
+    let user1 = ctx.get_user(0);
+
+    let file1 = user1.generate_file_with_chunks(2).await;
+    let revision1 = file1.active_revision.expect("No revision with file!");
+    assert!(bucket.keys("stage/").is_empty());
+
+    for i in 0..2 {
+        let chunk = &revision1.chunks[i];
+        let request = user1
+            .get(routes::node::chunks(file1.id, revision1.id, i as i64))
+            .await;
+
+        assert_eq!(request.status_code(), StatusCode::OK);
+        TestContext::validate_checksum(&chunk.checksum, request.as_bytes());
+    }
+
+    // remote chunks cannot seek, so the start of the range is skipped while streaming
+    let route = routes::node::chunks(file1.id, revision1.id, 0);
+    let chunk = user1.get(&route).await.into_bytes();
+    let request = user1.get(&route).add_header(RANGE, "bytes=100-199").await;
+    assert_eq!(request.status_code(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(request.as_bytes(), &chunk[100..200]);
+}
+
+#[tokio::test]
+pub async fn test_download_chunk_range() {
+    let ctx = TestContext::new(1).await;
+
+    let user1 = ctx.get_user(0);
+
+    let file1 = user1.generate_file_with_chunks(1).await;
+    let revision1 = file1.active_revision.expect("No revision with file!");
+    let route = routes::node::chunks(file1.id, revision1.id, 0);
+
+    let request = user1.get(&route).await;
+    assert_eq!(request.status_code(), StatusCode::OK);
+    assert_eq!(request.header(ACCEPT_RANGES), "bytes");
+    let chunk = request.into_bytes();
+    let size = chunk.len();
+
+    // (requested range, first byte, last byte)
+    let ranges = [
+        ("bytes=0-99".to_string(), 0, 99),
+        ("bytes=100-".to_string(), 100, size - 1),
+        ("bytes=-100".to_string(), size - 100, size - 1),
+        (format!("bytes=1000-{}", size + 1000), 1000, size - 1),
+    ];
+
+    for (range, first, last) in ranges {
+        let request = user1.get(&route).add_header(RANGE, &range).await;
+
+        assert_eq!(
+            request.status_code(),
+            StatusCode::PARTIAL_CONTENT,
+            "{range}"
+        );
+        assert_eq!(
+            request.header(CONTENT_RANGE),
+            format!("bytes {first}-{last}/{size}").as_str()
+        );
+        assert_eq!(
+            request.header(CONTENT_LENGTH),
+            (last - first + 1).to_string().as_str()
+        );
+        assert_eq!(request.as_bytes(), &chunk[first..=last]);
+    }
+
+    let request = user1
+        .get(&route)
+        .add_header(RANGE, format!("bytes={size}-"))
+        .await;
+    assert_eq!(request.status_code(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(
+        request.header(CONTENT_RANGE),
+        format!("bytes */{size}").as_str()
+    );
+
+    // multiple ranges are answered with the whole chunk
+    let request = user1.get(&route).add_header(RANGE, "bytes=0-9,20-29").await;
+    assert_eq!(request.status_code(), StatusCode::OK);
+    assert_eq!(request.as_bytes(), &chunk);
+}
+
+#[tokio::test]
+pub async fn test_download_chunk_conditional() {
+    let ctx = TestContext::new(1).await;
+
+    let user1 = ctx.get_user(0);
+
+    let file1 = user1.generate_file_with_chunks(2).await;
+    let revision1 = file1.active_revision.expect("No revision with file!");
+    let route = routes::node::chunks(file1.id, revision1.id, 0);
+
+    let request = user1.get(&route).await;
+    assert_eq!(request.status_code(), StatusCode::OK);
+
+    let etag = request.header(ETAG);
+    assert!(etag.to_str().unwrap().starts_with('"'));
+    let cache_control = request.header(CACHE_CONTROL);
+    assert!(cache_control.to_str().unwrap().contains("immutable"));
+    let chunk = request.into_bytes();
+
+    let request = user1
+        .get(&route)
+        .add_header(IF_NONE_MATCH, etag.clone())
+        .await;
+    assert_eq!(request.status_code(), StatusCode::NOT_MODIFIED);
+    assert_eq!(request.header(ETAG), etag);
+    assert!(request.as_bytes().is_empty());
+
+    // the other chunk of the revision has its own tag
+    let request = user1
+        .get(routes::node::chunks(file1.id, revision1.id, 1))
+        .add_header(IF_NONE_MATCH, etag.clone())
+        .await;
+    assert_eq!(request.status_code(), StatusCode::OK);
+    assert_ne!(request.header(ETAG), etag);
+
+    let request = user1
+        .get(&route)
+        .add_header(RANGE, "bytes=10-19")
+        .add_header(IF_RANGE, etag.clone())
+        .await;
+    assert_eq!(request.status_code(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(request.as_bytes(), &chunk[10..20]);
+
+    // the client has a part of something else, so it gets the whole chunk
+    let request = user1
+        .get(&route)
+        .add_header(RANGE, "bytes=10-19")
+        .add_header(IF_RANGE, "\"something-else\"")
+        .await;
+    assert_eq!(request.status_code(), StatusCode::OK);
+    assert_eq!(request.as_bytes(), &chunk);
+}
+
+#[tokio::test]
+pub async fn test_download_missing_chunk() {
+    let ctx = TestContext::new(1).await;
+
+    let user1 = ctx.get_user(0);
+
+    let file1 = user1.generate_file_with_chunks(1).await;
+    let revision1 = file1.active_revision.expect("No revision with file!");
+
+    let request = user1
+        .get(routes::node::chunks(file1.id, revision1.id, 2))
+        .await;
+
+    assert_eq!(request.status_code(), StatusCode::NOT_FOUND);
+
+    let ErrorResponse { error } = request.json();
+    assert_eq!(error.kind, ErrorKind::NotFound);
+    assert_eq!(
+        error.request_id.as_deref(),
+        Some(request.header("x-request-id").to_str().unwrap())
+    );
+}
+
 #[tokio::test]
 pub async fn test_parent_metadata_mismatch() {
     let ctx = TestContext::new(1).await;
@@ -498,6 +712,237 @@ pub async fn test_parent_metadata_mismatch() {
     assert_eq!(request.status_code(), StatusCode::CONFLICT);
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+pub async fn test_concurrent_create_file() {
+    let ctx = TestContext::with_db_file(1).await;
+
+    let user1 = ctx.get_user(0);
+    let root = user1.fetch_node_from_db(user1.get_root()).unwrap();
+
+    // all uploads are based on the same version of the parent
+    let bodies: Vec<_> = (0..8)
+        .map(|_| PostCreateFileRequest {
+            parent_metadata_version: root.metadata_change_counter,
+            parent_metadata: EncryptedMetadata::random(),
+            node_metadata: EncryptedMetadata::random(),
+            node_id: UUID::random(),
+            file_iv: IV::random(),
+            chunk_count: 0,
+        })
+        .collect();
+
+    let requests = bodies
+        .iter()
+        .map(|body| user1.post(routes::node::file::create(root.id)).json(body))
+        .collect();
+
+    let responses = TestContext::send_concurrently(requests).await;
+
+    let mut created = vec![];
+    for (response, body) in responses.iter().zip(&bodies) {
+        match response.status_code() {
+            StatusCode::CREATED => created.push(body),
+            StatusCode::CONFLICT => {}
+            status => panic!("Invalid Status code {status}"),
+        }
+    }
+
+    // only one upload may win, otherwise the keys of the other files would be lost
+    assert_eq!(created.len(), 1);
+
+    let root_after = user1.fetch_node_from_db(root.id).unwrap();
+    assert_eq!(root_after.metadata, created[0].parent_metadata);
+    assert_eq!(
+        root_after.metadata_change_counter,
+        root.metadata_change_counter + 1
+    );
+
+    let children = ctx.node.get_children(root.id).unwrap();
+    assert_eq!(children.len(), 1);
+    assert_eq!(children[0].id, created[0].node_id);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+pub async fn test_parallel_chunk_uploads() {
+    const CHUNKS: i64 = 32;
+    const CHUNK_SIZE: usize = 1024 * 1024;
+
+    let mut tracker = None;
+    let ctx = TestContext::with_wrapped_vfs(1, |vfs| {
+        let vfs = Arc::new(WriteTrackingVfs::new(vfs));
+        tracker = Some(vfs.clone());
+        vfs
+    })
+    .await;
+    let tracker = tracker.unwrap();
+
+    let user1 = ctx.get_user(0);
+    let storage_used = user1.fetch_user_from_db().storage_used;
+
+    let mut uploads = vec![];
+    for _ in 0..2 {
+        let create_file_body = PostCreateFileRequest {
+            parent_metadata_version: user1
+                .fetch_node_from_db(user1.get_root())
+                .unwrap()
+                .metadata_change_counter,
+            parent_metadata: EncryptedMetadata::random(),
+            node_metadata: EncryptedMetadata::random(),
+            node_id: UUID::random(),
+            file_iv: IV::random(),
+            chunk_count: CHUNKS,
+        };
+
+        let request = user1
+            .post(routes::node::file::create(user1.get_root()))
+            .json(&create_file_body)
+            .await;
+
+        let PostCreateFileResponse::Created(created_node) = request.json() else {
+            panic!("Wrong status code!");
+        };
+        let chunks: Vec<_> = (1..=CHUNKS)
+            .map(|_| TestContext::random_bytes(CHUNK_SIZE))
+            .collect();
+        uploads.push((created_node, chunks));
+    }
+
+    // upload the first file one chunk after another as a baseline
+    let (sequential_node, sequential_chunks) = &uploads[0];
+    let sequential_revision = sequential_node.current_revision.as_ref().unwrap().id;
+    for (index, chunk) in (1..).zip(sequential_chunks) {
+        let request = user1
+            .post(routes::node::chunks(
+                sequential_node.id,
+                sequential_revision,
+                index,
+            ))
+            .bytes(chunk.clone())
+            .await;
+        assert_eq!(request.status_code(), StatusCode::CREATED);
+    }
+    assert_eq!(tracker.peak_concurrent_writes(), 1);
+
+    let (parallel_node, parallel_chunks) = &uploads[1];
+    let parallel_revision = parallel_node.current_revision.as_ref().unwrap().id;
+    let requests = (1..)
+        .zip(parallel_chunks)
+        .map(|(index, chunk)| {
+            user1
+                .post(routes::node::chunks(
+                    parallel_node.id,
+                    parallel_revision,
+                    index,
+                ))
+                .bytes(chunk.clone())
+        })
+        .collect();
+
+    let responses = TestContext::send_concurrently(requests).await;
+    for response in &responses {
+        assert_eq!(response.status_code(), StatusCode::CREATED);
+    }
+
+    // the chunks of one revision are not written one after another
+    assert!(tracker.peak_concurrent_writes() > 1);
+
+    for (node, _) in &uploads {
+        let revision = node.current_revision.as_ref().unwrap().id;
+        let request = user1
+            .post(routes::node::file::commit(node.id, revision))
+            .await;
+        assert_eq!(request.status_code(), StatusCode::OK);
+    }
+
+    for (index, chunk) in (1..).zip(parallel_chunks) {
+        let stored = ctx
+            .state
+            .vfs
+            .read_chunk(&parallel_revision, index)
+            .await
+            .expect("Failed to read from VFS!");
+        assert_eq!(stored.data, *chunk);
+    }
+
+    assert_eq!(
+        user1.fetch_user_from_db().storage_used,
+        storage_used + da!(2 * CHUNKS as usize * CHUNK_SIZE)
+    );
+}
+
+#[tokio::test]
+pub async fn test_write_while_persisting_file() {
+    let mut paused_vfs = None;
+    let ctx = TestContext::with_wrapped_vfs(1, |vfs| {
+        let vfs = Arc::new(PausedCommitVfs::new(vfs));
+        paused_vfs = Some(vfs.clone());
+        vfs
+    })
+    .await;
+    let paused_vfs = paused_vfs.unwrap();
+
+    let user1 = ctx.get_user(0);
+
+    let create_file_body = PostCreateFileRequest {
+        parent_metadata_version: 0,
+        parent_metadata: EncryptedMetadata::random(),
+        node_metadata: EncryptedMetadata::random(),
+        node_id: UUID::random(),
+        file_iv: IV::random(),
+        chunk_count: 1,
+    };
+    let request = user1
+        .post(routes::node::file::create(user1.get_root()))
+        .json(&create_file_body)
+        .await;
+    let PostCreateFileResponse::Created(created_node) = request.json() else {
+        panic!("Wrong status code!");
+    };
+    let revision = created_node.current_revision.unwrap().id;
+
+    let request = user1
+        .post(routes::node::chunks(created_node.id, revision, 1))
+        .bytes(TestContext::random_bytes(4096))
+        .await;
+    assert_eq!(request.status_code(), StatusCode::CREATED);
+
+    let commit = user1
+        .post(routes::node::file::commit(created_node.id, revision))
+        .into_future();
+    let write_during_commit = async {
+        paused_vfs.wait_for_commit().await;
+
+        // other writers must not wait for the file to be persisted
+        let create_folder_body = PostCreateFolderRequest {
+            parent_metadata_version: user1
+                .fetch_node_from_db(user1.get_root())
+                .unwrap()
+                .metadata_change_counter,
+            parent_metadata: EncryptedMetadata::random(),
+            node_metadata: EncryptedMetadata::random(),
+            node_id: UUID::random(),
+        };
+        let request = user1
+            .post(routes::node::folder::create(user1.get_root()))
+            .json(&create_folder_body)
+            .await;
+
+        paused_vfs.resume_commit();
+        request
+    };
+    let (commit, write) = tokio::join!(commit, write_during_commit);
+
+    assert_eq!(write.status_code(), StatusCode::CREATED);
+    assert_eq!(commit.status_code(), StatusCode::OK);
+    assert!(
+        user1
+            .fetch_node_from_db(created_node.id)
+            .unwrap()
+            .current_revision
+            .is_some()
+    );
+}
+
 #[tokio::test]
 pub async fn test_update_file() {
     let ctx = TestContext::new(1).await;
@@ -564,3 +1009,193 @@ pub async fn test_update_folder_as_file() {
 
 #[tokio::test]
 pub async fn test_get_file_versions() {}
+
+#[tokio::test]
+pub async fn test_create_file_as_restricted_user() {
+    let ctx = TestContext::new(1).await;
+
+    let user1 = ctx.get_user(0);
+    user1.set_user_type(UserType::Restricted);
+
+    let id = UUID::random();
+
+    let create_file_body = PostCreateFileRequest {
+        parent_metadata_version: 0,
+        parent_metadata: EncryptedMetadata::random(),
+        node_metadata: EncryptedMetadata::random(),
+        node_id: id,
+        file_iv: IV::random(),
+        chunk_count: 1,
+    };
+
+    let request = user1
+        .post(routes::node::file::create(user1.get_root()))
+        .json(&create_file_body)
+        .await;
+
+    assert_eq!(request.status_code(), StatusCode::FORBIDDEN);
+    assert!(ctx.node.get_node(id).expect("Failed to get node").is_none());
+}
+
+#[tokio::test]
+pub async fn test_upload_chunk_as_restricted_user() {
+    let ctx = TestContext::new(1).await;
+
+    let user1 = ctx.get_user(0);
+
+    let id = UUID::random();
+
+    let create_file_body = PostCreateFileRequest {
+        parent_metadata_version: 0,
+        parent_metadata: EncryptedMetadata::random(),
+        node_metadata: EncryptedMetadata::random(),
+        node_id: id,
+        file_iv: IV::random(),
+        chunk_count: 1,
+    };
+
+    let request = user1
+        .post(routes::node::file::create(user1.get_root()))
+        .json(&create_file_body)
+        .await;
+
+    let PostCreateFileResponse::Created(created_node) = request.json() else {
+        panic!("Wrong status code!");
+    };
+    let current_revision = created_node.current_revision.unwrap().id;
+
+    user1.set_user_type(UserType::Restricted);
+
+    let request = user1
+        .post(routes::node::chunks(id, current_revision, 1))
+        .bytes(TestContext::random_bytes(4096))
+        .await;
+
+    assert_eq!(request.status_code(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+pub async fn test_download_file_as_restricted_user() {
+    let ctx = TestContext::new(1).await;
+
+    let user1 = ctx.get_user(0);
+
+    let file1 = user1.generate_file_with_chunks(1).await;
+    let revision1 = file1.active_revision.expect("No revision with file!");
+
+    user1.set_user_type(UserType::Restricted);
+
+    let request = user1
+        .get(routes::node::chunks(file1.id, revision1.id, 0))
+        .await;
+
+    assert_eq!(request.status_code(), StatusCode::OK);
+    TestContext::validate_checksum(&revision1.chunks[0].checksum, request.as_bytes());
+}
+
+#[tokio::test]
+pub async fn test_upload_chunk_exceeding_storage_limit() {
+    let ctx = TestContext::new(1).await;
+
+    let user1 = ctx.get_user(0);
+
+    let create_file_body = PostCreateFileRequest {
+        parent_metadata_version: 0,
+        parent_metadata: EncryptedMetadata::random(),
+        node_metadata: EncryptedMetadata::random(),
+        node_id: UUID::random(),
+        file_iv: IV::random(),
+        chunk_count: 1,
+    };
+
+    let request = user1
+        .post(routes::node::file::create(user1.get_root()))
+        .json(&create_file_body)
+        .await;
+
+    let PostCreateFileResponse::Created(created_node) = request.json() else {
+        panic!("Wrong status code!");
+    };
+    let current_revision = created_node.current_revision.unwrap().id;
+
+    let user = user1.fetch_user_from_db();
+    user1
+        .state
+        .user_repository
+        .update_user(UserEntity {
+            storage_limit: user.storage_used + da!(4 KiB),
+            ..user
+        })
+        .expect("Failed to update user");
+
+    let request = user1
+        .post(routes::node::chunks(created_node.id, current_revision, 1))
+        .bytes(TestContext::random_bytes(8192))
+        .await;
+
+    assert_eq!(request.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(user1.fetch_user_from_db().storage_used, user.storage_used);
+
+    // the rejected chunk was removed again, so it can be uploaded with a smaller size
+    let request = user1
+        .post(routes::node::chunks(created_node.id, current_revision, 1))
+        .bytes(TestContext::random_bytes(4096))
+        .await;
+
+    assert_eq!(request.status_code(), StatusCode::CREATED);
+    assert_eq!(
+        user1.fetch_user_from_db().storage_used,
+        user.storage_used + da!(4 KiB)
+    );
+}
+
+#[tokio::test]
+pub async fn test_upload_chunk_exceeding_max_chunk_size() {
+    let ctx = TestContext::new(1).await;
+
+    let user1 = ctx.get_user(0);
+
+    let create_file_body = PostCreateFileRequest {
+        parent_metadata_version: 0,
+        parent_metadata: EncryptedMetadata::random(),
+        node_metadata: EncryptedMetadata::random(),
+        node_id: UUID::random(),
+        file_iv: IV::random(),
+        chunk_count: 1,
+    };
+
+    let request = user1
+        .post(routes::node::file::create(user1.get_root()))
+        .json(&create_file_body)
+        .await;
+
+    let PostCreateFileResponse::Created(created_node) = request.json() else {
+        panic!("Wrong status code!");
+    };
+    let current_revision = created_node.current_revision.unwrap().id;
+    let storage_used = user1.fetch_user_from_db().storage_used;
+
+    let request = user1
+        .post(routes::node::chunks(created_node.id, current_revision, 1))
+        .bytes(TestContext::random_bytes(
+            MAX_CHUNK_SIZE.as_bytes() as usize + 1,
+        ))
+        .await;
+
+    assert_eq!(request.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+    let ErrorResponse { error } = request.json();
+    assert_eq!(error.kind, ErrorKind::PayloadTooLarge);
+    assert_eq!(user1.fetch_user_from_db().storage_used, storage_used);
+    assert!(!ctx.state.vfs.chunk_exists(&current_revision, 1).await);
+
+    let request = user1
+        .post(routes::node::chunks(created_node.id, current_revision, 1))
+        .bytes(TestContext::random_bytes(MAX_CHUNK_SIZE.as_bytes() as usize))
+        .await;
+
+    assert_eq!(request.status_code(), StatusCode::CREATED);
+    assert_eq!(
+        user1.fetch_user_from_db().storage_used,
+        storage_used + MAX_CHUNK_SIZE
+    );
+}
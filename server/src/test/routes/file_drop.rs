@@ -0,0 +1,445 @@
+use crate::service::file_drop::delete_stale_dropped_files;
+use crate::storage::vfs::FileStatus;
+use crate::test::utils::{TestContext, TestUserEntity};
+
+use crabdrive_common::da;
+use crabdrive_common::encrypted_metadata::EncryptedMetadata;
+use crabdrive_common::iv::IV;
+use crabdrive_common::payloads::node::request::file_drop::*;
+use crabdrive_common::payloads::node::response::file::CommitFileError;
+use crabdrive_common::payloads::node::response::file_drop::*;
+use crabdrive_common::payloads::node::response::node::GetNodeChildrenResponse;
+use crabdrive_common::routes;
+use crabdrive_common::storage::{FileDropId, FileRevision, NodeId};
+
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use chrono::{Duration, Utc};
+use pretty_assertions::assert_eq;
+
+async fn create_file_drop(user: &TestUserEntity, folder_id: NodeId) -> FileDropId {
+    let response = user
+        .post(routes::node::share::file_drop(folder_id))
+        .json(&PostFileDropRequest { expires_at: None })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let PostFileDropResponse::Ok(drop_id) = response.json() else {
+        panic!("Expected Ok");
+    };
+    drop_id
+}
+
+/// Drops a file with the given amount of chunks without committing it
+async fn drop_file(
+    server: &TestServer,
+    drop_id: FileDropId,
+    chunk_count: i64,
+) -> (NodeId, FileRevision) {
+    let node_id = NodeId::random();
+    let response = server
+        .post(&routes::drop::files(drop_id))
+        .json(&PostDropFileRequest {
+            node_id,
+            node_metadata: EncryptedMetadata::random(),
+            encrypted_metadata_key: vec![1, 2, 3],
+            file_iv: IV::random(),
+            chunk_count,
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+
+    let PostDropFileResponse::Created(revision) = response.json() else {
+        panic!("Expected Created");
+    };
+    (node_id, revision)
+}
+
+async fn children(user: &TestUserEntity, folder_id: NodeId) -> Vec<NodeId> {
+    let response = user.get(routes::node::children(folder_id)).await;
+    let GetNodeChildrenResponse::Ok(children) = response.json() else {
+        panic!("Expected Ok");
+    };
+    children.into_iter().map(|node| node.id).collect()
+}
+
+#[tokio::test]
+pub async fn test_drop_and_adopt_file() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let folder = user.generate_random_folder().await;
+    let existing_file = user.generate_file_in(folder.id).await;
+    let drop_id = create_file_drop(user, folder.id).await;
+
+    // the uploader only learns the public key of the owner
+    let response = ctx.server.get(&routes::drop::by_id(drop_id)).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(
+        response.json::<GetFileDropResponse>(),
+        GetFileDropResponse::Ok(FileDropInfo {
+            public_key: user.keys.public_key.clone(),
+            expires_at: None,
+        })
+    );
+
+    let folder_metadata = user.fetch_node_from_db(folder.id).unwrap().metadata;
+
+    let (node_id, revision) = drop_file(&ctx.server, drop_id, 2).await;
+    for index in 1..=2 {
+        let response = ctx
+            .server
+            .post(&routes::drop::chunks(drop_id, node_id, revision.id, index))
+            .bytes(TestContext::random_bytes(1024))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::CREATED);
+    }
+
+    let response = ctx
+        .server
+        .post(&routes::drop::commit(drop_id, node_id, revision.id))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    // the uploader cannot list the folder
+    let response = ctx.server.get(&routes::node::children(folder.id)).await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+
+    // the metadata of the folder was not touched and the file is hidden until it is adopted
+    let folder_entity = user.fetch_node_from_db(folder.id).unwrap();
+    assert_eq!(folder_entity.metadata, folder_metadata);
+    assert_eq!(children(user, folder.id).await, vec![existing_file.id]);
+
+    let response = user
+        .get(routes::node::share::dropped_files(folder.id))
+        .await;
+    let GetDroppedFilesResponse::Ok(dropped_files) = response.json() else {
+        panic!("Expected Ok");
+    };
+    assert_eq!(dropped_files.len(), 1);
+    assert_eq!(dropped_files[0].node.id, node_id);
+    assert_eq!(dropped_files[0].node.owner_id, user.id);
+    assert_eq!(dropped_files[0].encrypted_metadata_key, vec![1, 2, 3]);
+
+    let response = user
+        .post(routes::node::share::adopt_dropped_files(folder.id))
+        .json(&PostAdoptDroppedFilesRequest {
+            parent_metadata: EncryptedMetadata::random(),
+            parent_metadata_version: folder_entity.metadata_change_counter,
+            node_ids: vec![node_id],
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let mut children = children(user, folder.id).await;
+    children.sort();
+    let mut expected = vec![existing_file.id, node_id];
+    expected.sort();
+    assert_eq!(children, expected);
+
+    let response = user
+        .get(routes::node::share::dropped_files(folder.id))
+        .await;
+    assert_eq!(
+        response.json::<GetDroppedFilesResponse>(),
+        GetDroppedFilesResponse::Ok(vec![])
+    );
+
+    let response = user
+        .get(routes::node::chunks(node_id, revision.id, 1))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+}
+
+#[tokio::test]
+pub async fn test_file_drop_cannot_modify_other_files() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let folder = user.generate_random_folder().await;
+    let existing_file = user.generate_file_in(folder.id).await;
+    let existing_revision = existing_file.active_revision.unwrap();
+    let drop_id = create_file_drop(user, folder.id).await;
+
+    // files, which were not dropped through the link, cannot be written
+    let response = ctx
+        .server
+        .post(&routes::drop::chunks(
+            drop_id,
+            existing_file.id,
+            existing_revision.id,
+            1,
+        ))
+        .bytes(TestContext::random_bytes(1024))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    let response = ctx
+        .server
+        .post(&routes::drop::commit(
+            drop_id,
+            existing_file.id,
+            existing_revision.id,
+        ))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    // existing nodes cannot be replaced
+    let response = ctx
+        .server
+        .post(&routes::drop::files(drop_id))
+        .json(&PostDropFileRequest {
+            node_id: existing_file.id,
+            node_metadata: EncryptedMetadata::random(),
+            encrypted_metadata_key: vec![1, 2, 3],
+            file_iv: IV::random(),
+            chunk_count: 1,
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CONFLICT);
+
+    // a dropped file can only be written through the drop it was uploaded with
+    let other_drop_id = create_file_drop(user, folder.id).await;
+    let (node_id, revision) = drop_file(&ctx.server, drop_id, 1).await;
+    let response = ctx
+        .server
+        .post(&routes::drop::chunks(
+            other_drop_id,
+            node_id,
+            revision.id,
+            1,
+        ))
+        .bytes(TestContext::random_bytes(1024))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    // missing chunks are reported, committed files cannot be changed anymore
+    let response = ctx
+        .server
+        .post(&routes::drop::commit(drop_id, node_id, revision.id))
+        .await;
+    assert_eq!(
+        response.json::<PostDropCommitResponse>(),
+        PostDropCommitResponse::BadRequest(CommitFileError::MissingChunks(vec![1]))
+    );
+
+    let response = ctx
+        .server
+        .post(&routes::drop::chunks(drop_id, node_id, revision.id, 1))
+        .bytes(TestContext::random_bytes(1024))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+
+    let response = ctx
+        .server
+        .post(&routes::drop::commit(drop_id, node_id, revision.id))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let response = ctx
+        .server
+        .post(&routes::drop::chunks(drop_id, node_id, revision.id, 1))
+        .bytes(TestContext::random_bytes(1024))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+pub async fn test_expired_and_revoked_file_drop() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let folder = user_a.generate_random_folder().await;
+
+    let expired_drop = ctx
+        .state
+        .file_drop_repository
+        .create_file_drop(
+            folder.id,
+            user_a.id,
+            Some(Utc::now().naive_utc() - Duration::hours(1)),
+        )
+        .unwrap();
+
+    let response = ctx.server.get(&routes::drop::by_id(expired_drop.id)).await;
+    assert_eq!(response.status_code(), StatusCode::GONE);
+
+    let response = ctx
+        .server
+        .post(&routes::drop::files(expired_drop.id))
+        .json(&PostDropFileRequest {
+            node_id: NodeId::random(),
+            node_metadata: EncryptedMetadata::random(),
+            encrypted_metadata_key: vec![1, 2, 3],
+            file_iv: IV::random(),
+            chunk_count: 1,
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    let drop_id = create_file_drop(user_a, folder.id).await;
+    let (node_id, _) = drop_file(&ctx.server, drop_id, 1).await;
+
+    // only the owner can revoke the drop
+    let response = user_b.delete(routes::drop::by_id(drop_id)).await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let response = user_a.delete(routes::drop::by_id(drop_id)).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let response = ctx.server.get(&routes::drop::by_id(drop_id)).await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    // files dropped before can still be adopted, but nobody else can see them
+    let response = user_a
+        .get(routes::node::share::dropped_files(folder.id))
+        .await;
+    let GetDroppedFilesResponse::Ok(dropped_files) = response.json() else {
+        panic!("Expected Ok");
+    };
+    assert_eq!(dropped_files.len(), 1);
+    assert_eq!(dropped_files[0].node.id, node_id);
+
+    let response = user_b
+        .get(routes::node::share::dropped_files(folder.id))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+pub async fn test_file_drop_restrictions() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let folder = user_a.generate_random_folder().await;
+    let file = user_a.generate_random_file().await;
+
+    let response = user_a
+        .post(routes::node::share::file_drop(file.id))
+        .json(&PostFileDropRequest { expires_at: None })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let response = user_b
+        .post(routes::node::share::file_drop(folder.id))
+        .json(&PostFileDropRequest { expires_at: None })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    let response = user_a
+        .post(routes::node::share::file_drop(folder.id))
+        .json(&PostFileDropRequest {
+            expires_at: Some(Utc::now().naive_utc() - Duration::hours(1)),
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let drop_id = create_file_drop(user_a, folder.id).await;
+    let (node_id, _) = drop_file(&ctx.server, drop_id, 1).await;
+    let folder_version = user_a
+        .fetch_node_from_db(folder.id)
+        .unwrap()
+        .metadata_change_counter;
+
+    // unfinished uploads cannot be adopted
+    let response = user_a
+        .post(routes::node::share::adopt_dropped_files(folder.id))
+        .json(&PostAdoptDroppedFilesRequest {
+            parent_metadata: EncryptedMetadata::random(),
+            parent_metadata_version: folder_version,
+            node_ids: vec![node_id],
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    // only dropped files can be adopted
+    let response = user_a
+        .post(routes::node::share::adopt_dropped_files(folder.id))
+        .json(&PostAdoptDroppedFilesRequest {
+            parent_metadata: EncryptedMetadata::random(),
+            parent_metadata_version: folder_version,
+            node_ids: vec![file.id],
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let response = user_a
+        .post(routes::node::share::adopt_dropped_files(folder.id))
+        .json(&PostAdoptDroppedFilesRequest {
+            parent_metadata: EncryptedMetadata::random(),
+            parent_metadata_version: folder_version + 1,
+            node_ids: vec![],
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+pub async fn test_abandoned_dropped_files_are_deleted() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let folder = user.generate_random_folder().await;
+    let drop_id = create_file_drop(user, folder.id).await;
+    let storage_used = user.fetch_user_from_db().storage_used;
+
+    let (committed_id, committed_revision) = drop_file(&ctx.server, drop_id, 1).await;
+    let (abandoned_id, abandoned_revision) = drop_file(&ctx.server, drop_id, 2).await;
+    for (node_id, revision_id) in [
+        (committed_id, committed_revision.id),
+        (abandoned_id, abandoned_revision.id),
+    ] {
+        let response = ctx
+            .server
+            .post(&routes::drop::chunks(drop_id, node_id, revision_id, 1))
+            .bytes(TestContext::random_bytes(1024))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::CREATED);
+    }
+    let response = ctx
+        .server
+        .post(&routes::drop::commit(
+            drop_id,
+            committed_id,
+            committed_revision.id,
+        ))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(
+        user.fetch_user_from_db().storage_used,
+        storage_used + da!(2 KiB)
+    );
+
+    // uploads, which are still in progress, are kept
+    let count = delete_stale_dropped_files(&ctx.state, Utc::now().naive_utc() - Duration::hours(1))
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+
+    let count = delete_stale_dropped_files(&ctx.state, Utc::now().naive_utc() + Duration::hours(1))
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+
+    assert!(user.fetch_node_from_db(abandoned_id).is_none());
+    assert!(user.fetch_node_from_db(committed_id).is_some());
+    assert_eq!(
+        ctx.state.vfs.file_status(&abandoned_revision.id).await,
+        FileStatus::NotFound
+    );
+    assert_eq!(
+        user.fetch_user_from_db().storage_used,
+        storage_used + da!(1 KiB)
+    );
+
+    let response = user
+        .get(routes::node::share::dropped_files(folder.id))
+        .await;
+    let GetDroppedFilesResponse::Ok(dropped_files) = response.json() else {
+        panic!("Expected Ok");
+    };
+    assert_eq!(dropped_files.len(), 1);
+    assert_eq!(dropped_files[0].node.id, committed_id);
+}
@@ -3,7 +3,7 @@ use crate::test::utils::TestContext;
 use crabdrive_common::encrypted_metadata::EncryptedMetadata;
 use crabdrive_common::payloads::node::{request::folder::*, response::folder::*};
 use crabdrive_common::routes;
-use crabdrive_common::storage::NodeType;
+use crabdrive_common::storage::{NodeType, SharePermission};
 use crabdrive_common::uuid::UUID;
 
 use axum::http::StatusCode;
@@ -38,6 +38,7 @@ pub async fn test_create_folder() {
     let node = match request.json::<PostCreateFolderResponse>() {
         PostCreateFolderResponse::Created(encrypted_node) => encrypted_node,
         PostCreateFolderResponse::NotFound => panic!("Invalid Status code"),
+        PostCreateFolderResponse::Forbidden => panic!("Invalid Status code"),
         PostCreateFolderResponse::BadRequest => panic!("Invalid Status code"),
         PostCreateFolderResponse::Conflict => panic!("Invalid Status code"),
     };
@@ -46,7 +47,10 @@ pub async fn test_create_folder() {
     assert!(node.deleted_on.is_none());
     assert_eq!(node.node_type, NodeType::Folder);
     assert_eq!(node.encrypted_metadata, metadata);
-    assert_eq!(node.has_access, vec![(user1_id, user1_name)]);
+    assert_eq!(
+        node.has_access,
+        vec![(user1_id, user1_name, SharePermission::Manage)]
+    );
 }
 
 #[tokio::test]
@@ -118,3 +122,51 @@ pub async fn test_create_folder_in_file() {
 
     assert_eq!(request.status_code(), StatusCode::BAD_REQUEST);
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+pub async fn test_concurrent_create_folder() {
+    let ctx = TestContext::with_db_file(1).await;
+
+    let user1 = ctx.get_user(0);
+    let folder = user1.generate_random_folder().await;
+    let parent = user1.fetch_node_from_db(folder.id).unwrap();
+
+    let bodies: Vec<_> = (0..8)
+        .map(|_| PostCreateFolderRequest {
+            parent_metadata_version: parent.metadata_change_counter,
+            parent_metadata: EncryptedMetadata::random(),
+            node_metadata: EncryptedMetadata::random(),
+            node_id: UUID::random(),
+        })
+        .collect();
+
+    let requests = bodies
+        .iter()
+        .map(|body| {
+            user1
+                .post(routes::node::folder::create(parent.id))
+                .json(body)
+        })
+        .collect();
+
+    let responses = TestContext::send_concurrently(requests).await;
+
+    let created: Vec<_> = responses
+        .iter()
+        .zip(&bodies)
+        .filter(|(response, _)| response.status_code() == StatusCode::CREATED)
+        .map(|(_, body)| body)
+        .collect();
+    assert_eq!(created.len(), 1);
+    assert!(responses.iter().all(|response| {
+        [StatusCode::CREATED, StatusCode::CONFLICT].contains(&response.status_code())
+    }));
+
+    let parent_after = user1.fetch_node_from_db(parent.id).unwrap();
+    assert_eq!(parent_after.metadata, created[0].parent_metadata);
+    assert_eq!(
+        parent_after.metadata_change_counter,
+        parent.metadata_change_counter + 1
+    );
+    assert_eq!(ctx.node.get_children(parent.id).unwrap().len(), 1);
+}
@@ -1,5 +1,8 @@
+mod admin;
 mod auth;
 mod file;
+mod file_drop;
 mod folder;
 mod node;
+mod public_link;
 mod share;
@@ -3,6 +3,7 @@ use crate::test::utils::TestContext;
 use crabdrive_common::encrypted_metadata::EncryptedMetadata;
 use crabdrive_common::payloads::node::{request::node::*, response::node::*};
 use crabdrive_common::routes;
+use crabdrive_common::user::UserType;
 use crabdrive_common::uuid::UUID;
 
 use axum::http::StatusCode;
@@ -109,6 +110,49 @@ pub async fn test_patch_node_metadata_mismatch() {
     assert_eq!(response.status_code(), StatusCode::CONFLICT);
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+pub async fn test_concurrent_patch_node() {
+    let ctx = TestContext::with_db_file(1).await;
+    let user = ctx.get_user(0);
+
+    let folder = user.generate_random_folder().await;
+    let node_entity = user.fetch_node_from_db(folder.id).unwrap();
+
+    let payloads: Vec<_> = (0..8)
+        .map(|_| PatchNodeRequest {
+            node_metadata: EncryptedMetadata::random(),
+            node_change_count: node_entity.metadata_change_counter,
+        })
+        .collect();
+
+    let requests = payloads
+        .iter()
+        .map(|payload| user.patch(routes::node::by_id(folder.id)).json(payload))
+        .collect();
+
+    let responses = TestContext::send_concurrently(requests).await;
+
+    let successful: Vec<_> = responses
+        .iter()
+        .zip(&payloads)
+        .filter(|(response, _)| response.status_code() == StatusCode::OK)
+        .map(|(_, payload)| payload)
+        .collect();
+    assert_eq!(successful.len(), 1);
+    assert!(
+        responses.iter().all(
+            |response| [StatusCode::OK, StatusCode::CONFLICT].contains(&response.status_code())
+        )
+    );
+
+    let node_after = user.fetch_node_from_db(folder.id).unwrap();
+    assert_eq!(node_after.metadata, successful[0].node_metadata);
+    assert_eq!(
+        node_after.metadata_change_counter,
+        node_entity.metadata_change_counter + 1
+    );
+}
+
 // delete node
 
 #[tokio::test]
@@ -154,6 +198,23 @@ pub async fn test_delete_node() {
     assert_eq!(get_resp.status_code(), StatusCode::NOT_FOUND);
 }
 
+#[tokio::test]
+pub async fn test_delete_root_node() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let payload = DeleteNodeRequest {
+        parent_change_count: 0,
+        parent_node_metadata: EncryptedMetadata::random(),
+    };
+    let response = user
+        .delete(routes::node::by_id(user.get_root()))
+        .json(&payload)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+    assert!(user.fetch_node_from_db(user.get_root()).is_some());
+}
+
 // move node
 
 #[tokio::test]
@@ -347,6 +408,66 @@ pub async fn test_move_node_with_invalid_metadata_change_counter() {
     assert_eq!(response.status_code(), StatusCode::CONFLICT);
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+pub async fn test_concurrent_move_into_same_folder() {
+    let ctx = TestContext::with_db_file(1).await;
+    let user = ctx.get_user(0);
+
+    let source_folder = user.generate_random_folder().await;
+    let target_folder = user.generate_random_folder().await;
+    let mut files = vec![];
+    for _ in 0..8 {
+        files.push(user.generate_file_in(source_folder.id).await);
+    }
+
+    let source = user.fetch_node_from_db(source_folder.id).unwrap();
+    let target = user.fetch_node_from_db(target_folder.id).unwrap();
+
+    // every move is based on the same version of both folders
+    let requests = files
+        .iter()
+        .map(|file| {
+            user.post(routes::node::move_to(file.id))
+                .json(&PostMoveNodeRequest {
+                    to_node_id: target.id,
+                    from_node_metadata: EncryptedMetadata::random(),
+                    to_node_metadata: EncryptedMetadata::random(),
+                    from_node_change_counter: source.metadata_change_counter,
+                    to_node_change_counter: target.metadata_change_counter,
+                })
+        })
+        .collect();
+
+    let responses = TestContext::send_concurrently(requests).await;
+
+    let moved = responses
+        .iter()
+        .filter(|response| response.status_code() == StatusCode::OK)
+        .count();
+    assert_eq!(moved, 1);
+    assert!(
+        responses.iter().all(
+            |response| [StatusCode::OK, StatusCode::CONFLICT].contains(&response.status_code())
+        )
+    );
+
+    // the losing moves must not have touched the tree
+    assert_eq!(ctx.node.get_children(source.id).unwrap().len(), 7);
+    assert_eq!(ctx.node.get_children(target.id).unwrap().len(), 1);
+    assert_eq!(
+        user.fetch_node_from_db(source.id)
+            .unwrap()
+            .metadata_change_counter,
+        source.metadata_change_counter + 1
+    );
+    assert_eq!(
+        user.fetch_node_from_db(target.id)
+            .unwrap()
+            .metadata_change_counter,
+        target.metadata_change_counter + 1
+    );
+}
+
 #[tokio::test]
 pub async fn test_move_root_and_trash_node() {
     let ctx = TestContext::new(1).await;
@@ -367,7 +488,7 @@ pub async fn test_move_root_and_trash_node() {
         .json(&payload)
         .await;
 
-    response.assert_status_internal_server_error();
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
 
     let payload = PostMoveNodeRequest {
         to_node_id: target_folder1.id,
@@ -382,7 +503,7 @@ pub async fn test_move_root_and_trash_node() {
         .json(&payload)
         .await;
 
-    response.assert_status_internal_server_error();
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
 }
 
 #[tokio::test]
@@ -567,3 +688,43 @@ pub async fn test_unauthorized_access() {
 
     assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
 }
+
+#[tokio::test]
+pub async fn test_move_node_as_restricted_user() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let source_folder = user.generate_random_folder().await;
+    let target_folder = user.generate_random_folder().await;
+    let file = user.generate_file_in(source_folder.id).await;
+
+    user.set_user_type(UserType::Restricted);
+
+    let payload = PostMoveNodeRequest {
+        to_node_id: target_folder.id,
+        from_node_metadata: EncryptedMetadata::random(),
+        to_node_metadata: EncryptedMetadata::random(),
+        from_node_change_counter: 1,
+        to_node_change_counter: 0,
+    };
+
+    let response = user
+        .post(routes::node::move_to(file.id))
+        .json(&payload)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let response = user
+        .post(routes::node::move_to_trash(file.id))
+        .json(&payload)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let node = user.fetch_node_from_db(file.id).unwrap();
+    assert_eq!(node.parent_id, Some(source_folder.id));
+    assert!(node.deleted_on.is_none());
+
+    // Reading is still possible
+    let response = user.get(routes::node::by_id(file.id)).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+}
@@ -0,0 +1,253 @@
+use crate::db::operations;
+use crate::test::utils::{TestContext, TestUserEntity};
+
+use crabdrive_common::encrypted_metadata::EncryptedMetadata;
+use crabdrive_common::payloads::node::request::node::PostMoveNodeToTrashRequest;
+use crabdrive_common::payloads::node::request::public_link::PostPublicLinkRequest;
+use crabdrive_common::payloads::node::request::share::PostAcceptShareRequest;
+use crabdrive_common::payloads::node::request::share::PostShareNodeRequest;
+use crabdrive_common::payloads::node::response::public_link::*;
+use crabdrive_common::payloads::node::response::share::PostShareNodeResponse;
+use crabdrive_common::routes;
+use crabdrive_common::storage::{NodeId, PublicLinkId, SharePermission};
+
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use pretty_assertions::assert_eq;
+
+async fn create_public_link(user: &TestUserEntity, node_id: NodeId) -> PublicLinkId {
+    let response = user
+        .post(routes::node::share::public_link(node_id))
+        .json(&PostPublicLinkRequest {
+            wrapped_metadata_key: user.keys.master_key.clone(),
+            expires_at: None,
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let PostPublicLinkResponse::Ok(link_id) = response.json() else {
+        panic!("Expected Ok");
+    };
+    link_id
+}
+
+#[tokio::test]
+pub async fn test_download_with_public_link() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let file = user.generate_file_with_chunks(1).await;
+    let revision = file.active_revision.expect("No revision with file!");
+
+    let link_id = create_public_link(user, file.id).await;
+
+    // the link can be used without being signed in
+    let response = ctx.server.get(&routes::public::by_id(link_id)).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let GetPublicLinkResponse::Ok(public_node) = response.json() else {
+        panic!("Expected Ok");
+    };
+    assert_eq!(public_node.id, file.id);
+    assert_eq!(public_node.wrapped_metadata_key, user.keys.master_key);
+    assert_eq!(public_node.current_revision.unwrap().id, revision.id);
+
+    let response = ctx.server.get(&routes::public::versions(link_id)).await;
+    let GetPublicVersionsResponse::Ok(versions) = response.json() else {
+        panic!("Expected Ok");
+    };
+    assert_eq!(versions.len(), 1);
+
+    let response = ctx
+        .server
+        .get(&routes::public::chunks(link_id, revision.id, 0))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    TestContext::validate_checksum(&revision.chunks[0].checksum, response.as_bytes());
+}
+
+#[tokio::test]
+pub async fn test_public_link_only_for_files() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let folder = user.generate_random_folder().await;
+
+    let response = user
+        .post(routes::node::share::public_link(folder.id))
+        .json(&PostPublicLinkRequest {
+            wrapped_metadata_key: user.keys.master_key.clone(),
+            expires_at: None,
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+pub async fn test_public_link_requires_manage_permission() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let file = user_a.generate_file_with_chunks(1).await;
+
+    let response = user_b
+        .post(routes::node::share::public_link(file.id))
+        .json(&PostPublicLinkRequest {
+            wrapped_metadata_key: user_b.keys.master_key.clone(),
+            expires_at: None,
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    let response = user_a
+        .post(routes::node::share::share(file.id))
+        .json(&PostShareNodeRequest {
+            wrapped_metadata_key: user_a.keys.master_key.clone(),
+            permission: SharePermission::Edit,
+            expires_at: None,
+            revoke_on_expiry: false,
+        })
+        .await;
+    let PostShareNodeResponse::Ok(share_id) = response.json() else {
+        panic!("Expected Ok");
+    };
+    user_b
+        .post(routes::node::share::accept_share(share_id))
+        .json(&PostAcceptShareRequest {
+            new_wrapped_metadata_key: user_b.keys.master_key.clone(),
+        })
+        .await
+        .assert_status_ok();
+
+    let response = user_b
+        .post(routes::node::share::public_link(file.id))
+        .json(&PostPublicLinkRequest {
+            wrapped_metadata_key: user_b.keys.master_key.clone(),
+            expires_at: None,
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let link_id = create_public_link(user_a, file.id).await;
+
+    let response = user_b.delete(routes::public::by_id(link_id)).await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+pub async fn test_revoke_public_link() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let file = user.generate_file_with_chunks(1).await;
+    let revision = file.active_revision.expect("No revision with file!");
+    let link_id = create_public_link(user, file.id).await;
+
+    let response = user.delete(routes::public::by_id(link_id)).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let response = ctx.server.get(&routes::public::by_id(link_id)).await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    let response = ctx
+        .server
+        .get(&routes::public::chunks(link_id, revision.id, 0))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+pub async fn test_expired_public_link() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let file = user.generate_file_with_chunks(1).await;
+    let revision = file.active_revision.expect("No revision with file!");
+
+    let response = user
+        .post(routes::node::share::public_link(file.id))
+        .json(&PostPublicLinkRequest {
+            wrapped_metadata_key: user.keys.master_key.clone(),
+            expires_at: Some(Utc::now().naive_utc() - Duration::hours(1)),
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let public_link = ctx
+        .state
+        .public_link_repository
+        .create_public_link(
+            file.id,
+            user.id,
+            user.keys.master_key.clone(),
+            Some(Utc::now().naive_utc() - Duration::minutes(1)),
+        )
+        .unwrap();
+
+    let response = ctx.server.get(&routes::public::by_id(public_link.id)).await;
+    assert_eq!(response.status_code(), StatusCode::GONE);
+    assert!(matches!(
+        response.json::<GetPublicLinkResponse>(),
+        GetPublicLinkResponse::Expired
+    ));
+
+    let response = ctx
+        .server
+        .get(&routes::public::chunks(public_link.id, revision.id, 0))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+pub async fn test_public_link_of_trashed_file() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let file = user.generate_file_with_chunks(1).await;
+    let link_id = create_public_link(user, file.id).await;
+
+    let root_node = user.fetch_node_from_db(user.get_root()).unwrap();
+    let trash_node = user.fetch_node_from_db(user.get_trash()).unwrap();
+    user.post(routes::node::move_to_trash(file.id))
+        .json(&PostMoveNodeToTrashRequest {
+            to_node_id: trash_node.id,
+            from_node_metadata: EncryptedMetadata::random(),
+            to_node_metadata: EncryptedMetadata::random(),
+            from_node_change_counter: root_node.metadata_change_counter,
+            to_node_change_counter: trash_node.metadata_change_counter,
+        })
+        .await
+        .assert_status_ok();
+
+    let response = ctx.server.get(&routes::public::by_id(link_id)).await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+pub async fn test_delete_expired_public_links() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let file = user.generate_file_with_chunks(1).await;
+    let repository = &ctx.state.public_link_repository;
+
+    let expired = repository
+        .create_public_link(
+            file.id,
+            user.id,
+            user.keys.master_key.clone(),
+            Some(Utc::now().naive_utc() - Duration::minutes(1)),
+        )
+        .unwrap();
+    let not_expired = create_public_link(user, file.id).await;
+
+    let mut conn = ctx.state.db_pool.get().unwrap();
+    let count =
+        operations::public_link::delete_expired_public_links(&mut conn, Utc::now().naive_utc())
+            .unwrap();
+    assert_eq!(count, 1);
+
+    assert!(repository.get_public_link(expired.id).unwrap().is_none());
+    assert!(repository.get_public_link(not_expired).unwrap().is_some());
+}
@@ -1,10 +1,20 @@
-use crate::test::utils::TestContext;
-
+use crate::db::operations;
+use crate::test::utils::{TestContext, TestUserEntity};
+
+use crabdrive_common::encrypted_metadata::EncryptedMetadata;
+use crabdrive_common::iv::IV;
+use crabdrive_common::payloads::error::{ErrorKind, ErrorResponse};
+use crabdrive_common::payloads::node::request::{
+    file::PostUpdateFileRequest, folder::PostCreateFolderRequest, node::PatchNodeRequest,
+};
 use crabdrive_common::payloads::node::{request::share::*, response::node::*, response::share::*};
 use crabdrive_common::routes;
+use crabdrive_common::storage::{NodeId, ShareId, SharePermission};
+use crabdrive_common::user::UserType;
 use crabdrive_common::uuid::UUID;
 
 use axum::http::StatusCode;
+use chrono::{Duration, Utc};
 use pretty_assertions::assert_eq;
 
 #[tokio::test]
@@ -16,6 +26,9 @@ pub async fn test_sharing_node() {
 
     let payload = PostShareNodeRequest {
         wrapped_metadata_key: user.keys.master_key.clone(),
+        permission: SharePermission::Edit,
+        expires_at: None,
+        revoke_on_expiry: false,
     };
 
     let response = user
@@ -36,6 +49,9 @@ pub async fn test_share_flow() {
 
     let share_payload = PostShareNodeRequest {
         wrapped_metadata_key: user_a.keys.master_key.clone(),
+        permission: SharePermission::Edit,
+        expires_at: None,
+        revoke_on_expiry: false,
     };
     let response = user_a
         .post(routes::node::share::share(folder.id))
@@ -77,6 +93,9 @@ pub async fn test_sharing_root_and_trash_node() {
 
     let payload = PostShareNodeRequest {
         wrapped_metadata_key: user.keys.master_key.clone(),
+        permission: SharePermission::Edit,
+        expires_at: None,
+        revoke_on_expiry: false,
     };
 
     let response_root = user
@@ -108,6 +127,9 @@ pub async fn test_sharing_deleted_node() {
 
     let payload = PostShareNodeRequest {
         wrapped_metadata_key: user.keys.master_key.clone(),
+        permission: SharePermission::Edit,
+        expires_at: None,
+        revoke_on_expiry: false,
     };
 
     let response = user
@@ -128,6 +150,9 @@ pub async fn test_resharing_a_shared_node() {
 
     let share_payload = PostShareNodeRequest {
         wrapped_metadata_key: user_a.keys.master_key.clone(),
+        permission: SharePermission::Edit,
+        expires_at: None,
+        revoke_on_expiry: false,
     };
     let share_res = user_a
         .post(routes::node::share::share(folder.id))
@@ -166,6 +191,9 @@ pub async fn test_get_accept_share_info() {
 
     let share_payload = PostShareNodeRequest {
         wrapped_metadata_key: user_a.keys.master_key.clone(),
+        permission: SharePermission::Edit,
+        expires_at: None,
+        revoke_on_expiry: false,
     };
 
     let response = user_a
@@ -210,6 +238,9 @@ pub async fn test_accept_share() {
 
     let share_payload = PostShareNodeRequest {
         wrapped_metadata_key: user_a.keys.master_key.clone(),
+        permission: SharePermission::Edit,
+        expires_at: None,
+        revoke_on_expiry: false,
     };
     let response = user_a
         .post(routes::node::share::share(folder.id))
@@ -257,6 +288,9 @@ pub async fn test_accept_already_accepted_share() {
 
     let share_payload = PostShareNodeRequest {
         wrapped_metadata_key: user_a.keys.master_key.clone(),
+        permission: SharePermission::Edit,
+        expires_at: None,
+        revoke_on_expiry: false,
     };
 
     let response = user_a
@@ -302,6 +336,9 @@ pub async fn test_get_accepted_shared_nodes() {
     let folder = user_a.generate_random_folder().await;
     let payload = PostShareNodeRequest {
         wrapped_metadata_key: user_a.keys.master_key.clone(),
+        permission: SharePermission::Edit,
+        expires_at: None,
+        revoke_on_expiry: false,
     };
 
     let response = user_a
@@ -342,6 +379,9 @@ pub async fn test_accessible_path_shared() {
 
     let share_payload = PostShareNodeRequest {
         wrapped_metadata_key: user_a.keys.master_key.clone(),
+        permission: SharePermission::Edit,
+        expires_at: None,
+        revoke_on_expiry: false,
     };
     let share_res = user_a
         .post(routes::node::share::share(parent_folder.id))
@@ -388,6 +428,9 @@ pub async fn test_get_node_share_info() {
 
     let share_payload = PostShareNodeRequest {
         wrapped_metadata_key: user_a.keys.master_key.clone(),
+        permission: SharePermission::Edit,
+        expires_at: None,
+        revoke_on_expiry: false,
     };
     let response = user_a
         .post(routes::node::share::share(folder.id))
@@ -432,3 +475,860 @@ pub async fn test_get_node_share_info_not_found() {
 
     assert_eq!(info_res.status_code(), StatusCode::NOT_FOUND);
 }
+
+#[tokio::test]
+pub async fn test_sharing_node_as_restricted_user() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let folder = user.generate_random_folder().await;
+    user.set_user_type(UserType::Restricted);
+
+    let payload = PostShareNodeRequest {
+        wrapped_metadata_key: user.keys.master_key.clone(),
+        permission: SharePermission::Edit,
+        expires_at: None,
+        revoke_on_expiry: false,
+    };
+
+    let response = user
+        .post(routes::node::share::share(folder.id))
+        .json(&payload)
+        .await;
+
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+}
+
+/// Share a node of `owner` and let `recipient` accept it. Returns the ID of the share.
+async fn share_and_accept(
+    owner: &TestUserEntity,
+    recipient: &TestUserEntity,
+    node_id: NodeId,
+    permission: SharePermission,
+) -> ShareId {
+    let response = owner
+        .post(routes::node::share::share(node_id))
+        .json(&PostShareNodeRequest {
+            wrapped_metadata_key: owner.keys.master_key.clone(),
+            permission,
+            expires_at: None,
+            revoke_on_expiry: false,
+        })
+        .await;
+    let PostShareNodeResponse::Ok(share_id) = response.json() else {
+        panic!("Expected Ok");
+    };
+
+    recipient
+        .post(routes::node::share::accept_share(share_id))
+        .json(&PostAcceptShareRequest {
+            new_wrapped_metadata_key: recipient.keys.master_key.clone(),
+        })
+        .await
+        .assert_status_ok();
+
+    share_id
+}
+
+async fn accepted_shared_nodes(user: &TestUserEntity) -> Vec<NodeId> {
+    let GetAcceptedSharedResponse::Ok(nodes) = user
+        .get(routes::node::share::get_accepted_shared())
+        .await
+        .json();
+    nodes.into_iter().map(|(_, node)| node.id).collect()
+}
+
+#[tokio::test]
+pub async fn test_revoke_pending_share() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let folder = user_a.generate_random_folder().await;
+    let response = user_a
+        .post(routes::node::share::share(folder.id))
+        .json(&PostShareNodeRequest {
+            wrapped_metadata_key: user_a.keys.master_key.clone(),
+            permission: SharePermission::Edit,
+            expires_at: None,
+            revoke_on_expiry: false,
+        })
+        .await;
+    let PostShareNodeResponse::Ok(share_id) = response.json() else {
+        panic!("Expected Ok");
+    };
+
+    let delete_res = user_a
+        .delete(routes::node::share::share_by_id(share_id))
+        .await;
+    assert_eq!(delete_res.status_code(), StatusCode::OK);
+    assert_eq!(
+        delete_res.json::<DeleteShareResponse>(),
+        DeleteShareResponse::Ok
+    );
+
+    let info_res = user_b
+        .get(routes::node::share::get_share_accept_info(share_id))
+        .await;
+    assert_eq!(info_res.status_code(), StatusCode::NOT_FOUND);
+
+    let accept_res = user_b
+        .post(routes::node::share::accept_share(share_id))
+        .json(&PostAcceptShareRequest {
+            new_wrapped_metadata_key: user_b.keys.master_key.clone(),
+        })
+        .await;
+    assert_eq!(accept_res.status_code(), StatusCode::NOT_FOUND);
+
+    let delete_again_res = user_a
+        .delete(routes::node::share::share_by_id(share_id))
+        .await;
+    assert_eq!(delete_again_res.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+pub async fn test_revoke_accepted_share() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let folder = user_a.generate_random_folder().await;
+    let file = user_a.generate_file_in(folder.id).await;
+    let share_id = share_and_accept(user_a, user_b, folder.id, SharePermission::Edit).await;
+
+    assert_eq!(accepted_shared_nodes(user_b).await, vec![folder.id]);
+
+    user_a
+        .delete(routes::node::share::share_by_id(share_id))
+        .await
+        .assert_status_ok();
+
+    assert!(accepted_shared_nodes(user_b).await.is_empty());
+    for node_id in [folder.id, file.id] {
+        let node_res = user_b.get(routes::node::by_id(node_id)).await;
+        assert_eq!(node_res.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    let GetNodeResponse::Ok(node) = user_a.get(routes::node::by_id(folder.id)).await.json() else {
+        panic!("Expected Ok");
+    };
+    assert_eq!(
+        node.has_access,
+        vec![(user_a.id, user_a.username.clone(), SharePermission::Manage)]
+    );
+}
+
+#[tokio::test]
+pub async fn test_remove_accepted_user() {
+    let ctx = TestContext::new(3).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+    let user_c = ctx.get_user(2);
+
+    let folder = user_a.generate_random_folder().await;
+    share_and_accept(user_a, user_b, folder.id, SharePermission::Edit).await;
+    share_and_accept(user_a, user_c, folder.id, SharePermission::Edit).await;
+
+    let delete_res = user_a
+        .delete(routes::node::share::share_user(folder.id, user_b.id))
+        .await;
+    assert_eq!(delete_res.status_code(), StatusCode::OK);
+
+    let node_res = user_b.get(routes::node::by_id(folder.id)).await;
+    assert_eq!(node_res.status_code(), StatusCode::NOT_FOUND);
+    assert!(accepted_shared_nodes(user_b).await.is_empty());
+
+    // Other users keep their access
+    let node_res = user_c.get(routes::node::by_id(folder.id)).await;
+    assert_eq!(node_res.status_code(), StatusCode::OK);
+    assert_eq!(accepted_shared_nodes(user_c).await, vec![folder.id]);
+
+    let delete_again_res = user_a
+        .delete(routes::node::share::share_user(folder.id, user_b.id))
+        .await;
+    assert_eq!(delete_again_res.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+pub async fn test_revoke_share_of_other_user() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let folder = user_a.generate_random_folder().await;
+    let share_id = share_and_accept(user_a, user_b, folder.id, SharePermission::Edit).await;
+
+    let delete_res = user_b
+        .delete(routes::node::share::share_by_id(share_id))
+        .await;
+    assert_eq!(delete_res.status_code(), StatusCode::BAD_REQUEST);
+
+    let delete_user_res = user_b
+        .delete(routes::node::share::share_user(folder.id, user_b.id))
+        .await;
+    assert_eq!(delete_user_res.status_code(), StatusCode::BAD_REQUEST);
+
+    let node_res = user_b.get(routes::node::by_id(folder.id)).await;
+    assert_eq!(node_res.status_code(), StatusCode::OK);
+}
+
+#[tokio::test]
+pub async fn test_view_permission() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let folder = user_a.generate_random_folder().await;
+    let file = user_a.generate_file_in(folder.id).await;
+    share_and_accept(user_a, user_b, folder.id, SharePermission::View).await;
+
+    // Reading is allowed
+    for node_id in [folder.id, file.id] {
+        let node_res = user_b.get(routes::node::by_id(node_id)).await;
+        assert_eq!(node_res.status_code(), StatusCode::OK);
+    }
+    let children_res = user_b.get(routes::node::children(folder.id)).await;
+    assert_eq!(children_res.status_code(), StatusCode::OK);
+
+    // Modifying is not
+    let folder_entity = user_a.fetch_node_from_db(folder.id).unwrap();
+    let create_res = user_b
+        .post(routes::node::folder::create(folder.id))
+        .json(&PostCreateFolderRequest {
+            parent_metadata_version: folder_entity.metadata_change_counter,
+            parent_metadata: EncryptedMetadata::random(),
+            node_metadata: EncryptedMetadata::random(),
+            node_id: UUID::random(),
+        })
+        .await;
+    assert_eq!(create_res.status_code(), StatusCode::FORBIDDEN);
+
+    let file_entity = user_a.fetch_node_from_db(file.id).unwrap();
+    let patch_res = user_b
+        .patch(routes::node::by_id(file.id))
+        .json(&PatchNodeRequest {
+            node_metadata: EncryptedMetadata::random(),
+            node_change_count: file_entity.metadata_change_counter,
+        })
+        .await;
+    assert_eq!(patch_res.status_code(), StatusCode::FORBIDDEN);
+
+    let update_res = user_b
+        .post(routes::node::file::update(file.id))
+        .json(&PostUpdateFileRequest {
+            file_iv: IV::new([0; 12]),
+            chunk_count: 1,
+        })
+        .await;
+    assert_eq!(update_res.status_code(), StatusCode::FORBIDDEN);
+
+    let revision_id = file_entity.current_revision.unwrap();
+    let chunk_res = user_b
+        .post(routes::node::chunks(file.id, revision_id, 1))
+        .bytes(TestContext::random_bytes(16))
+        .await;
+    assert_eq!(chunk_res.status_code(), StatusCode::FORBIDDEN);
+
+    assert_eq!(
+        user_a.fetch_node_from_db(file.id).unwrap().metadata,
+        file_entity.metadata
+    );
+}
+
+#[tokio::test]
+pub async fn test_edit_permission() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let folder = user_a.generate_random_folder().await;
+    let file = user_a.generate_file_in(folder.id).await;
+    share_and_accept(user_a, user_b, folder.id, SharePermission::Edit).await;
+
+    let folder_entity = user_a.fetch_node_from_db(folder.id).unwrap();
+    let create_res = user_b
+        .post(routes::node::folder::create(folder.id))
+        .json(&PostCreateFolderRequest {
+            parent_metadata_version: folder_entity.metadata_change_counter,
+            parent_metadata: EncryptedMetadata::random(),
+            node_metadata: EncryptedMetadata::random(),
+            node_id: UUID::random(),
+        })
+        .await;
+    assert_eq!(create_res.status_code(), StatusCode::CREATED);
+
+    let file_entity = user_a.fetch_node_from_db(file.id).unwrap();
+    let patch_res = user_b
+        .patch(routes::node::by_id(file.id))
+        .json(&PatchNodeRequest {
+            node_metadata: EncryptedMetadata::random(),
+            node_change_count: file_entity.metadata_change_counter,
+        })
+        .await;
+    assert_eq!(patch_res.status_code(), StatusCode::OK);
+
+    // Sharing requires the manage permission
+    let share_res = user_b
+        .post(routes::node::share::share(folder.id))
+        .json(&PostShareNodeRequest {
+            wrapped_metadata_key: user_b.keys.master_key.clone(),
+            permission: SharePermission::View,
+            expires_at: None,
+            revoke_on_expiry: false,
+        })
+        .await;
+    assert_eq!(share_res.status_code(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+pub async fn test_manage_permission() {
+    let ctx = TestContext::new(3).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+    let user_c = ctx.get_user(2);
+
+    let folder = user_a.generate_random_folder().await;
+    share_and_accept(user_a, user_b, folder.id, SharePermission::Manage).await;
+
+    // A user with the manage permission can share the node further and revoke the shares
+    let share_id = share_and_accept(user_b, user_c, folder.id, SharePermission::View).await;
+
+    let node_res = user_c.get(routes::node::by_id(folder.id)).await;
+    assert_eq!(node_res.status_code(), StatusCode::OK);
+
+    user_b
+        .delete(routes::node::share::share_by_id(share_id))
+        .await
+        .assert_status_ok();
+
+    let node_res = user_c.get(routes::node::by_id(folder.id)).await;
+    assert_eq!(node_res.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+pub async fn test_access_list_contains_permissions() {
+    let ctx = TestContext::new(3).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+    let user_c = ctx.get_user(2);
+
+    let folder = user_a.generate_random_folder().await;
+    let subfolder = user_a.generate_folder_in(folder.id).await;
+    share_and_accept(user_a, user_b, folder.id, SharePermission::View).await;
+    share_and_accept(user_a, user_c, folder.id, SharePermission::View).await;
+    // A share of a subfolder with a higher permission can still be accepted
+    share_and_accept(user_a, user_c, subfolder.id, SharePermission::Edit).await;
+
+    let GetNodeResponse::Ok(node) = user_a.get(routes::node::by_id(subfolder.id)).await.json()
+    else {
+        panic!("Expected Ok");
+    };
+
+    let mut has_access = node.has_access;
+    has_access.sort_by_key(|(_, username, _)| username.clone());
+    let mut expected = vec![
+        (user_a.id, user_a.username.clone(), SharePermission::Manage),
+        (user_b.id, user_b.username.clone(), SharePermission::View),
+        (user_c.id, user_c.username.clone(), SharePermission::Edit),
+    ];
+    expected.sort_by_key(|(_, username, _)| username.clone());
+    assert_eq!(has_access, expected);
+}
+
+async fn create_expiring_share(
+    owner: &TestUserEntity,
+    node_id: NodeId,
+    revoke_on_expiry: bool,
+) -> ShareId {
+    let response = owner
+        .post(routes::node::share::share(node_id))
+        .json(&PostShareNodeRequest {
+            wrapped_metadata_key: owner.keys.master_key.clone(),
+            permission: SharePermission::Edit,
+            expires_at: Some(Utc::now().naive_utc() + Duration::hours(1)),
+            revoke_on_expiry,
+        })
+        .await;
+    let PostShareNodeResponse::Ok(share_id) = response.json() else {
+        panic!("Expected Ok");
+    };
+    share_id
+}
+
+/// Move the expiry of a share into the past
+fn expire_share(ctx: &TestContext, share_id: ShareId) {
+    let mut share = ctx
+        .state
+        .share_repository
+        .get_share(share_id)
+        .unwrap()
+        .unwrap();
+    share.expires_at = Some(Utc::now().naive_utc() - Duration::minutes(1));
+    ctx.state.share_repository.update_share(share).unwrap();
+}
+
+#[tokio::test]
+pub async fn test_share_with_expiry_in_the_past() {
+    let ctx = TestContext::new(1).await;
+    let user = ctx.get_user(0);
+
+    let folder = user.generate_random_folder().await;
+
+    let response = user
+        .post(routes::node::share::share(folder.id))
+        .json(&PostShareNodeRequest {
+            wrapped_metadata_key: user.keys.master_key.clone(),
+            permission: SharePermission::Edit,
+            expires_at: Some(Utc::now().naive_utc() - Duration::hours(1)),
+            revoke_on_expiry: false,
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+pub async fn test_accept_expired_share() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let folder = user_a.generate_random_folder().await;
+    let share_id = create_expiring_share(user_a, folder.id, false).await;
+
+    let info_res = user_b
+        .get(routes::node::share::get_share_accept_info(share_id))
+        .await;
+    assert_eq!(info_res.status_code(), StatusCode::OK);
+
+    expire_share(&ctx, share_id);
+
+    let info_res = user_b
+        .get(routes::node::share::get_share_accept_info(share_id))
+        .await;
+    assert_eq!(info_res.status_code(), StatusCode::GONE);
+    assert!(matches!(
+        info_res.json::<GetAcceptShareInfoResponse>(),
+        GetAcceptShareInfoResponse::Expired
+    ));
+
+    let accept_res = user_b
+        .post(routes::node::share::accept_share(share_id))
+        .json(&PostAcceptShareRequest {
+            new_wrapped_metadata_key: user_b.keys.master_key.clone(),
+        })
+        .await;
+    assert_eq!(accept_res.status_code(), StatusCode::GONE);
+    assert_eq!(
+        accept_res.json::<PostAcceptShareResponse>(),
+        PostAcceptShareResponse::Expired
+    );
+
+    let node_res = user_b.get(routes::node::by_id(folder.id)).await;
+    assert_eq!(node_res.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+pub async fn test_access_after_expiry() {
+    let ctx = TestContext::new(3).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+    let user_c = ctx.get_user(2);
+
+    let folder = user_a.generate_random_folder().await;
+
+    // user_b keeps the access, user_c loses it
+    let share_b = create_expiring_share(user_a, folder.id, false).await;
+    let share_c = create_expiring_share(user_a, folder.id, true).await;
+    for (user, share_id) in [(user_b, share_b), (user_c, share_c)] {
+        user.post(routes::node::share::accept_share(share_id))
+            .json(&PostAcceptShareRequest {
+                new_wrapped_metadata_key: user.keys.master_key.clone(),
+            })
+            .await
+            .assert_status_ok();
+    }
+
+    expire_share(&ctx, share_b);
+    expire_share(&ctx, share_c);
+
+    let node_res = user_b.get(routes::node::by_id(folder.id)).await;
+    assert_eq!(node_res.status_code(), StatusCode::OK);
+    assert_eq!(accepted_shared_nodes(user_b).await, vec![folder.id]);
+
+    let node_res = user_c.get(routes::node::by_id(folder.id)).await;
+    assert_eq!(node_res.status_code(), StatusCode::NOT_FOUND);
+    assert!(accepted_shared_nodes(user_c).await.is_empty());
+
+    let GetNodeResponse::Ok(node) = user_a.get(routes::node::by_id(folder.id)).await.json() else {
+        panic!("Expected Ok");
+    };
+    assert!(!node.has_access.iter().any(|(id, _, _)| *id == user_c.id));
+}
+
+#[tokio::test]
+pub async fn test_delete_expired_shares() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let folder = user_a.generate_random_folder().await;
+    let pending = create_expiring_share(user_a, folder.id, false).await;
+    let accepted = create_expiring_share(user_a, folder.id, false).await;
+    let not_expired = create_expiring_share(user_a, folder.id, false).await;
+
+    user_b
+        .post(routes::node::share::accept_share(accepted))
+        .json(&PostAcceptShareRequest {
+            new_wrapped_metadata_key: user_b.keys.master_key.clone(),
+        })
+        .await
+        .assert_status_ok();
+
+    expire_share(&ctx, pending);
+    expire_share(&ctx, accepted);
+
+    let mut conn = ctx.state.db_pool.get().unwrap();
+    let count =
+        operations::share::delete_expired_shares(&mut conn, Utc::now().naive_utc()).unwrap();
+    assert_eq!(count, 1);
+
+    let share_repository = &ctx.state.share_repository;
+    assert!(share_repository.get_share(pending).unwrap().is_none());
+    assert!(share_repository.get_share(accepted).unwrap().is_some());
+    assert!(share_repository.get_share(not_expired).unwrap().is_some());
+}
+
+/// Share a node of `owner` directly with `recipient`. Returns the ID of the share.
+async fn share_with_user(
+    owner: &TestUserEntity,
+    recipient: &TestUserEntity,
+    node_id: NodeId,
+    permission: SharePermission,
+) -> ShareId {
+    let response = owner
+        .post(routes::node::share::share_with_user(node_id))
+        .json(&PostShareNodeWithUserRequest {
+            username: recipient.username.clone(),
+            wrapped_metadata_key: owner.keys.master_key.clone(),
+            encrypted_share_key: TestContext::random_bytes(256).to_vec(),
+            permission,
+            expires_at: None,
+            revoke_on_expiry: false,
+        })
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let PostShareNodeResponse::Ok(share_id) = response.json() else {
+        panic!("Expected Ok");
+    };
+    share_id
+}
+
+async fn share_inbox(user: &TestUserEntity) -> Vec<ShareOffer> {
+    let GetShareInboxResponse::Ok(offers) = user.get(routes::node::share::inbox()).await.json();
+    offers
+}
+
+#[tokio::test]
+pub async fn test_get_user_public_key() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let response = user_a
+        .get(routes::node::share::user_public_key(&user_b.username))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(
+        response.json::<GetUserPublicKeyResponse>(),
+        GetUserPublicKeyResponse::Ok(user_b.keys.public_key.clone())
+    );
+
+    let response = user_a
+        .get(routes::node::share::user_public_key("does-not-exist"))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+pub async fn test_share_with_user() {
+    let ctx = TestContext::new(3).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+    let user_c = ctx.get_user(2);
+
+    let folder = user_a.generate_random_folder().await;
+    let share_id = share_with_user(user_a, user_b, folder.id, SharePermission::View).await;
+
+    let offers = share_inbox(user_b).await;
+    assert_eq!(offers.len(), 1);
+    assert_eq!(offers[0].share_id, share_id);
+    assert_eq!(offers[0].node_id, folder.id);
+    assert_eq!(offers[0].shared_by, user_a.username);
+    assert_eq!(offers[0].permission, SharePermission::View);
+    assert_eq!(offers[0].encrypted_share_key.len(), 256);
+
+    // nobody else can see or accept the share
+    assert!(share_inbox(user_c).await.is_empty());
+    let info_res = user_c
+        .get(routes::node::share::get_share_accept_info(share_id))
+        .await;
+    assert_eq!(info_res.status_code(), StatusCode::NOT_FOUND);
+    let accept_res = user_c
+        .post(routes::node::share::accept_share(share_id))
+        .json(&PostAcceptShareRequest {
+            new_wrapped_metadata_key: user_c.keys.master_key.clone(),
+        })
+        .await;
+    assert_eq!(accept_res.status_code(), StatusCode::NOT_FOUND);
+
+    user_b
+        .post(routes::node::share::accept_share(share_id))
+        .json(&PostAcceptShareRequest {
+            new_wrapped_metadata_key: user_b.keys.master_key.clone(),
+        })
+        .await
+        .assert_status_ok();
+
+    assert!(share_inbox(user_b).await.is_empty());
+    assert_eq!(accepted_shared_nodes(user_b).await, vec![folder.id]);
+
+    let share = ctx
+        .state
+        .share_repository
+        .get_share(share_id)
+        .unwrap()
+        .unwrap();
+    assert!(share.encrypted_share_key.is_none());
+}
+
+#[tokio::test]
+pub async fn test_share_with_user_revoked_on_expiry() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let folder = user_a.generate_random_folder().await;
+    let response = user_a
+        .post(routes::node::share::share_with_user(folder.id))
+        .json(&PostShareNodeWithUserRequest {
+            username: user_b.username.clone(),
+            wrapped_metadata_key: user_a.keys.master_key.clone(),
+            encrypted_share_key: TestContext::random_bytes(256).to_vec(),
+            permission: SharePermission::Edit,
+            expires_at: Some(Utc::now().naive_utc() + Duration::hours(1)),
+            revoke_on_expiry: true,
+        })
+        .await;
+    let PostShareNodeResponse::Ok(share_id) = response.json() else {
+        panic!("Expected Ok");
+    };
+
+    user_b
+        .post(routes::node::share::accept_share(share_id))
+        .json(&PostAcceptShareRequest {
+            new_wrapped_metadata_key: user_b.keys.master_key.clone(),
+        })
+        .await
+        .assert_status_ok();
+    assert_eq!(accepted_shared_nodes(user_b).await, vec![folder.id]);
+
+    expire_share(&ctx, share_id);
+
+    let node_res = user_b.get(routes::node::by_id(folder.id)).await;
+    assert_eq!(node_res.status_code(), StatusCode::NOT_FOUND);
+    assert!(accepted_shared_nodes(user_b).await.is_empty());
+}
+
+#[tokio::test]
+pub async fn test_share_with_invalid_user() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let folder = user_a.generate_random_folder().await;
+
+    let share_with = |username: String| {
+        user_a
+            .post(routes::node::share::share_with_user(folder.id))
+            .json(&PostShareNodeWithUserRequest {
+                username,
+                wrapped_metadata_key: user_a.keys.master_key.clone(),
+                encrypted_share_key: TestContext::random_bytes(256).to_vec(),
+                permission: SharePermission::Edit,
+                expires_at: None,
+                revoke_on_expiry: false,
+            })
+    };
+
+    let response = share_with("does-not-exist".to_string()).await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let response = share_with(user_a.username.clone()).await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    // users without a key pair cannot receive shares
+    let mut user_entity = user_b.fetch_user_from_db();
+    user_entity.public_key = vec![];
+    ctx.state.user_repository.update_user(user_entity).unwrap();
+
+    let response = share_with(user_b.username.clone()).await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    assert!(share_inbox(user_b).await.is_empty());
+}
+
+#[tokio::test]
+pub async fn test_decline_share() {
+    let ctx = TestContext::new(3).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+    let user_c = ctx.get_user(2);
+
+    let folder = user_a.generate_random_folder().await;
+    let share_id = share_with_user(user_a, user_b, folder.id, SharePermission::Edit).await;
+
+    // only the recipient can decline the share
+    let response = user_c
+        .delete(routes::node::share::inbox_share(share_id))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    let response = user_b
+        .delete(routes::node::share::inbox_share(share_id))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    assert!(share_inbox(user_b).await.is_empty());
+    assert!(
+        ctx.state
+            .share_repository
+            .get_share(share_id)
+            .unwrap()
+            .is_none()
+    );
+}
+
+async fn outgoing_shares(user: &TestUserEntity, url: String) -> Vec<OutgoingShare> {
+    let response = user.get(url).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let GetOutgoingSharesResponse::Ok(shares) = response.json();
+    shares
+}
+
+#[tokio::test]
+pub async fn test_outgoing_shares() {
+    let ctx = TestContext::new(3).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+    let user_c = ctx.get_user(2);
+
+    let folder_1 = user_a.generate_random_folder().await;
+    let folder_2 = user_a.generate_random_folder().await;
+
+    let accepted = share_and_accept(user_a, user_b, folder_1.id, SharePermission::Edit).await;
+    let pending = share_with_user(user_a, user_c, folder_2.id, SharePermission::View).await;
+
+    let shares = outgoing_shares(user_a, routes::node::share::outgoing_shares()).await;
+    assert_eq!(shares.len(), 2);
+
+    let accepted_share = shares.iter().find(|s| s.share_id == accepted).unwrap();
+    assert_eq!(accepted_share.node_id, folder_1.id);
+    assert_eq!(accepted_share.permission, SharePermission::Edit);
+    assert!(!accepted_share.pending);
+    assert!(accepted_share.shared_with.is_none());
+    assert!(accepted_share.time_accepted.is_some());
+    assert_eq!(
+        accepted_share.accepted_by,
+        Some(ShareUser {
+            id: user_b.id,
+            username: user_b.username.clone(),
+        })
+    );
+
+    let pending_share = shares.iter().find(|s| s.share_id == pending).unwrap();
+    assert_eq!(pending_share.node_id, folder_2.id);
+    assert!(pending_share.pending);
+    assert!(pending_share.accepted_by.is_none());
+    assert!(pending_share.time_accepted.is_none());
+    assert_eq!(
+        pending_share.shared_with.as_ref().map(|user| user.id),
+        Some(user_c.id)
+    );
+
+    // shares of other users are not listed, even if the user accepted them
+    assert!(
+        outgoing_shares(user_b, routes::node::share::outgoing_shares())
+            .await
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+pub async fn test_outgoing_shares_of_node() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let folder_1 = user_a.generate_random_folder().await;
+    let folder_2 = user_a.generate_random_folder().await;
+
+    let share_id = share_and_accept(user_a, user_b, folder_1.id, SharePermission::View).await;
+    share_and_accept(user_a, user_b, folder_2.id, SharePermission::View).await;
+
+    let shares = outgoing_shares(
+        user_a,
+        routes::node::share::outgoing_shares_of_node(folder_1.id),
+    )
+    .await;
+    assert_eq!(shares.len(), 1);
+    assert_eq!(shares[0].share_id, share_id);
+
+    // revoked shares disappear from the overview
+    user_a
+        .delete(routes::node::share::share_by_id(share_id))
+        .await
+        .assert_status_ok();
+    assert!(
+        outgoing_shares(
+            user_a,
+            routes::node::share::outgoing_shares_of_node(folder_1.id),
+        )
+        .await
+        .is_empty()
+    );
+}
+
+#[tokio::test]
+pub async fn test_share_with_missing_keys() {
+    let ctx = TestContext::new(2).await;
+    let user_a = ctx.get_user(0);
+    let user_b = ctx.get_user(1);
+
+    let folder = user_a.generate_random_folder().await;
+    let share_id = share_with_user(user_a, user_b, folder.id, SharePermission::View).await;
+
+    // the row breaks the invariants of pending direct shares
+    let mut share = ctx
+        .state
+        .share_repository
+        .get_share(share_id)
+        .unwrap()
+        .unwrap();
+    share.shared_encryption_key = None;
+    share.encrypted_share_key = None;
+    ctx.state.share_repository.update_share(share).unwrap();
+
+    for url in [
+        routes::node::share::inbox(),
+        routes::node::share::get_share_accept_info(share_id),
+    ] {
+        let response = user_b.get(url).await;
+        assert_eq!(response.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let ErrorResponse { error } = response.json();
+        assert_eq!(error.kind, ErrorKind::Internal);
+        assert_eq!(
+            error.request_id.as_deref(),
+            Some(response.header("x-request-id").to_str().unwrap())
+        );
+    }
+}
@@ -1,11 +1,17 @@
 use crate::storage::vfs::backend::c3::model::{FileTransfer, FileTransferState};
 use crate::storage::vfs::backend::c3::*;
-use crate::storage::vfs::{FileChunk, FileRepository, FileStatus, FileSystemError};
+use crate::storage::vfs::{ChunkContents, FileChunk, FileRepository, FileStatus, FileSystemError};
 use crate::test::utils::TestContext;
 
 use bytes::Bytes;
+use crabdrive_common::da;
 use crabdrive_common::uuid::UUID;
+use std::io;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_util::io::StreamReader;
 
 #[tokio::test]
 async fn test_file_transfer_state_machine() {
@@ -36,7 +42,7 @@ async fn test_c3_lifecycle() {
     let ctx = TestContext::new(0).await;
     let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
 
-    let mut c3 = C3::new(temp_dir.path().to_path_buf(), ctx.state.db_pool, 10, 5).await;
+    let c3 = C3::new(temp_dir.path().to_path_buf(), ctx.state.db_pool, 10, 5).await;
 
     let key = UUID::random();
 
@@ -74,7 +80,7 @@ async fn test_c3_abort_transfer() {
     let ctx = TestContext::new(0).await;
     let temp_dir = tempfile::tempdir().unwrap();
 
-    let mut c3 = C3::new(
+    let c3 = C3::new(
         temp_dir.path().to_path_buf(),
         ctx.state.db_pool.clone(),
         10,
@@ -104,7 +110,7 @@ async fn test_c3_abort_transfer() {
 async fn test_c3_already_exists() {
     let ctx = TestContext::new(0).await;
     let temp_dir = tempfile::tempdir().unwrap();
-    let mut c3 = C3::new(
+    let c3 = C3::new(
         temp_dir.path().to_path_buf(),
         ctx.state.db_pool.clone(),
         10,
@@ -123,7 +129,7 @@ async fn test_c3_already_exists() {
 async fn test_c3_delete_staged_fails() {
     let ctx = TestContext::new(0).await;
     let temp_dir = tempfile::tempdir().unwrap();
-    let mut c3 = C3::new(
+    let c3 = C3::new(
         temp_dir.path().to_path_buf(),
         ctx.state.db_pool.clone(),
         10,
@@ -142,7 +148,7 @@ async fn test_c3_delete_staged_fails() {
 async fn test_c3_abort_persisted_fails() {
     let ctx = TestContext::new(0).await;
     let temp_dir = tempfile::tempdir().unwrap();
-    let mut c3 = C3::new(
+    let c3 = C3::new(
         temp_dir.path().to_path_buf(),
         ctx.state.db_pool.clone(),
         10,
@@ -157,3 +163,201 @@ async fn test_c3_abort_persisted_fails() {
     let err = c3.abort(&key).await.unwrap_err();
     assert!(matches!(err, FileSystemError::NotFound));
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_c3_concurrent_writes() {
+    let ctx = TestContext::new(0).await;
+    let temp_dir = tempfile::tempdir().unwrap();
+    let c3: Arc<dyn FileRepository + Send + Sync> = Arc::new(
+        C3::new(
+            temp_dir.path().to_path_buf(),
+            ctx.state.db_pool.clone(),
+            10,
+            5,
+        )
+        .await,
+    );
+
+    let keys: Vec<UUID> = (0..4).map(|_| UUID::random()).collect();
+    for key in &keys {
+        c3.create_file(key).await.unwrap();
+    }
+
+    // every chunk of every file is written twice at the same time
+    let writes: Vec<_> = keys
+        .iter()
+        .flat_map(|key| (1..=8).flat_map(move |index| [(*key, index), (*key, index)]))
+        .map(|(key, index)| {
+            let c3 = Arc::clone(&c3);
+            tokio::spawn(async move {
+                let chunk = FileChunk {
+                    index,
+                    data: TestContext::random_bytes(64 * 1024),
+                };
+                c3.write_chunk(&key, chunk).await
+            })
+        })
+        .collect();
+
+    let mut written = 0;
+    for write in writes {
+        match write.await.unwrap() {
+            Ok(()) => written += 1,
+            Err(FileSystemError::AlreadyExists) => {}
+            Err(e) => panic!("Failed to write chunk: {e}"),
+        }
+    }
+    assert_eq!(written, keys.len() * 8);
+
+    for key in &keys {
+        c3.commit_file(key).await.unwrap();
+        assert_eq!(c3.file_status(key).await, FileStatus::Persisted);
+    }
+}
+
+#[tokio::test]
+async fn test_c3_write_chunk_stream() {
+    let ctx = TestContext::new(0).await;
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+
+    let c3 = C3::new(temp_dir.path().to_path_buf(), ctx.state.db_pool, 10, 5).await;
+
+    let key = UUID::random();
+    c3.create_file(&key).await.expect("Failed to create file");
+
+    // the client disconnects during the upload
+    let contents = StreamReader::new(futures_util::stream::iter([
+        Ok(Bytes::from("crabdrive")),
+        Err(io::Error::from(io::ErrorKind::ConnectionAborted)),
+    ]));
+    let result = c3
+        .write_chunk_stream(&key, 1, Box::pin(contents), da!(1 KiB))
+        .await;
+    assert!(matches!(
+        result,
+        Err(FileSystemError::Io(io::ErrorKind::ConnectionAborted, _))
+    ));
+    assert!(!c3.chunk_exists(&key, 1).await);
+
+    let data = TestContext::random_bytes(2048);
+    let result = c3
+        .write_chunk_stream(&key, 1, Box::pin(io::Cursor::new(data)), da!(1 KiB))
+        .await;
+    assert!(matches!(result, Err(FileSystemError::TooLarge(_))));
+    assert!(!c3.chunk_exists(&key, 1).await);
+
+    // nothing of the failed uploads is left in the staging area
+    let staged = utils::shard_path(key, &temp_dir.path().join("stage"));
+    assert_eq!(std::fs::read_dir(&staged).unwrap().count(), 0);
+
+    let data = TestContext::random_bytes(1024);
+    let size = c3
+        .write_chunk_stream(&key, 1, Box::pin(io::Cursor::new(data.clone())), da!(1 KiB))
+        .await
+        .expect("Failed to write chunk");
+    assert_eq!(size, da!(1 KiB));
+
+    let result = c3
+        .write_chunk_stream(&key, 1, Box::pin(&b"crabdrive"[..]), da!(1 KiB))
+        .await;
+    assert!(matches!(result, Err(FileSystemError::AlreadyExists)));
+
+    c3.commit_file(&key).await.expect("Failed to commit file");
+
+    let read_chunk = c3.read_chunk(&key, 1).await.expect("Failed to read chunk");
+    assert_eq!(read_chunk.data, data);
+}
+
+#[tokio::test]
+async fn test_c3_commit_after_cancelled_write() {
+    let ctx = TestContext::new(0).await;
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+
+    let c3 = C3::new(temp_dir.path().to_path_buf(), ctx.state.db_pool, 10, 5).await;
+
+    let key = UUID::random();
+    c3.create_file(&key).await.expect("Failed to create file");
+
+    let data = TestContext::random_bytes(1024);
+    c3.write_chunk_stream(&key, 1, Box::pin(io::Cursor::new(data.clone())), da!(1 KiB))
+        .await
+        .expect("Failed to write chunk");
+
+    // the client stops sending the second chunk and the request is dropped mid-write
+    let (mut sender, receiver) = tokio::io::duplex(64);
+    sender.write_all(b"crabdrive").await.unwrap();
+    let result = tokio::time::timeout(
+        Duration::from_millis(100),
+        c3.write_chunk_stream(&key, 2, Box::pin(receiver), da!(1 KiB)),
+    )
+    .await;
+    assert!(result.is_err(), "The write should still be in flight");
+    assert!(!c3.chunk_exists(&key, 2).await);
+
+    tokio::time::timeout(Duration::from_secs(5), c3.commit_file(&key))
+        .await
+        .expect("Commit is blocked by the cancelled write")
+        .expect("Failed to commit file");
+    assert_eq!(c3.file_status(&key).await, FileStatus::Persisted);
+
+    let read_chunk = c3.read_chunk(&key, 1).await.expect("Failed to read chunk");
+    assert_eq!(read_chunk.data, data);
+    assert!(matches!(
+        c3.read_chunk(&key, 2).await,
+        Err(FileSystemError::NotFound)
+    ));
+}
+
+#[tokio::test]
+async fn test_c3_open_chunk() {
+    let ctx = TestContext::new(0).await;
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+
+    let c3 = C3::new(temp_dir.path().to_path_buf(), ctx.state.db_pool, 10, 5).await;
+
+    let key = UUID::random();
+    c3.create_file(&key).await.expect("Failed to create file");
+
+    let chunks: Vec<_> = (1..=3).map(|_| TestContext::random_bytes(4096)).collect();
+    for (index, data) in (1..).zip(&chunks) {
+        let chunk = FileChunk {
+            index,
+            data: data.clone(),
+        };
+        c3.write_chunk(&key, chunk)
+            .await
+            .expect("Failed to write chunk");
+    }
+    c3.commit_file(&key).await.expect("Failed to commit file");
+
+    // uncached chunks are read from disk
+    let ChunkContents::File { mut file, size } =
+        c3.open_chunk(&key, 1).await.expect("Failed to open chunk")
+    else {
+        panic!("Chunk was cached");
+    };
+    assert_eq!(size, da!(4 KiB));
+
+    let mut data = vec![];
+    file.read_to_end(&mut data).await.unwrap();
+    assert_eq!(data, chunks[0]);
+
+    // opening the first chunk prefetches the following ones
+    let mut contents = c3.open_chunk(&key, 2).await.expect("Failed to open chunk");
+    for _ in 0..100 {
+        if matches!(contents, ChunkContents::Buffered(_)) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        contents = c3.open_chunk(&key, 2).await.expect("Failed to open chunk");
+    }
+    let ChunkContents::Buffered(bytes) = contents else {
+        panic!("Chunk was not prefetched");
+    };
+    assert_eq!(bytes, chunks[1]);
+
+    assert!(matches!(
+        c3.open_chunk(&key, 4).await,
+        Err(FileSystemError::NotFound)
+    ));
+}
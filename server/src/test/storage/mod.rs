@@ -1,2 +1,3 @@
 mod c3;
+mod s3;
 mod sfs;
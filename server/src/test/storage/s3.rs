@@ -0,0 +1,225 @@
+use crate::storage::vfs::backend::s3::S3;
+use crate::storage::vfs::{ChunkContents, FileChunk, FileRepository, FileStatus, FileSystemError};
+use crate::test::utils::{TestBucket, TestContext};
+
+use bytes::Bytes;
+use crabdrive_common::da;
+use crabdrive_common::uuid::UUID;
+use std::io;
+use tokio::io::AsyncReadExt;
+use tokio_util::io::StreamReader;
+
+#[tokio::test]
+async fn test_s3_lifecycle() {
+    let bucket = TestBucket::start().await;
+    let s3 = S3::new(&bucket.config);
+
+    let key = UUID::random();
+
+    assert_eq!(s3.file_status(&key).await, FileStatus::NotFound);
+
+    s3.create_file(&key).await.expect("Failed to create file");
+    assert_eq!(s3.file_status(&key).await, FileStatus::Staged);
+
+    let chunks: Vec<_> = (0..3).map(|_| TestContext::random_bytes(4096)).collect();
+    for (index, data) in (0..).zip(&chunks) {
+        let chunk = FileChunk {
+            index,
+            data: data.clone(),
+        };
+        s3.write_chunk(&key, chunk)
+            .await
+            .expect("Failed to write chunk");
+        assert!(s3.chunk_exists(&key, index).await);
+    }
+    assert!(!s3.chunk_exists(&key, 3).await);
+
+    // chunks can not be read before the file was committed
+    assert!(matches!(
+        s3.read_chunk(&key, 0).await,
+        Err(FileSystemError::NotFound)
+    ));
+
+    s3.commit_file(&key).await.expect("Failed to commit file");
+    assert_eq!(s3.file_status(&key).await, FileStatus::Persisted);
+    assert!(!s3.chunk_exists(&key, 0).await);
+    // the staging area is cleaned up after the chunks were copied
+    assert!(bucket.keys("stage/").is_empty());
+
+    for (index, data) in (0..).zip(&chunks) {
+        let read_chunk = s3
+            .read_chunk(&key, index)
+            .await
+            .expect("Failed to read chunk");
+        assert_eq!(&read_chunk.data, data);
+    }
+
+    s3.delete_file(&key).await.expect("Failed to delete file");
+    assert_eq!(s3.file_status(&key).await, FileStatus::NotFound);
+    assert!(bucket.keys("").is_empty());
+}
+
+#[tokio::test]
+async fn test_s3_abort_transfer() {
+    let bucket = TestBucket::start().await;
+    let s3 = S3::new(&bucket.config);
+
+    let key = UUID::random();
+    s3.create_file(&key).await.unwrap();
+
+    let chunk = FileChunk {
+        index: 0,
+        data: Bytes::from("abort"),
+    };
+    s3.write_chunk(&key, chunk).await.unwrap();
+    assert_eq!(s3.file_status(&key).await, FileStatus::Staged);
+
+    s3.abort(&key).await.expect("Failed to abort transfer");
+
+    assert_eq!(s3.file_status(&key).await, FileStatus::NotFound);
+    assert!(!s3.chunk_exists(&key, 0).await);
+    assert!(bucket.keys("").is_empty());
+
+    // neither aborting twice nor writing into an aborted transfer is possible
+    assert!(matches!(
+        s3.abort(&key).await,
+        Err(FileSystemError::NotFound)
+    ));
+    let chunk = FileChunk {
+        index: 1,
+        data: Bytes::from("abort"),
+    };
+    assert!(matches!(
+        s3.write_chunk(&key, chunk).await,
+        Err(FileSystemError::NotFound)
+    ));
+}
+
+#[tokio::test]
+async fn test_s3_already_exists() {
+    let bucket = TestBucket::start().await;
+    let s3 = S3::new(&bucket.config);
+
+    let key = UUID::random();
+    s3.create_file(&key).await.unwrap();
+
+    let err = s3.create_file(&key).await.unwrap_err();
+    assert!(matches!(err, FileSystemError::AlreadyExists));
+
+    let chunk = FileChunk {
+        index: 0,
+        data: Bytes::from("crabdrive"),
+    };
+    s3.write_chunk(&key, chunk).await.unwrap();
+    let chunk = FileChunk {
+        index: 0,
+        data: Bytes::from("overwritten"),
+    };
+    let err = s3.write_chunk(&key, chunk).await.unwrap_err();
+    assert!(matches!(err, FileSystemError::AlreadyExists));
+}
+
+#[tokio::test]
+async fn test_s3_write_chunk_stream() {
+    let bucket = TestBucket::start().await;
+    let s3 = S3::new(&bucket.config);
+
+    let key = UUID::random();
+    s3.create_file(&key).await.expect("Failed to create file");
+
+    // the client disconnects during the upload
+    let contents = StreamReader::new(futures_util::stream::iter([
+        Ok(Bytes::from("crabdrive")),
+        Err(io::Error::from(io::ErrorKind::ConnectionAborted)),
+    ]));
+    let result = s3
+        .write_chunk_stream(&key, 1, Box::pin(contents), da!(1 KiB))
+        .await;
+    assert!(matches!(
+        result,
+        Err(FileSystemError::Io(io::ErrorKind::ConnectionAborted, _))
+    ));
+    assert!(!s3.chunk_exists(&key, 1).await);
+
+    let data = TestContext::random_bytes(2048);
+    let result = s3
+        .write_chunk_stream(&key, 1, Box::pin(io::Cursor::new(data)), da!(1 KiB))
+        .await;
+    assert!(matches!(result, Err(FileSystemError::TooLarge(_))));
+    assert!(!s3.chunk_exists(&key, 1).await);
+
+    // the uploads of the failed chunks were aborted
+    assert_eq!(bucket.pending_uploads(), 0);
+    assert_eq!(bucket.keys("stage/").len(), 1);
+
+    let data = TestContext::random_bytes(1024);
+    let size = s3
+        .write_chunk_stream(&key, 1, Box::pin(io::Cursor::new(data.clone())), da!(1 KiB))
+        .await
+        .expect("Failed to write chunk");
+    assert_eq!(size, da!(1 KiB));
+
+    // chunks larger than a part are uploaded in multiple parts
+    let large_data = TestContext::random_bytes(12 * 1024 * 1024);
+    let size = s3
+        .write_chunk_stream(
+            &key,
+            2,
+            Box::pin(io::Cursor::new(large_data.clone())),
+            da!(16 MiB),
+        )
+        .await
+        .expect("Failed to write chunk");
+    assert_eq!(size, da!(12 MiB));
+
+    let result = s3
+        .write_chunk_stream(&key, 1, Box::pin(&b"crabdrive"[..]), da!(1 KiB))
+        .await;
+    assert!(matches!(result, Err(FileSystemError::AlreadyExists)));
+
+    // only the transfer marker and the chunks are left
+    assert_eq!(bucket.pending_uploads(), 0);
+    assert_eq!(bucket.keys("stage/").len(), 3);
+
+    s3.commit_file(&key).await.expect("Failed to commit file");
+
+    let read_chunk = s3.read_chunk(&key, 1).await.expect("Failed to read chunk");
+    assert_eq!(read_chunk.data, data);
+    let read_chunk = s3.read_chunk(&key, 2).await.expect("Failed to read chunk");
+    assert_eq!(read_chunk.data, large_data);
+}
+
+#[tokio::test]
+async fn test_s3_open_chunk() {
+    let bucket = TestBucket::start().await;
+    let s3 = S3::new(&bucket.config);
+
+    let key = UUID::random();
+    s3.create_file(&key).await.expect("Failed to create file");
+
+    let data = TestContext::random_bytes(4096);
+    let chunk = FileChunk {
+        index: 1,
+        data: data.clone(),
+    };
+    s3.write_chunk(&key, chunk)
+        .await
+        .expect("Failed to write chunk");
+    s3.commit_file(&key).await.expect("Failed to commit file");
+
+    let ChunkContents::Stream { mut contents, size } =
+        s3.open_chunk(&key, 1).await.expect("Failed to open chunk")
+    else {
+        panic!("Chunk was not streamed");
+    };
+    assert_eq!(size, da!(4 KiB));
+
+    let mut read = vec![];
+    contents.read_to_end(&mut read).await.unwrap();
+    assert_eq!(read, data);
+
+    assert!(matches!(
+        s3.open_chunk(&key, 2).await,
+        Err(FileSystemError::NotFound)
+    ));
+}
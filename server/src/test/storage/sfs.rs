@@ -1,7 +1,9 @@
 #[cfg(test)]
 mod tests {
+    use crate::storage::vfs::ChunkContents;
     use crate::storage::vfs::FileChunk;
     use crate::storage::vfs::FileRepository;
+    use crate::storage::vfs::FileSystemError;
     use crate::storage::vfs::backend::Sfs;
 
     use crabdrive_common::da;
@@ -9,7 +11,10 @@ mod tests {
     use crabdrive_common::uuid::UUID;
 
     use rand::{Rng, rng};
+    use std::io;
     use tempfile::TempDir;
+    use tokio::io::AsyncReadExt;
+    use tokio_util::io::StreamReader;
 
     use crate::storage::vfs::model::FileStatus;
     use pretty_assertions::assert_eq;
@@ -20,7 +25,7 @@ mod tests {
         let path = tempdir.path().to_path_buf();
 
         // This test writes all files into a temporary directory, which are then deleted directly after.
-        let mut sfs = Sfs::new(path);
+        let sfs = Sfs::new(path);
 
         // Test with 16 files, containing 16 chunks à 100KB of garbage data each.
         // For testing, 100KB should be enough.
@@ -104,4 +109,128 @@ mod tests {
 
         drop(tempdir)
     }
+
+    #[tokio::test]
+    async fn test_sfs_abort_transfer() {
+        let tempdir = TempDir::new().expect("Failed to create temporary directory.");
+        let sfs = Sfs::new(tempdir.path().to_path_buf());
+
+        let file_key = UUID::random();
+        sfs.create_file(&file_key)
+            .await
+            .expect("Failed to start transfer");
+        sfs.write_chunk(
+            &file_key,
+            FileChunk {
+                index: 0,
+                data: bytes::Bytes::from("abort"),
+            },
+        )
+        .await
+        .expect("Failed to write chunk");
+
+        assert_eq!(sfs.file_status(&file_key).await, FileStatus::Staged);
+
+        sfs.abort(&file_key)
+            .await
+            .expect("Failed to abort transfer");
+
+        assert_eq!(sfs.file_status(&file_key).await, FileStatus::NotFound);
+        assert!(!sfs.chunk_exists(&file_key, 0).await);
+        // Aborting twice is not possible
+        assert!(sfs.abort(&file_key).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sfs_write_chunk_stream() {
+        let tempdir = TempDir::new().expect("Failed to create temporary directory.");
+        let sfs = Sfs::new(tempdir.path().to_path_buf());
+
+        let file_key = UUID::random();
+        sfs.create_file(&file_key)
+            .await
+            .expect("Failed to start transfer");
+
+        // The client disconnects during the upload
+        let contents = StreamReader::new(futures_util::stream::iter([
+            Ok(bytes::Bytes::from("crab")),
+            Err(io::Error::from(io::ErrorKind::ConnectionAborted)),
+        ]));
+        let result = sfs
+            .write_chunk_stream(&file_key, 0, Box::pin(contents), da!(1 KB))
+            .await;
+        assert!(matches!(result, Err(FileSystemError::Io(_, _))));
+        assert!(!sfs.chunk_exists(&file_key, 0).await);
+
+        let result = sfs
+            .write_chunk_stream(&file_key, 0, Box::pin(&[0u8; 1001][..]), da!(1 KB))
+            .await;
+        assert!(matches!(result, Err(FileSystemError::TooLarge(_))));
+        assert!(!sfs.chunk_exists(&file_key, 0).await);
+
+        // Only the successfully written chunk is stored
+        let size = sfs
+            .write_chunk_stream(&file_key, 0, Box::pin(&[7u8; 1000][..]), da!(1 KB))
+            .await
+            .expect("Failed to write chunk");
+        assert_eq!(size, da!(1 KB));
+
+        let staged = tempdir.path().join(file_key.to_string());
+        assert_eq!(std::fs::read_dir(&staged).unwrap().count(), 1);
+
+        sfs.commit_file(&file_key)
+            .await
+            .expect("Failed to end transfer");
+
+        let chunk = sfs
+            .read_chunk(&file_key, 0)
+            .await
+            .expect("Failed to read chunk back");
+        assert_eq!(chunk.data.as_ref(), &[7u8; 1000]);
+    }
+
+    #[tokio::test]
+    async fn test_sfs_open_chunk() {
+        let tempdir = TempDir::new().expect("Failed to create temporary directory.");
+        let sfs = Sfs::new(tempdir.path().to_path_buf());
+
+        let file_key = UUID::random();
+        sfs.create_file(&file_key)
+            .await
+            .expect("Failed to start transfer");
+        sfs.write_chunk(
+            &file_key,
+            FileChunk {
+                index: 0,
+                data: bytes::Bytes::from("streamed"),
+            },
+        )
+        .await
+        .expect("Failed to write chunk");
+
+        // Chunks of unfinished transfers cannot be downloaded
+        assert!(sfs.open_chunk(&file_key, 0).await.is_err());
+
+        sfs.commit_file(&file_key)
+            .await
+            .expect("Failed to end transfer");
+
+        let ChunkContents::File { mut file, size } = sfs
+            .open_chunk(&file_key, 0)
+            .await
+            .expect("Failed to open chunk")
+        else {
+            panic!("SFS does not buffer chunks");
+        };
+        assert_eq!(size, da!(8 B));
+
+        let mut data = String::new();
+        file.read_to_string(&mut data).await.unwrap();
+        assert_eq!(data, "streamed");
+
+        assert!(matches!(
+            sfs.open_chunk(&file_key, 1).await,
+            Err(FileSystemError::NotFound)
+        ));
+    }
 }
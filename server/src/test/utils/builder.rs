@@ -76,7 +76,7 @@ impl<'a> NodeBuilder<'a> {
             // gen 1-10 chunks if not specified
             let chunk_count = self.chunk_count.unwrap_or_else(|| rng.random_range(1..=10));
 
-            let revision = self
+            let mut revision = self
                 .state
                 .revision_repository
                 .create_revision(
@@ -92,7 +92,7 @@ impl<'a> NodeBuilder<'a> {
                 chunks: Vec::new(),
             };
 
-            let mut vfs = self.state.vfs.write().await;
+            let vfs = &self.state.vfs;
             vfs.create_file(&revision.id)
                 .await
                 .expect("Failed to create file in VFS");
@@ -121,6 +121,12 @@ impl<'a> NodeBuilder<'a> {
                 .await
                 .expect("Failed to commit VFS file");
 
+            revision.upload_ended_on = Some(chrono::Local::now().naive_local());
+            self.state
+                .revision_repository
+                .update_revision(revision)
+                .expect("Failed to commit revision");
+
             node.current_revision = Some(revision.id);
             self.state
                 .node_repository
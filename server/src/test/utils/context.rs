@@ -1,33 +1,79 @@
 use crate::http::{AppConfig, AppState};
 use crate::storage::node::NodeRepository;
+use crate::storage::vfs::FileRepository;
 
 use super::TestUserEntity;
 
+use std::future::IntoFuture;
 use std::ops::Range;
 use std::sync::Arc;
 
-use axum_test::TestServer;
+use argon2::password_hash::SaltString;
+use argon2::password_hash::rand_core::OsRng;
+use axum_test::{TestRequest, TestResponse, TestServer};
 use bytes::Bytes;
+use crabdrive_common::user::KdfParams;
 use crabdrive_common::uuid::UUID;
 use rand::{Rng, distr::Alphanumeric};
 use sha2::{Digest, Sha256};
+use tempfile::TempDir;
 use tracing::Level;
 
+/// The storage backend as it is shared by the app state
+pub type SharedVfs = Arc<dyn FileRepository + Send + Sync>;
+
 pub struct TestContext {
     pub server: Arc<TestServer>,
     pub state: AppState,
     pub users: Vec<TestUserEntity>,
     // repos
     pub node: Arc<dyn NodeRepository + Send + Sync>,
+    /// Keeps the database of contexts created with [`TestContext::with_db_file`] alive
+    _db_dir: Option<TempDir>,
 }
 
 impl TestContext {
     pub async fn new(amount_users: u32) -> Self {
-        let mut config = AppConfig::test();
+        TestContext::with_config(amount_users, AppConfig::test()).await
+    }
+
+    /// Create a context with a modified test configuration (see [`AppConfig::test`])
+    pub async fn with_config(amount_users: u32, mut config: AppConfig) -> Self {
         // https://stackoverflow.com/questions/58649529/how-to-create-multiple-memory-databases-in-sqlite3
         config.db.path = format!("file:{}?mode=memory&cache=shared", UUID::random());
+        TestContext::create(amount_users, config, None).await
+    }
 
-        let (router, state) = crate::http::server::create_app(config).await;
+    /// Create a context, which stores the database in a temporary file. Shared in-memory databases
+    /// lock whole tables instead of waiting for each other, so use this for tests that send
+    /// requests concurrently.
+    pub async fn with_db_file(amount_users: u32) -> Self {
+        TestContext::with_wrapped_vfs(amount_users, |vfs| vfs).await
+    }
+
+    /// Like [`TestContext::with_db_file`], but the storage backend is wrapped with `wrap`, e.g. to
+    /// observe how it is used by the routes
+    pub async fn with_wrapped_vfs(
+        amount_users: u32,
+        wrap: impl FnOnce(SharedVfs) -> SharedVfs,
+    ) -> Self {
+        let db_dir = tempfile::tempdir().expect("Failed to create database directory!");
+
+        let mut config = AppConfig::test();
+        config.db.path = db_dir.path().join("crabdrive.db").display().to_string();
+
+        let mut state = AppState::new(config).await;
+        state.vfs = wrap(state.vfs);
+        TestContext::from_state(amount_users, state, Some(db_dir)).await
+    }
+
+    async fn create(amount_users: u32, config: AppConfig, db_dir: Option<TempDir>) -> Self {
+        let state = AppState::new(config).await;
+        TestContext::from_state(amount_users, state, db_dir).await
+    }
+
+    async fn from_state(amount_users: u32, state: AppState, db_dir: Option<TempDir>) -> Self {
+        let router = crate::http::server::create_router(state.clone());
 
         let server = TestServer::new(router).expect("Failed to create test server!");
         let arc = Arc::new(server);
@@ -50,6 +96,7 @@ impl TestContext {
             users,
             node: state.node_repository.clone(),
             state,
+            _db_dir: db_dir,
         }
     }
 
@@ -69,6 +116,21 @@ impl TestContext {
         assert_eq!(format!("{:x}", Sha256::digest(bytes)), expected)
     }
 
+    /// Sends all requests at the same time and returns the responses in the same order. Run the
+    /// test on a multi-threaded runtime, to let the requests actually race each other.
+    pub async fn send_concurrently(requests: Vec<TestRequest>) -> Vec<TestResponse> {
+        let handles: Vec<_> = requests
+            .into_iter()
+            .map(|request| tokio::spawn(request.into_future()))
+            .collect();
+
+        let mut responses = Vec::with_capacity(handles.len());
+        for handle in handles {
+            responses.push(handle.await.expect("Request panicked"));
+        }
+        responses
+    }
+
     pub fn random_range(len_range: Range<usize>) -> usize {
         let mut rng = rand::rng();
         rng.random_range(len_range)
@@ -87,6 +149,12 @@ impl TestContext {
             .collect()
     }
 
+    /// KDF parameters with the current version and a random salt, like the client creates them
+    pub fn random_kdf_params() -> KdfParams {
+        let salt = SaltString::generate(&mut OsRng);
+        KdfParams::new(KdfParams::CURRENT_VERSION, Some(salt.as_str().to_string()))
+    }
+
     pub fn random_bytes(size: usize) -> bytes::Bytes {
         let mut rng = rand::rng();
         let mut data = vec![0u8; size];
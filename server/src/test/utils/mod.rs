@@ -1,11 +1,15 @@
 pub mod builder;
 pub mod context;
 pub mod entities;
+pub mod s3;
 pub mod user;
+pub mod vfs;
 
 pub use builder::NodeBuilder;
 pub use context::TestContext;
 pub use entities::TestChunk;
 pub use entities::TestNodeEntity;
 pub use entities::TestRevisionEntity;
+pub use s3::TestBucket;
 pub use user::TestUserEntity;
+pub use vfs::{PausedCommitVfs, WriteTrackingVfs};
@@ -0,0 +1,272 @@
+use crate::http::config::S3Config;
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use axum::Router;
+use axum::extract::{DefaultBodyLimit, Path, Query, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use bytes::{Bytes, BytesMut};
+use chrono::{DateTime, Utc};
+use crabdrive_common::uuid::UUID;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+type Objects = Arc<Mutex<BTreeMap<String, (Bytes, DateTime<Utc>)>>>;
+/// Unfinished multipart uploads by their ID, with the key and the parts by their number
+type Uploads = Arc<Mutex<HashMap<String, (String, BTreeMap<usize, Bytes>)>>>;
+
+#[derive(Clone, Default)]
+struct Bucket {
+    objects: Objects,
+    uploads: Uploads,
+}
+
+const BUCKET: &str = "crabdrive-test";
+
+/// A minimal S3-compatible server, which keeps its objects in memory. It only understands the
+/// requests the `S3` storage backend sends: conditional puts, copies, multipart uploads, gets,
+/// deletes and listing a prefix. The server is stopped once the bucket is dropped.
+pub struct TestBucket {
+    /// Points the `S3` backend to this bucket
+    pub config: S3Config,
+    bucket: Bucket,
+    server: JoinHandle<()>,
+}
+
+impl TestBucket {
+    pub async fn start() -> Self {
+        let bucket = Bucket::default();
+        let router = Router::new()
+            .route("/{bucket}", get(list_objects))
+            .route(
+                "/{bucket}/{*key}",
+                get(get_object)
+                    .put(put_object)
+                    .post(post_object)
+                    .delete(delete_object),
+            )
+            // parts are several MiB large
+            .layer(DefaultBodyLimit::disable())
+            .with_state(bucket.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind test bucket!");
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        Self {
+            config: S3Config {
+                endpoint: Some(format!("http://{address}")),
+                region: "us-east-1".into(),
+                bucket: BUCKET.into(),
+                access_key_id: Some("crabdrive".into()),
+                secret_access_key: Some("crabdrive".into()),
+            },
+            bucket,
+            server,
+        }
+    }
+
+    /// The keys of all stored objects starting with `prefix`
+    pub fn keys(&self, prefix: &str) -> Vec<String> {
+        self.bucket
+            .objects
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+
+    /// The amount of multipart uploads, which were neither completed nor aborted
+    pub fn pending_uploads(&self) -> usize {
+        self.bucket.uploads.lock().unwrap().len()
+    }
+}
+
+impl Drop for TestBucket {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+fn etag(contents: &Bytes) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{:x}\"", contents.len())).unwrap()
+}
+
+async fn list_objects(
+    State(Bucket { objects, .. }): State<Bucket>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let prefix = query.get("prefix").cloned().unwrap_or_default();
+    let contents: String = objects
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(key, _)| key.starts_with(&prefix))
+        .map(|(key, (contents, modified))| {
+            format!(
+                "<Contents><Key>{key}</Key><Size>{}</Size><LastModified>{}</LastModified></Contents>",
+                contents.len(),
+                modified.to_rfc3339()
+            )
+        })
+        .collect();
+
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+        <ListBucketResult><Name>{BUCKET}</Name><Prefix>{prefix}</Prefix>{contents}</ListBucketResult>"
+    );
+    ([(header::CONTENT_TYPE, "application/xml")], body).into_response()
+}
+
+async fn get_object(
+    State(Bucket { objects, .. }): State<Bucket>,
+    Path((_, key)): Path<(String, String)>,
+) -> Response {
+    let Some((contents, _)) = objects.lock().unwrap().get(&key).cloned() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    (
+        [
+            (header::CONTENT_LENGTH, HeaderValue::from(contents.len())),
+            (header::ETAG, etag(&contents)),
+        ],
+        contents,
+    )
+        .into_response()
+}
+
+/// The contents of the object a copy request refers to, if it is one
+fn copy_source(
+    objects: &BTreeMap<String, (Bytes, DateTime<Utc>)>,
+    headers: &HeaderMap,
+) -> Option<Option<Bytes>> {
+    let source = headers.get("x-amz-copy-source")?;
+    let source = source.to_str().unwrap().trim_start_matches('/');
+    let source = source.strip_prefix(&format!("{BUCKET}/")).unwrap_or(source);
+    Some(objects.get(source).map(|(contents, _)| contents.clone()))
+}
+
+fn xml(body: String) -> Response {
+    (
+        [(header::CONTENT_TYPE, "application/xml")],
+        format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>{body}"),
+    )
+        .into_response()
+}
+
+async fn put_object(
+    State(Bucket { objects, uploads }): State<Bucket>,
+    Path((_, key)): Path<(String, String)>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let mut objects = objects.lock().unwrap();
+
+    let copied = copy_source(&objects, &headers);
+    let contents = match copied.clone() {
+        Some(Some(contents)) => contents,
+        Some(None) => return StatusCode::NOT_FOUND.into_response(),
+        None => body,
+    };
+
+    // upload a part of a multipart upload
+    if let Some(upload_id) = query.get("uploadId") {
+        let part_number: usize = query["partNumber"].parse().unwrap();
+        let mut uploads = uploads.lock().unwrap();
+        let Some((_, parts)) = uploads.get_mut(upload_id) else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+        let etag = etag(&contents);
+        parts.insert(part_number, contents);
+        return match copied {
+            Some(_) => xml(format!(
+                "<CopyPartResult><ETag>{}</ETag></CopyPartResult>",
+                etag.to_str().unwrap()
+            )),
+            None => [(header::ETAG, etag)].into_response(),
+        };
+    }
+
+    if headers.get(header::IF_NONE_MATCH).is_some_and(|v| v == "*") && objects.contains_key(&key) {
+        return StatusCode::PRECONDITION_FAILED.into_response();
+    }
+
+    let etag = etag(&contents);
+    objects.insert(key, (contents, Utc::now()));
+    [(header::ETAG, etag)].into_response()
+}
+
+/// Create (`?uploads`) or complete (`?uploadId=`) a multipart upload
+async fn post_object(
+    State(Bucket { objects, uploads }): State<Bucket>,
+    Path((_, key)): Path<(String, String)>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if query.contains_key("uploads") {
+        let upload_id = UUID::random().to_string();
+        uploads
+            .lock()
+            .unwrap()
+            .insert(upload_id.clone(), (key.clone(), BTreeMap::new()));
+        return xml(format!(
+            "<InitiateMultipartUploadResult><Bucket>{BUCKET}</Bucket><Key>{key}</Key>\
+            <UploadId>{upload_id}</UploadId></InitiateMultipartUploadResult>"
+        ));
+    }
+
+    let Some(upload_id) = query.get("uploadId") else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let mut objects = objects.lock().unwrap();
+    let mut uploads = uploads.lock().unwrap();
+    if headers.get(header::IF_NONE_MATCH).is_some_and(|v| v == "*") && objects.contains_key(&key) {
+        return StatusCode::PRECONDITION_FAILED.into_response();
+    }
+    let Some((_, parts)) = uploads.remove(upload_id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    // only the parts listed in the request make up the object
+    let mut contents = BytesMut::new();
+    for part in body.split("<PartNumber>").skip(1) {
+        let (number, _) = part.split_once("</PartNumber>").unwrap();
+        match parts.get(&number.parse::<usize>().unwrap()) {
+            Some(part) => contents.extend_from_slice(part),
+            None => return StatusCode::BAD_REQUEST.into_response(),
+        }
+    }
+
+    let contents = contents.freeze();
+    let etag = etag(&contents);
+    objects.insert(key, (contents, Utc::now()));
+    xml(format!(
+        "<CompleteMultipartUploadResult><ETag>{}</ETag></CompleteMultipartUploadResult>",
+        etag.to_str().unwrap()
+    ))
+}
+
+/// Delete an object or abort a multipart upload (`?uploadId=`)
+async fn delete_object(
+    State(Bucket { objects, uploads }): State<Bucket>,
+    Path((_, key)): Path<(String, String)>,
+    Query(query): Query<HashMap<String, String>>,
+) -> StatusCode {
+    if let Some(upload_id) = query.get("uploadId") {
+        uploads.lock().unwrap().remove(upload_id);
+    } else {
+        objects.lock().unwrap().remove(&key);
+    }
+    StatusCode::NO_CONTENT
+}
@@ -2,12 +2,12 @@ use crate::http::AppState;
 use crate::storage::node::NodeEntity;
 use crate::user::UserEntity;
 
-use super::{NodeBuilder, TestNodeEntity};
+use super::{NodeBuilder, TestContext, TestNodeEntity};
 
 use crabdrive_common::da;
 use crabdrive_common::encrypted_metadata::EncryptedMetadata;
 use crabdrive_common::storage::{NodeId, NodeType};
-use crabdrive_common::user::UserKeys;
+use crabdrive_common::user::{UserKeys, UserType};
 use crabdrive_common::uuid::UUID;
 
 use std::sync::Arc;
@@ -34,7 +34,13 @@ impl TestUserEntity {
 
         let mut user_entity = state
             .user_repository
-            .create_user(&username, &password, da!(128 MiB), keys.clone())
+            .create_user(
+                &username,
+                &password,
+                da!(128 MiB),
+                keys.clone(),
+                TestContext::random_kdf_params(),
+            )
             .expect("Failed to create user!");
 
         let root_node = state
@@ -102,12 +108,33 @@ impl TestUserEntity {
             .authorization_bearer(&self.token)
     }
 
+    pub fn put(&self, url: impl AsRef<str>) -> TestRequest {
+        self.server
+            .put(url.as_ref())
+            .authorization_bearer(&self.token)
+    }
+
     pub fn delete(&self, url: impl AsRef<str>) -> TestRequest {
         self.server
             .delete(url.as_ref())
             .authorization_bearer(&self.token)
     }
 
+    /// Changes the role of the user directly in the repository
+    pub fn set_user_type(&self, user_type: UserType) {
+        let user = self
+            .state
+            .user_repository
+            .get_user(self.id)
+            .expect("Database error during user fetch")
+            .expect("User not found");
+
+        self.state
+            .user_repository
+            .update_user(UserEntity { user_type, ..user })
+            .expect("Failed to update user type");
+    }
+
     pub fn get_root(&self) -> NodeId {
         self.entity.root_node.unwrap()
     }
@@ -162,4 +189,13 @@ impl TestUserEntity {
             .get_node(node_id)
             .expect("Database error during node fetch")
     }
+
+    /// Fetches the fresh user directly from the repository
+    pub fn fetch_user_from_db(&self) -> UserEntity {
+        self.state
+            .user_repository
+            .get_user(self.id)
+            .expect("Database error during user fetch")
+            .expect("User does not exist")
+    }
 }
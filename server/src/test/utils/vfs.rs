@@ -0,0 +1,209 @@
+use super::context::SharedVfs;
+
+use crate::storage::vfs::{
+    ChunkContents, ChunkReader, FileChunk, FileKey, FileRepository, FileStatus, FileSystemError,
+};
+
+use crabdrive_common::data::DataAmount;
+use crabdrive_common::storage::ChunkIndex;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::Notify;
+
+/// Forwards everything to another storage backend and records how many chunks were written at the
+/// same time
+pub struct WriteTrackingVfs {
+    inner: SharedVfs,
+    in_flight: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+/// Counts a write as in flight until it is dropped, so that cancelled writes are counted as well
+struct InFlightWrite<'a>(&'a WriteTrackingVfs);
+
+impl<'a> InFlightWrite<'a> {
+    fn start(vfs: &'a WriteTrackingVfs) -> Self {
+        let in_flight = vfs.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        vfs.peak.fetch_max(in_flight, Ordering::SeqCst);
+        Self(vfs)
+    }
+}
+
+impl Drop for InFlightWrite<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl WriteTrackingVfs {
+    pub fn new(inner: SharedVfs) -> Self {
+        Self {
+            inner,
+            in_flight: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    /// The highest amount of chunks, which were written at the same time
+    pub fn peak_concurrent_writes(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait::async_trait]
+impl FileRepository for WriteTrackingVfs {
+    async fn chunk_exists(&self, key: &FileKey, index: ChunkIndex) -> bool {
+        self.inner.chunk_exists(key, index).await
+    }
+
+    async fn file_status(&self, key: &FileKey) -> FileStatus {
+        self.inner.file_status(key).await
+    }
+
+    async fn create_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        self.inner.create_file(key).await
+    }
+
+    async fn write_chunk(&self, key: &FileKey, contents: FileChunk) -> Result<(), FileSystemError> {
+        let _write = InFlightWrite::start(self);
+        self.inner.write_chunk(key, contents).await
+    }
+
+    async fn write_chunk_stream(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+        contents: ChunkReader,
+        max_size: DataAmount,
+    ) -> Result<DataAmount, FileSystemError> {
+        let _write = InFlightWrite::start(self);
+        self.inner
+            .write_chunk_stream(key, index, contents, max_size)
+            .await
+    }
+
+    async fn delete_chunk(&self, key: &FileKey, index: ChunkIndex) -> Result<(), FileSystemError> {
+        self.inner.delete_chunk(key, index).await
+    }
+
+    async fn commit_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        self.inner.commit_file(key).await
+    }
+
+    async fn abort(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        self.inner.abort(key).await
+    }
+
+    async fn delete_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        self.inner.delete_file(key).await
+    }
+
+    async fn read_chunk(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+    ) -> Result<FileChunk, FileSystemError> {
+        self.inner.read_chunk(key, index).await
+    }
+
+    async fn open_chunk(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+    ) -> Result<ChunkContents, FileSystemError> {
+        self.inner.open_chunk(key, index).await
+    }
+}
+
+/// Forwards everything to another storage backend, but pauses every persisted file until
+/// [`PausedCommitVfs::resume_commit`] is called, to simulate a slow backend
+pub struct PausedCommitVfs {
+    inner: SharedVfs,
+    commit_started: Notify,
+    commit_resumed: Notify,
+}
+
+impl PausedCommitVfs {
+    pub fn new(inner: SharedVfs) -> Self {
+        Self {
+            inner,
+            commit_started: Notify::new(),
+            commit_resumed: Notify::new(),
+        }
+    }
+
+    /// Wait until a file is being persisted
+    pub async fn wait_for_commit(&self) {
+        self.commit_started.notified().await
+    }
+
+    /// Let the paused file be persisted
+    pub fn resume_commit(&self) {
+        self.commit_resumed.notify_one()
+    }
+}
+
+#[async_trait::async_trait]
+impl FileRepository for PausedCommitVfs {
+    async fn chunk_exists(&self, key: &FileKey, index: ChunkIndex) -> bool {
+        self.inner.chunk_exists(key, index).await
+    }
+
+    async fn file_status(&self, key: &FileKey) -> FileStatus {
+        self.inner.file_status(key).await
+    }
+
+    async fn create_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        self.inner.create_file(key).await
+    }
+
+    async fn write_chunk(&self, key: &FileKey, contents: FileChunk) -> Result<(), FileSystemError> {
+        self.inner.write_chunk(key, contents).await
+    }
+
+    async fn write_chunk_stream(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+        contents: ChunkReader,
+        max_size: DataAmount,
+    ) -> Result<DataAmount, FileSystemError> {
+        self.inner
+            .write_chunk_stream(key, index, contents, max_size)
+            .await
+    }
+
+    async fn delete_chunk(&self, key: &FileKey, index: ChunkIndex) -> Result<(), FileSystemError> {
+        self.inner.delete_chunk(key, index).await
+    }
+
+    async fn commit_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        self.commit_started.notify_one();
+        self.commit_resumed.notified().await;
+        self.inner.commit_file(key).await
+    }
+
+    async fn abort(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        self.inner.abort(key).await
+    }
+
+    async fn delete_file(&self, key: &FileKey) -> Result<(), FileSystemError> {
+        self.inner.delete_file(key).await
+    }
+
+    async fn read_chunk(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+    ) -> Result<FileChunk, FileSystemError> {
+        self.inner.read_chunk(key, index).await
+    }
+
+    async fn open_chunk(
+        &self,
+        key: &FileKey,
+        index: ChunkIndex,
+    ) -> Result<ChunkContents, FileSystemError> {
+        self.inner.open_chunk(key, index).await
+    }
+}
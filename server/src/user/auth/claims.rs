@@ -15,3 +15,14 @@ pub struct Claims {
     /// A unique ID for each JWT
     pub jti: String,
 }
+
+/// Claims of the token, which is issued after the password of a user with a second factor was
+/// verified. It is only accepted by the second login step, never as a bearer token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecondFactorClaims {
+    pub user_id: UserId,
+    /// Unix timestamp when the token **exp**ires.
+    pub exp: i64,
+    /// Always `true`. Makes sure the claims of a bearer token cannot be decoded as these.
+    pub second_factor: bool,
+}
@@ -1,20 +1,23 @@
 use crate::http::AppState;
+use crate::user::persistence::access_token_repository::is_access_token;
 use crate::user::persistence::model::user_entity::UserEntity;
 use anyhow::Result;
 use axum::extract::FromRequestParts;
-use axum::http::StatusCode;
 use axum::http::request::Parts;
+use axum::http::{Method, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::{Json, RequestPartsExt};
 use axum_extra::TypedHeader;
 use axum_extra::headers::Authorization;
 use axum_extra::headers::authorization::Bearer;
+use crabdrive_common::user::{AccessTokenScope, UserType};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tracing::debug;
 
 pub mod claims;
 pub mod secrets;
+pub mod totp;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum AuthError {
@@ -22,6 +25,8 @@ pub enum AuthError {
     Expired,
     NoToken,
     ServerError,
+    /// The user is authenticated, but their `UserType` does not allow the request
+    Forbidden,
 }
 
 impl IntoResponse for AuthError {
@@ -33,16 +38,79 @@ impl IntoResponse for AuthError {
             AuthError::NoToken => "Missing token",
             AuthError::Expired => "Expired token",
             AuthError::ServerError => "Internal server error",
+            AuthError::Forbidden => "Insufficient permissions",
+        };
+
+        let status = match self {
+            AuthError::Forbidden => StatusCode::FORBIDDEN,
+            _ => StatusCode::UNAUTHORIZED,
         };
 
         let body = Json(json!({
             "error": error_message,
         }));
 
-        (StatusCode::UNAUTHORIZED, body).into_response()
+        (status, body).into_response()
+    }
+}
+
+/// How the bearer token of a request was issued
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Authentication {
+    /// A JWT issued on login
+    Session,
+    /// A personal access token
+    AccessToken(AccessTokenScope),
+}
+
+/// Verify the bearer token of a request, which is either a JWT or a personal access token
+async fn authenticate(
+    parts: &mut Parts,
+    state: &AppState,
+) -> Result<(UserEntity, Authentication), AuthError> {
+    let TypedHeader(Authorization(bearer)) = parts
+        .extract::<TypedHeader<Authorization<Bearer>>>()
+        .await
+        .map_err(|_| AuthError::NoToken)?;
+
+    if is_access_token(bearer.token()) {
+        let access_token = state
+            .access_token_repository
+            .verify_access_token(bearer.token())
+            .map_err(|_| AuthError::ServerError)
+            .inspect_err(|e| {
+                tracing::error!("Database error while verifying access token: {:?}", e)
+            })?
+            .ok_or(AuthError::Unauthorized)?;
+
+        let user = state
+            .user_repository
+            .get_user(access_token.user_id)
+            .map_err(|_| AuthError::ServerError)?
+            .ok_or(AuthError::Unauthorized)?;
+
+        debug!(
+            "Authenticated user with access token {}: {}:{}",
+            access_token.id, user.username, user.id
+        );
+
+        return Ok((user, Authentication::AccessToken(access_token.scope)));
     }
+
+    let user = state
+        .user_repository
+        .verify_jwt(bearer.token())
+        .map_err(|_| AuthError::ServerError)
+        .inspect_err(|e| tracing::error!("Database error while verifying user claims: {:?}", e))?
+        .ok_or(AuthError::Unauthorized)?;
+
+    debug!("Authenticated user: {}:{}", user.username, user.id);
+
+    Ok((user, Authentication::Session))
 }
 
+/// Accepts JWTs and personal access tokens. Read-only access tokens are rejected with
+/// `403 Forbidden` for all requests, except `GET` and `HEAD`.
 impl FromRequestParts<AppState> for UserEntity {
     type Rejection = AuthError;
 
@@ -50,22 +118,89 @@ impl FromRequestParts<AppState> for UserEntity {
         parts: &mut Parts,
         state: &AppState,
     ) -> Result<Self, Self::Rejection> {
-        let TypedHeader(Authorization(bearer)) = parts
-            .extract::<TypedHeader<Authorization<Bearer>>>()
-            .await
-            .map_err(|_| AuthError::NoToken)?;
-
-        let user = state
-            .user_repository
-            .verify_jwt(bearer.token())
-            .map_err(|_| AuthError::ServerError)
-            .inspect_err(|e| {
-                tracing::error!("Database error while verifying user claims: {:?}", e)
-            })?
-            .ok_or(AuthError::Unauthorized)?;
+        let (user, authentication) = authenticate(parts, state).await?;
 
-        debug!("Authenticated user: {}:{}", user.username, user.id);
+        if authentication == Authentication::AccessToken(AccessTokenScope::Read)
+            && parts.method != Method::GET
+            && parts.method != Method::HEAD
+        {
+            debug!(
+                "Denied write access with read-only access token for {}",
+                user.id
+            );
+            return Err(AuthError::Forbidden);
+        }
 
         Ok(user)
     }
 }
+
+/// A user authenticated with the JWT of a login session. Required to manage the account (e.g. the
+/// password, sessions or access tokens). Personal access tokens are rejected with
+/// `403 Forbidden`.
+pub struct SessionUser(pub UserEntity);
+
+impl FromRequestParts<AppState> for SessionUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let (user, authentication) = authenticate(parts, state).await?;
+
+        if authentication != Authentication::Session {
+            debug!(
+                "Denied account management with access token for {}",
+                user.id
+            );
+            return Err(AuthError::Forbidden);
+        }
+
+        Ok(SessionUser(user))
+    }
+}
+
+/// An authenticated user with the `UserType::Admin` role. Rejects all other users with
+/// `403 Forbidden`.
+pub struct AdminUser(pub UserEntity);
+
+impl FromRequestParts<AppState> for AdminUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let user = UserEntity::from_request_parts(parts, state).await?;
+
+        if user.user_type != UserType::Admin {
+            debug!("Denied admin access for user {}", user.id);
+            return Err(AuthError::Forbidden);
+        }
+
+        Ok(AdminUser(user))
+    }
+}
+
+/// An authenticated user, which is allowed to modify data. `UserType::Restricted` users only have
+/// read access and are rejected with `403 Forbidden`.
+pub struct ReadWriteUser(pub UserEntity);
+
+impl FromRequestParts<AppState> for ReadWriteUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let user = UserEntity::from_request_parts(parts, state).await?;
+
+        if user.user_type == UserType::Restricted {
+            debug!("Denied write access for restricted user {}", user.id);
+            return Err(AuthError::Forbidden);
+        }
+
+        Ok(ReadWriteUser(user))
+    }
+}
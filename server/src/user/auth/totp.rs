@@ -0,0 +1,191 @@
+//! Time-based one-time passwords ([RFC 6238](https://datatracker.ietf.org/doc/html/rfc6238)), with
+//! the parameters every authenticator app supports: HMAC-SHA1, 6 digits and a period of 30
+//! seconds.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+const DIGITS: u32 = 6;
+const PERIOD: i64 = 30;
+/// The amount of time steps a code is accepted before and after its own time step, to allow for
+/// clock drift and slow typing.
+const ALLOWED_DRIFT: i64 = 1;
+const SECRET_LENGTH: usize = 20;
+const ISSUER: &str = "crabdrive";
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Generate a random secret. Returns the base32 encoded secret.
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; SECRET_LENGTH];
+    OsRng.fill_bytes(&mut secret);
+    base32_encode(&secret)
+}
+
+/// The `otpauth://` URI authenticator apps expect in the QR code
+pub fn provisioning_uri(username: &str, secret: &str) -> String {
+    // Usernames are alphanumeric, so nothing needs to be escaped
+    format!(
+        "otpauth://totp/{ISSUER}:{username}?secret={secret}&issuer={ISSUER}&algorithm=SHA1&digits={DIGITS}&period={PERIOD}"
+    )
+}
+
+/// The time step of a unix timestamp
+pub fn time_step(timestamp: i64) -> i64 {
+    timestamp.div_euclid(PERIOD)
+}
+
+/// Compute the code of a time step
+pub fn generate_code(secret: &[u8], step: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // Dynamic truncation, see RFC 4226, section 5.3
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS),
+        width = DIGITS as usize
+    )
+}
+
+/// Check a code against the time steps around `timestamp`. Returns the matching time step, or
+/// `None` if the code is invalid or the secret cannot be decoded. Codes of a time step before
+/// or at `last_step` are rejected, as they were already used.
+pub fn verify_code(
+    secret: &str,
+    code: &str,
+    timestamp: i64,
+    last_step: Option<i64>,
+) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let secret = base32_decode(secret)?;
+    let current_step = time_step(timestamp);
+
+    (current_step - ALLOWED_DRIFT..=current_step + ALLOWED_DRIFT)
+        .filter(|step| last_step.is_none_or(|last_step| *step > last_step))
+        .find(|step| generate_code(&secret, *step) == code)
+}
+
+/// Encode bytes as base32 (RFC 4648) without padding
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+/// Decode base32 (RFC 4648). Padding, whitespace and lowercase letters are accepted.
+pub fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in encoded.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|a| *a as char == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The SHA1 secret of the test vectors in RFC 6238, appendix B
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_rfc_6238_vectors() {
+        // The RFC uses 8 digits, so only the last 6 digits are compared
+        let vectors = [
+            (59, "94287082"),
+            (1111111109, "07081804"),
+            (1111111111, "14050471"),
+            (1234567890, "89005924"),
+            (2000000000, "69279037"),
+            (20000000000, "65353130"),
+        ];
+
+        for (timestamp, code) in vectors {
+            assert_eq!(
+                generate_code(RFC_SECRET, time_step(timestamp)),
+                code[2..],
+                "Wrong code at {timestamp}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_code() {
+        let secret = base32_encode(RFC_SECRET);
+        let code = generate_code(RFC_SECRET, time_step(1111111109));
+
+        assert_eq!(
+            verify_code(&secret, &code, 1111111109, None),
+            Some(37037036)
+        );
+        // The previous and next time step are accepted as well
+        assert_eq!(
+            verify_code(&secret, &code, 1111111109 - 30, None),
+            Some(37037036)
+        );
+        assert_eq!(
+            verify_code(&secret, &code, 1111111109 + 30, None),
+            Some(37037036)
+        );
+        assert_eq!(verify_code(&secret, &code, 1111111109 + 60, None), None);
+        // Codes cannot be used twice
+        assert_eq!(
+            verify_code(&secret, &code, 1111111109, Some(37037036)),
+            None
+        );
+        assert_eq!(verify_code(&secret, "12345", 1111111109, None), None);
+    }
+
+    #[test]
+    fn test_base32() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("MZXW6YTBOI======").unwrap(), b"foobar");
+        assert_eq!(base32_decode("mzxw 6ytb oi").unwrap(), b"foobar");
+        assert_eq!(base32_decode("MZXW1"), None);
+
+        let secret = generate_secret();
+        assert_eq!(base32_decode(&secret).unwrap().len(), SECRET_LENGTH);
+    }
+}
@@ -3,6 +3,8 @@ pub mod persistence;
 
 pub use persistence::model::token::SessionId;
 
+pub use persistence::model::access_token_entity::AccessTokenEntity;
+pub use persistence::model::invite_code_entity::InviteCodeEntity;
 pub use persistence::model::token::BlacklistedTokenEntity;
 pub use persistence::model::token::RefreshTokenEntity;
 pub use persistence::model::user_entity::UserEntity;
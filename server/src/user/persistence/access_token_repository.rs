@@ -0,0 +1,115 @@
+use crate::db::connection::DbPool;
+use crate::db::operations::access_token::*;
+use crate::user::AccessTokenEntity;
+
+use crabdrive_common::user::{AccessTokenId, AccessTokenScope, UserId};
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use chrono::{NaiveDateTime, Utc};
+use nanoid::nanoid;
+use sha2::{Digest, Sha256};
+
+type AccessToken = String;
+
+/// All access tokens start with this prefix. It distinguishes them from JWTs and makes leaked
+/// tokens easy to find.
+pub const ACCESS_TOKEN_PREFIX: &str = "crabdrive_pat_";
+
+pub trait AccessTokenRepository {
+    /// Create a new access token. Returns the plain token, which is not stored.
+    fn create_access_token(
+        &self,
+        user_id: UserId,
+        name: &str,
+        scope: AccessTokenScope,
+        expires_at: Option<NaiveDateTime>,
+    ) -> Result<(AccessToken, AccessTokenEntity)>;
+    /// Get all access tokens of a user
+    fn list_access_tokens(&self, user_id: UserId) -> Result<Vec<AccessTokenEntity>>;
+    /// Revoke (delete) an access token. Returns `None` if the user has no such token.
+    fn delete_access_token(
+        &self,
+        user_id: UserId,
+        id: AccessTokenId,
+    ) -> Result<Option<AccessTokenEntity>>;
+    /// Revoke all access tokens of a user. Returns the amount of revoked tokens.
+    fn delete_access_tokens(&self, user_id: UserId) -> Result<usize>;
+    /// Verify a plain access token and record its use. Returns `None` if the token is unknown or
+    /// expired.
+    fn verify_access_token(&self, token: &str) -> Result<Option<AccessTokenEntity>>;
+}
+
+/// Whether a bearer token is an access token (and not a JWT)
+pub fn is_access_token(token: &str) -> bool {
+    token.starts_with(ACCESS_TOKEN_PREFIX)
+}
+
+fn hash_access_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub struct AccessTokenRepositoryImpl {
+    db_pool: Arc<DbPool>,
+}
+
+impl AccessTokenRepositoryImpl {
+    pub fn new(db_pool: Arc<DbPool>) -> Self {
+        Self { db_pool }
+    }
+}
+
+impl AccessTokenRepository for AccessTokenRepositoryImpl {
+    fn create_access_token(
+        &self,
+        user_id: UserId,
+        name: &str,
+        scope: AccessTokenScope,
+        expires_at: Option<NaiveDateTime>,
+    ) -> Result<(AccessToken, AccessTokenEntity)> {
+        let mut conn = self.db_pool.get()?;
+
+        let token = format!("{ACCESS_TOKEN_PREFIX}{}", nanoid!(40));
+
+        let access_token = AccessTokenEntity {
+            id: AccessTokenId::random(),
+            user_id,
+            name: name.to_string(),
+            token_hash: hash_access_token(&token),
+            scope,
+            created_at: Utc::now().naive_utc(),
+            expires_at,
+            last_used_at: None,
+        };
+
+        let access_token = insert_access_token(&mut conn, &access_token)
+            .context("Failed to insert access token")?;
+        Ok((token, access_token))
+    }
+
+    fn list_access_tokens(&self, user_id: UserId) -> Result<Vec<AccessTokenEntity>> {
+        let mut conn = self.db_pool.get()?;
+        select_access_tokens(&mut conn, user_id).context("Failed to select access tokens")
+    }
+
+    fn delete_access_token(
+        &self,
+        user_id: UserId,
+        id: AccessTokenId,
+    ) -> Result<Option<AccessTokenEntity>> {
+        let mut conn = self.db_pool.get()?;
+        delete_access_token(&mut conn, user_id, id).context("Failed to delete access token")
+    }
+
+    fn delete_access_tokens(&self, user_id: UserId) -> Result<usize> {
+        let mut conn = self.db_pool.get()?;
+        delete_access_tokens(&mut conn, user_id).context("Failed to delete access tokens")
+    }
+
+    fn verify_access_token(&self, token: &str) -> Result<Option<AccessTokenEntity>> {
+        let mut conn = self.db_pool.get()?;
+        use_access_token(&mut conn, &hash_access_token(token), Utc::now().naive_utc())
+            .context("Failed to verify access token")
+    }
+}
@@ -0,0 +1,151 @@
+use crate::db::connection::DbPool;
+use crate::db::operations::invite::*;
+use crate::user::InviteCodeEntity;
+
+use crabdrive_common::data::DataAmount;
+use crabdrive_common::user::{InviteCodeId, UserId, UserType};
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use chrono::{NaiveDateTime, Utc};
+use nanoid::nanoid;
+use sha2::{Digest, Sha512};
+
+type InviteCode = String;
+
+pub trait InviteRepository {
+    /// Create a new invite code. Returns the plain code, which is not stored.
+    fn create_invite_code(
+        &self,
+        created_by: UserId,
+        expires_at: Option<NaiveDateTime>,
+        max_uses: Option<i64>,
+        user_type: UserType,
+        storage_limit: DataAmount,
+    ) -> Result<(InviteCode, InviteCodeEntity)>;
+    /// Create a code, which can be redeemed once to register an account with `username`. The
+    /// username counts as taken until the code was redeemed, expired or revoked.
+    fn create_user_invite_code(
+        &self,
+        created_by: UserId,
+        username: &str,
+        user_type: UserType,
+        storage_limit: DataAmount,
+    ) -> Result<(InviteCode, InviteCodeEntity)>;
+    /// Get all invite codes
+    fn list_invite_codes(&self) -> Result<Vec<InviteCodeEntity>>;
+    /// Revoke (delete) an invite code. Returns `None` if the code does not exist.
+    fn delete_invite_code(&self, id: InviteCodeId) -> Result<Option<InviteCodeEntity>>;
+    /// Get an invite code, if it can still be redeemed. Does not count as a use.
+    fn get_redeemable_invite_code(&self, code: &str) -> Result<Option<InviteCodeEntity>>;
+    /// Get the redeemable code, which reserves `username` (see `create_user_invite_code`)
+    fn get_reserving_invite_code(&self, username: &str) -> Result<Option<InviteCodeEntity>>;
+}
+
+/// Hash an invite code. Uses the same format as `AuthConfig::invite_code_hash`.
+pub fn hash_invite_code(code: &str) -> String {
+    format!("{:02x}", Sha512::digest(code.as_bytes()))
+}
+
+pub struct InviteRepositoryImpl {
+    db_pool: Arc<DbPool>,
+}
+
+impl InviteRepositoryImpl {
+    pub fn new(db_pool: Arc<DbPool>) -> Self {
+        Self { db_pool }
+    }
+}
+
+impl InviteRepositoryImpl {
+    fn insert_new_invite_code(
+        &self,
+        created_by: UserId,
+        expires_at: Option<NaiveDateTime>,
+        max_uses: Option<i64>,
+        user_type: UserType,
+        storage_limit: DataAmount,
+        username: Option<String>,
+    ) -> Result<(InviteCode, InviteCodeEntity)> {
+        let mut conn = self.db_pool.get()?;
+
+        let code = nanoid!(24);
+
+        let invite_code = InviteCodeEntity {
+            id: InviteCodeId::random(),
+            code_hash: hash_invite_code(&code),
+            created_by: Some(created_by),
+            created_at: Utc::now().naive_utc(),
+            expires_at,
+            max_uses,
+            uses: 0,
+            user_type,
+            storage_limit,
+            username,
+        };
+
+        let invite_code =
+            insert_invite_code(&mut conn, &invite_code).context("Failed to insert invite code")?;
+        Ok((code, invite_code))
+    }
+}
+
+impl InviteRepository for InviteRepositoryImpl {
+    fn create_invite_code(
+        &self,
+        created_by: UserId,
+        expires_at: Option<NaiveDateTime>,
+        max_uses: Option<i64>,
+        user_type: UserType,
+        storage_limit: DataAmount,
+    ) -> Result<(InviteCode, InviteCodeEntity)> {
+        self.insert_new_invite_code(
+            created_by,
+            expires_at,
+            max_uses,
+            user_type,
+            storage_limit,
+            None,
+        )
+    }
+
+    fn create_user_invite_code(
+        &self,
+        created_by: UserId,
+        username: &str,
+        user_type: UserType,
+        storage_limit: DataAmount,
+    ) -> Result<(InviteCode, InviteCodeEntity)> {
+        self.insert_new_invite_code(
+            created_by,
+            None,
+            Some(1),
+            user_type,
+            storage_limit,
+            Some(username.to_string()),
+        )
+    }
+
+    fn list_invite_codes(&self) -> Result<Vec<InviteCodeEntity>> {
+        let mut conn = self.db_pool.get()?;
+        select_invite_codes(&mut conn).context("Failed to select invite codes")
+    }
+
+    fn delete_invite_code(&self, id: InviteCodeId) -> Result<Option<InviteCodeEntity>> {
+        let mut conn = self.db_pool.get()?;
+        delete_invite_code(&mut conn, id).context("Failed to delete invite code")
+    }
+
+    fn get_redeemable_invite_code(&self, code: &str) -> Result<Option<InviteCodeEntity>> {
+        let mut conn = self.db_pool.get()?;
+        select_redeemable_invite_code(&mut conn, &hash_invite_code(code), Utc::now().naive_utc())
+            .context("Failed to select invite code")
+    }
+
+    fn get_reserving_invite_code(&self, username: &str) -> Result<Option<InviteCodeEntity>> {
+        let mut conn = self.db_pool.get()?;
+        select_reserving_invite_code(&mut conn, username, Utc::now().naive_utc())
+            .context("Failed to select invite code")
+    }
+}
@@ -1,2 +1,4 @@
+pub mod access_token_repository;
+pub mod invite_repository;
 pub mod model;
 pub mod user_repository;
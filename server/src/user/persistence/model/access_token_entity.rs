@@ -0,0 +1,21 @@
+use chrono::NaiveDateTime;
+use crabdrive_common::user::{AccessTokenId, AccessTokenScope, UserId};
+use diesel::{AsChangeset, Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Insertable, AsChangeset, Clone)]
+#[diesel(table_name = crate::db::schema::AccessToken)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct AccessTokenEntity {
+    pub id: AccessTokenId,
+    pub user_id: UserId,
+    pub name: String,
+    /// The SHA-256 hash of the token (hex encoded)
+    pub token_hash: String,
+    pub scope: AccessTokenScope,
+    pub created_at: NaiveDateTime,
+    /// The token is rejected after this point in time (UTC)
+    pub expires_at: Option<NaiveDateTime>,
+    /// Updated every time the token is accepted
+    pub last_used_at: Option<NaiveDateTime>,
+}
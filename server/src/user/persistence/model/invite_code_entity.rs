@@ -0,0 +1,29 @@
+use chrono::NaiveDateTime;
+use crabdrive_common::data::DataAmount;
+use crabdrive_common::user::{InviteCodeId, UserId, UserType};
+use diesel::{AsChangeset, Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Insertable, AsChangeset, Clone)]
+#[diesel(table_name = crate::db::schema::InviteCode)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct InviteCodeEntity {
+    pub id: InviteCodeId,
+    /// The SHA-512 hash of the invite code (hex encoded)
+    pub code_hash: String,
+    /// The admin who created the code. `None` if the admin has been deleted.
+    pub created_by: Option<UserId>,
+    pub created_at: NaiveDateTime,
+    /// The code can not be redeemed after this point in time (UTC)
+    pub expires_at: Option<NaiveDateTime>,
+    /// How often the code can be redeemed. `None` means unlimited.
+    pub max_uses: Option<i64>,
+    /// How often the code has been redeemed
+    pub uses: i64,
+    /// The role of users registering with this code
+    pub user_type: UserType,
+    /// The storage limit of users registering with this code
+    pub storage_limit: DataAmount,
+    /// The only username, which can be registered with this code. Any username, if `None`.
+    pub username: Option<String>,
+}
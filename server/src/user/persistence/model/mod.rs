@@ -1,2 +1,4 @@
+pub mod access_token_entity;
+pub mod invite_code_entity;
 pub mod token;
 pub mod user_entity;
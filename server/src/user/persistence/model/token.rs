@@ -1,13 +1,12 @@
 use crate::user::UserEntity;
 use chrono::NaiveDateTime;
 use crabdrive_common::user::UserId;
-use crabdrive_common::uuid::UUID;
 use diesel::prelude::Associations;
 
 use diesel::{AsChangeset, Insertable, Queryable, Selectable};
 use serde::{Deserialize, Serialize};
 
-pub type SessionId = UUID;
+pub use crabdrive_common::user::SessionId;
 
 #[derive(
     Associations,
@@ -34,6 +33,9 @@ pub struct RefreshTokenEntity {
     pub expires_at: NaiveDateTime,
     /// If a token is refreshed, `invalidated_at` will be set to the current timestamp.
     pub invalidated_at: Option<NaiveDateTime>,
+    /// Datetime when the session was created. Carried over to every token of the session. `None`
+    /// for sessions created before this was tracked.
+    pub session_created_at: Option<NaiveDateTime>,
 }
 
 #[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Insertable, AsChangeset, Clone)]
@@ -2,7 +2,7 @@ use chrono::NaiveDateTime;
 use crabdrive_common::data::DataAmount;
 use crabdrive_common::encryption_key::EncryptionKey;
 use crabdrive_common::storage::NodeId;
-use crabdrive_common::user::{UserId, UserType};
+use crabdrive_common::user::{KdfParams, KdfVersion, UserId, UserType};
 use diesel::prelude::Identifiable;
 use diesel::{AsChangeset, Insertable, Queryable, Selectable};
 use serde::{Deserialize, Serialize};
@@ -53,4 +53,30 @@ pub struct UserEntity {
 
     // should be created when the user first logs in
     pub trash_node: Option<NodeId>,
+
+    // set on every update, `None` if the user was never updated
+    pub updated_at: Option<NaiveDateTime>,
+
+    // hash of the password derived from the recovery code, `None` if no recovery key was created
+    pub recovery_password_hash: Option<String>,
+
+    // encrypted with key derived from the recovery code
+    pub recovery_master_key: Option<EncryptionKey>,
+
+    // parameters the client used to derive the password, see `KdfParams`
+    pub kdf_salt: Option<String>,
+    pub kdf_version: KdfVersion,
+
+    // base32 encoded, only used for the second factor while `totp_enabled` is set
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+
+    // the time step of the last accepted TOTP code, to prevent reusing a code
+    pub totp_last_step: Option<i64>,
+}
+
+impl UserEntity {
+    pub fn kdf_params(&self) -> KdfParams {
+        KdfParams::new(self.kdf_version, self.kdf_salt.clone())
+    }
 }
@@ -1,14 +1,18 @@
 use crate::db::connection::DbPool;
 use crate::db::operations::token::*;
+use crate::db::operations::totp::*;
 use crate::db::operations::user::*;
 
-use crate::user::auth::claims::Claims;
+use crate::storage::revision::RevisionEntity;
+use crate::user::auth::claims::{Claims, SecondFactorClaims};
 use crate::user::auth::secrets::Keys;
+use crate::user::auth::totp;
 
 use crate::user::{BlacklistedTokenEntity, RefreshTokenEntity, SessionId, UserEntity};
 
 use crabdrive_common::data::DataAmount;
-use crabdrive_common::user::{UserId, UserKeys, UserType};
+use crabdrive_common::encryption_key::EncryptionKey;
+use crabdrive_common::user::{KdfParams, UserId, UserKeys, UserType};
 use crabdrive_common::uuid::UUID;
 
 use std::sync::Arc;
@@ -20,15 +24,29 @@ use argon2::{Argon2, PasswordHasher};
 use argon2::{PasswordHash, PasswordVerifier};
 use chrono::{DateTime, Local, TimeDelta, Utc};
 use crabdrive_common::da;
-use diesel::Connection;
+use diesel::{Connection, SqliteConnection};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
 use nanoid::nanoid;
 use sha2::{Digest, Sha256};
 
 const JWT_EXPIRY: i64 = 60 * 9;
+/// The leeway `jsonwebtoken` allows when validating the expiry of a JWT
+const JWT_LEEWAY: i64 = 60;
+/// The time a user has to enter the second factor after entering the password
+const SECOND_FACTOR_CHALLENGE_EXPIRY: i64 = 60 * 5;
+const BACKUP_CODE_COUNT: usize = 10;
+/// Lowercase letters and digits without the ones, which are easily confused (`0`, `1`, `i`, `l`,
+/// `o`)
+const BACKUP_CODE_ALPHABET: [char; 31] = [
+    '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'j', 'k', 'm',
+    'n', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
+];
 
 type Jwt = String;
 type RefreshToken = String;
+type TotpSecret = String;
+type BackupCode = String;
+type SecondFactorChallenge = String;
 
 pub trait UserRepository {
     /// Create a new user
@@ -38,6 +56,7 @@ pub trait UserRepository {
         password: &str,
         storage_limit: DataAmount,
         keys: UserKeys,
+        kdf_params: KdfParams,
     ) -> Result<UserEntity>;
     /// Get a user by ID
     fn get_user(&self, id: UserId) -> Result<Option<UserEntity>>;
@@ -47,8 +66,47 @@ pub trait UserRepository {
     fn get_user_by_username(&self, username: &str) -> Result<Option<UserEntity>>;
     /// Update a username
     fn update_user(&self, updated_entity: UserEntity) -> Result<UserEntity>;
+    /// Set a new password. The keys must be wrapped with the key derived from the new password
+    /// with `kdf_params`.
+    fn change_password(
+        &self,
+        id: UserId,
+        password: &str,
+        keys: UserKeys,
+        kdf_params: KdfParams,
+    ) -> Result<UserEntity>;
+    /// Set the recovery key of a user. Replaces an existing recovery key.
+    fn set_recovery_key(
+        &self,
+        id: UserId,
+        recovery_password: &str,
+        recovery_master_key: EncryptionKey,
+    ) -> Result<UserEntity>;
+    /// Remove the recovery key of a user
+    fn delete_recovery_key(&self, id: UserId) -> Result<UserEntity>;
+    /// Set the key pair of a user. The private key must be wrapped with the master key.
+    fn set_key_pair(
+        &self,
+        id: UserId,
+        public_key: Vec<u8>,
+        private_key: EncryptionKey,
+    ) -> Result<UserEntity>;
+    /// Validate the recovery password of a user. Returns `None` if the user has no recovery key.
+    fn authenticate_recovery(
+        &self,
+        username: &str,
+        recovery_password: &str,
+    ) -> Result<Option<UserEntity>>;
     /// Hard-delete a user from the database
     fn delete_user(&self, id: UserId) -> Result<UserEntity>;
+    /// Get a page of users, ordered by their creation date
+    fn list_users(&self, offset: u64, limit: u64) -> Result<Vec<UserEntity>>;
+    /// Get the total amount of users
+    fn count_users(&self) -> Result<u64>;
+    /// Hard-delete a user and all of their nodes, revisions and shares. Returns the deleted
+    /// revisions, so that the associated chunks can be deleted. Returns `None` if the user does not
+    /// exist.
+    fn purge_user(&self, id: UserId) -> Result<Option<(UserEntity, Vec<RevisionEntity>)>>;
     /// Verify if a JWT is valid
     fn verify_jwt(&self, jwt: &str) -> Result<Option<UserEntity>>;
     /// Create a new session. This will create a new refresh token and JWT
@@ -57,6 +115,39 @@ pub trait UserRepository {
     fn refresh_session(&self, rtoken: &str) -> Result<Option<(RefreshToken, Jwt)>>;
     /// Invalidate session by ID. This will also blacklist the provided JWT.
     fn close_session(&self, jwt: &str) -> Result<()>;
+    /// Get the ID of the session, in which a JWT was issued
+    fn get_session_id(&self, jwt: &str) -> Result<SessionId>;
+    /// Get all active sessions of a user. Returns the currently valid refresh token of each session.
+    fn list_sessions(&self, user_id: UserId) -> Result<Vec<RefreshTokenEntity>>;
+    /// Revoke a session of a user. Invalidates all refresh tokens and blacklists all JWTs issued
+    /// in the session. Returns `false` if the session does not exist or is already revoked.
+    fn revoke_session(&self, user_id: UserId, session_id: SessionId) -> Result<bool>;
+    /// Revoke all sessions of a user except `current_session`. Returns the amount of revoked
+    /// sessions.
+    fn revoke_other_sessions(&self, user_id: UserId, current_session: SessionId) -> Result<u64>;
+    /// Revoke all sessions of a user. Returns the amount of revoked sessions.
+    fn revoke_all_sessions(&self, user_id: UserId) -> Result<u64>;
+    /// Start the TOTP enrolment with a new secret. Replaces an unconfirmed secret. The second
+    /// factor is only required after the enrolment was confirmed with `enable_totp`.
+    fn start_totp_enrolment(&self, id: UserId) -> Result<TotpSecret>;
+    /// Require the second factor on login and create new backup codes. Returns the plain backup
+    /// codes, which are not stored.
+    fn enable_totp(&self, id: UserId) -> Result<Vec<BackupCode>>;
+    /// Stop requiring the second factor. Deletes the secret and all backup codes.
+    fn disable_totp(&self, id: UserId) -> Result<UserEntity>;
+    /// Replace all backup codes. Returns the plain backup codes, which are not stored.
+    fn regenerate_backup_codes(&self, id: UserId) -> Result<Vec<BackupCode>>;
+    /// Validate a TOTP code against the (possibly unconfirmed) secret of a user. Each code is only
+    /// accepted once.
+    fn verify_totp_code(&self, user: &UserEntity, code: &str) -> Result<bool>;
+    /// Validate the second factor of a user, which is either a TOTP code or a backup code. Each
+    /// code is only accepted once.
+    fn verify_second_factor(&self, user: &UserEntity, code: &str) -> Result<bool>;
+    /// Create a token, which proves that the password of the user was correct
+    fn create_second_factor_challenge(&self, user_id: UserId) -> Result<SecondFactorChallenge>;
+    /// Verify a token created with `create_second_factor_challenge`. Returns `None` if the token
+    /// is invalid or expired.
+    fn verify_second_factor_challenge(&self, challenge: &str) -> Result<Option<UserEntity>>;
 }
 
 pub struct UserRepositoryImpl {
@@ -120,11 +211,46 @@ fn create_new_refresh_token(
         session_id: session_id.unwrap_or(UUID::random()),
         expires_at: expiry_time,
         invalidated_at: None,
+        session_created_at: Some(Local::now().naive_local()),
     };
 
     (refresh_token, tok)
 }
 
+pub(crate) fn hash_password(password: &str) -> String {
+    let password_salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &password_salt)
+        .unwrap()
+        .to_string()
+}
+
+/// Generate new backup codes. Returns the plain codes and their hashes.
+fn generate_backup_codes() -> (Vec<BackupCode>, Vec<String>) {
+    let codes: Vec<BackupCode> = (0..BACKUP_CODE_COUNT)
+        .map(|_| nanoid!(10, &BACKUP_CODE_ALPHABET))
+        .collect();
+    let hashes = codes.iter().map(|code| hash_backup_code(code)).collect();
+    (codes, hashes)
+}
+
+/// Hash a backup code. Ignores the case and separators, so the code can be typed in any format.
+fn hash_backup_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    format!("{:02x}", Sha256::digest(normalized.as_bytes()))
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    let parsed_hash = PasswordHash::new(password_hash).expect("Failed to hash password!");
+    Argon2::default()
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .is_ok()
+}
+
 impl UserRepository for UserRepositoryImpl {
     fn create_user(
         &self,
@@ -132,14 +258,11 @@ impl UserRepository for UserRepositoryImpl {
         password: &str,
         storage_limit: DataAmount,
         keys: UserKeys,
+        kdf_params: KdfParams,
     ) -> Result<UserEntity> {
         let mut conn = self.db_pool.get()?;
 
-        let password_salt = SaltString::generate(&mut OsRng);
-        let password_hash = Argon2::default()
-            .hash_password(password.as_bytes(), &password_salt)
-            .unwrap()
-            .to_string();
+        let password_hash = hash_password(password);
 
         let user = UserEntity {
             user_type: UserType::User,
@@ -159,6 +282,18 @@ impl UserRepository for UserRepositoryImpl {
             root_node: None,
             trash_key: keys.trash_key,
             trash_node: None,
+
+            updated_at: None,
+
+            recovery_password_hash: None,
+            recovery_master_key: None,
+
+            kdf_salt: kdf_params.salt,
+            kdf_version: kdf_params.version,
+
+            totp_secret: None,
+            totp_enabled: false,
+            totp_last_step: None,
         };
 
         insert_user(&mut conn, &user).context("Failed to insert user")?;
@@ -171,11 +306,7 @@ impl UserRepository for UserRepositoryImpl {
             return Ok(None);
         };
 
-        let parsed_hash = PasswordHash::new(&user.password_hash).expect("Failed to hash password!");
-        if Argon2::default()
-            .verify_password(password.as_bytes(), &parsed_hash)
-            .is_err()
-        {
+        if !verify_password(password, &user.password_hash) {
             tracing::debug!("Wrong password!");
             return Ok(None);
         }
@@ -183,6 +314,29 @@ impl UserRepository for UserRepositoryImpl {
         Ok(Some(user))
     }
 
+    fn authenticate_recovery(
+        &self,
+        username: &str,
+        recovery_password: &str,
+    ) -> Result<Option<UserEntity>> {
+        let Some(user) = self.get_user_by_username(username)? else {
+            tracing::debug!("User not found");
+            return Ok(None);
+        };
+
+        let Some(recovery_password_hash) = &user.recovery_password_hash else {
+            tracing::debug!("User has no recovery key");
+            return Ok(None);
+        };
+
+        if !verify_password(recovery_password, recovery_password_hash) {
+            tracing::debug!("Wrong recovery password!");
+            return Ok(None);
+        }
+
+        Ok(Some(user))
+    }
+
     fn get_user(&self, id: UserId) -> Result<Option<UserEntity>> {
         let mut conn = self.db_pool.get()?;
         select_user(&mut conn, id).context("Failed to select user")
@@ -195,14 +349,96 @@ impl UserRepository for UserRepositoryImpl {
 
     fn update_user(&self, updated_entity: UserEntity) -> Result<UserEntity> {
         let mut conn = self.db_pool.get()?;
+        let updated_entity = UserEntity {
+            updated_at: Some(Utc::now().naive_utc()),
+            ..updated_entity
+        };
         update_user(&mut conn, &updated_entity).context("Failed to update user")
     }
 
+    fn change_password(
+        &self,
+        id: UserId,
+        password: &str,
+        keys: UserKeys,
+        kdf_params: KdfParams,
+    ) -> Result<UserEntity> {
+        let mut conn = self.db_pool.get()?;
+        let password_hash = hash_password(password);
+        update_user_credentials(
+            &mut conn,
+            id,
+            &password_hash,
+            &keys,
+            &kdf_params,
+            Utc::now().naive_utc(),
+        )
+        .context("Failed to update user credentials")
+    }
+
+    fn set_recovery_key(
+        &self,
+        id: UserId,
+        recovery_password: &str,
+        recovery_master_key: EncryptionKey,
+    ) -> Result<UserEntity> {
+        let mut conn = self.db_pool.get()?;
+        let recovery_password_hash = hash_password(recovery_password);
+        update_user_recovery_key(
+            &mut conn,
+            id,
+            Some(&recovery_password_hash),
+            Some(&recovery_master_key),
+            Utc::now().naive_utc(),
+        )
+        .context("Failed to set recovery key")
+    }
+
+    fn delete_recovery_key(&self, id: UserId) -> Result<UserEntity> {
+        let mut conn = self.db_pool.get()?;
+        update_user_recovery_key(&mut conn, id, None, None, Utc::now().naive_utc())
+            .context("Failed to delete recovery key")
+    }
+
+    fn set_key_pair(
+        &self,
+        id: UserId,
+        public_key: Vec<u8>,
+        private_key: EncryptionKey,
+    ) -> Result<UserEntity> {
+        let mut conn = self.db_pool.get()?;
+        update_user_key_pair(
+            &mut conn,
+            id,
+            &public_key,
+            &private_key,
+            Utc::now().naive_utc(),
+        )
+        .context("Failed to set key pair")
+    }
+
     fn delete_user(&self, id: UserId) -> Result<UserEntity> {
         let mut conn = self.db_pool.get()?;
         delete_user(&mut conn, id).context("Failed to delete user")
     }
 
+    fn list_users(&self, offset: u64, limit: u64) -> Result<Vec<UserEntity>> {
+        let mut conn = self.db_pool.get()?;
+        select_users(&mut conn, offset.try_into()?, limit.try_into()?)
+            .context("Failed to select users")
+    }
+
+    fn count_users(&self) -> Result<u64> {
+        let mut conn = self.db_pool.get()?;
+        let count = count_users(&mut conn).context("Failed to count users")?;
+        Ok(count.try_into()?)
+    }
+
+    fn purge_user(&self, id: UserId) -> Result<Option<(UserEntity, Vec<RevisionEntity>)>> {
+        let mut conn = self.db_pool.get()?;
+        purge_user(&mut conn, id).context("Failed to purge user")
+    }
+
     fn verify_jwt(&self, jwt: &str) -> Result<Option<UserEntity>> {
         let mut conn = self.db_pool.get()?;
 
@@ -210,6 +446,10 @@ impl UserRepository for UserRepositoryImpl {
         if selected_blacklisted_token(&mut conn, &claims.jti)?.is_some() {
             return Ok(None);
         }
+        // Revoked sessions are blacklisted as a whole
+        if selected_blacklisted_token(&mut conn, &claims.session_id.to_string())?.is_some() {
+            return Ok(None);
+        }
 
         let user = self.get_user(claims.user_id)?.or_else(|| {
             tracing::warn!("Found valid JWT, but user {} is invalid!", claims.user_id);
@@ -249,6 +489,11 @@ impl UserRepository for UserRepositoryImpl {
         }
         let r_tok = r_tok.unwrap();
 
+        if selected_blacklisted_token(&mut conn, &r_tok.session_id.to_string())?.is_some() {
+            tracing::debug!("Attempted refresh on revoked session {}", r_tok.session_id);
+            return Ok(None);
+        }
+
         if now >= r_tok.expires_at {
             tracing::warn!("Already expired refresh token");
             return Ok(None);
@@ -270,8 +515,9 @@ impl UserRepository for UserRepositoryImpl {
             }
         }
 
-        let (raw_new_r_tok, new_r_tok) =
+        let (raw_new_r_tok, mut new_r_tok) =
             create_new_refresh_token(r_tok.user_id, Some(r_tok.session_id));
+        new_r_tok.session_created_at = r_tok.session_created_at;
         let new_jwt = create_jwt(
             r_tok.user_id,
             r_tok.session_id,
@@ -312,6 +558,195 @@ impl UserRepository for UserRepositoryImpl {
             Ok::<(), anyhow::Error>(())
         })
     }
+
+    fn get_session_id(&self, jwt: &str) -> Result<SessionId> {
+        let claims = decode_jwt(jwt, &self.secrets.decoding_key)?;
+        Ok(claims.session_id)
+    }
+
+    fn list_sessions(&self, user_id: UserId) -> Result<Vec<RefreshTokenEntity>> {
+        let mut conn = self.db_pool.get()?;
+        select_active_refresh_tokens(&mut conn, user_id, Local::now().naive_local())
+            .context("Failed to select sessions")
+    }
+
+    fn revoke_session(&self, user_id: UserId, session_id: SessionId) -> Result<bool> {
+        let mut conn = self.db_pool.get()?;
+        conn.transaction(|conn| revoke_user_session(conn, user_id, session_id))
+            .context("Failed to revoke session")
+    }
+
+    fn revoke_other_sessions(&self, user_id: UserId, current_session: SessionId) -> Result<u64> {
+        let mut conn = self.db_pool.get()?;
+        conn.transaction(|conn| revoke_user_sessions(conn, user_id, Some(current_session)))
+            .context("Failed to revoke sessions")
+    }
+
+    fn revoke_all_sessions(&self, user_id: UserId) -> Result<u64> {
+        let mut conn = self.db_pool.get()?;
+        conn.transaction(|conn| revoke_user_sessions(conn, user_id, None))
+            .context("Failed to revoke sessions")
+    }
+
+    fn start_totp_enrolment(&self, id: UserId) -> Result<TotpSecret> {
+        let mut conn = self.db_pool.get()?;
+        let secret = totp::generate_secret();
+        update_user_totp(
+            &mut conn,
+            id,
+            Some(&secret),
+            false,
+            None,
+            Utc::now().naive_utc(),
+        )
+        .context("Failed to store TOTP secret")?;
+        Ok(secret)
+    }
+
+    fn enable_totp(&self, id: UserId) -> Result<Vec<BackupCode>> {
+        let mut conn = self.db_pool.get()?;
+        let (codes, code_hashes) = generate_backup_codes();
+
+        conn.transaction(|conn| {
+            let user = select_user(conn, id)?.context("User not found")?;
+            update_user_totp(
+                conn,
+                id,
+                user.totp_secret.as_deref(),
+                true,
+                user.totp_last_step,
+                Utc::now().naive_utc(),
+            )?;
+            replace_totp_backup_codes(conn, id, &code_hashes)
+        })
+        .context("Failed to enable TOTP")?;
+
+        Ok(codes)
+    }
+
+    fn disable_totp(&self, id: UserId) -> Result<UserEntity> {
+        let mut conn = self.db_pool.get()?;
+        conn.transaction(|conn| {
+            delete_totp_backup_codes(conn, id)?;
+            update_user_totp(conn, id, None, false, None, Utc::now().naive_utc())
+        })
+        .context("Failed to disable TOTP")
+    }
+
+    fn regenerate_backup_codes(&self, id: UserId) -> Result<Vec<BackupCode>> {
+        let mut conn = self.db_pool.get()?;
+        let (codes, code_hashes) = generate_backup_codes();
+        replace_totp_backup_codes(&mut conn, id, &code_hashes)
+            .context("Failed to replace backup codes")?;
+        Ok(codes)
+    }
+
+    fn verify_totp_code(&self, user: &UserEntity, code: &str) -> Result<bool> {
+        let Some(secret) = &user.totp_secret else {
+            tracing::debug!("User has no TOTP secret");
+            return Ok(false);
+        };
+
+        let Some(step) =
+            totp::verify_code(secret, code, Utc::now().timestamp(), user.totp_last_step)
+        else {
+            tracing::debug!("Wrong TOTP code!");
+            return Ok(false);
+        };
+
+        // The entity may be outdated, the database decides whether the code was already used
+        let mut conn = self.db_pool.get()?;
+        update_totp_last_step(&mut conn, user.id, step).context("Failed to use TOTP code")
+    }
+
+    fn verify_second_factor(&self, user: &UserEntity, code: &str) -> Result<bool> {
+        if !user.totp_enabled {
+            return Ok(false);
+        }
+
+        if self.verify_totp_code(user, code)? {
+            return Ok(true);
+        }
+
+        let mut conn = self.db_pool.get()?;
+        delete_totp_backup_code(&mut conn, user.id, &hash_backup_code(code))
+            .context("Failed to use backup code")
+    }
+
+    fn create_second_factor_challenge(&self, user_id: UserId) -> Result<SecondFactorChallenge> {
+        let lifetime = TimeDelta::new(SECOND_FACTOR_CHALLENGE_EXPIRY, 0).unwrap();
+        let claims = SecondFactorClaims {
+            user_id,
+            exp: (Utc::now() + lifetime).timestamp(),
+            second_factor: true,
+        };
+
+        let challenge =
+            jsonwebtoken::encode(&Header::default(), &claims, &self.secrets.encoding_key)?;
+        Ok(challenge)
+    }
+
+    fn verify_second_factor_challenge(&self, challenge: &str) -> Result<Option<UserEntity>> {
+        let Ok(token_data) = jsonwebtoken::decode::<SecondFactorClaims>(
+            challenge,
+            &self.secrets.decoding_key,
+            &Validation::default(),
+        ) else {
+            tracing::debug!("Invalid second factor challenge");
+            return Ok(None);
+        };
+
+        if !token_data.claims.second_factor {
+            return Ok(None);
+        }
+
+        self.get_user(token_data.claims.user_id)
+    }
+}
+
+/// Revoke all active sessions of a user, except `except`. Returns the amount of revoked sessions.
+fn revoke_user_sessions(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    except: Option<SessionId>,
+) -> Result<u64> {
+    let sessions = select_active_refresh_tokens(conn, user_id, Local::now().naive_local())?;
+
+    let mut revoked_count = 0;
+    for session in sessions {
+        if Some(session.session_id) != except
+            && revoke_user_session(conn, user_id, session.session_id)?
+        {
+            revoked_count += 1;
+        }
+    }
+
+    Ok(revoked_count)
+}
+
+/// Invalidate all refresh tokens of a session and blacklist the session, which rejects all JWTs
+/// issued in it. The blacklist entry is kept until the last of these JWTs has expired.
+fn revoke_user_session(
+    conn: &mut SqliteConnection,
+    user_id: UserId,
+    session_id: SessionId,
+) -> Result<bool> {
+    let invalidated_count =
+        invalidate_user_session(conn, user_id, session_id, Local::now().naive_local())?;
+    if invalidated_count == 0 {
+        return Ok(false);
+    }
+
+    let lifetime = TimeDelta::new(JWT_EXPIRY + JWT_LEEWAY, 0).unwrap();
+    insert_blacklisted_token(
+        conn,
+        &BlacklistedTokenEntity {
+            id: session_id.to_string(),
+            expires_at: Utc::now().naive_utc() + lifetime,
+        },
+    )?;
+
+    Ok(true)
 }
 
 #[cfg(test)]